@@ -0,0 +1,197 @@
+use crate::config;
+
+// Unix file type bits, as stored in the external attributes of zip entries.
+const S_IFMT: u32 = 0o170000;
+const S_IFREG: u32 = 0o100000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Extract a zip archive into the given directory, enforcing the
+/// configured entry-count and decompressed-size limits.
+pub fn extract_zip(
+  archive_path: &std::path::Path,
+  output_dir: &std::path::Path,
+  network_config: &config::NetworkConfig,
+) -> Result<(), String> {
+  let archive_file = match std::fs::File::open(archive_path) {
+    Ok(file) => file,
+    Err(error) => return Err(format!("failed to open the package archive: {}", error)),
+  };
+
+  let mut archive = match zip::ZipArchive::new(archive_file) {
+    Ok(archive) => archive,
+    Err(error) => return Err(format!("failed to read the package archive: {}", error)),
+  };
+
+  if archive.len() > network_config.max_extracted_entries {
+    return Err(format!(
+      "the package archive contains too many entries ({}, the limit is {})",
+      archive.len(),
+      network_config.max_extracted_entries
+    ));
+  }
+
+  let mut extracted_size: u64 = 0;
+
+  for index in 0..archive.len() {
+    let mut entry = match archive.by_index(index) {
+      Ok(entry) => entry,
+      Err(error) => return Err(format!("failed to read the package archive: {}", error)),
+    };
+
+    let relative_path = sanitize_entry_path(entry.name())?;
+    let entry_path = output_dir.join(&relative_path);
+    let entry_type = entry.unix_mode().map(|mode| mode & S_IFMT);
+
+    match entry_type {
+      // Archives created on some platforms only record permission bits.
+      None | Some(0) | Some(S_IFREG) | Some(S_IFDIR) => {}
+      Some(S_IFLNK) => {
+        let mut link_target = String::new();
+
+        if let Err(error) = std::io::Read::read_to_string(&mut entry, &mut link_target) {
+          return Err(format!("failed to read the package archive: {}", error));
+        }
+
+        extract_symlink(&relative_path, &link_target, output_dir)?;
+
+        continue;
+      }
+      Some(_) => {
+        return Err(format!(
+          "the package archive contains an unsupported file type at `{}`",
+          entry.name()
+        ))
+      }
+    }
+
+    if entry.is_dir() {
+      if let Err(error) = std::fs::create_dir_all(&entry_path) {
+        return Err(format!("failed to create directory: {}", error));
+      }
+
+      continue;
+    }
+
+    if let Some(parent) = entry_path.parent() {
+      if let Err(error) = std::fs::create_dir_all(parent) {
+        return Err(format!("failed to create directory: {}", error));
+      }
+    }
+
+    let mut output_file = match std::fs::File::create(&entry_path) {
+      Ok(file) => file,
+      Err(error) => return Err(format!("failed to create extracted file: {}", error)),
+    };
+
+    // The declared size of an entry can't be trusted, so limit the
+    // amount of bytes actually read instead.
+    let remaining_size = network_config.max_extracted_size - extracted_size;
+    let mut limited_entry = std::io::Read::take(&mut entry, remaining_size + 1);
+
+    let written_size = match std::io::copy(&mut limited_entry, &mut output_file) {
+      Ok(written_size) => written_size,
+      Err(error) => return Err(format!("failed to extract the package archive: {}", error)),
+    };
+
+    if written_size > remaining_size {
+      return Err(format!(
+        "the package archive exceeds the decompressed size limit ({})",
+        indicatif::HumanBytes(network_config.max_extracted_size)
+      ));
+    }
+
+    extracted_size += written_size;
+  }
+
+  Ok(())
+}
+
+/// Ensure that an entry's path is relative and can't escape the output
+/// directory (also known as "zip-slip").
+fn sanitize_entry_path(name: &str) -> Result<std::path::PathBuf, String> {
+  let path = std::path::Path::new(name);
+  let mut sanitized_path = std::path::PathBuf::new();
+
+  if name.contains('\\') || name.contains('\0') {
+    return Err(format!(
+      "the package archive contains an invalid path `{}`",
+      name
+    ));
+  }
+
+  for component in path.components() {
+    match component {
+      std::path::Component::Normal(part) => sanitized_path.push(part),
+      std::path::Component::CurDir => {}
+      _ => {
+        return Err(format!(
+          "the package archive contains an unsafe path `{}`",
+          name
+        ))
+      }
+    }
+  }
+
+  Ok(sanitized_path)
+}
+
+/// Create a symbolic link, as long as its target remains within the
+/// output directory.
+fn extract_symlink(
+  relative_path: &std::path::Path,
+  link_target: &str,
+  output_dir: &std::path::Path,
+) -> Result<(), String> {
+  let mut resolved_target = relative_path
+    .parent()
+    .unwrap_or_else(|| std::path::Path::new(""))
+    .to_path_buf();
+
+  for component in std::path::Path::new(link_target).components() {
+    let is_escaping = match component {
+      std::path::Component::Normal(part) => {
+        resolved_target.push(part);
+
+        false
+      }
+      std::path::Component::CurDir => false,
+      std::path::Component::ParentDir => !resolved_target.pop(),
+      std::path::Component::RootDir | std::path::Component::Prefix(_) => true,
+    };
+
+    if is_escaping {
+      return Err(format!(
+        "the package archive contains a symbolic link escaping the package directory at `{}`",
+        relative_path.display()
+      ));
+    }
+  }
+
+  let link_path = output_dir.join(relative_path);
+
+  if let Some(parent) = link_path.parent() {
+    if let Err(error) = std::fs::create_dir_all(parent) {
+      return Err(format!("failed to create directory: {}", error));
+    }
+  }
+
+  #[cfg(unix)]
+  let link_result = std::os::unix::fs::symlink(link_target, &link_path);
+
+  #[cfg(not(unix))]
+  let link_result: std::io::Result<()> = Err(std::io::Error::new(
+    std::io::ErrorKind::Other,
+    "symbolic links are not supported on this platform",
+  ));
+
+  if let Err(error) = link_result {
+    return Err(format!(
+      "failed to create symbolic link `{}`: {}",
+      relative_path.display(),
+      error
+    ));
+  }
+
+  Ok(())
+}
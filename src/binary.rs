@@ -0,0 +1,149 @@
+use crate::{config, package};
+
+const PATH_BIN: &str = "bin";
+const PATH_INSTALLED_FILE: &str = "installed.toml";
+const PATH_BUILDING: &str = ".building";
+
+/// Records where an installed executable was built from.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct InstalledBinary {
+  pub version: String,
+  pub source: String,
+  pub reference: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct InstalledBinaries {
+  #[serde(default)]
+  binaries: std::collections::BTreeMap<String, InstalledBinary>,
+}
+
+/// Retrieve the directory installed executables are placed in.
+pub fn bin_dir() -> Result<std::path::PathBuf, String> {
+  Ok(config::grip_home()?.join(PATH_BIN))
+}
+
+/// Retrieve a fresh directory to fetch and build a package in before
+/// its executable is installed.
+pub fn build_dir() -> Result<std::path::PathBuf, String> {
+  let build_dir = bin_dir()?
+    .join(PATH_BUILDING)
+    .join(std::process::id().to_string());
+
+  if build_dir.exists() {
+    if let Err(error) = std::fs::remove_dir_all(&build_dir) {
+      return Err(format!("failed to clear the build directory: {}", error));
+    }
+  }
+
+  if let Err(error) = std::fs::create_dir_all(&build_dir) {
+    return Err(format!("failed to create the build directory: {}", error));
+  }
+
+  Ok(build_dir)
+}
+
+/// Determine whether the directory of installed executables is part of
+/// `PATH`, so that they can be invoked by name.
+pub fn is_bin_dir_in_path() -> bool {
+  let bin_dir = match bin_dir() {
+    Ok(bin_dir) => bin_dir,
+    Err(_) => return false,
+  };
+
+  std::env::var_os("PATH")
+    .map(|paths| std::env::split_paths(&paths).any(|path| path == bin_dir))
+    .unwrap_or(false)
+}
+
+fn binary_path(name: &str) -> Result<std::path::PathBuf, String> {
+  let mut binary_path = bin_dir()?.join(name);
+
+  binary_path.set_extension(std::env::consts::EXE_EXTENSION);
+
+  Ok(binary_path)
+}
+
+/// List the installed executables, by name.
+pub fn installed() -> Result<std::collections::BTreeMap<String, InstalledBinary>, String> {
+  let installed_path = config::grip_home()?.join(PATH_INSTALLED_FILE);
+
+  if !installed_path.exists() {
+    return Ok(std::collections::BTreeMap::new());
+  }
+
+  match toml::from_str::<InstalledBinaries>(&package::fetch_file_contents(&installed_path)?) {
+    Ok(installed_binaries) => Ok(installed_binaries.binaries),
+    Err(error) => Err(format!(
+      "failed to parse `{}`: {}",
+      installed_path.display(),
+      error
+    )),
+  }
+}
+
+fn write_installed(
+  binaries: std::collections::BTreeMap<String, InstalledBinary>,
+) -> Result<(), String> {
+  let installed_path = config::grip_home()?.join(PATH_INSTALLED_FILE);
+
+  let contents = match toml::ser::to_string_pretty(&InstalledBinaries { binaries }) {
+    Ok(contents) => contents,
+    Err(error) => return Err(format!("failed to serialize installed binaries: {}", error)),
+  };
+
+  if let Err(error) = std::fs::write(&installed_path, contents) {
+    return Err(format!(
+      "failed to write `{}`: {}",
+      installed_path.display(),
+      error
+    ));
+  }
+
+  Ok(())
+}
+
+/// Copy a built executable into the directory of installed executables,
+/// replacing any previous installation of the same name. Returns the
+/// installed path.
+pub fn install(
+  executable_path: &std::path::Path,
+  name: &str,
+  installed_binary: InstalledBinary,
+) -> Result<std::path::PathBuf, String> {
+  let binary_path = binary_path(name)?;
+
+  if let Err(error) = std::fs::create_dir_all(bin_dir()?) {
+    return Err(format!("failed to create the bin directory: {}", error));
+  }
+
+  // Copying preserves the executable's permissions.
+  if let Err(error) = std::fs::copy(executable_path, &binary_path) {
+    return Err(format!("failed to install `{}`: {}", name, error));
+  }
+
+  let mut binaries = installed()?;
+
+  binaries.insert(name.to_string(), installed_binary);
+  write_installed(binaries)?;
+
+  Ok(binary_path)
+}
+
+/// Remove an installed executable.
+pub fn uninstall(name: &str) -> Result<(), String> {
+  let binary_path = binary_path(name)?;
+  let mut binaries = installed()?;
+
+  if binaries.remove(name).is_none() && !binary_path.exists() {
+    return Err(format!("no executable named `{}` is installed", name));
+  }
+
+  if binary_path.exists() {
+    if let Err(error) = std::fs::remove_file(&binary_path) {
+      return Err(format!("failed to remove `{}`: {}", name, error));
+    }
+  }
+
+  write_installed(binaries)
+}
@@ -3,19 +3,258 @@ use gecko::lint::Lint;
 use gecko::llvm_lowering::Lower;
 use gecko::semantic_check::SemanticCheck;
 
+/// The kind of output produced by a build.
+#[derive(Clone, Copy, PartialEq)]
+pub enum EmitKind {
+  Ir,
+  Bitcode,
+  Assembly,
+  Object,
+  Executable,
+  Tokens,
+  Ast,
+}
+
+impl EmitKind {
+  pub fn extension(&self) -> &'static str {
+    match self {
+      EmitKind::Ir => "ll",
+      EmitKind::Bitcode => "bc",
+      EmitKind::Assembly => "s",
+      EmitKind::Object => {
+        if cfg!(windows) {
+          "obj"
+        } else {
+          "o"
+        }
+      }
+      EmitKind::Executable => std::env::consts::EXE_EXTENSION,
+      EmitKind::Tokens => "tokens",
+      EmitKind::Ast => "ast",
+    }
+  }
+}
+
+impl std::str::FromStr for EmitKind {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value {
+      "ir" => Ok(EmitKind::Ir),
+      "bc" => Ok(EmitKind::Bitcode),
+      "asm" => Ok(EmitKind::Assembly),
+      "obj" => Ok(EmitKind::Object),
+      "exe" => Ok(EmitKind::Executable),
+      "tokens" => Ok(EmitKind::Tokens),
+      "ast" => Ok(EmitKind::Ast),
+      _ => Err(format!("unknown emit kind `{}`", value)),
+    }
+  }
+}
+
+/// How much the lowered module is optimized (`-O<level>`).
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq)]
+pub enum OptLevel {
+  #[serde(rename = "0")]
+  O0,
+  #[serde(rename = "1")]
+  O1,
+  #[serde(rename = "2")]
+  O2,
+  #[serde(rename = "3")]
+  O3,
+  /// Like `O2`, but favoring smaller code.
+  #[serde(rename = "s")]
+  Os,
+  /// Like `Os`, but more aggressively.
+  #[serde(rename = "z")]
+  Oz,
+}
+
+impl OptLevel {
+  pub fn name(&self) -> &'static str {
+    match self {
+      OptLevel::O0 => "O0",
+      OptLevel::O1 => "O1",
+      OptLevel::O2 => "O2",
+      OptLevel::O3 => "O3",
+      OptLevel::Os => "Os",
+      OptLevel::Oz => "Oz",
+    }
+  }
+
+  fn llvm_level(&self) -> inkwell::OptimizationLevel {
+    match self {
+      OptLevel::O0 => inkwell::OptimizationLevel::None,
+      OptLevel::O1 => inkwell::OptimizationLevel::Less,
+      OptLevel::O2 | OptLevel::Os | OptLevel::Oz => inkwell::OptimizationLevel::Default,
+      OptLevel::O3 => inkwell::OptimizationLevel::Aggressive,
+    }
+  }
+
+  fn size_level(&self) -> u32 {
+    match self {
+      OptLevel::Os => 1,
+      OptLevel::Oz => 2,
+      _ => 0,
+    }
+  }
+}
+
+impl std::str::FromStr for OptLevel {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value {
+      "0" => Ok(OptLevel::O0),
+      "1" => Ok(OptLevel::O1),
+      "2" => Ok(OptLevel::O2),
+      "3" => Ok(OptLevel::O3),
+      "s" => Ok(OptLevel::Os),
+      "z" => Ok(OptLevel::Oz),
+      _ => Err(format!("unknown optimization level `{}`", value)),
+    }
+  }
+}
+
+/// A diagnostic produced by the driver, along with the package and
+/// source file it originated from (if known).
+#[derive(Clone)]
+pub struct DriverDiagnostic {
+  pub package_name: Option<String>,
+  /// The file the diagnostic's span refers to.
+  pub source_file: Option<std::path::PathBuf>,
+  pub diagnostic: gecko::diagnostic::Diagnostic,
+}
+
+impl DriverDiagnostic {
+  pub fn new(package_name: Option<String>, diagnostic: gecko::diagnostic::Diagnostic) -> Self {
+    Self {
+      package_name,
+      source_file: None,
+      diagnostic,
+    }
+  }
+
+  pub fn with_source_file(mut self, source_file: &std::path::Path) -> Self {
+    self.source_file = Some(source_file.to_path_buf());
+
+    self
+  }
+
+  pub fn is_error(&self) -> bool {
+    self.diagnostic.severity == gecko::diagnostic::Severity::Error
+  }
+}
+
+/// Counters describing the workload of the last build.
+#[derive(Default, Clone)]
+pub struct Statistics {
+  pub files_parsed: usize,
+  pub tokens_lexed: usize,
+  pub nodes_lowered: usize,
+}
+
+const PATH_CACHE_DIR: &str = ".cache";
+const PATH_DEPS_DIR: &str = "deps";
+const PATH_DEPS_HASH_EXTENSION: &str = "sha256";
+const PATH_BITCODE_EXTENSION: &str = "bc";
+const PATH_CACHE_INDEX: &str = "index.json";
+const PATH_CACHE_MODULE: &str = "module.bc";
+
+/// The inputs of a build whose result is cached.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq)]
+struct CacheInputs {
+  grip_version: String,
+  profile: String,
+  target: String,
+  require_main: bool,
+  verify: bool,
+  debug_info: bool,
+  /// The enabled features of each package.
+  features: std::collections::BTreeMap<String, Vec<String>>,
+  /// The content hash of each source file, keyed by package and path.
+  file_hashes: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CacheIndex {
+  inputs: CacheInputs,
+  /// Results of the build which aren't part of the module.
+  interface: Vec<crate::schema::InterfaceFunction>,
+}
+
+/// A step of a build, reported to the driver's event handler as it
+/// begins.
+pub enum BuildEvent {
+  /// The sources of a package are being compiled (or checked). The
+  /// index is one-based.
+  Compiling {
+    package_name: String,
+    index: usize,
+    count: usize,
+  },
+  /// A source file is being parsed, out of all of the build's files.
+  /// The index is one-based.
+  Parsing { index: usize, count: usize },
+  /// The parsed sources are being type-checked and linted.
+  Checking,
+  /// The checked sources are being lowered into LLVM IR.
+  Lowering,
+}
+
 /// Serves as the driver for the Gecko compiler.
 ///
 /// Can be used to compile a single file, or multiple, and produce
 /// a single LLVM module.
 pub struct Driver<'a, 'ctx> {
+  /// The source files of each package, along with the package's name.
+  /// The sources of the package being built are registered first.
   pub source_files: Vec<(String, std::path::PathBuf)>,
+  /// The contents of each source file. Sources which don't live on disk
+  /// (such as stdin) are provided beforehand, and the rest are filled
+  /// in as they are read, so that diagnostics can display them.
   pub file_contents: std::collections::HashMap<std::path::PathBuf, String>,
   pub llvm_module: &'a inkwell::module::Module<'ctx>,
+  llvm_context: &'ctx inkwell::context::Context,
+  /// The time spent on each phase of the last build.
+  pub timings: Vec<(String, std::time::Duration)>,
+  /// Whether a missing `main` function should be reported as an error.
+  pub require_main: bool,
+  /// The top-level functions declared by each package, collected during
+  /// the last build.
+  pub interface: Vec<crate::schema::InterfaceFunction>,
+  pub statistics: Statistics,
+  /// Where the results of builds are cached, so that unchanged sources
+  /// aren't compiled again. Caching is disabled if unset.
+  pub cache_dir: Option<std::path::PathBuf>,
+  /// Where the modules of dependencies are cached as bitcode, so that
+  /// each is only lowered once. Caching is disabled if unset.
+  pub deps_dir: Option<std::path::PathBuf>,
+  /// The number of threads used to read and lex source files.
+  pub jobs: usize,
+  /// Whether the lowered module is checked by the LLVM verifier.
+  pub verify: bool,
+  /// Whether to describe the lowered module in debug info.
+  pub debug_info: bool,
+  /// The enabled features of each package.
+  // TODO: Expose the features to gecko's conditional constructs, once it
+  // ... has any.
+  pub features: std::collections::HashMap<String, Vec<String>>,
+  /// The names of the packages each package depends on.
+  pub dependencies: std::collections::HashMap<String, Vec<String>>,
+  /// The name of the profile being built with.
+  pub profile: String,
+  /// Problems with the manifests of the packages being built, which are
+  /// reported ahead of those of their sources.
+  pub manifest_diagnostics: Vec<DriverDiagnostic>,
+  /// Invoked as each step of a build begins, such as to display its
+  /// progress.
+  pub event_handler: Option<Box<dyn FnMut(BuildEvent)>>,
   cache: gecko::cache::Cache,
   name_resolver: gecko::name_resolution::NameResolver,
   lint_context: gecko::lint::LintContext,
   type_context: gecko::semantic_check::SemanticCheckContext,
-  llvm_generator: gecko::llvm_lowering::LlvmGenerator<'a, 'ctx>,
 }
 
 impl<'a, 'ctx> Driver<'a, 'ctx> {
@@ -27,55 +266,440 @@ impl<'a, 'ctx> Driver<'a, 'ctx> {
       source_files: Vec::new(),
       file_contents: std::collections::HashMap::new(),
       llvm_module,
+      llvm_context,
+      timings: Vec::new(),
+      require_main: true,
+      interface: Vec::new(),
+      statistics: Statistics::default(),
+      cache_dir: None,
+      deps_dir: None,
+      jobs: 1,
+      verify: true,
+      debug_info: false,
+      features: std::collections::HashMap::new(),
+      dependencies: std::collections::HashMap::new(),
+      profile: package::PROFILE_DEBUG.to_string(),
+      manifest_diagnostics: Vec::new(),
+      event_handler: None,
       cache: gecko::cache::Cache::new(),
       name_resolver: gecko::name_resolution::NameResolver::new(),
       lint_context: gecko::lint::LintContext::new(),
       type_context: gecko::semantic_check::SemanticCheckContext::new(),
-      llvm_generator: gecko::llvm_lowering::LlvmGenerator::new(llvm_context, &llvm_module),
     }
   }
 
-  fn read_and_lex(&self, source_file: &std::path::PathBuf) -> Vec<gecko::lexer::Token> {
-    // FIXME: Performing unsafe operations temporarily.
+  pub fn read_and_lex(&self, source_file: &std::path::PathBuf) -> Vec<gecko::lexer::Token> {
+    lex_source_file(&self.file_contents, source_file)
+  }
 
-    let source_code = package::fetch_file_contents(&source_file).unwrap();
-    let tokens = gecko::lexer::Lexer::from_str(source_code.as_str()).lex_all();
+  /// Read and lex a source file, reporting lexing errors instead of
+  /// failing on them.
+  pub fn try_read_and_lex(
+    &self,
+    source_file: &std::path::PathBuf,
+  ) -> Result<Vec<gecko::lexer::Token>, gecko::diagnostic::Diagnostic> {
+    try_lex_source_code(&read_source_file(&self.file_contents, source_file))
+  }
 
-    // BUG: This will fail if there were lexing errors. Unsafe unwrap.
-    // FIXME: What about illegal tokens?
-    // TODO: This might be inefficient for larger programs, so consider passing an option to the lexer.
-    // Filter tokens to only include those that are relevant (ignore whitespace, comments, etc.).
-    tokens
-      .unwrap()
-      .into_iter()
-      .filter(|token| {
-        !matches!(
-          token.0,
-          gecko::lexer::TokenKind::Whitespace(_) | gecko::lexer::TokenKind::Comment(_)
-        )
-      })
-      .collect()
+  /// Lex all source files, and produce a listing of their tokens.
+  pub fn dump_tokens(&self) -> String {
+    let mut output = String::new();
+
+    for (package_name, source_file) in &self.source_files {
+      output.push_str(&format!("// {}: {}\n", package_name, source_file.display()));
+
+      for token in self.read_and_lex(source_file) {
+        output.push_str(&format!("{:?}\n", token));
+      }
+    }
+
+    output
+  }
+
+  /// Lex and parse all source files, and produce a listing of each of
+  /// their top-level nodes, along with their package and file.
+  pub fn parse_all(
+    &mut self,
+  ) -> Result<Vec<(String, std::path::PathBuf, Vec<String>)>, DriverDiagnostic> {
+    let mut parsed_files = Vec::new();
+
+    for (package_name, source_file) in &self.source_files {
+      let source_code = read_source_file(&self.file_contents, source_file);
+      let tokens = lex_source_code(&source_code);
+
+      self.file_contents.insert(source_file.clone(), source_code);
+
+      let root_nodes = match gecko::parser::Parser::new(tokens, &mut self.cache).parse_all() {
+        Ok(root_nodes) => root_nodes,
+        Err(diagnostic) => {
+          return Err(
+            DriverDiagnostic::new(Some(package_name.clone()), diagnostic)
+              .with_source_file(source_file),
+          )
+        }
+      };
+
+      parsed_files.push((
+        package_name.clone(),
+        source_file.clone(),
+        root_nodes
+          .iter()
+          .map(|root_node| format!("{:#?}", root_node))
+          .collect(),
+      ));
+    }
+
+    Ok(parsed_files)
+  }
+
+  /// Lex and parse all source files, and produce a listing of their
+  /// top-level nodes.
+  pub fn dump_ast(&mut self) -> Result<String, DriverDiagnostic> {
+    let mut output = String::new();
+
+    for (package_name, source_file, nodes) in self.parse_all()? {
+      output.push_str(&format_parsed_file(&package_name, &source_file, &nodes));
+    }
+
+    Ok(output)
+  }
+
+  /// Lex and parse a single source file, and produce a listing of
+  /// each of its top-level nodes.
+  pub fn dump_file_ast(
+    &mut self,
+    source_file: &std::path::PathBuf,
+  ) -> Result<Vec<String>, gecko::diagnostic::Diagnostic> {
+    let tokens = self.read_and_lex(source_file);
+    let root_nodes = gecko::parser::Parser::new(tokens, &mut self.cache).parse_all()?;
+
+    Ok(
+      root_nodes
+        .iter()
+        .map(|root_node| format!("{:#?}", root_node))
+        .collect(),
+    )
+  }
+
+  /// Run all phases up to (but excluding) lowering.
+  pub fn check(&mut self) -> Vec<DriverDiagnostic> {
+    self.run(false)
   }
 
   // REVIEW: Consider accepting the source files here? More strict?
-  pub fn build(&mut self) -> Vec<gecko::diagnostic::Diagnostic> {
-    // FIXME: Must name the LLVM module with the initial package's name.
-    self.llvm_generator.module_name = "my_project".to_string();
+  pub fn build(&mut self) -> Vec<DriverDiagnostic> {
+    // A manifest may have become invalid without any source changing.
+    let cache_dir = match self.cache_dir.clone() {
+      Some(cache_dir)
+        if !self
+          .manifest_diagnostics
+          .iter()
+          .any(DriverDiagnostic::is_error) =>
+      {
+        cache_dir
+      }
+      _ => return self.run(true),
+    };
+
+    // Unreadable sources are reported by the build itself.
+    let file_hashes = match self.hash_source_files() {
+      Ok(file_hashes) => file_hashes,
+      Err(_) => return self.run(true),
+    };
+
+    if self.load_cached(&cache_dir, &file_hashes) {
+      return Vec::new();
+    }
+
+    let diagnostics = self.run(true);
+
+    // Builds with warnings aren't cached, so that they are reported again.
+    if diagnostics.is_empty() {
+      if let Err(error) = self.store_cached(&cache_dir, file_hashes) {
+        log::warn!("failed to cache the build: {}", error);
+      }
+    }
 
+    diagnostics
+  }
+
+  fn hash_source_files(&self) -> Result<std::collections::BTreeMap<String, String>, String> {
+    let mut file_hashes = std::collections::BTreeMap::new();
+
+    for (package_name, source_file) in &self.source_files {
+      let file_hash = match self.file_contents.get(source_file) {
+        Some(contents) => crate::checksum::sha256_bytes(contents.as_bytes()),
+        None => crate::checksum::sha256_file(source_file)?,
+      };
+
+      file_hashes.insert(
+        format!("{}:{}", package_name, source_file.display()),
+        file_hash,
+      );
+    }
+
+    Ok(file_hashes)
+  }
+
+  fn cache_inputs(&self, file_hashes: std::collections::BTreeMap<String, String>) -> CacheInputs {
+    CacheInputs {
+      grip_version: clap::crate_version!().to_string(),
+      profile: self.profile.clone(),
+      target: package::target_triple(),
+      require_main: self.require_main,
+      verify: self.verify,
+      debug_info: self.debug_info,
+      features: self
+        .features
+        .iter()
+        .map(|(package_name, features)| (package_name.clone(), features.clone()))
+        .collect(),
+      file_hashes,
+    }
+  }
+
+  /// Hash the sources of a package, along with its features.
+  fn package_hash(&self, package_name: &str) -> Option<String> {
+    let mut package_sources = String::new();
+
+    // A package is lowered differently under other features.
+    if let Some(features) = self.features.get(package_name) {
+      package_sources.push_str(&format!("features: {}", features.join(",")));
+    }
+
+    for (source_package_name, source_file) in &self.source_files {
+      if source_package_name != package_name {
+        continue;
+      }
+
+      let file_hash = match self.file_contents.get(source_file) {
+        Some(contents) => crate::checksum::sha256_bytes(contents.as_bytes()),
+        None => crate::checksum::sha256_file(source_file).ok()?,
+      };
+
+      package_sources.push_str(&format!("\n{} {}", source_file.display(), file_hash));
+    }
+
+    Some(crate::checksum::sha256_bytes(package_sources.as_bytes()))
+  }
+
+  /// Determine where a dependency's module is cached, along with the
+  /// hash of the inputs it was lowered from: its sources, those of the
+  /// packages it depends on (transitively), the profile, the target and
+  /// whether it is described in debug info.
+  fn dependency_cache(&self, package_name: &str) -> Option<(std::path::PathBuf, String)> {
+    let deps_dir = self.deps_dir.as_ref()?;
+
+    let mut cache_key = format!(
+      "{}\nprofile: {}\ntarget: {}\ndebug-info: {}",
+      clap::crate_version!(),
+      self.profile,
+      package::target_triple(),
+      self.debug_info
+    );
+
+    let mut lowering_inputs = std::collections::BTreeSet::new();
+    let mut queue = vec![package_name.to_string()];
+
+    while let Some(input_package_name) = queue.pop() {
+      if let Some(dependencies) = self.dependencies.get(&input_package_name) {
+        queue.extend(
+          dependencies
+            .iter()
+            .filter(|dependency| !lowering_inputs.contains(*dependency))
+            .cloned(),
+        );
+      }
+
+      lowering_inputs.insert(input_package_name);
+    }
+
+    for input_package_name in &lowering_inputs {
+      cache_key.push_str(&format!(
+        "\n{} {}",
+        input_package_name,
+        self.package_hash(input_package_name)?
+      ));
+    }
+
+    Some((
+      deps_dir
+        .join(package_name)
+        .with_extension(PATH_BITCODE_EXTENSION),
+      crate::checksum::sha256_bytes(cache_key.as_bytes()),
+    ))
+  }
+
+  /// Link a dependency's cached module into the given module, if its
+  /// sources haven't changed since. Returns whether it was used.
+  fn load_cached_dependency(
+    &self,
+    package_module: &inkwell::module::Module<'ctx>,
+    bitcode_path: &std::path::Path,
+    package_hash: &str,
+  ) -> bool {
+    let cached_hash =
+      package::fetch_file_contents(&bitcode_path.with_extension(PATH_DEPS_HASH_EXTENSION));
+
+    if cached_hash.ok().as_deref() != Some(package_hash) {
+      return false;
+    }
+
+    match inkwell::module::Module::parse_bitcode_from_path(bitcode_path, self.llvm_context) {
+      Ok(cached_module) => package_module.link_in_module(cached_module).is_ok(),
+      Err(_) => false,
+    }
+  }
+
+  /// Load the result of a previous build from the cache, if none of its
+  /// inputs changed. Returns whether the cached result was used.
+  // REVISE: A single changed file invalidates the whole result. Unchanged
+  // ... dependencies are still loaded from their own cached modules.
+  fn load_cached(
+    &mut self,
+    cache_dir: &std::path::Path,
+    file_hashes: &std::collections::BTreeMap<String, String>,
+  ) -> bool {
+    let cache_index = match package::fetch_file_contents(&cache_dir.join(PATH_CACHE_INDEX))
+      .ok()
+      .and_then(|contents| serde_json::from_str::<CacheIndex>(&contents).ok())
+    {
+      Some(cache_index) => cache_index,
+      None => return false,
+    };
+
+    if cache_index.inputs != self.cache_inputs(file_hashes.clone()) {
+      return false;
+    }
+
+    let cached_module = match inkwell::module::Module::parse_bitcode_from_path(
+      cache_dir.join(PATH_CACHE_MODULE),
+      self.llvm_context,
+    ) {
+      Ok(cached_module) => cached_module,
+      Err(_) => return false,
+    };
+
+    if self.llvm_module.link_in_module(cached_module).is_err() {
+      return false;
+    }
+
+    self.interface = cache_index.interface;
+    self.timings.clear();
+
+    true
+  }
+
+  fn store_cached(
+    &self,
+    cache_dir: &std::path::Path,
+    file_hashes: std::collections::BTreeMap<String, String>,
+  ) -> Result<(), String> {
+    if let Err(error) = std::fs::create_dir_all(cache_dir) {
+      return Err(format!("failed to create the cache directory: {}", error));
+    }
+
+    if !self
+      .llvm_module
+      .write_bitcode_to_path(&cache_dir.join(PATH_CACHE_MODULE))
+    {
+      return Err("failed to write the cached module".to_string());
+    }
+
+    let cache_index = CacheIndex {
+      inputs: self.cache_inputs(file_hashes),
+      interface: self.interface.clone(),
+    };
+
+    let cache_index = match serde_json::to_string(&cache_index) {
+      Ok(cache_index) => cache_index,
+      Err(error) => return Err(format!("failed to serialize the cache index: {}", error)),
+    };
+
+    if let Err(error) = std::fs::write(cache_dir.join(PATH_CACHE_INDEX), cache_index) {
+      return Err(format!("failed to write the cache index: {}", error));
+    }
+
+    Ok(())
+  }
+
+  fn run(&mut self, lower: bool) -> Vec<DriverDiagnostic> {
     // FIXME: This function may be too complex (too many loops). Find a way to simplify the loops?
 
-    let mut diagnostics = Vec::new();
+    let mut diagnostics = self.manifest_diagnostics.clone();
     let mut ast = std::collections::HashMap::new();
+    let mut phase_start = std::time::Instant::now();
+
+    if diagnostics.iter().any(DriverDiagnostic::is_error) {
+      return diagnostics;
+    }
+
+    self.timings.clear();
+    self.statistics = Statistics::default();
+
+    let mut package_count = 0;
+    let mut package_index = 0;
+    let mut current_package = None;
+
+    // The sources of each package are registered together.
+    for (index, (package_name, _)) in self.source_files.iter().enumerate() {
+      if index == 0 || self.source_files[index - 1].0 != *package_name {
+        package_count += 1;
+      }
+    }
+
+    let lexed_files = lex_source_files(&self.file_contents, &self.source_files, self.jobs);
+
+    self.record_timing("lex", &mut phase_start);
+
+    // Parse, perform name resolution (declarations) and collect the AST
+    // (top-level nodes) from each source file.
+    // REVISE: Parsing remains sequential, since the parser registers
+    // ... declarations into the shared cache.
+    let mut module_paths = std::collections::HashMap::new();
+    // The package named by each module qualifier.
+    let mut qualified_packages = std::collections::HashMap::new();
+
+    let file_count = self.source_files.len();
+
+    for (file_index, ((package_name, source_file), (source_code, tokens))) in
+      self.source_files.iter().zip(lexed_files).enumerate()
+    {
+      self.file_contents.insert(source_file.clone(), source_code);
+
+      if current_package != Some(package_name) {
+        current_package = Some(package_name);
+        package_index += 1;
+
+        if let Some(event_handler) = &mut self.event_handler {
+          event_handler(BuildEvent::Compiling {
+            package_name: package_name.clone(),
+            index: package_index,
+            count: package_count,
+          });
+        }
+      }
+
+      if let Some(event_handler) = &mut self.event_handler {
+        event_handler(BuildEvent::Parsing {
+          index: file_index + 1,
+          count: file_count,
+        });
+      }
+
+      self.statistics.files_parsed += 1;
+      self.statistics.tokens_lexed += tokens.len();
 
-    // Read, lex, parse, perform name resolution (declarations)
-    // and collect the AST (top-level nodes) from each source file.
-    for (package_name, source_file) in &self.source_files {
-      let tokens = self.read_and_lex(source_file);
       let mut parser = gecko::parser::Parser::new(tokens, &mut self.cache);
 
       let root_nodes = match parser.parse_all() {
         Ok(nodes) => nodes,
-        Err(diagnostic) => return vec![diagnostic],
+        Err(diagnostic) => {
+          return vec![
+            DriverDiagnostic::new(Some(package_name.clone()), diagnostic)
+              .with_source_file(source_file),
+          ]
+        }
       };
 
       // TODO: File names need to conform to identifier rules.
@@ -85,84 +709,887 @@ impl<'a, 'ctx> Driver<'a, 'ctx> {
         .to_string_lossy()
         .to_string();
 
-      let global_qualifier = (package_name.clone(), source_file_name.clone());
+      // Hyphens aren't part of identifiers.
+      let global_qualifier = (package_name.replace('-', "_"), source_file_name.clone());
+
+      if let Some(diagnostic) =
+        Self::check_module_clash(&module_paths, &global_qualifier, source_file)
+      {
+        diagnostics.push(diagnostic);
+      }
 
+      module_paths.insert(global_qualifier.clone(), source_file.clone());
+      qualified_packages.insert(global_qualifier.0.clone(), package_name.clone());
       ast.insert(global_qualifier.clone(), root_nodes);
     }
 
-    // After all the ASTs have been collected, perform name resolution.
-    diagnostics.extend(self.name_resolver.run(&mut ast, &mut self.cache));
+    self.record_timing("parse", &mut phase_start);
+
+    // A shadowed module is missing from the AST, so later phases would
+    // only report confusing errors.
+    if diagnostics.iter().any(DriverDiagnostic::is_error) {
+      return diagnostics;
+    }
+
+    if self.require_main {
+      diagnostics.extend(Self::check_main_function(&ast));
+    }
 
-    if self.cache.main_function_id.is_none() {
-      diagnostics.push(gecko::diagnostic::Diagnostic {
-        severity: gecko::diagnostic::Severity::Error,
-        message: "no main function defined".to_string(),
-        span: None,
-      });
+    self.interface.clear();
+
+    // TODO: Only include public functions, along with their signatures.
+    for ((package_qualifier, module_name), root_nodes) in &ast {
+      for root_node in root_nodes {
+        if let gecko::ast::NodeKind::Function(function) = &root_node.kind {
+          self.interface.push(crate::schema::InterfaceFunction {
+            package: qualified_packages[package_qualifier].clone(),
+            module: module_name.clone(),
+            name: function.name.clone(),
+          });
+        }
+      }
     }
 
+    // After all the ASTs have been collected, perform name resolution.
+    // ... The resolver runs over all modules at once, so its diagnostics
+    // ... can only be attributed when there is a single source file.
+    let single_source = match self.source_files.as_slice() {
+      [(package_name, source_file)] => Some((package_name.clone(), source_file.clone())),
+      _ => None,
+    };
+
+    diagnostics.extend(
+      self
+        .name_resolver
+        .run(&mut ast, &mut self.cache)
+        .into_iter()
+        .map(|diagnostic| match &single_source {
+          Some((package_name, source_file)) => {
+            DriverDiagnostic::new(Some(package_name.clone()), diagnostic)
+              .with_source_file(source_file)
+          }
+          None => DriverDiagnostic::new(None, diagnostic),
+        }),
+    );
+    self.record_timing("name_resolution", &mut phase_start);
+
     // Cannot continue to other phases if name resolution failed.
-    if diagnostics
-      .iter()
-      .any(|diagnostic| diagnostic.severity == gecko::diagnostic::Severity::Error)
-    {
+    if diagnostics.iter().any(DriverDiagnostic::is_error) {
       return diagnostics;
     }
 
     let readonly_ast = ast
-      .into_values()
-      .flatten()
       .into_iter()
-      .map(|node| std::rc::Rc::new(node))
+      .flat_map(|(global_qualifier, root_nodes)| {
+        let source_file = module_paths[&global_qualifier].clone();
+        let package_name = qualified_packages[&global_qualifier.0].clone();
+
+        root_nodes.into_iter().map(move |node| {
+          (
+            package_name.clone(),
+            source_file.clone(),
+            std::rc::Rc::new(node),
+          )
+        })
+      })
       .collect::<Vec<_>>();
 
+    if let Some(event_handler) = &mut self.event_handler {
+      event_handler(BuildEvent::Checking);
+    }
+
     // Once symbols are resolved, we can proceed to the other phases.
-    for root_node in &readonly_ast {
+    for (package_name, source_file, root_node) in &readonly_ast {
       root_node.check(&mut self.type_context, &self.cache);
 
+      let lint_diagnostic_count = self.lint_context.diagnostic_builder.diagnostics.len();
+
       // TODO: Can we mix linting with type-checking without any problems?
       root_node.lint(&self.cache, &mut self.lint_context);
+
+      diagnostics.extend(
+        self.lint_context.diagnostic_builder.diagnostics[lint_diagnostic_count..]
+          .iter()
+          .cloned()
+          .map(|diagnostic| {
+            DriverDiagnostic::new(Some(package_name.clone()), diagnostic)
+              .with_source_file(source_file)
+          }),
+      );
     }
 
+    let lint_diagnostic_count = self.lint_context.diagnostic_builder.diagnostics.len();
+
     self.lint_context.finalize(&self.cache);
 
-    let semantic_check_result =
-      gecko::semantic_check::SemanticCheckContext::run(&readonly_ast, &self.cache);
+    // The nodes of each source file (which are adjacent) are checked
+    // ... together, so that their diagnostics can be attributed to it.
+    let mut file_nodes: Vec<(
+      &String,
+      &std::path::PathBuf,
+      Vec<std::rc::Rc<gecko::ast::Node>>,
+    )> = Vec::new();
 
-    // FIXME: Make use of the returned imports!
+    for (package_name, source_file, root_node) in &readonly_ast {
+      match file_nodes.last_mut() {
+        Some((_, last_source_file, nodes)) if *last_source_file == source_file => {
+          nodes.push(std::rc::Rc::clone(root_node))
+        }
+        _ => file_nodes.push((
+          package_name,
+          source_file,
+          vec![std::rc::Rc::clone(root_node)],
+        )),
+      }
+    }
+
+    for (package_name, source_file, nodes) in file_nodes {
+      let semantic_check_result =
+        gecko::semantic_check::SemanticCheckContext::run(&nodes, &self.cache);
+
+      // FIXME: Make use of the returned imports!
+
+      diagnostics.extend(semantic_check_result.0.into_iter().map(|diagnostic| {
+        DriverDiagnostic::new(Some(package_name.clone()), diagnostic).with_source_file(source_file)
+      }));
+    }
+
+    diagnostics.extend(
+      self.lint_context.diagnostic_builder.diagnostics[lint_diagnostic_count..]
+        .iter()
+        .cloned()
+        .map(|diagnostic| DriverDiagnostic::new(None, diagnostic)),
+    );
 
-    diagnostics.extend(semantic_check_result.0);
-    diagnostics.extend(self.lint_context.diagnostic_builder.diagnostics.clone());
+    self.record_timing("semantic_check", &mut phase_start);
 
     // TODO: Any way for better efficiency (less loops)?
     // Lowering cannot proceed if there was an error.
-    if diagnostics
-      .iter()
-      .any(|diagnostic| diagnostic.severity == gecko::diagnostic::Severity::Error)
-    {
+    if !lower || diagnostics.iter().any(DriverDiagnostic::is_error) {
       return diagnostics;
     }
 
-    // REVISE: For efficiency, and to solve caching issues, only lower the `main` function here.
-    // ... Any referenced entity within it (thus the whole program) will be lowered and cached
-    // ... accordingly from there on.
-    // BUG: Extern functions shouldn't be lowered directly. They are no longer under a wrapper
-    // ... node, which ensures their caching. This means that, first they will be forcefully lowered
-    // ... here (without caching), then when referenced, since they haven't been cached.
-    // Once symbols are resolved, we can proceed to the other phases.
-    for root_node in &readonly_ast {
-      if let gecko::ast::NodeKind::Function(function) = &root_node.kind {
-        // Only lower the main function.
-        if function.name == gecko::llvm_lowering::MAIN_FUNCTION_NAME {
-          root_node.lower(&mut self.llvm_generator, &self.cache);
-
-          // TODO: Need to manually cache the main function here. This is because
-          // ... if it is called once again, since it isn't cached, it will be re-lowered.
+    if let Some(event_handler) = &mut self.event_handler {
+      event_handler(BuildEvent::Lowering);
+    }
+
+    let root_package_name = self
+      .source_files
+      .first()
+      .map(|(package_name, _)| package_name);
+    let mut package_names = Vec::new();
+
+    for (package_name, _) in &self.source_files {
+      if !package_names.contains(&package_name) {
+        package_names.push(package_name);
+      }
+    }
+
+    // Each package is lowered into its own module, which is then linked
+    // into the final module.
+    for package_name in package_names {
+      let is_root = Some(package_name) == root_package_name;
+      let package_module = self.llvm_context.create_module(package_name);
+
+      let dependency_cache = if is_root {
+        None
+      } else {
+        self.dependency_cache(package_name)
+      };
+
+      let is_cached = match &dependency_cache {
+        Some((bitcode_path, package_hash)) => {
+          self.load_cached_dependency(&package_module, bitcode_path, package_hash)
+        }
+        None => false,
+      };
+
+      if !is_cached {
+        let mut llvm_generator =
+          gecko::llvm_lowering::LlvmGenerator::new(self.llvm_context, &package_module);
+
+        llvm_generator.module_name = package_name.clone();
+
+        // REVISE: For efficiency, and to solve caching issues, only lower the `main` function
+        // ... of the package being built. Any referenced entity within it (thus the whole
+        // ... program) will be lowered and cached accordingly from there on. This means
+        // ... that referenced functions of dependencies are lowered again into its module.
+        // BUG: Extern functions shouldn't be lowered directly. They are no longer under a wrapper
+        // ... node, which ensures their caching. This means that, first they will be forcefully lowered
+        // ... here (without caching), then when referenced, since they haven't been cached.
+        for (_, _, root_node) in readonly_ast
+          .iter()
+          .filter(|(node_package_name, _, _)| node_package_name == package_name)
+        {
+          if let gecko::ast::NodeKind::Function(function) = &root_node.kind {
+            // Dependencies are lowered as a whole, since they are
+            // compiled once for any package using them. Otherwise, only
+            // lower the main function, which is unique at this point.
+            if !is_root || function.name == gecko::llvm_lowering::MAIN_FUNCTION_NAME {
+              root_node.lower(&mut llvm_generator, &self.cache);
+              self.statistics.nodes_lowered += 1;
+
+              // TODO: Need to manually cache the main function here. This is because
+              // ... if it is called once again, since it isn't cached, it will be re-lowered.
+            }
+          }
+        }
+
+        if !is_root {
+          // The package being built may define the same functions, once
+          // it references them.
+          for function in package_module.get_functions() {
+            if function.count_basic_blocks() > 0 {
+              function.set_linkage(inkwell::module::Linkage::LinkOnceODR);
+            }
+          }
+        }
+
+        if let Some((bitcode_path, package_hash)) = &dependency_cache {
+          if let Err(error) = store_cached_dependency(&package_module, bitcode_path, package_hash) {
+            log::warn!("failed to cache dependency `{}`: {}", package_name, error);
+          }
         }
       }
+
+      if let Err(error) = self.llvm_module.link_in_module(package_module) {
+        diagnostics.push(DriverDiagnostic::new(
+          Some(package_name.clone()),
+          gecko::diagnostic::Diagnostic {
+            severity: gecko::diagnostic::Severity::Error,
+            message: format!("failed to link the module: {}", error),
+            span: None,
+          },
+        ));
+      }
+    }
+
+    self.record_timing("lowering", &mut phase_start);
+
+    if self.debug_info && !diagnostics.iter().any(DriverDiagnostic::is_error) {
+      let source_maps = self
+        .file_contents
+        .iter()
+        .map(|(source_file, source_code)| {
+          (
+            source_file.clone(),
+            crate::debug_info::SourceMap::new(source_code, &lex_source_code(source_code)),
+          )
+        })
+        .collect::<std::collections::HashMap<_, _>>();
+
+      let function_definitions = readonly_ast
+        .iter()
+        .filter_map(|(_, source_file, root_node)| match &root_node.kind {
+          gecko::ast::NodeKind::Function(function) => Some(crate::debug_info::FunctionDefinition {
+            name: function.name.clone(),
+            source_file: source_file.clone(),
+          }),
+          _ => None,
+        })
+        .collect::<Vec<_>>();
+
+      if let Some((_, root_file)) = self.source_files.first() {
+        crate::debug_info::describe_functions(
+          self.llvm_context,
+          self.llvm_module,
+          root_file,
+          &source_maps,
+          &function_definitions,
+        );
+      }
+
+      self.record_timing("debug_info", &mut phase_start);
+    }
+
+    // An invalid module is never the user's fault, but would otherwise
+    // surface later as an obscure LLVM failure (or a miscompilation).
+    if self.verify && !diagnostics.iter().any(DriverDiagnostic::is_error) {
+      if let Err(error) = self.llvm_module.verify() {
+        diagnostics.push(DriverDiagnostic::new(
+          self
+            .source_files
+            .first()
+            .map(|(package_name, _)| package_name.clone()),
+          gecko::diagnostic::Diagnostic {
+            severity: gecko::diagnostic::Severity::Error,
+            message: format!(
+              "internal compiler error: the lowered module is invalid: {}\n\
+              note: this is a bug in the compiler; please report it, attaching the archive \
+              produced by `grip build --record <archive>`",
+              error.to_string().trim_end()
+            ),
+            span: None,
+          },
+        ));
+      }
+
+      self.record_timing("verification", &mut phase_start);
     }
 
     // TODO: We should have diagnostics ordered/sorted (by severity then phase).
     diagnostics
   }
+
+  /// Verify that a source file's module doesn't clash with that of a
+  /// previously parsed file of the same package. Identical modules are
+  /// an error, since one would shadow the other. Modules differing only
+  /// by case are a warning, since they would clash on case-insensitive
+  /// file systems (such as on Windows or macOS).
+  fn check_module_clash(
+    module_paths: &std::collections::HashMap<(String, String), std::path::PathBuf>,
+    global_qualifier: &(String, String),
+    source_file: &std::path::Path,
+  ) -> Option<DriverDiagnostic> {
+    let (package_name, module_name) = global_qualifier;
+
+    let (severity, existing_file) = match module_paths.get(global_qualifier) {
+      Some(existing_file) => (gecko::diagnostic::Severity::Error, existing_file),
+      None => module_paths
+        .iter()
+        .find_map(|((other_package_name, other_module_name), path)| {
+          if other_package_name == package_name
+            && other_module_name.to_lowercase() == module_name.to_lowercase()
+          {
+            Some((gecko::diagnostic::Severity::Warning, path))
+          } else {
+            None
+          }
+        })?,
+    };
+
+    Some(
+      DriverDiagnostic::new(
+        Some(package_name.clone()),
+        gecko::diagnostic::Diagnostic {
+          message: if severity == gecko::diagnostic::Severity::Error {
+            format!(
+              "module `{}` is defined by both `{}` and `{}`",
+              module_name,
+              existing_file.display(),
+              source_file.display()
+            )
+          } else {
+            format!(
+              "modules of `{}` and `{}` differ only by case, and would clash on case-insensitive file systems",
+              existing_file.display(),
+              source_file.display()
+            )
+          },
+          severity,
+          span: None,
+        },
+      )
+      .with_source_file(source_file),
+    )
+  }
+
+  /// Verify that exactly one `main` function exists across all
+  /// modules, and that its signature is that of an entry point.
+  fn check_main_function(
+    ast: &std::collections::HashMap<(String, String), Vec<gecko::ast::Node>>,
+  ) -> Vec<DriverDiagnostic> {
+    let mut main_functions = Vec::new();
+
+    for ((package_name, module_name), root_nodes) in ast {
+      for root_node in root_nodes {
+        if let gecko::ast::NodeKind::Function(function) = &root_node.kind {
+          if function.name == gecko::llvm_lowering::MAIN_FUNCTION_NAME {
+            main_functions.push((package_name, module_name, function));
+          }
+        }
+      }
+    }
+
+    // Keep the output deterministic, regardless of the map's order.
+    main_functions.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+    let error = |package_name: Option<&String>, message: String| {
+      DriverDiagnostic::new(
+        package_name.cloned(),
+        gecko::diagnostic::Diagnostic {
+          severity: gecko::diagnostic::Severity::Error,
+          message,
+          // TODO: Point at the definitions, once nodes carry their spans.
+          span: None,
+        },
+      )
+    };
+
+    match main_functions.as_slice() {
+      [] => vec![error(None, "no main function defined".to_string())],
+      [(package_name, _, function)] => {
+        // REVIEW: Should the return type be verified as well?
+        if !function.signature.parameters.is_empty() || function.signature.is_variadic {
+          vec![error(
+            Some(package_name),
+            "the main function must not take any parameters".to_string(),
+          )]
+        } else {
+          Vec::new()
+        }
+      }
+      _ => vec![error(
+        None,
+        format!(
+          "exactly one main function must be defined, but found {}: {}",
+          main_functions.len(),
+          main_functions
+            .iter()
+            .map(|(package_name, module_name, _)| format!("`{}::{}`", package_name, module_name))
+            .collect::<Vec<_>>()
+            .join(", ")
+        ),
+      )],
+    }
+  }
+
+  fn record_timing(&mut self, phase: &str, phase_start: &mut std::time::Instant) {
+    self
+      .timings
+      .push((phase.to_string(), phase_start.elapsed()));
+
+    *phase_start = std::time::Instant::now();
+  }
+}
+
+/// Read and lex a source file, keeping only the relevant tokens.
+fn read_source_file(
+  file_contents: &std::collections::HashMap<std::path::PathBuf, String>,
+  source_file: &std::path::PathBuf,
+) -> String {
+  // FIXME: Performing unsafe operations temporarily.
+
+  // Sources which don't live on disk (such as stdin) are provided beforehand.
+  match file_contents.get(source_file) {
+    Some(contents) => contents.clone(),
+    None => package::fetch_file_contents(&source_file).unwrap(),
+  }
+}
+
+fn lex_source_file(
+  file_contents: &std::collections::HashMap<std::path::PathBuf, String>,
+  source_file: &std::path::PathBuf,
+) -> Vec<gecko::lexer::Token> {
+  lex_source_code(&read_source_file(file_contents, source_file))
+}
+
+fn lex_source_code(source_code: &str) -> Vec<gecko::lexer::Token> {
+  // BUG: This will fail if there were lexing errors. Unsafe unwrap.
+  try_lex_source_code(source_code).unwrap()
+}
+
+fn try_lex_source_code(
+  source_code: &str,
+) -> Result<Vec<gecko::lexer::Token>, gecko::diagnostic::Diagnostic> {
+  let tokens = gecko::lexer::Lexer::from_str(source_code).lex_all()?;
+
+  // FIXME: What about illegal tokens?
+  // TODO: This might be inefficient for larger programs, so consider passing an option to the lexer.
+  // Filter tokens to only include those that are relevant (ignore whitespace, comments, etc.).
+  Ok(
+    tokens
+      .into_iter()
+      .filter(|token| {
+        !matches!(
+          token.0,
+          gecko::lexer::TokenKind::Whitespace(_) | gecko::lexer::TokenKind::Comment(_)
+        )
+      })
+      .collect(),
+  )
+}
+
+/// Read and lex source files on up to `jobs` threads. The contents and
+/// tokens of each file are returned in the same order as the files.
+fn lex_source_files(
+  file_contents: &std::collections::HashMap<std::path::PathBuf, String>,
+  source_files: &[(String, std::path::PathBuf)],
+  jobs: usize,
+) -> Vec<(String, Vec<gecko::lexer::Token>)> {
+  if source_files.is_empty() {
+    return Vec::new();
+  }
+
+  let jobs = std::cmp::max(jobs, 1);
+  let chunk_size = (source_files.len() + jobs - 1) / jobs;
+
+  std::thread::scope(|scope| {
+    let handles = source_files
+      .chunks(chunk_size)
+      .map(|chunk| {
+        scope.spawn(move || {
+          chunk
+            .iter()
+            .map(|(_, source_file)| {
+              let source_code = read_source_file(file_contents, source_file);
+              let tokens = lex_source_code(&source_code);
+
+              (source_code, tokens)
+            })
+            .collect::<Vec<_>>()
+        })
+      })
+      .collect::<Vec<_>>();
+
+    handles
+      .into_iter()
+      .flat_map(|handle| {
+        handle
+          .join()
+          .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+      })
+      .collect()
+  })
+}
+
+fn store_cached_dependency(
+  package_module: &inkwell::module::Module<'_>,
+  bitcode_path: &std::path::Path,
+  package_hash: &str,
+) -> Result<(), String> {
+  if let Some(deps_dir) = bitcode_path.parent() {
+    if let Err(error) = std::fs::create_dir_all(deps_dir) {
+      return Err(format!(
+        "failed to create the dependencies directory: {}",
+        error
+      ));
+    }
+  }
+
+  if !package_module.write_bitcode_to_path(bitcode_path) {
+    return Err("failed to write the module".to_string());
+  }
+
+  if let Err(error) = std::fs::write(
+    bitcode_path.with_extension(PATH_DEPS_HASH_EXTENSION),
+    package_hash,
+  ) {
+    return Err(format!("failed to write the module's hash: {}", error));
+  }
+
+  Ok(())
+}
+
+/// Format the top-level nodes of a parsed file as a pretty-printed tree,
+/// preceded by a comment naming the file.
+pub fn format_parsed_file(
+  package_name: &str,
+  source_file: &std::path::Path,
+  nodes: &[String],
+) -> String {
+  let mut output = format!("// {}: {}\n", package_name, source_file.display());
+
+  for node in nodes {
+    output.push_str(node);
+    output.push('\n');
+  }
+
+  output
+}
+
+/// Run the standard LLVM optimization pipeline on a module.
+pub fn optimize(llvm_module: &inkwell::module::Module<'_>, opt_level: OptLevel) {
+  let pass_manager_builder = inkwell::passes::PassManagerBuilder::create();
+  let pass_manager = inkwell::passes::PassManager::create(());
+
+  pass_manager_builder.set_optimization_level(opt_level.llvm_level());
+  pass_manager_builder.set_size_level(opt_level.size_level());
+  pass_manager_builder.populate_module_pass_manager(&pass_manager);
+  pass_manager.run_on(llvm_module);
+}
+
+/// Retrieve the directory where the artifacts of a build are written,
+/// so that builds for different targets and profiles don't overwrite
+/// each other's artifacts.
+pub fn artifact_dir(target_triple: &str, profile: &str) -> std::path::PathBuf {
+  std::path::PathBuf::from(crate::DEFAULT_OUTPUT_DIR)
+    .join(target_triple)
+    .join(profile)
+}
+
+/// Retrieve the directory where the result of a package's last build is
+/// cached. Each member of a workspace has its own.
+pub fn cache_dir(package_name: &str) -> std::path::PathBuf {
+  std::path::PathBuf::from(crate::DEFAULT_OUTPUT_DIR)
+    .join(PATH_CACHE_DIR)
+    .join(package_name)
+}
+
+/// Retrieve the directory where the modules of a package's dependencies
+/// are cached. Each member of a workspace has its own, since members may
+/// enable different features of the same dependencies.
+pub fn deps_dir(package_name: &str) -> std::path::PathBuf {
+  std::path::PathBuf::from(crate::DEFAULT_OUTPUT_DIR)
+    .join(PATH_DEPS_DIR)
+    .join(package_name)
+}
+
+/// Retrieve the path of a package's executable within an artifact
+/// directory.
+pub fn executable_path(artifact_dir: &std::path::Path, package_name: &str) -> std::path::PathBuf {
+  let mut executable_path = artifact_dir.join(package_name);
+
+  executable_path.set_extension(std::env::consts::EXE_EXTENSION);
+
+  executable_path
+}
+
+/// Apply a profile's panic strategy to the functions defined in a
+/// lowered module. When aborting, no function can unwind, which lets
+/// LLVM drop the unwinding tables.
+pub fn apply_panic_strategy(
+  llvm_module: &inkwell::module::Module<'_>,
+  panic_strategy: package::PanicStrategy,
+) {
+  if panic_strategy != package::PanicStrategy::Abort {
+    return;
+  }
+
+  let nounwind = llvm_module.get_context().create_enum_attribute(
+    inkwell::attributes::Attribute::get_named_enum_kind_id("nounwind"),
+    0,
+  );
+
+  for function in llvm_module.get_functions() {
+    // Declarations are external, and may unwind.
+    if function.count_basic_blocks() > 0 {
+      function.add_attribute(inkwell::attributes::AttributeLoc::Function, nounwind);
+    }
+  }
+}
+
+/// Create a target machine for the target being built for (the host,
+/// unless `--target` is given).
+pub fn create_target_machine(
+  optimization_level: inkwell::OptimizationLevel,
+  reloc_mode: inkwell::targets::RelocMode,
+) -> Result<inkwell::targets::TargetMachine, String> {
+  let is_host_target = package::is_host_target();
+
+  if is_host_target {
+    inkwell::targets::Target::initialize_native(&inkwell::targets::InitializationConfig::default())?;
+  } else {
+    inkwell::targets::Target::initialize_all(&inkwell::targets::InitializationConfig::default());
+  }
+
+  let target_triple = inkwell::targets::TargetTriple::create(&package::target_triple());
+
+  let target = match inkwell::targets::Target::from_triple(&target_triple) {
+    Ok(target) => target,
+    Err(error) => return Err(format!("failed to resolve the target: {}", error)),
+  };
+
+  // The host's CPU (and its features) only applies to the host.
+  let (cpu_name, cpu_features) = if is_host_target {
+    (
+      inkwell::targets::TargetMachine::get_host_cpu_name().to_string(),
+      inkwell::targets::TargetMachine::get_host_cpu_features().to_string(),
+    )
+  } else {
+    ("generic".to_string(), String::new())
+  };
+
+  let target_machine = target.create_target_machine(
+    &target_triple,
+    &cpu_name,
+    &cpu_features,
+    optimization_level,
+    reloc_mode,
+    inkwell::targets::CodeModel::Default,
+  );
+
+  match target_machine {
+    Some(target_machine) => Ok(target_machine),
+    None => Err("failed to create the target machine".to_string()),
+  }
+}
+
+/// The name given to private globals shared across codegen units.
+const CODEGEN_UNIT_GLOBAL_PREFIX: &str = "grip.cgu.global.";
+
+/// Compile one of the codegen units of a module, given as bitcode. Each
+/// unit defines its share of the functions, and only declares the rest.
+fn emit_codegen_unit(
+  bitcode: &[u8],
+  unit: usize,
+  codegen_units: usize,
+  profile: &package::Profile,
+) -> Result<Vec<u8>, String> {
+  // Contexts can't be shared across threads.
+  let llvm_context = inkwell::context::Context::create();
+
+  let memory_buffer =
+    inkwell::memory_buffer::MemoryBuffer::create_from_memory_range_copy(bitcode, "codegen-unit");
+
+  let unit_module =
+    match inkwell::module::Module::parse_bitcode_from_buffer(&memory_buffer, &llvm_context) {
+      Ok(unit_module) => unit_module,
+      Err(error) => return Err(format!("failed to read codegen unit {}: {}", unit, error)),
+    };
+
+  let defined_functions = unit_module
+    .get_functions()
+    .filter(|function| function.count_basic_blocks() > 0);
+
+  // Definitions kept by another unit become `available_externally`, so
+  // that they aren't emitted here. Since they are referenced across
+  // units, none of them may remain private.
+  for (index, function) in defined_functions.enumerate() {
+    function.set_linkage(if index % codegen_units == unit {
+      inkwell::module::Linkage::External
+    } else {
+      inkwell::module::Linkage::AvailableExternally
+    });
+  }
+
+  // Constant private globals (such as string literals) are duplicated
+  // ... into every unit which uses them. Mutable ones must have a single
+  // ... definition, so they are promoted to hidden globals (named alike
+  // ... in every unit), and defined by the first unit.
+  for (index, global) in unit_module.get_globals().enumerate() {
+    let is_local = matches!(
+      global.get_linkage(),
+      inkwell::module::Linkage::Private | inkwell::module::Linkage::Internal
+    );
+
+    if is_local {
+      if global.is_constant() {
+        continue;
+      }
+
+      global
+        .as_pointer_value()
+        .set_name(&format!("{}{}", CODEGEN_UNIT_GLOBAL_PREFIX, index));
+
+      global.set_linkage(inkwell::module::Linkage::External);
+      global.set_visibility(inkwell::GlobalVisibility::Hidden);
+    }
+
+    if unit != 0 && global.get_initializer().is_some() {
+      global.set_linkage(inkwell::module::Linkage::AvailableExternally);
+    }
+  }
+
+  emit(&unit_module, EmitKind::Object, profile)
+}
+
+/// Emit a lowered module as object files (one per codegen unit of the
+/// profile), of which up to `jobs` are compiled in parallel. The first
+/// (or only) unit is written to the given path, and the rest alongside it.
+pub fn write_objects(
+  llvm_module: &inkwell::module::Module<'_>,
+  profile: &package::Profile,
+  object_path: &std::path::Path,
+  jobs: usize,
+) -> Result<Vec<std::path::PathBuf>, String> {
+  let defined_function_count = llvm_module
+    .get_functions()
+    .filter(|function| function.count_basic_blocks() > 0)
+    .count();
+
+  let codegen_units = profile.codegen_units.min(defined_function_count).max(1);
+
+  let objects = if codegen_units == 1 {
+    vec![emit(llvm_module, EmitKind::Object, profile)?]
+  } else {
+    let bitcode = llvm_module.write_bitcode_to_memory().as_slice().to_vec();
+
+    // Each worker compiles every `workers`-th unit.
+    let workers = codegen_units.min(std::cmp::max(jobs, 1));
+
+    let mut unit_objects = std::thread::scope(|scope| {
+      let handles = (0..workers)
+        .map(|worker| {
+          let bitcode = &bitcode;
+
+          scope.spawn(move || {
+            (worker..codegen_units)
+              .step_by(workers)
+              .map(|unit| {
+                emit_codegen_unit(bitcode, unit, codegen_units, profile)
+                  .map(|object| (unit, object))
+              })
+              .collect::<Result<Vec<_>, String>>()
+          })
+        })
+        .collect::<Vec<_>>();
+
+      handles
+        .into_iter()
+        .map(|handle| {
+          handle
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+        .collect::<Result<Vec<_>, String>>()
+    })?
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+
+    unit_objects.sort_by_key(|(unit, _)| *unit);
+
+    unit_objects.into_iter().map(|(_, object)| object).collect()
+  };
+
+  let mut object_paths = Vec::new();
+
+  for (unit, object) in objects.iter().enumerate() {
+    let unit_object_path = if unit == 0 {
+      object_path.to_path_buf()
+    } else {
+      object_path.with_extension(format!("{}.{}", unit, EmitKind::Object.extension()))
+    };
+
+    if let Err(error) = std::fs::write(&unit_object_path, object) {
+      return Err(format!(
+        "failed to write object file `{}`: {}",
+        unit_object_path.display(),
+        error
+      ));
+    }
+
+    object_paths.push(unit_object_path);
+  }
+
+  Ok(object_paths)
+}
+
+/// Produce the requested output from a lowered module.
+pub fn emit(
+  llvm_module: &inkwell::module::Module<'_>,
+  emit_kind: EmitKind,
+  profile: &package::Profile,
+) -> Result<Vec<u8>, String> {
+  match emit_kind {
+    EmitKind::Ir => Ok(llvm_module.print_to_string().to_bytes().to_vec()),
+    EmitKind::Bitcode => Ok(llvm_module.write_bitcode_to_memory().as_slice().to_vec()),
+    EmitKind::Assembly | EmitKind::Object => {
+      let reloc_mode = if profile.pie {
+        inkwell::targets::RelocMode::PIC
+      } else {
+        inkwell::targets::RelocMode::Default
+      };
+
+      let target_machine = create_target_machine(inkwell::OptimizationLevel::Default, reloc_mode)?;
+
+      let file_type = if emit_kind == EmitKind::Assembly {
+        inkwell::targets::FileType::Assembly
+      } else {
+        inkwell::targets::FileType::Object
+      };
+
+      match target_machine.write_to_memory_buffer(llvm_module, file_type) {
+        Ok(memory_buffer) => Ok(memory_buffer.as_slice().to_vec()),
+        Err(error) => Err(format!("failed to emit machine code: {}", error)),
+      }
+    }
+    EmitKind::Executable => {
+      Err("executables are produced by linking an object file, not emitted directly".to_string())
+    }
+    EmitKind::Tokens | EmitKind::Ast => {
+      Err("tokens and AST dumps are not produced from a lowered module".to_string())
+    }
+  }
 }
@@ -0,0 +1,168 @@
+use crate::{config, package};
+
+const PATH_CACHE: &str = "cache";
+const PATH_KNOWN_PROJECTS: &str = "projects";
+
+pub struct CacheEntry {
+  pub path: std::path::PathBuf,
+  pub size: u64,
+  pub modified: std::time::SystemTime,
+}
+
+/// Retrieve the directory of the user-wide cache.
+pub fn cache_dir() -> Result<std::path::PathBuf, String> {
+  Ok(config::grip_home()?.join(PATH_CACHE))
+}
+
+/// List all entries in the user-wide cache.
+pub fn entries() -> Result<Vec<CacheEntry>, String> {
+  let cache_dir = cache_dir()?;
+
+  if !cache_dir.exists() {
+    return Ok(Vec::new());
+  }
+
+  let read_dir = match std::fs::read_dir(&cache_dir) {
+    Ok(read_dir) => read_dir,
+    Err(error) => return Err(format!("failed to read the cache directory: {}", error)),
+  };
+
+  let mut entries = Vec::new();
+
+  for dir_entry in read_dir.flatten() {
+    let path = dir_entry.path();
+
+    let metadata = match dir_entry.metadata() {
+      Ok(metadata) => metadata,
+      Err(error) => return Err(format!("failed to read cache entry metadata: {}", error)),
+    };
+
+    entries.push(CacheEntry {
+      size: if metadata.is_dir() {
+        directory_size(&path)
+      } else {
+        metadata.len()
+      },
+      modified: metadata
+        .modified()
+        .unwrap_or(std::time::SystemTime::UNIX_EPOCH),
+      path,
+    });
+  }
+
+  Ok(entries)
+}
+
+/// Copy a file into the user-wide cache, under the given name.
+pub fn store(file_path: &std::path::Path, name: &str) -> Result<(), String> {
+  let cache_dir = cache_dir()?;
+
+  if let Err(error) = std::fs::create_dir_all(&cache_dir) {
+    return Err(format!("failed to create the cache directory: {}", error));
+  }
+
+  if let Err(error) = std::fs::copy(file_path, cache_dir.join(name)) {
+    return Err(format!(
+      "failed to store `{}` in the cache: {}",
+      name, error
+    ));
+  }
+
+  Ok(())
+}
+
+pub fn remove_entry(entry: &CacheEntry) -> Result<(), String> {
+  let remove_result = if entry.path.is_dir() {
+    std::fs::remove_dir_all(&entry.path)
+  } else {
+    std::fs::remove_file(&entry.path)
+  };
+
+  if let Err(error) = remove_result {
+    return Err(format!(
+      "failed to remove cache entry `{}`: {}",
+      entry.path.display(),
+      error
+    ));
+  }
+
+  Ok(())
+}
+
+/// Remember a project's root directory, so that the cache entries it
+/// references are preserved when cleaning the cache.
+pub fn record_project(project_dir: &std::path::Path) -> Result<(), String> {
+  let mut known_projects = known_projects()?;
+
+  if known_projects
+    .iter()
+    .any(|known_project| known_project == project_dir)
+  {
+    return Ok(());
+  }
+
+  known_projects.push(project_dir.to_path_buf());
+
+  let known_projects_path = config::grip_home()?.join(PATH_KNOWN_PROJECTS);
+
+  let contents = known_projects
+    .iter()
+    .map(|known_project| known_project.to_string_lossy().to_string())
+    .collect::<Vec<_>>()
+    .join("\n");
+
+  if let Err(error) = std::fs::create_dir_all(config::grip_home()?)
+    .and_then(|_| std::fs::write(known_projects_path, contents))
+  {
+    return Err(format!("failed to record the project: {}", error));
+  }
+
+  Ok(())
+}
+
+pub fn known_projects() -> Result<Vec<std::path::PathBuf>, String> {
+  let known_projects_path = config::grip_home()?.join(PATH_KNOWN_PROJECTS);
+
+  if !known_projects_path.exists() {
+    return Ok(Vec::new());
+  }
+
+  Ok(
+    package::fetch_file_contents(&known_projects_path)?
+      .lines()
+      .filter(|line| !line.trim().is_empty())
+      .map(std::path::PathBuf::from)
+      .collect(),
+  )
+}
+
+/// Determine whether a cache entry belongs to a dependency of any of
+/// the known projects which still exist.
+pub fn is_referenced(entry: &CacheEntry, known_projects: &[std::path::PathBuf]) -> bool {
+  let entry_name = match entry.path.file_stem() {
+    Some(file_stem) => file_stem.to_string_lossy().to_string(),
+    None => return false,
+  };
+
+  known_projects.iter().any(|known_project| {
+    package::fetch_manifest(&known_project.join(package::PATH_MANIFEST_FILE))
+      .map(|manifest| manifest.declares_dependency(&entry_name))
+      .unwrap_or(false)
+  })
+}
+
+pub fn directory_size(path: &std::path::Path) -> u64 {
+  let read_dir = match std::fs::read_dir(path) {
+    Ok(read_dir) => read_dir,
+    Err(_) => return 0,
+  };
+
+  read_dir
+    .flatten()
+    .map(|dir_entry| match dir_entry.metadata() {
+      Ok(metadata) if metadata.is_dir() => directory_size(&dir_entry.path()),
+      Ok(metadata) => metadata.len(),
+      Err(_) => 0,
+    })
+    .sum()
+}
@@ -0,0 +1,71 @@
+use sha2::Digest;
+
+pub fn sha256_bytes(bytes: &[u8]) -> String {
+  format!("{:x}", sha2::Sha256::digest(bytes))
+}
+
+pub fn sha256_file(path: &std::path::Path) -> Result<String, String> {
+  match std::fs::read(path) {
+    Ok(bytes) => Ok(sha256_bytes(&bytes)),
+    Err(error) => Err(format!(
+      "failed to read `{}` for hashing: {}",
+      path.display(),
+      error
+    )),
+  }
+}
+
+/// Compute a checksum of a directory's tree, covering the relative
+/// paths and contents of its files. Git metadata is not covered.
+pub fn sha256_dir(path: &std::path::Path) -> Result<String, String> {
+  let mut file_paths = Vec::new();
+  let mut pending_dirs = vec![path.to_path_buf()];
+
+  while let Some(dir) = pending_dirs.pop() {
+    let read_dir = match std::fs::read_dir(&dir) {
+      Ok(read_dir) => read_dir,
+      Err(error) => {
+        return Err(format!(
+          "failed to read `{}` for hashing: {}",
+          dir.display(),
+          error
+        ))
+      }
+    };
+
+    for dir_entry in read_dir.flatten() {
+      let entry_path = dir_entry.path();
+
+      if entry_path.is_dir() {
+        if dir_entry.file_name() != ".git" {
+          pending_dirs.push(entry_path);
+        }
+      } else {
+        file_paths.push(entry_path);
+      }
+    }
+  }
+
+  let mut hashed_files = Vec::new();
+
+  for file_path in file_paths {
+    // Paths always use forward slashes, so that checksums are portable.
+    let relative_path = file_path
+      .strip_prefix(path)
+      .unwrap_or(&file_path)
+      .to_string_lossy()
+      .replace('\\', "/");
+
+    hashed_files.push((relative_path, sha256_file(&file_path)?));
+  }
+
+  hashed_files.sort();
+
+  let mut tree_listing = String::new();
+
+  for (relative_path, file_checksum) in hashed_files {
+    tree_listing.push_str(&format!("{}  {}\n", file_checksum, relative_path));
+  }
+
+  Ok(sha256_bytes(tree_listing.as_bytes()))
+}
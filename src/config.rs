@@ -0,0 +1,141 @@
+const PATH_GRIP_HOME: &str = ".grip";
+const PATH_CONFIG_FILE: &str = "config.toml";
+const PATH_CREDENTIALS_FILE: &str = "credentials.toml";
+const ENV_GITHUB_TOKEN: &str = "GRIP_GITHUB_TOKEN";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub enum RegistryKind {
+  #[serde(rename = "github")]
+  GitHub,
+  #[serde(rename = "http")]
+  Http,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct RegistryConfig {
+  pub kind: RegistryKind,
+  pub url: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub enum IndexKind {
+  #[serde(rename = "http")]
+  Http,
+  #[serde(rename = "git")]
+  Git,
+}
+
+/// An index mapping bare package names (such as `json`) to their
+/// source, either a JSON document served over HTTP(S), or a git
+/// repository containing one as `index.json`.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct IndexConfig {
+  pub kind: IndexKind,
+  pub url: String,
+  /// The branch of a git index to use.
+  pub branch: Option<String>,
+}
+
+/// Limits applied to network operations, and to the extraction of
+/// downloaded archives. Timeouts are in seconds, and sizes in bytes.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct NetworkConfig {
+  pub connect_timeout: u64,
+  pub read_timeout: u64,
+  pub max_archive_size: u64,
+  pub max_extracted_entries: usize,
+  pub max_extracted_size: u64,
+  /// How many times a failed download is retried (resuming from where
+  /// it stopped, if possible).
+  pub retries: u32,
+}
+
+impl Default for NetworkConfig {
+  fn default() -> Self {
+    Self {
+      connect_timeout: 10,
+      read_timeout: 30,
+      max_archive_size: 100 * 1024 * 1024,
+      max_extracted_entries: 10_000,
+      max_extracted_size: 500 * 1024 * 1024,
+      retries: 3,
+    }
+  }
+}
+
+/// User-wide configuration, stored under `~/.grip/config.toml`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+pub struct Config {
+  pub registry: Option<RegistryConfig>,
+  pub index: Option<IndexConfig>,
+  #[serde(default)]
+  pub network: NetworkConfig,
+  /// The command of an external formatter (such as `gecko-fmt`) used by
+  /// `grip fmt` instead of the built-in one. It reads the source code
+  /// from its standard input, and writes it formatted to its standard
+  /// output.
+  pub formatter: Option<String>,
+}
+
+#[derive(serde::Deserialize, Default)]
+struct GitHubCredentials {
+  token: Option<String>,
+}
+
+/// Secrets used to access registries, stored apart from the
+/// configuration under `~/.grip/credentials.toml`.
+#[derive(serde::Deserialize, Default)]
+struct Credentials {
+  #[serde(default)]
+  github: GitHubCredentials,
+}
+
+/// Retrieve the directory where grip stores user-wide state.
+pub fn grip_home() -> Result<std::path::PathBuf, String> {
+  let home_dir = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+
+  match home_dir {
+    Some(home_dir) => Ok(std::path::PathBuf::from(home_dir).join(PATH_GRIP_HOME)),
+    None => Err("failed to determine the user's home directory".to_string()),
+  }
+}
+
+/// Read the user-wide configuration file, falling back to the
+/// default configuration if it does not exist.
+pub fn fetch_config() -> Result<Config, String> {
+  let config_path = grip_home()?.join(PATH_CONFIG_FILE);
+
+  if !config_path.exists() {
+    return Ok(Config::default());
+  }
+
+  let config_contents = crate::package::fetch_file_contents(&config_path)?;
+
+  match toml::from_str::<Config>(config_contents.as_str()) {
+    Ok(config) => Ok(config),
+    Err(error) => Err(format!("failed to parse configuration file: {}", error)),
+  }
+}
+
+/// Retrieve the token to authenticate GitHub requests with (allowing
+/// access to private repositories, and higher rate limits), from the
+/// `GRIP_GITHUB_TOKEN` environment variable or the credentials file.
+pub fn github_token() -> Result<Option<String>, String> {
+  if let Some(token) = std::env::var_os(ENV_GITHUB_TOKEN) {
+    return Ok(Some(token.to_string_lossy().to_string()).filter(|token| !token.is_empty()));
+  }
+
+  let credentials_path = grip_home()?.join(PATH_CREDENTIALS_FILE);
+
+  if !credentials_path.exists() {
+    return Ok(None);
+  }
+
+  let credentials_contents = crate::package::fetch_file_contents(&credentials_path)?;
+
+  match toml::from_str::<Credentials>(credentials_contents.as_str()) {
+    Ok(credentials) => Ok(credentials.github.token),
+    Err(error) => Err(format!("failed to parse credentials file: {}", error)),
+  }
+}
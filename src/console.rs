@@ -12,18 +12,20 @@ impl log::Log for Logger {
       // TODO: Use lighter colors.
       // TODO: There is an extra newline at the end of the message. Is this causing it?
 
-      println!(
-        // TODO: Width not working because of the color codes.
-        "{:>7}: {}",
-        match record.level() {
-          log::Level::Error => ansi_term::Colour::Red.paint("error"),
-          log::Level::Warn => ansi_term::Colour::Yellow.paint("warning"),
-          log::Level::Info => ansi_term::Colour::Cyan.paint("info"),
-          log::Level::Debug => ansi_term::Colour::Purple.paint("debug"),
-          log::Level::Trace => ansi_term::Colour::White.paint("trace"),
-        },
-        record.args()
-      );
+      suspend_progress(|| {
+        println!(
+          // TODO: Width not working because of the color codes.
+          "{:>7}: {}",
+          match record.level() {
+            log::Level::Error => ansi_term::Colour::Red.paint("error"),
+            log::Level::Warn => ansi_term::Colour::Yellow.paint("warning"),
+            log::Level::Info => ansi_term::Colour::Cyan.paint("info"),
+            log::Level::Debug => ansi_term::Colour::Purple.paint("debug"),
+            log::Level::Trace => ansi_term::Colour::White.paint("trace"),
+          },
+          record.args()
+        )
+      });
     }
   }
 
@@ -32,16 +34,172 @@ impl log::Log for Logger {
   }
 }
 
+static QUIET: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Suppress status lines and progress bars. Diagnostics are still
+/// printed.
+pub fn set_quiet(quiet: bool) {
+  QUIET.store(quiet, std::sync::atomic::Ordering::Relaxed);
+}
+
+fn is_quiet() -> bool {
+  QUIET.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// The progress bar of the build currently in progress, if any. Anything
+/// printed while it is shown must go through `suspend_progress`, or it
+/// would leave a copy of the bar behind.
+static PROGRESS_BAR: std::sync::Mutex<Option<indicatif::ProgressBar>> = std::sync::Mutex::new(None);
+
+/// Show the progress of the current build phase, such as `parsing` (along
+/// with how many of its items are done), creating the progress bar if it
+/// isn't already shown. A length of zero shows the phase only.
+pub fn set_progress(phase: &str, position: u64, length: u64) {
+  if is_quiet() {
+    return;
+  }
+
+  let mut progress_bar = PROGRESS_BAR.lock().unwrap();
+
+  let progress_bar = progress_bar.get_or_insert_with(|| {
+    let progress_bar = indicatif::ProgressBar::new(0);
+
+    progress_bar.set_style(
+      indicatif::ProgressStyle::default_bar().template("{prefix:>12.cyan.bold} [{bar:30}] {msg}"),
+    );
+
+    progress_bar
+  });
+
+  progress_bar.set_prefix(phase.to_string());
+  progress_bar.set_length(length);
+  progress_bar.set_position(position);
+
+  progress_bar.set_message(if length == 0 {
+    String::new()
+  } else {
+    format!("{}/{}", position, length)
+  });
+}
+
+/// Clear the progress bar, once the build is over.
+pub fn finish_progress() {
+  if let Some(progress_bar) = PROGRESS_BAR.lock().unwrap().take() {
+    progress_bar.finish_and_clear();
+  }
+}
+
+/// Hide the progress bar (if shown) while printing, then redraw it below
+/// what was printed.
+pub fn suspend_progress<T>(print: impl FnOnce() -> T) -> T {
+  match PROGRESS_BAR.lock().unwrap().as_ref() {
+    Some(progress_bar) => progress_bar.suspend(print),
+    None => print(),
+  }
+}
+
+/// Print the phase a long-running command is in (such as `Compiling` or
+/// `Linking`), followed by what it applies to.
+pub fn print_status(phase: &str, message: &str) {
+  if is_quiet() {
+    return;
+  }
+
+  suspend_progress(|| {
+    eprintln!(
+      "{} {}",
+      // Pad before painting, since color codes would count towards the width.
+      ansi_term::Colour::Green
+        .bold()
+        .paint(format!("{:>12}", phase)),
+      message
+    )
+  });
+}
+
+/// How verbose printed diagnostics are.
+#[derive(Clone, Copy, PartialEq)]
+pub enum DiagnosticStyle {
+  /// A single line per diagnostic.
+  Short,
+  /// The message, along with the labeled source.
+  Full,
+  /// Like `Full`, but with additional surrounding source lines.
+  Expanded,
+}
+
+impl std::str::FromStr for DiagnosticStyle {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value {
+      "short" => Ok(DiagnosticStyle::Short),
+      "full" => Ok(DiagnosticStyle::Full),
+      "expanded" => Ok(DiagnosticStyle::Expanded),
+      _ => Err(format!("unknown diagnostic style `{}`", value)),
+    }
+  }
+}
+
+static DIAGNOSTIC_STYLE: std::sync::atomic::AtomicU8 = std::sync::atomic::AtomicU8::new(1);
+
+pub fn set_diagnostic_style(diagnostic_style: DiagnosticStyle) {
+  DIAGNOSTIC_STYLE.store(diagnostic_style as u8, std::sync::atomic::Ordering::Relaxed);
+}
+
+fn diagnostic_style() -> DiagnosticStyle {
+  match DIAGNOSTIC_STYLE.load(std::sync::atomic::Ordering::Relaxed) {
+    0 => DiagnosticStyle::Short,
+    2 => DiagnosticStyle::Expanded,
+    _ => DiagnosticStyle::Full,
+  }
+}
+
+pub const DEFAULT_ERROR_LIMIT: usize = 50;
+
+static ERROR_LIMIT: std::sync::atomic::AtomicUsize =
+  std::sync::atomic::AtomicUsize::new(DEFAULT_ERROR_LIMIT);
+
+/// Set the number of errors to print before the rest are only counted.
+/// A limit of zero disables the cap.
+pub fn set_error_limit(error_limit: usize) {
+  ERROR_LIMIT.store(error_limit, std::sync::atomic::Ordering::Relaxed);
+}
+
+pub fn error_limit() -> Option<usize> {
+  match ERROR_LIMIT.load(std::sync::atomic::Ordering::Relaxed) {
+    0 => None,
+    error_limit => Some(error_limit),
+  }
+}
+
+fn create_term_config() -> codespan_reporting::term::Config {
+  let mut config = codespan_reporting::term::Config::default();
+
+  match diagnostic_style() {
+    DiagnosticStyle::Short => config.display_style = codespan_reporting::term::DisplayStyle::Short,
+    DiagnosticStyle::Full => {}
+    DiagnosticStyle::Expanded => {
+      config.start_context_lines *= 2;
+      config.end_context_lines *= 2;
+    }
+  }
+
+  config
+}
+
+/// Print a diagnostic, displaying its span against the given file (if
+/// both are known).
 pub fn print_diagnostic(
-  files: Vec<(&String, &String)>,
+  files: &codespan_reporting::files::SimpleFiles<String, String>,
+  file_id: Option<usize>,
   diagnostic: &gecko::diagnostic::Diagnostic,
 ) {
   let writer = codespan_reporting::term::termcolor::StandardStream::stderr(
     codespan_reporting::term::termcolor::ColorChoice::Auto,
   );
 
-  let config = codespan_reporting::term::Config::default();
-  let mut codespan_files = codespan_reporting::files::SimpleFiles::new();
+  let config = create_term_config();
 
   let mut codespan_diagnostic =
     codespan_reporting::diagnostic::Diagnostic::new(match diagnostic.severity {
@@ -51,28 +209,84 @@ pub fn print_diagnostic(
     .with_message(diagnostic.message.clone());
 
   // Display the source (if applicable).
-  if let Some(span) = &diagnostic.span {
+  if let (Some(span), Some(file_id)) = (&diagnostic.span, file_id) {
     // TODO: Is there a need to re-assign here?
     codespan_diagnostic =
       codespan_diagnostic.with_labels(vec![codespan_reporting::diagnostic::Label::primary(
-        // FIXME: Temporary value. Need actual file id (is this the index?).
-        0,
+        file_id,
         span.clone(),
       )]);
   }
 
-  for file in files {
-    codespan_files.add(file.0, file.1);
-  }
-
-  let emit_result = codespan_reporting::term::emit(
-    &mut writer.lock(),
-    &config,
-    &codespan_files,
-    &codespan_diagnostic,
-  );
+  let emit_result = suspend_progress(|| {
+    codespan_reporting::term::emit(&mut writer.lock(), &config, files, &codespan_diagnostic)
+  });
 
   if let Err(error) = emit_result {
     eprintln!("failed to emit diagnostic to the console: {}", error);
   }
 }
+
+/// Print a diagnostic of a single source file.
+pub fn print_file_diagnostic(
+  file_name: &str,
+  source_code: &str,
+  diagnostic: &gecko::diagnostic::Diagnostic,
+) {
+  let mut files = codespan_reporting::files::SimpleFiles::new();
+  let file_id = files.add(file_name.to_string(), source_code.to_string());
+
+  print_diagnostic(&files, Some(file_id), diagnostic);
+}
+
+fn source_position(source_code: &str, offset: usize) -> crate::schema::SourcePosition {
+  // Offsets past the end (such as at the end of file) are clamped.
+  let preceding = source_code.get(..offset).unwrap_or(source_code);
+  let line_start = preceding.rfind('\n').map_or(0, |index| index + 1);
+
+  crate::schema::SourcePosition {
+    offset,
+    line: preceding.matches('\n').count() + 1,
+    column: preceding[line_start..].chars().count() + 1,
+  }
+}
+
+/// Print a diagnostic as a single line of JSON, for consumption by
+/// editors and other tools.
+pub fn print_json_diagnostic(
+  driver_diagnostic: &crate::build::DriverDiagnostic,
+  source_code: Option<&str>,
+) -> Result<(), String> {
+  let diagnostic = &driver_diagnostic.diagnostic;
+
+  let span = match (&diagnostic.span, source_code) {
+    (Some(span), Some(source_code)) => Some(crate::schema::DiagnosticSpan {
+      start: source_position(source_code, span.start),
+      end: source_position(source_code, span.end),
+    }),
+    _ => None,
+  };
+
+  let diagnostic_message = crate::schema::DiagnosticMessage {
+    package: driver_diagnostic.package_name.clone(),
+    file: driver_diagnostic
+      .source_file
+      .as_ref()
+      .map(|source_file| source_file.display().to_string()),
+    span,
+    severity: match diagnostic.severity {
+      gecko::diagnostic::Severity::Error => "error",
+      gecko::diagnostic::Severity::Warning => "warning",
+    }
+    .to_string(),
+    message: diagnostic.message.clone(),
+    notes: Vec::new(),
+  };
+
+  println!(
+    "{}",
+    crate::schema::to_json("diagnostic", &diagnostic_message)?
+  );
+
+  Ok(())
+}
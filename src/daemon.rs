@@ -0,0 +1,172 @@
+use crate::{package, schema};
+
+/// Responds with the resolved packages, their dependencies, and the
+/// module each of their source files defines.
+const METHOD_PROJECT_MODEL: &str = "grip/projectModel";
+const METHOD_SHUTDOWN: &str = "shutdown";
+const METHOD_EXIT: &str = "exit";
+const ERROR_METHOD_NOT_FOUND: i64 = -32601;
+const ERROR_INTERNAL: i64 = -32603;
+
+/// The project model, along with the modification times of the files
+/// it was computed from.
+struct ProjectModel {
+  inputs: Vec<Option<std::time::SystemTime>>,
+  metadata: serde_json::Value,
+}
+
+fn modified_times() -> Vec<Option<std::time::SystemTime>> {
+  [package::manifest_path(), package::PATH_PACKAGE_LOCK.into()]
+    .iter()
+    .map(|path| {
+      std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+    })
+    .collect()
+}
+
+fn compute_project_model() -> Result<ProjectModel, String> {
+  let inputs = modified_times();
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+
+  let metadata = schema::Metadata {
+    root: package_manifest.name.clone(),
+    packages: package::collect_build_units(&package_manifest)?
+      .iter()
+      .map(schema::package_metadata)
+      .collect(),
+  };
+
+  match serde_json::to_value(&metadata) {
+    Ok(metadata) => Ok(ProjectModel { inputs, metadata }),
+    Err(error) => Err(format!("failed to serialize the project model: {}", error)),
+  }
+}
+
+/// Read a message framed by a `Content-Length` header (as in the
+/// Language Server Protocol). Returns `None` once the input ends.
+fn read_message(reader: &mut impl std::io::BufRead) -> Result<Option<serde_json::Value>, String> {
+  let mut content_length = None;
+
+  loop {
+    let mut line = String::new();
+
+    match reader.read_line(&mut line) {
+      Ok(0) => return Ok(None),
+      Ok(_) => {}
+      Err(error) => return Err(format!("failed to read a message header: {}", error)),
+    }
+
+    let line = line.trim_end();
+
+    // Headers are terminated by an empty line.
+    if line.is_empty() {
+      break;
+    }
+
+    if let Some(value) = line.strip_prefix("Content-Length:") {
+      content_length = value.trim().parse::<usize>().ok();
+    }
+  }
+
+  let content_length = match content_length {
+    Some(content_length) => content_length,
+    None => return Err("message is missing its `Content-Length` header".to_string()),
+  };
+
+  let mut content = vec![0; content_length];
+
+  if let Err(error) = reader.read_exact(&mut content) {
+    return Err(format!("failed to read a message: {}", error));
+  }
+
+  match serde_json::from_slice(&content) {
+    Ok(message) => Ok(Some(message)),
+    Err(error) => Err(format!("failed to parse a message: {}", error)),
+  }
+}
+
+fn write_message(message: &serde_json::Value) -> Result<(), String> {
+  let content = message.to_string();
+  let mut stdout = std::io::stdout();
+
+  let write_result = std::io::Write::write_all(
+    &mut stdout,
+    format!("Content-Length: {}\r\n\r\n{}", content.len(), content).as_bytes(),
+  )
+  .and_then(|_| std::io::Write::flush(&mut stdout));
+
+  if let Err(error) = write_result {
+    return Err(format!("failed to write a message: {}", error));
+  }
+
+  Ok(())
+}
+
+/// Serve JSON-RPC requests over stdin and stdout, until asked to exit.
+/// The project model is kept in memory, and only computed again once
+/// the manifest or the lock file change.
+pub fn run() -> Result<(), String> {
+  let stdin = std::io::stdin();
+  let mut reader = stdin.lock();
+  let mut project_model: Option<ProjectModel> = None;
+
+  while let Some(message) = read_message(&mut reader)? {
+    let method = message["method"].as_str().unwrap_or_default();
+
+    if method == METHOD_EXIT {
+      break;
+    }
+
+    // Notifications have no id, and expect no response.
+    let id = match message.get("id") {
+      Some(id) => id.clone(),
+      None => continue,
+    };
+
+    let result = match method {
+      METHOD_PROJECT_MODEL => {
+        let is_stale = project_model.as_ref().map_or(true, |project_model| {
+          project_model.inputs != modified_times()
+        });
+
+        if is_stale {
+          project_model = None;
+        }
+
+        let current_project_model = match project_model.take() {
+          Some(project_model) => Ok(project_model),
+          None => compute_project_model(),
+        };
+
+        match current_project_model {
+          Ok(current_project_model) => {
+            let metadata = current_project_model.metadata.clone();
+
+            project_model = Some(current_project_model);
+
+            Ok(metadata)
+          }
+          Err(error) => Err((ERROR_INTERNAL, error)),
+        }
+      }
+      METHOD_SHUTDOWN => Ok(serde_json::Value::Null),
+      _ => Err((
+        ERROR_METHOD_NOT_FOUND,
+        format!("unknown method `{}`", method),
+      )),
+    };
+
+    write_message(&match result {
+      Ok(result) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+      Err((code, message)) => serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+      }),
+    })?;
+  }
+
+  Ok(())
+}
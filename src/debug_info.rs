@@ -0,0 +1,217 @@
+use inkwell::debug_info::{AsDIScope, DIFlagsConstants};
+use inkwell::values::AsValueRef;
+
+/// Maps byte offsets of a source file to lines and columns.
+pub struct SourceMap {
+  source_code: String,
+  /// The byte offset at which each line starts.
+  line_starts: Vec<usize>,
+  /// The byte offset of the name of each function defined in the file.
+  function_offsets: std::collections::HashMap<String, usize>,
+}
+
+impl SourceMap {
+  /// Map a source file, given its (significant) tokens.
+  pub fn new(source_code: &str, tokens: &[gecko::lexer::Token]) -> Self {
+    let line_starts = std::iter::once(0)
+      .chain(source_code.match_indices('\n').map(|(index, _)| index + 1))
+      .collect();
+
+    let mut function_offsets = std::collections::HashMap::new();
+
+    for window in tokens.windows(2) {
+      if let (gecko::lexer::TokenKind::KeywordFn, gecko::lexer::TokenKind::Identifier(name)) =
+        (&window[0].0, &window[1].0)
+      {
+        function_offsets
+          .entry(name.clone())
+          .or_insert(window[1].1.start);
+      }
+    }
+
+    Self {
+      source_code: source_code.to_string(),
+      line_starts,
+      function_offsets,
+    }
+  }
+
+  /// Retrieve the line and column (both starting at 1) of a byte offset.
+  pub fn line_column(&self, offset: usize) -> (u32, u32) {
+    let line_index = match self.line_starts.binary_search(&offset) {
+      Ok(line_index) => line_index,
+      Err(next_line_index) => next_line_index - 1,
+    };
+
+    let column = self.source_code[self.line_starts[line_index]..offset]
+      .chars()
+      .count();
+
+    (line_index as u32 + 1, column as u32 + 1)
+  }
+
+  /// Find the line and column at which a function is defined.
+  pub fn find_function(&self, name: &str) -> Option<(u32, u32)> {
+    self
+      .function_offsets
+      .get(name)
+      .map(|offset| self.line_column(*offset))
+  }
+}
+
+/// A function to describe in the debug info, and where it is defined.
+pub struct FunctionDefinition {
+  pub name: String,
+  pub source_file: std::path::PathBuf,
+}
+
+fn split_path(source_file: &std::path::Path) -> (String, String) {
+  let file_name = source_file
+    .file_name()
+    .map(|file_name| file_name.to_string_lossy().to_string())
+    .unwrap_or_default();
+
+  let directory = source_file
+    .parent()
+    .and_then(|parent| std::fs::canonicalize(parent).ok())
+    .map(|parent| parent.to_string_lossy().to_string())
+    .unwrap_or_default();
+
+  (file_name, directory)
+}
+
+/// Attach a location to each instruction of a function.
+fn locate_instructions(
+  function: inkwell::values::FunctionValue<'_>,
+  location: inkwell::debug_info::DILocation<'_>,
+) {
+  for basic_block in function.get_basic_blocks() {
+    let mut instruction = basic_block.get_first_instruction();
+
+    while let Some(current_instruction) = instruction {
+      // SAFETY: Both the instruction and the location belong to the
+      // ... module's context, which outlives this call.
+      unsafe {
+        llvm_sys::debuginfo::LLVMInstructionSetDebugLoc(
+          current_instruction.as_value_ref(),
+          location.as_mut_ptr(),
+        );
+      }
+
+      instruction = current_instruction.get_next_instruction();
+    }
+  }
+}
+
+/// Describe the functions of a lowered module in DWARF debug info, so
+/// that debuggers can map them back to the source files defining them.
+///
+/// Instructions are located at the definition of their function, since
+/// locations of statements can only be attached by the lowering, which
+/// knows the span each instruction originates from. Calls between
+/// described functions must be located for the module to be valid.
+// TODO: Pass the source maps to the lowering, once it can attach
+// ... locations to the instructions it produces.
+pub fn describe_functions<'ctx>(
+  llvm_context: &'ctx inkwell::context::Context,
+  llvm_module: &inkwell::module::Module<'ctx>,
+  root_file: &std::path::Path,
+  source_maps: &std::collections::HashMap<std::path::PathBuf, SourceMap>,
+  function_definitions: &[FunctionDefinition],
+) {
+  // The module is only optimized once it has been described.
+  let is_optimized = false;
+
+  llvm_module.add_basic_value_flag(
+    "Debug Info Version",
+    inkwell::module::FlagBehavior::Warning,
+    llvm_context
+      .i32_type()
+      .const_int(inkwell::debug_info::debug_metadata_version() as u64, false),
+  );
+
+  llvm_module.add_basic_value_flag(
+    "Dwarf Version",
+    inkwell::module::FlagBehavior::Warning,
+    llvm_context.i32_type().const_int(4, false),
+  );
+
+  let (root_file_name, root_directory) = split_path(root_file);
+
+  let (debug_info_builder, compile_unit) = llvm_module.create_debug_info_builder(
+    true,
+    // DWARF has no language code for gecko.
+    inkwell::debug_info::DWARFSourceLanguage::C,
+    &root_file_name,
+    &root_directory,
+    concat!("grip ", env!("CARGO_PKG_VERSION")),
+    is_optimized,
+    "",
+    0,
+    "",
+    inkwell::debug_info::DWARFEmissionKind::Full,
+    0,
+    false,
+    false,
+    "",
+    "",
+  );
+
+  let mut files = std::collections::HashMap::new();
+
+  for function_definition in function_definitions {
+    let function = match llvm_module.get_function(&function_definition.name) {
+      Some(function) if function.count_basic_blocks() > 0 => function,
+      _ => continue,
+    };
+
+    let (line, column) = source_maps
+      .get(&function_definition.source_file)
+      .and_then(|source_map| source_map.find_function(&function_definition.name))
+      .unwrap_or((0, 0));
+
+    let file = *files
+      .entry(function_definition.source_file.clone())
+      .or_insert_with(|| {
+        let (file_name, directory) = split_path(&function_definition.source_file);
+
+        debug_info_builder.create_file(&file_name, &directory)
+      });
+
+    let subroutine_type = debug_info_builder.create_subroutine_type(
+      file,
+      None,
+      &[],
+      inkwell::debug_info::DIFlags::PUBLIC,
+    );
+
+    let subprogram = debug_info_builder.create_function(
+      compile_unit.as_debug_info_scope(),
+      &function_definition.name,
+      None,
+      file,
+      line,
+      subroutine_type,
+      false,
+      true,
+      line,
+      inkwell::debug_info::DIFlags::PUBLIC,
+      is_optimized,
+    );
+
+    function.set_subprogram(subprogram);
+
+    locate_instructions(
+      function,
+      debug_info_builder.create_debug_location(
+        llvm_context,
+        line,
+        column,
+        subprogram.as_debug_info_scope(),
+        None,
+      ),
+    );
+  }
+
+  debug_info_builder.finalize();
+}
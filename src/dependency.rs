@@ -1,23 +1,47 @@
-use crate::package;
+use crate::{features, package, registry, scheduler};
 
-type DependencyGraph = std::collections::HashMap<String, Vec<String>>;
+pub const DEFAULT_REFERENCE: &str = "master";
 
-fn build_dependency_graph(manifest: package::Manifest) -> Result<DependencyGraph, String> {
+/// Determine whether a reference is pinned (a full commit SHA or a
+/// semver tag, such as `v1.2.3`), as opposed to a branch name which may
+/// move over time (including ones like `2.x`).
+pub fn is_pinned_reference(reference: &str) -> bool {
+  let is_commit = reference.len() == 40 && reference.chars().all(|c| c.is_ascii_hexdigit());
+  let version = reference.strip_prefix('v').unwrap_or(reference);
+  let is_version_tag = semver::Version::parse(version).is_ok();
+
+  is_commit || is_version_tag
+}
+
+pub type DependencyGraph = std::collections::HashMap<String, Vec<String>>;
+
+/// Build the graph of a package's installed dependencies, following the
+/// dependencies each manifest declares according to `dependencies_of`
+/// (such as those of the active target only).
+pub fn build_dependency_graph(
+  manifest: package::Manifest,
+  dependencies_of: fn(&package::Manifest) -> package::Dependencies,
+) -> Result<DependencyGraph, String> {
   let mut dependency_graph = DependencyGraph::new();
-  let mut dependencies_queue = std::collections::VecDeque::from(manifest.dependencies);
+  let mut dependencies_queue = dependencies_of(&manifest)
+    .into_iter()
+    .collect::<std::collections::VecDeque<_>>();
 
   // REVISE: This isn't actually a queue. It's being popped, so its used as a stack.
   // ... This means that the search algorithm being used is breadth-first instead of
   // ... depth-first.
-  while let Some(dependency_name) = dependencies_queue.pop_front() {
-    let mut manifest_path = std::path::PathBuf::from(package::PATH_DEPENDENCIES);
-
-    manifest_path.push(dependency_name.clone());
-    manifest_path.push(package::PATH_MANIFEST_FILE);
+  while let Some((dependency_name, dependency_spec)) = dependencies_queue.pop_front() {
+    // Shared (or cyclic) dependencies only need to be visited once.
+    if dependency_graph.contains_key(&dependency_name) {
+      continue;
+    }
 
-    let dependencies = package::fetch_manifest(&manifest_path)?.dependencies;
+    let dependencies = dependencies_of(&package::fetch_dependency_manifest(
+      &dependency_name,
+      &dependency_spec,
+    )?);
 
-    dependency_graph.insert(dependency_name, dependencies.clone());
+    dependency_graph.insert(dependency_name, dependencies.keys().cloned().collect());
 
     // TODO: Does this 'push_back' all the elements?
     // dependencies_queue.extend(dependencies);
@@ -30,25 +54,113 @@ fn build_dependency_graph(manifest: package::Manifest) -> Result<DependencyGraph
   Ok(dependency_graph)
 }
 
-fn is_dependency_cyclic(dependency_graph: &DependencyGraph, dependency_name: String) -> bool {
-  let mut visited = std::collections::HashSet::new();
-  let mut queue = std::collections::VecDeque::new();
+/// Find a dependency cycle reachable from a package, and return its
+/// path (starting and ending with the same package).
+pub fn find_cycle(dependency_graph: &DependencyGraph, package_name: &str) -> Option<Vec<String>> {
+  fn visit(
+    dependency_graph: &DependencyGraph,
+    package_name: &str,
+    path: &mut Vec<String>,
+    finished: &mut std::collections::HashSet<String>,
+  ) -> Option<Vec<String>> {
+    if let Some(index) = path.iter().position(|name| name == package_name) {
+      let mut cycle = path[index..].to_vec();
+
+      cycle.push(package_name.to_string());
 
-  queue.push_back(dependency_name);
+      return Some(cycle);
+    }
 
-  while let Some(dependency_name) = queue.pop_front() {
-    if visited.contains(&dependency_name) {
-      return true;
+    if finished.contains(package_name) {
+      return None;
     }
 
-    visited.insert(dependency_name.clone());
+    path.push(package_name.to_string());
+
+    let mut dependencies = dependency_graph
+      .get(package_name)
+      .cloned()
+      .unwrap_or_default();
+
+    // Report the same cycle on every run.
+    dependencies.sort();
 
-    if let Some(dependencies) = dependency_graph.get(&dependency_name) {
-      queue.extend(dependencies.iter().cloned());
+    for dependency_name in dependencies {
+      if let Some(cycle) = visit(dependency_graph, &dependency_name, path, finished) {
+        return Some(cycle);
+      }
     }
+
+    path.pop();
+    finished.insert(package_name.to_string());
+
+    None
+  }
+
+  visit(
+    dependency_graph,
+    package_name,
+    &mut Vec::new(),
+    &mut std::collections::HashSet::new(),
+  )
+}
+
+/// Ensure that the dependency graph of a package has no cycles, which
+/// could never be built.
+pub fn check_cycles(root_manifest: &package::Manifest) -> Result<(), String> {
+  let mut dependency_graph = build_dependency_graph(
+    root_manifest.clone(),
+    package::Manifest::active_dependencies,
+  )?;
+
+  dependency_graph
+    .entry(root_manifest.name.clone())
+    .or_insert_with(|| root_manifest.active_dependencies().into_keys().collect());
+
+  match find_cycle(&dependency_graph, &root_manifest.name) {
+    Some(cycle) => Err(format!("dependency cycle detected: {}", cycle.join(" -> "))),
+    None => Ok(()),
+  }
+}
+
+/// Determine which of a package's (direct or transitive) dependencies
+/// would no longer be depended upon by anything without one of its
+/// direct dependencies, including that dependency itself.
+pub fn orphaned_by_removal(
+  root_manifest: &package::Manifest,
+  other_manifests: &[package::Manifest],
+  dependency_name: &str,
+) -> Result<Vec<String>, String> {
+  let dependency_graph =
+    build_dependency_graph(root_manifest.clone(), package::Manifest::all_dependencies)?;
+  let mut remaining_manifest = root_manifest.clone();
+
+  remaining_manifest.dependencies.remove(dependency_name);
+
+  for target_config in remaining_manifest.targets.values_mut() {
+    target_config.dependencies.remove(dependency_name);
+  }
+
+  let mut remaining_graph =
+    build_dependency_graph(remaining_manifest, package::Manifest::all_dependencies)?;
+
+  // Packages installed for other members of the workspace are still
+  // ... needed.
+  for other_manifest in other_manifests {
+    remaining_graph.extend(build_dependency_graph(
+      other_manifest.clone(),
+      package::Manifest::all_dependencies,
+    )?);
   }
 
-  false
+  let mut orphaned = dependency_graph
+    .into_keys()
+    .filter(|name| !remaining_graph.contains_key(name))
+    .collect::<Vec<_>>();
+
+  orphaned.sort();
+
+  Ok(orphaned)
 }
 
 fn find_most_used_dependency(dependency_graph: DependencyGraph) -> Option<String> {
@@ -66,3 +178,365 @@ fn find_most_used_dependency(dependency_graph: DependencyGraph) -> Option<String
 
   most_used
 }
+
+/// A package resolved from a registry, prior to downloading it.
+pub struct RemotePackage {
+  pub path: String,
+  pub reference: String,
+  pub manifest: package::Manifest,
+  pub archive_url: String,
+  pub archive_size: Option<u64>,
+}
+
+/// Resolve a package and all of its transitive dependencies from the
+/// registry, without downloading or writing anything to disk.
+///
+/// Only registry dependencies are resolved, at the versions chosen by
+/// `resolve_versions` (with the package's default features); git and
+/// path dependencies are skipped.
+pub async fn resolve_remote(
+  registry: &(dyn registry::Registry + Send + Sync),
+  scheduler: &scheduler::Scheduler,
+  package_path: &str,
+  reference: &str,
+) -> Result<Vec<RemotePackage>, String> {
+  let manifest = registry.fetch_manifest(package_path, reference).await?;
+  let root_features = features::resolve(&manifest, &[], true)?;
+  let resolved = resolve_versions(registry, &manifest, &root_features).await?;
+
+  // The archive sizes of all the packages are fetched concurrently.
+  let pending = std::iter::once((package_path.to_string(), reference.to_string(), manifest))
+    .chain(resolved.into_iter().map(|resolved_package| {
+      (
+        resolved_package.source,
+        resolved_package.reference,
+        resolved_package.manifest,
+      )
+    }))
+    .map(|(package_path, reference, manifest)| {
+      scheduler.run(async move {
+        Ok::<_, String>(RemotePackage {
+          archive_url: registry.archive_url(&package_path, &reference),
+          archive_size: registry.archive_size(&package_path, &reference).await?,
+          path: package_path,
+          reference,
+          manifest,
+        })
+      })
+    })
+    .collect::<Vec<_>>();
+
+  futures_util::future::join_all(pending)
+    .await
+    .into_iter()
+    .collect()
+}
+
+/// A package version chosen by the resolver.
+pub struct ResolvedPackage {
+  pub name: String,
+  /// The registry path of the package.
+  pub source: String,
+  pub version: semver::Version,
+  /// The tag the version was published under.
+  pub reference: String,
+  /// The manifest of the chosen version.
+  pub manifest: package::Manifest,
+  /// The features enabled in the package.
+  pub features: Vec<String>,
+}
+
+/// List the dependencies a resolved package uses, to be resolved in
+/// turn, along with the features it requests of each.
+fn transitive_dependencies(
+  dependent: &str,
+  manifest: &package::Manifest,
+  resolved_features: &features::ResolvedFeatures,
+) -> Vec<(
+  String,
+  String,
+  package::DependencySpec,
+  std::collections::BTreeSet<String>,
+)> {
+  features::used_dependencies(manifest, manifest.all_dependencies(), resolved_features)
+    .into_iter()
+    .map(|(dependency_name, dependency_spec)| {
+      let requested_features = resolved_features
+        .dependency_features
+        .get(&dependency_name)
+        .cloned()
+        .unwrap_or_default();
+
+      (
+        dependent.to_string(),
+        dependency_name,
+        dependency_spec,
+        requested_features,
+      )
+    })
+    .collect()
+}
+
+struct Requirement {
+  dependent: String,
+  version_requirement: semver::VersionReq,
+}
+
+/// Parse the tags of a package into versions, ignoring tags which are
+/// not versions. The result is sorted from newest to oldest.
+pub fn parse_version_tags(tags: Vec<String>) -> Vec<(semver::Version, String)> {
+  let mut versions = tags
+    .into_iter()
+    .filter_map(|tag| {
+      semver::Version::parse(tag.strip_prefix('v').unwrap_or(&tag))
+        .ok()
+        .map(|version| (version, tag))
+    })
+    .collect::<Vec<_>>();
+
+  versions.sort_by(|a, b| b.0.cmp(&a.0));
+
+  versions
+}
+
+/// Compute a set of versions for the registry dependencies of a
+/// package (and their own, transitively) which satisfies the version
+/// requirements of all of their dependents. The newest acceptable
+/// version of each package is chosen. The dependencies of all targets
+/// are resolved, so that the lock holds whichever is built for.
+///
+/// Optional dependencies are only resolved once enabled, starting from
+/// the root package's features, and following the features each package
+/// requests of its own dependencies.
+///
+/// Git and path dependencies are fixed by their specification, and are
+/// not resolved.
+// REVIEW: Choices are revisited when a later requirement excludes them,
+// ... but there is no backtracking across packages; a conflict may be
+// ... reported even though an older version of a dependent would work.
+pub async fn resolve_versions(
+  registry: &(dyn registry::Registry + Send + Sync),
+  root_manifest: &package::Manifest,
+  root_features: &features::ResolvedFeatures,
+) -> Result<Vec<ResolvedPackage>, String> {
+  let mut requirements = std::collections::BTreeMap::<String, Vec<Requirement>>::new();
+  let mut sources = std::collections::HashMap::<String, String>::new();
+  let mut available_versions = std::collections::HashMap::new();
+  let mut selected = std::collections::BTreeMap::<String, (semver::Version, String)>::new();
+  let mut manifests = std::collections::HashMap::<String, package::Manifest>::new();
+  let mut feature_requests = features::FeatureRequests::default();
+  let mut queue = std::collections::VecDeque::new();
+
+  for (dependency_name, dependency_spec) in &features::used_dependencies(
+    root_manifest,
+    root_manifest.all_dependencies(),
+    root_features,
+  ) {
+    queue.push_back((
+      root_manifest.name.clone(),
+      dependency_name.clone(),
+      dependency_spec.clone(),
+      root_features
+        .dependency_features
+        .get(dependency_name)
+        .cloned()
+        .unwrap_or_default(),
+    ));
+  }
+
+  while let Some((dependent, dependency_name, dependency_spec, requested_features)) =
+    queue.pop_front()
+  {
+    let (registry_path, version_requirement) = match (
+      dependency_spec.registry_path(&dependency_name),
+      dependency_spec.version_requirement(),
+    ) {
+      (Some(registry_path), Some(version_requirement)) => {
+        (registry_path.to_string(), version_requirement)
+      }
+      _ => continue,
+    };
+
+    let version_requirement = match semver::VersionReq::parse(version_requirement) {
+      Ok(version_requirement) => version_requirement,
+      Err(error) => {
+        return Err(format!(
+          "invalid version requirement `{}` for `{}` (required by `{}`): {}",
+          version_requirement, dependency_name, dependent, error
+        ))
+      }
+    };
+
+    match sources.get(&dependency_name) {
+      Some(source) if source != &registry_path => {
+        return Err(format!(
+          "dependency `{}` is required from both `{}` and `{}` (required by `{}`)",
+          dependency_name, source, registry_path, dependent
+        ))
+      }
+      Some(_) => {}
+      None => {
+        sources.insert(dependency_name.clone(), registry_path.clone());
+      }
+    }
+
+    let package_requirements = requirements.entry(dependency_name.clone()).or_default();
+
+    // A dependent is visited again when it requests more features.
+    if !package_requirements.iter().any(|requirement| {
+      requirement.dependent == dependent && requirement.version_requirement == version_requirement
+    }) {
+      package_requirements.push(Requirement {
+        dependent: dependent.clone(),
+        version_requirement,
+      });
+    }
+
+    let package_requirements = &requirements[&dependency_name];
+    let has_new_features = feature_requests.request(&dependency_name, &requested_features);
+
+    // The current choice is kept for as long as it remains acceptable,
+    // but more features may enable more of its dependencies.
+    if let Some((version, _)) = selected.get(&dependency_name) {
+      if package_requirements
+        .iter()
+        .all(|requirement| requirement.version_requirement.matches(version))
+      {
+        if has_new_features {
+          let manifest = &manifests[&dependency_name];
+
+          queue.extend(transitive_dependencies(
+            &dependency_name,
+            manifest,
+            &feature_requests.resolve(&dependency_name, manifest)?,
+          ));
+        }
+
+        continue;
+      }
+    }
+
+    if !available_versions.contains_key(&dependency_name) {
+      available_versions.insert(
+        dependency_name.clone(),
+        parse_version_tags(registry.resolve_versions(&registry_path).await?),
+      );
+    }
+
+    let versions = &available_versions[&dependency_name];
+
+    let (version, reference) = match versions.iter().find(|(version, _)| {
+      package_requirements
+        .iter()
+        .all(|requirement| requirement.version_requirement.matches(version))
+    }) {
+      Some(choice) => choice.clone(),
+      None => {
+        return Err(explain_conflict(
+          &dependency_name,
+          package_requirements,
+          versions,
+        ))
+      }
+    };
+
+    // The requirements of the previously chosen version no longer apply.
+    if selected.contains_key(&dependency_name) {
+      for package_requirements in requirements.values_mut() {
+        package_requirements.retain(|requirement| requirement.dependent != dependency_name);
+      }
+    }
+
+    let manifest = registry.fetch_manifest(&registry_path, &reference).await?;
+
+    queue.extend(transitive_dependencies(
+      &dependency_name,
+      &manifest,
+      &feature_requests.resolve(&dependency_name, &manifest)?,
+    ));
+
+    manifests.insert(dependency_name.clone(), manifest);
+
+    selected.insert(dependency_name, (version, reference));
+  }
+
+  // Packages only required by a version which was later replaced are
+  // no longer needed.
+  let mut reachable = std::collections::HashSet::new();
+  let mut reachable_queue = features::used_dependencies(
+    root_manifest,
+    root_manifest.all_dependencies(),
+    root_features,
+  )
+  .into_keys()
+  .collect::<Vec<_>>();
+
+  while let Some(dependency_name) = reachable_queue.pop() {
+    if reachable.insert(dependency_name.clone()) {
+      if let Some(manifest) = manifests.get(&dependency_name) {
+        reachable_queue.extend(
+          features::used_dependencies(
+            manifest,
+            manifest.all_dependencies(),
+            &feature_requests.resolve(&dependency_name, manifest)?,
+          )
+          .into_keys(),
+        );
+      }
+    }
+  }
+
+  selected
+    .into_iter()
+    .filter(|(name, _)| reachable.contains(name))
+    .map(|(name, (version, reference))| {
+      let manifest = manifests.remove(&name).unwrap();
+
+      Ok(ResolvedPackage {
+        source: sources[&name].clone(),
+        features: feature_requests
+          .resolve(&name, &manifest)?
+          .enabled
+          .into_iter()
+          .collect(),
+        manifest,
+        name,
+        version,
+        reference,
+      })
+    })
+    .collect::<Result<Vec<_>, String>>()
+}
+
+fn explain_conflict(
+  dependency_name: &str,
+  requirements: &[Requirement],
+  versions: &[(semver::Version, String)],
+) -> String {
+  let mut explanation = format!(
+    "no version of `{}` satisfies all of its requirements:",
+    dependency_name
+  );
+
+  for requirement in requirements {
+    explanation.push_str(&format!(
+      "\n  `{}` requires `{}`",
+      requirement.dependent, requirement.version_requirement
+    ));
+  }
+
+  if versions.is_empty() {
+    explanation.push_str("\nno versions are available");
+  } else {
+    explanation.push_str(&format!(
+      "\navailable versions: {}",
+      versions
+        .iter()
+        .map(|(version, _)| version.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+    ));
+  }
+
+  explanation
+}
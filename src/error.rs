@@ -0,0 +1,58 @@
+/// The failure of a grip command, categorized by its cause so that
+/// scripts can tell them apart by the exit code.
+#[derive(Debug)]
+pub enum GripError {
+  /// The manifest or the lock is missing, invalid or inconsistent.
+  Manifest(String),
+  /// The network couldn't (or may not) be accessed.
+  Network(String),
+  /// Reading or writing a file failed.
+  Io(String),
+  /// The sources could not be compiled.
+  Compile(String),
+  /// The compiled program could not be linked.
+  Link(String),
+  Other(String),
+}
+
+impl GripError {
+  /// The code grip exits with upon this failure.
+  pub fn exit_code(&self) -> i32 {
+    match self {
+      GripError::Other(_) => 1,
+      GripError::Compile(_) => 2,
+      GripError::Link(_) => 3,
+      GripError::Manifest(_) => 4,
+      GripError::Network(_) => 5,
+      GripError::Io(_) => 6,
+    }
+  }
+}
+
+impl std::fmt::Display for GripError {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      GripError::Manifest(message)
+      | GripError::Network(message)
+      | GripError::Io(message)
+      | GripError::Compile(message)
+      | GripError::Link(message)
+      | GripError::Other(message) => write!(formatter, "{}", message),
+    }
+  }
+}
+
+impl std::error::Error for GripError {}
+
+/// Failures which weren't categorized where they occurred.
+impl From<String> for GripError {
+  fn from(message: String) -> Self {
+    GripError::Other(message)
+  }
+}
+
+impl From<GripError> for String {
+  fn from(error: GripError) -> Self {
+    error.to_string()
+  }
+}
@@ -0,0 +1,204 @@
+use crate::package;
+
+/// The feature enabled by default, unless `--no-default-features` is
+/// given.
+pub const FEATURE_DEFAULT: &str = "default";
+/// The prefix of feature entries enabling an optional dependency.
+const PREFIX_DEPENDENCY: &str = "dep:";
+
+/// The features requested on the command line, for the package being
+/// built.
+#[derive(Clone, Default)]
+pub struct FeatureSelection {
+  pub features: Vec<String>,
+  pub no_default_features: bool,
+}
+
+impl FeatureSelection {
+  /// Parse a comma- or space-separated list of features.
+  pub fn parse(features: Option<&str>, no_default_features: bool) -> Self {
+    Self {
+      features: features
+        .unwrap_or("")
+        .split(|char: char| char == ',' || char.is_whitespace())
+        .filter(|feature| !feature.is_empty())
+        .map(|feature| feature.to_string())
+        .collect(),
+      no_default_features,
+    }
+  }
+}
+
+/// The features of a package enabled for a build, and what they enable
+/// in its dependencies.
+#[derive(Default)]
+pub struct ResolvedFeatures {
+  pub enabled: std::collections::BTreeSet<String>,
+  /// The optional dependencies which are enabled.
+  pub dependencies: std::collections::BTreeSet<String>,
+  /// The features requested of each dependency.
+  pub dependency_features: std::collections::BTreeMap<String, std::collections::BTreeSet<String>>,
+}
+
+/// Collect the dependencies which are only used when a feature enables
+/// them (`dep:<name>`).
+pub fn optional_dependencies(manifest: &package::Manifest) -> std::collections::BTreeSet<&str> {
+  manifest
+    .features
+    .values()
+    .flatten()
+    .filter_map(|entry| entry.strip_prefix(PREFIX_DEPENDENCY))
+    .collect()
+}
+
+/// Determine the features of a package which are enabled, given those
+/// requested, following what each enables in turn. The default feature
+/// is included if `use_default` is set and the package declares it.
+pub fn resolve(
+  manifest: &package::Manifest,
+  requested: &[String],
+  use_default: bool,
+) -> Result<ResolvedFeatures, String> {
+  let mut resolved = ResolvedFeatures::default();
+  let mut queue = requested.to_vec();
+
+  if use_default && manifest.features.contains_key(FEATURE_DEFAULT) {
+    queue.push(FEATURE_DEFAULT.to_string());
+  }
+
+  while let Some(feature) = queue.pop() {
+    if !resolved.enabled.insert(feature.clone()) {
+      continue;
+    }
+
+    let entries = match manifest.features.get(&feature) {
+      Some(entries) => entries,
+      None => {
+        return Err(format!(
+          "package `{}` has no feature `{}`",
+          manifest.name, feature
+        ))
+      }
+    };
+
+    for entry in entries {
+      if let Some(dependency_name) = entry.strip_prefix(PREFIX_DEPENDENCY) {
+        resolved.dependencies.insert(dependency_name.to_string());
+      } else if let Some((dependency_name, dependency_feature)) = entry.split_once('/') {
+        resolved.dependencies.insert(dependency_name.to_string());
+
+        resolved
+          .dependency_features
+          .entry(dependency_name.to_string())
+          .or_default()
+          .insert(dependency_feature.to_string());
+      } else {
+        queue.push(entry.clone());
+      }
+    }
+  }
+
+  Ok(resolved)
+}
+
+/// Resolve the features of the package being operated on, as selected
+/// on the command line.
+pub fn resolve_selected(manifest: &package::Manifest) -> Result<ResolvedFeatures, String> {
+  let feature_selection = package::feature_selection();
+
+  resolve(
+    manifest,
+    &feature_selection.features,
+    !feature_selection.no_default_features,
+  )
+}
+
+/// Retrieve those of a package's dependencies which are in use, given
+/// its resolved features: optional dependencies are only used once one
+/// of its enabled features enables them.
+pub fn used_dependencies(
+  manifest: &package::Manifest,
+  dependencies: package::Dependencies,
+  resolved: &ResolvedFeatures,
+) -> package::Dependencies {
+  let optional_dependencies = optional_dependencies(manifest);
+
+  dependencies
+    .into_iter()
+    .filter(|(dependency_name, _)| {
+      !optional_dependencies.contains(dependency_name.as_str())
+        || resolved.dependencies.contains(dependency_name)
+    })
+    .collect()
+}
+
+/// Collects the features requested of each package of a dependency
+/// graph while it is walked. Since more features may enable more of a
+/// package's dependencies, a package whose requested features grow must
+/// be walked again, until none do.
+#[derive(Default)]
+pub struct FeatureRequests {
+  requested: std::collections::HashMap<String, std::collections::BTreeSet<String>>,
+}
+
+impl FeatureRequests {
+  /// Request features of a package. Returns whether any of them weren't
+  /// requested before.
+  pub fn request(
+    &mut self,
+    package_name: &str,
+    features: &std::collections::BTreeSet<String>,
+  ) -> bool {
+    let requested = self.requested.entry(package_name.to_string()).or_default();
+    let previous_length = requested.len();
+
+    requested.extend(features.iter().cloned());
+
+    requested.len() != previous_length
+  }
+
+  /// Resolve the features of a dependency, which are those requested of
+  /// it so far, along with its default ones (dependencies always have
+  /// their default features enabled).
+  pub fn resolve(
+    &self,
+    package_name: &str,
+    manifest: &package::Manifest,
+  ) -> Result<ResolvedFeatures, String> {
+    let requested = self
+      .requested
+      .get(package_name)
+      .map(|requested| requested.iter().cloned().collect::<Vec<_>>())
+      .unwrap_or_default();
+
+    resolve(manifest, &requested, true)
+  }
+}
+
+/// Ensure that every entry of a package's features refers to one of its
+/// features or dependencies.
+pub fn validate(manifest: &package::Manifest) -> Result<(), String> {
+  for (feature, entries) in &manifest.features {
+    for entry in entries {
+      let dependency_name = entry.strip_prefix(PREFIX_DEPENDENCY).or_else(|| {
+        entry
+          .split_once('/')
+          .map(|(dependency_name, _)| dependency_name)
+      });
+
+      let is_known = match dependency_name {
+        Some(dependency_name) => manifest.declares_dependency(dependency_name),
+        None => manifest.features.contains_key(entry),
+      };
+
+      if !is_known {
+        return Err(format!(
+          "feature `{}` of package `{}` enables `{}`, which is neither a feature nor a dependency",
+          feature, manifest.name, entry
+        ));
+      }
+    }
+  }
+
+  Ok(())
+}
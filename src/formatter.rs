@@ -0,0 +1,192 @@
+const INDENT: &str = "  ";
+
+/// Where the scanner is within the source code, carried from one line
+/// to the next.
+#[derive(Default)]
+struct ScanState {
+  /// The amount of unclosed braces, brackets and parentheses.
+  depth: usize,
+  /// The delimiter of the string (or character) literal being scanned,
+  /// if any.
+  literal_delimiter: Option<char>,
+  is_in_block_comment: bool,
+}
+
+impl ScanState {
+  /// Scan a line, tracking the nesting and the literals and comments it
+  /// opens or closes. Delimiters within literals and comments are
+  /// ignored.
+  fn scan(&mut self, line: &str) {
+    let mut chars = line.chars().peekable();
+
+    while let Some(char) = chars.next() {
+      if self.is_in_block_comment {
+        if char == '*' && chars.peek() == Some(&'/') {
+          chars.next();
+          self.is_in_block_comment = false;
+        }
+
+        continue;
+      }
+
+      if let Some(literal_delimiter) = self.literal_delimiter {
+        if char == '\\' {
+          chars.next();
+        } else if char == literal_delimiter {
+          self.literal_delimiter = None;
+        }
+
+        continue;
+      }
+
+      match char {
+        '/' if chars.peek() == Some(&'/') => break,
+        '/' if chars.peek() == Some(&'*') => {
+          chars.next();
+          self.is_in_block_comment = true;
+        }
+        '"' | '\'' => self.literal_delimiter = Some(char),
+        '{' | '[' | '(' => self.depth += 1,
+        '}' | ']' | ')' => self.depth = self.depth.saturating_sub(1),
+        _ => {}
+      }
+    }
+  }
+}
+
+/// Normalize the layout of source code, without changing its tokens:
+/// lines are re-indented by their nesting, trailing whitespace is
+/// removed, consecutive blank lines are collapsed into one, and the code
+/// ends with a single newline. Lines continuing a literal or a block
+/// comment are left untouched.
+// TODO: Replace with a pretty-printer over the AST, once the parser
+// ... preserves comments.
+pub fn normalize(source_code: &str) -> String {
+  let mut state = ScanState::default();
+  let mut output = String::new();
+  let mut is_previous_line_blank = true;
+
+  for line in source_code.lines() {
+    if state.literal_delimiter.is_some() || state.is_in_block_comment {
+      // Whitespace within a literal is significant.
+      if state.literal_delimiter.is_some() {
+        output.push_str(line);
+      } else {
+        output.push_str(line.trim_end());
+      }
+
+      output.push('\n');
+      state.scan(line);
+      is_previous_line_blank = false;
+
+      continue;
+    }
+
+    let content = line.trim();
+
+    if content.is_empty() {
+      if !is_previous_line_blank {
+        output.push('\n');
+      }
+
+      is_previous_line_blank = true;
+
+      continue;
+    }
+
+    // Closing delimiters are aligned with the line which opened them.
+    let leading_closers = content
+      .chars()
+      .take_while(|char| matches!(char, '}' | ']' | ')'))
+      .count();
+
+    output.push_str(&INDENT.repeat(state.depth.saturating_sub(leading_closers)));
+    output.push_str(content);
+    state.scan(content);
+
+    // The trailing whitespace was part of an unterminated literal.
+    if state.literal_delimiter.is_some() {
+      output.push_str(&line[line.trim_end().len()..]);
+    }
+
+    output.push('\n');
+    is_previous_line_blank = false;
+  }
+
+  let output_length = output.trim_end_matches('\n').len();
+
+  output.truncate(output_length);
+
+  if !output.is_empty() {
+    output.push('\n');
+  }
+
+  output
+}
+
+/// Format source code with an external formatter (such as `gecko-fmt`),
+/// which reads it from its standard input, and writes the formatted code
+/// to its standard output.
+pub fn run_external(formatter: &str, source_code: &str) -> Result<String, String> {
+  let mut command_parts = formatter.split_whitespace();
+
+  let program = match command_parts.next() {
+    Some(program) => program,
+    None => return Err("the configured formatter is empty".to_string()),
+  };
+
+  let mut child = match std::process::Command::new(program)
+    .args(command_parts)
+    .stdin(std::process::Stdio::piped())
+    .stdout(std::process::Stdio::piped())
+    .stderr(std::process::Stdio::piped())
+    .spawn()
+  {
+    Ok(child) => child,
+    Err(error) => {
+      return Err(format!(
+        "failed to run the formatter `{}`: {}",
+        program, error
+      ))
+    }
+  };
+
+  // The code is written from another thread, so that a formatter which
+  // ... writes its output as it reads doesn't block on a full pipe.
+  let writer = child.stdin.take().map(|mut stdin| {
+    let source_code = source_code.to_string();
+
+    std::thread::spawn(move || std::io::Write::write_all(&mut stdin, source_code.as_bytes()))
+  });
+
+  let output = match child.wait_with_output() {
+    Ok(output) => output,
+    Err(error) => {
+      return Err(format!(
+        "failed to run the formatter `{}`: {}",
+        program, error
+      ))
+    }
+  };
+
+  if let Some(writer) = writer {
+    writer.join().ok();
+  }
+
+  if !output.status.success() {
+    return Err(format!(
+      "the formatter `{}` failed ({}): {}",
+      program,
+      output.status,
+      String::from_utf8_lossy(&output.stderr).trim()
+    ));
+  }
+
+  match String::from_utf8(output.stdout) {
+    Ok(formatted_code) => Ok(formatted_code),
+    Err(error) => Err(format!(
+      "the formatter `{}` produced invalid UTF-8: {}",
+      program, error
+    )),
+  }
+}
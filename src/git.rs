@@ -0,0 +1,290 @@
+pub struct CloneOptions {
+  /// Whether to fetch the full history (including tags), instead of
+  /// only the requested reference's latest commit.
+  pub full_history: bool,
+  /// Whether to also clone the repository's submodules.
+  pub submodules: bool,
+}
+
+/// Clone a git repository at the given reference (branch or tag).
+pub fn clone(
+  url: &str,
+  reference: &str,
+  destination: &std::path::Path,
+  options: &CloneOptions,
+) -> Result<(), String> {
+  let mut command = std::process::Command::new("git");
+
+  command.args(&["clone", "--quiet", "--branch", reference]);
+
+  if !options.full_history {
+    command.args(&["--depth", "1"]);
+  }
+
+  if options.submodules {
+    command.arg("--recurse-submodules");
+
+    if !options.full_history {
+      command.arg("--shallow-submodules");
+    }
+  }
+
+  command.arg(url).arg(destination);
+  run(command, "failed to clone the repository")
+}
+
+/// Fast-forward a cloned repository's current branch to its remote.
+pub fn pull(repository_path: &std::path::Path) -> Result<(), String> {
+  let mut command = std::process::Command::new("git");
+
+  command
+    .args(&["pull", "--quiet", "--ff-only"])
+    .current_dir(repository_path);
+
+  run(command, "failed to update the repository")
+}
+
+/// Check out a commit in a cloned repository. The repository must have
+/// been cloned with its full history.
+pub fn checkout(repository_path: &std::path::Path, commit: &str) -> Result<(), String> {
+  let mut command = std::process::Command::new("git");
+
+  command
+    .args(&["checkout", "--quiet", commit])
+    .current_dir(repository_path);
+
+  run(command, &format!("failed to check out `{}`", commit))
+}
+
+/// Retrieve the commit currently checked out in a repository.
+pub fn head_commit(repository_path: &std::path::Path) -> Result<String, String> {
+  let output = std::process::Command::new("git")
+    .args(&["rev-parse", "HEAD"])
+    .current_dir(repository_path)
+    .output();
+
+  match output {
+    Ok(output) if output.status.success() => {
+      Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+    Ok(output) => Err(format!(
+      "failed to resolve the checked out commit: {}",
+      String::from_utf8_lossy(&output.stderr).trim()
+    )),
+    Err(error) => Err(format!(
+      "failed to resolve the checked out commit: failed to run `git`: {}",
+      error
+    )),
+  }
+}
+
+/// Resolve the commit a reference currently points to in a remote
+/// repository, without cloning it.
+pub fn remote_commit(url: &str, reference: &str) -> Result<String, String> {
+  let output = std::process::Command::new("git")
+    .args(&["ls-remote", url, reference])
+    .output();
+
+  let output = match output {
+    Ok(output) if output.status.success() => output,
+    Ok(output) => {
+      return Err(format!(
+        "failed to resolve `{}` in `{}`: {}",
+        reference,
+        url,
+        String::from_utf8_lossy(&output.stderr).trim()
+      ))
+    }
+    Err(error) => {
+      return Err(format!(
+        "failed to resolve `{}` in `{}`: failed to run `git`: {}",
+        reference, url, error
+      ))
+    }
+  };
+
+  let stdout = String::from_utf8_lossy(&output.stdout);
+
+  match stdout.split_whitespace().next() {
+    Some(commit) => Ok(commit.to_string()),
+    None => Err(format!(
+      "reference `{}` does not exist in `{}`",
+      reference, url
+    )),
+  }
+}
+
+/// Determine whether a repository has no uncommitted changes (including
+/// untracked files which aren't ignored).
+pub fn is_clean(repository_path: &std::path::Path) -> Result<bool, String> {
+  let mut command = std::process::Command::new("git");
+
+  command
+    .args(&["status", "--porcelain"])
+    .current_dir(repository_path);
+
+  Ok(run_output(command, "failed to read the repository status")?.is_empty())
+}
+
+/// Determine whether a tag exists in a repository.
+pub fn tag_exists(repository_path: &std::path::Path, tag: &str) -> bool {
+  std::process::Command::new("git")
+    .args(&["rev-parse", "--quiet", "--verify"])
+    .arg(format!("refs/tags/{}", tag))
+    .current_dir(repository_path)
+    .output()
+    .map_or(false, |output| output.status.success())
+}
+
+/// Create an annotated tag at the checked out commit.
+pub fn create_tag(
+  repository_path: &std::path::Path,
+  tag: &str,
+  message: &str,
+) -> Result<(), String> {
+  let mut command = std::process::Command::new("git");
+
+  command
+    .args(&["tag", "--annotate", tag, "--message", message])
+    .current_dir(repository_path);
+
+  run(command, &format!("failed to create tag `{}`", tag))
+}
+
+/// Delete a tag of a repository.
+pub fn delete_tag(repository_path: &std::path::Path, tag: &str) -> Result<(), String> {
+  let mut command = std::process::Command::new("git");
+
+  command
+    .args(&["tag", "--delete", tag])
+    .current_dir(repository_path);
+
+  run(command, &format!("failed to delete tag `{}`", tag))
+}
+
+/// Delete a tag from a remote.
+pub fn delete_remote_tag(
+  repository_path: &std::path::Path,
+  remote: &str,
+  tag: &str,
+) -> Result<(), String> {
+  let mut command = std::process::Command::new("git");
+
+  command
+    .args(&["push", "--quiet", "--delete", remote])
+    .arg(format!("refs/tags/{}", tag))
+    .current_dir(repository_path);
+
+  run(
+    command,
+    &format!("failed to delete tag `{}` from `{}`", tag, remote),
+  )
+}
+
+/// Discard the commits and changes of the checked out branch which
+/// aren't part of its upstream branch.
+pub fn reset_to_upstream(repository_path: &std::path::Path) -> Result<(), String> {
+  let mut command = std::process::Command::new("git");
+
+  command
+    .args(&["reset", "--quiet", "--hard", "@{upstream}"])
+    .current_dir(repository_path);
+
+  run(command, "failed to reset the repository")
+}
+
+/// Retrieve the URL of a remote of a repository.
+pub fn remote_url(repository_path: &std::path::Path, remote: &str) -> Result<String, String> {
+  let mut command = std::process::Command::new("git");
+
+  command
+    .args(&["remote", "get-url", remote])
+    .current_dir(repository_path);
+
+  run_output(command, &format!("failed to read remote `{}`", remote))
+}
+
+/// Push a reference (such as a tag, or the current branch with `HEAD`)
+/// to a remote.
+pub fn push(
+  repository_path: &std::path::Path,
+  remote: &str,
+  reference: &str,
+) -> Result<(), String> {
+  let mut command = std::process::Command::new("git");
+
+  command
+    .args(&["push", "--quiet", remote, reference])
+    .current_dir(repository_path);
+
+  run(
+    command,
+    &format!("failed to push `{}` to `{}`", reference, remote),
+  )
+}
+
+/// Commit all changes of a repository.
+pub fn commit_all(repository_path: &std::path::Path, message: &str) -> Result<(), String> {
+  let mut command = std::process::Command::new("git");
+
+  command
+    .args(&["commit", "--quiet", "--all", "--message", message])
+    .current_dir(repository_path);
+
+  run(command, "failed to commit the changes")
+}
+
+/// Retrieve the configured author (`Name <email>`) of commits, if any.
+pub fn author() -> Option<String> {
+  let config_value = |key: &str| {
+    let mut command = std::process::Command::new("git");
+
+    command.args(&["config", "--get", key]);
+
+    run_output(command, "failed to read the git configuration")
+      .ok()
+      .filter(|value| !value.is_empty())
+  };
+
+  let name = config_value("user.name")?;
+
+  match config_value("user.email") {
+    Some(email) => Some(format!("{} <{}>", name, email)),
+    None => Some(name),
+  }
+}
+
+/// Run a git command, and retrieve its (trimmed) standard output.
+fn run_output(mut command: std::process::Command, context: &str) -> Result<String, String> {
+  let output = match command.output() {
+    Ok(output) => output,
+    Err(error) => return Err(format!("{}: failed to run `git`: {}", context, error)),
+  };
+
+  if !output.status.success() {
+    return Err(format!(
+      "{}: {}",
+      context,
+      String::from_utf8_lossy(&output.stderr).trim()
+    ));
+  }
+
+  Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn run(mut command: std::process::Command, context: &str) -> Result<(), String> {
+  let output = match command.output() {
+    Ok(output) => output,
+    Err(error) => return Err(format!("{}: failed to run `git`: {}", context, error)),
+  };
+
+  if !output.status.success() {
+    return Err(format!(
+      "{}: {}",
+      context,
+      String::from_utf8_lossy(&output.stderr).trim()
+    ));
+  }
+
+  Ok(())
+}
@@ -0,0 +1,136 @@
+use crate::package;
+
+/// The directory containing the tests of a package. Each source file
+/// directly within it is a test program, whose `main` function returns
+/// zero if the test passed.
+pub const PATH_TESTS: &str = "tests";
+
+pub struct TestOutcome {
+  pub name: String,
+  pub passed: bool,
+  pub duration: std::time::Duration,
+  /// The captured standard output and error of the test.
+  pub output: Vec<u8>,
+}
+
+/// Find the tests of a package, named after their files. Only the tests
+/// whose names contain the filter (if any) are included.
+pub fn collect_tests(
+  package_manifest: &package::Manifest,
+  filter: Option<&str>,
+) -> Result<Vec<(String, std::path::PathBuf)>, String> {
+  let tests_dir = package::package_dir().join(PATH_TESTS);
+
+  if !tests_dir.is_dir() {
+    return Ok(Vec::new());
+  }
+
+  let mut tests = package::read_sources_dir(&tests_dir, &package_manifest.source_extensions())?
+    .into_iter()
+    .map(|test_file| {
+      (
+        test_file.file_stem().unwrap().to_string_lossy().to_string(),
+        test_file,
+      )
+    })
+    .filter(|(test_name, _)| filter.map_or(true, |filter| test_name.contains(filter)))
+    .collect::<Vec<_>>();
+
+  tests.sort();
+
+  Ok(tests)
+}
+
+/// Retrieve the build units of a test, which is compiled as part of
+/// the package being tested (the first build unit). An executable's own
+/// sources are left out, since its `main` function would conflict with
+/// that of the test.
+pub fn test_build_units(
+  build_units: &[package::BuildUnit],
+  test_file: &std::path::Path,
+) -> Vec<package::BuildUnit> {
+  let mut build_units = build_units.to_vec();
+
+  if let Some(build_unit) = build_units.first_mut() {
+    if build_unit.manifest.ty == package::PackageType::Executable {
+      build_unit.source_files.clear();
+    }
+
+    build_unit.source_files.push(test_file.to_path_buf());
+  }
+
+  build_units
+}
+
+/// Run a test's executable, capturing its output.
+pub fn run_test(
+  test_name: &str,
+  executable_path: &std::path::Path,
+  env: &std::collections::BTreeMap<String, String>,
+) -> Result<TestOutcome, String> {
+  let start = std::time::Instant::now();
+
+  let output = match std::process::Command::new(executable_path)
+    .envs(env)
+    .output()
+  {
+    Ok(output) => output,
+    Err(error) => return Err(format!("failed to run test `{}`: {}", test_name, error)),
+  };
+
+  let duration = start.elapsed();
+  let passed = output.status.success();
+
+  println!(
+    "test {} ... {} ({}ms)",
+    test_name,
+    if passed {
+      ansi_term::Colour::Green.paint("ok")
+    } else {
+      ansi_term::Colour::Red.paint("FAILED")
+    },
+    duration.as_millis()
+  );
+
+  let mut captured_output = output.stdout;
+
+  captured_output.extend(output.stderr);
+
+  Ok(TestOutcome {
+    name: test_name.to_string(),
+    passed,
+    duration,
+    output: captured_output,
+  })
+}
+
+/// Print the output of the failed tests, followed by the totals.
+pub fn print_summary(test_outcomes: &[TestOutcome]) {
+  let failed = test_outcomes
+    .iter()
+    .filter(|test_outcome| !test_outcome.passed)
+    .collect::<Vec<_>>();
+
+  for test_outcome in &failed {
+    println!("\n---- {} output ----", test_outcome.name);
+    println!(
+      "{}",
+      String::from_utf8_lossy(&test_outcome.output).trim_end()
+    );
+  }
+
+  println!(
+    "\ntest result: {}. {} passed; {} failed; finished in {}ms",
+    if failed.is_empty() {
+      ansi_term::Colour::Green.paint("ok")
+    } else {
+      ansi_term::Colour::Red.paint("FAILED")
+    },
+    test_outcomes.len() - failed.len(),
+    failed.len(),
+    test_outcomes
+      .iter()
+      .map(|test_outcome| test_outcome.duration.as_millis())
+      .sum::<u128>()
+  );
+}
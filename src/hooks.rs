@@ -0,0 +1,218 @@
+use crate::package;
+use std::io::BufRead;
+
+/// The environment variable through which hooks are told the build
+/// profile (such as `debug`).
+pub const ENV_PROFILE: &str = "GRIP_PROFILE";
+/// The environment variable through which hooks are told the target
+/// triple being built for.
+pub const ENV_TARGET: &str = "GRIP_TARGET";
+/// The environment variable through which hooks are told the name of
+/// the package.
+pub const ENV_PACKAGE_NAME: &str = "GRIP_PACKAGE_NAME";
+
+/// A point of the build at which the package's hooks are run.
+#[derive(Clone, Copy)]
+pub enum Hook {
+  PreBuild,
+  PostBuild,
+  PreRun,
+}
+
+impl Hook {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Hook::PreBuild => "pre-build",
+      Hook::PostBuild => "post-build",
+      Hook::PreRun => "pre-run",
+    }
+  }
+
+  fn commands<'a>(&self, hooks_config: &'a package::HooksConfig) -> &'a [String] {
+    match self {
+      Hook::PreBuild => &hooks_config.pre_build,
+      Hook::PostBuild => &hooks_config.post_build,
+      Hook::PreRun => &hooks_config.pre_run,
+    }
+  }
+}
+
+fn shell_command(command_line: &str) -> std::process::Command {
+  if cfg!(windows) {
+    let mut command = std::process::Command::new("cmd");
+
+    command.arg("/C").arg(command_line);
+
+    command
+  } else {
+    let mut command = std::process::Command::new("sh");
+
+    command.arg("-c").arg(command_line);
+
+    command
+  }
+}
+
+/// Run one of a hook's commands, logging each line of its output as it
+/// is written.
+fn run_command(
+  hook: Hook,
+  command_line: &str,
+  mut command: std::process::Command,
+) -> Result<(), String> {
+  let mut child = match command
+    .stdout(std::process::Stdio::piped())
+    .stderr(std::process::Stdio::piped())
+    .spawn()
+  {
+    Ok(child) => child,
+    Err(error) => {
+      return Err(format!(
+        "failed to run the `{}` hook `{}`: {}",
+        hook.name(),
+        command_line,
+        error
+      ))
+    }
+  };
+
+  let stderr = child.stderr.take().map(|stderr| {
+    std::thread::spawn(move || {
+      for line in std::io::BufReader::new(stderr).lines().flatten() {
+        log::warn!("{}: {}", hook.name(), line);
+      }
+    })
+  });
+
+  if let Some(stdout) = child.stdout.take() {
+    for line in std::io::BufReader::new(stdout).lines().flatten() {
+      log::info!("{}: {}", hook.name(), line);
+    }
+  }
+
+  if let Some(stderr) = stderr {
+    stderr.join().ok();
+  }
+
+  let status = match child.wait() {
+    Ok(status) => status,
+    Err(error) => {
+      return Err(format!(
+        "failed to wait for the `{}` hook `{}`: {}",
+        hook.name(),
+        command_line,
+        error
+      ))
+    }
+  };
+
+  if !status.success() {
+    return Err(format!(
+      "the `{}` hook `{}` failed: {}",
+      hook.name(),
+      command_line,
+      status
+    ));
+  }
+
+  Ok(())
+}
+
+/// Run the commands of one of the package's hooks, in order, from the
+/// package's directory. The build hooks of its (direct or transitive)
+/// dependencies are run beforehand, so that any sources they generate
+/// exist by the time the package is built. The first command to fail
+/// fails the hook.
+pub fn run(
+  hook: Hook,
+  manifest: &package::Manifest,
+  profile: &package::Profile,
+) -> Result<(), String> {
+  if let Hook::PreBuild | Hook::PostBuild = hook {
+    let mut visited = std::collections::HashSet::new();
+    let mut dependencies = Vec::new();
+    let mut queue = manifest
+      .active_dependencies()
+      .into_iter()
+      .collect::<std::collections::VecDeque<_>>();
+
+    while let Some((dependency_name, dependency_spec)) = queue.pop_front() {
+      if !visited.insert(dependency_name.clone()) {
+        continue;
+      }
+
+      let dependency_manifest =
+        package::fetch_dependency_manifest(&dependency_name, &dependency_spec)?;
+
+      queue.extend(dependency_manifest.active_dependencies());
+
+      dependencies.push((
+        dependency_manifest,
+        package::dependency_dir(&dependency_name, &dependency_spec),
+      ));
+    }
+
+    // The most distant dependencies are run first.
+    for (dependency_manifest, dependency_dir) in dependencies.iter().rev() {
+      run_package(hook, dependency_manifest, dependency_dir, profile)?;
+    }
+  }
+
+  let package_dir = match package::package_dir() {
+    package_dir if package_dir.as_os_str().is_empty() => std::path::Path::new("."),
+    package_dir => package_dir,
+  };
+
+  run_package(hook, manifest, package_dir, profile)
+}
+
+/// Run the commands of one of a single package's hooks, from the given
+/// directory.
+fn run_package(
+  hook: Hook,
+  manifest: &package::Manifest,
+  package_dir: &std::path::Path,
+  profile: &package::Profile,
+) -> Result<(), String> {
+  let commands = hook.commands(&manifest.hooks);
+
+  if commands.is_empty() {
+    return Ok(());
+  }
+
+  let out_dir = package::out_dir(&manifest.name);
+
+  if let Err(error) = std::fs::create_dir_all(&out_dir) {
+    return Err(format!(
+      "failed to create the generated sources directory: {}",
+      error
+    ));
+  }
+
+  let out_dir = match out_dir.canonicalize() {
+    Ok(out_dir) => out_dir,
+    Err(error) => {
+      return Err(format!(
+        "failed to resolve the generated sources directory: {}",
+        error
+      ))
+    }
+  };
+
+  for command_line in commands {
+    log::info!("running the `{}` hook `{}`", hook.name(), command_line);
+
+    let mut command = shell_command(command_line);
+
+    command
+      .current_dir(package_dir)
+      .env(package::ENV_OUT_DIR, &out_dir)
+      .env(ENV_PROFILE, &profile.name)
+      .env(ENV_TARGET, package::target_triple())
+      .env(ENV_PACKAGE_NAME, &manifest.name);
+
+    run_command(hook, command_line, command)?;
+  }
+
+  Ok(())
+}
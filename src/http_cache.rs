@@ -0,0 +1,149 @@
+use crate::{cache, checksum, package, registry};
+
+const PATH_HTTP_CACHE: &str = "http";
+/// How long cached responses are used without revalidating them, so
+/// that repeated runs within a day don't send any requests.
+const MAX_AGE: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CachedResponse {
+  url: String,
+  etag: Option<String>,
+  last_modified: Option<String>,
+  /// Seconds since the Unix epoch.
+  fetched_at: u64,
+  body: String,
+}
+
+/// An on-disk cache of textual registry responses (such as version
+/// lists and manifests), revalidated using `ETag` and `Last-Modified`.
+pub struct HttpCache {
+  dir: Option<std::path::PathBuf>,
+  /// Whether to ignore fresh entries, and always revalidate them.
+  refresh: bool,
+}
+
+impl HttpCache {
+  pub fn new(refresh: bool) -> Self {
+    Self {
+      // Without a cache directory, every request is sent.
+      dir: cache::cache_dir()
+        .ok()
+        .map(|cache_dir| cache_dir.join(PATH_HTTP_CACHE)),
+      refresh,
+    }
+  }
+
+  fn entry_path(&self, url: &str) -> Option<std::path::PathBuf> {
+    self.dir.as_ref().map(|dir| {
+      dir
+        .join(checksum::sha256_bytes(url.as_bytes()))
+        .with_extension("json")
+    })
+  }
+
+  fn load(&self, url: &str) -> Option<CachedResponse> {
+    let contents = package::fetch_file_contents(&self.entry_path(url)?).ok()?;
+
+    serde_json::from_str::<CachedResponse>(&contents)
+      .ok()
+      // Guard against hash collisions.
+      .filter(|cached_response| cached_response.url == url)
+  }
+
+  fn store(&self, cached_response: &CachedResponse) -> Result<(), String> {
+    let entry_path = match self.entry_path(&cached_response.url) {
+      Some(entry_path) => entry_path,
+      None => return Ok(()),
+    };
+
+    let contents = match serde_json::to_string(cached_response) {
+      Ok(contents) => contents,
+      Err(error) => {
+        return Err(format!(
+          "failed to serialize the cached response: {}",
+          error
+        ))
+      }
+    };
+
+    if let Err(error) = std::fs::create_dir_all(entry_path.parent().unwrap())
+      .and_then(|_| std::fs::write(&entry_path, contents))
+    {
+      return Err(format!("failed to cache the response: {}", error));
+    }
+
+    Ok(())
+  }
+
+  /// Send a GET request for the given URL and read its response as
+  /// text, using the cached response if it's fresh or unmodified.
+  pub async fn get_text(
+    &self,
+    request: reqwest::RequestBuilder,
+    url: &str,
+    context: &str,
+  ) -> Result<String, String> {
+    let now = std::time::SystemTime::now()
+      .duration_since(std::time::UNIX_EPOCH)
+      .map_or(0, |duration| duration.as_secs());
+
+    let cached_response = self.load(url);
+    let mut request = request;
+
+    if let Some(cached_response) = &cached_response {
+      if !self.refresh && now.saturating_sub(cached_response.fetched_at) < MAX_AGE.as_secs() {
+        return Ok(cached_response.body.clone());
+      }
+
+      if let Some(etag) = &cached_response.etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+      }
+
+      if let Some(last_modified) = &cached_response.last_modified {
+        request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+      }
+    }
+
+    let response = match request.send().await {
+      Ok(response) => response,
+      Err(error) => return Err(format!("{}: {}", context, error)),
+    };
+
+    let mut cached_response = match cached_response {
+      Some(cached_response) if response.status() == reqwest::StatusCode::NOT_MODIFIED => {
+        cached_response
+      }
+      _ => {
+        let response = registry::check_status(response, context)?;
+        let etag = header_value(&response, reqwest::header::ETAG);
+        let last_modified = header_value(&response, reqwest::header::LAST_MODIFIED);
+
+        CachedResponse {
+          url: url.to_string(),
+          etag,
+          last_modified,
+          fetched_at: now,
+          body: registry::read_text(response).await?,
+        }
+      }
+    };
+
+    cached_response.fetched_at = now;
+
+    // Failing to cache the response shouldn't fail the request.
+    if let Err(error) = self.store(&cached_response) {
+      log::warn!("{}", error);
+    }
+
+    Ok(cached_response.body)
+  }
+}
+
+fn header_value(response: &reqwest::Response, name: reqwest::header::HeaderName) -> Option<String> {
+  response
+    .headers()
+    .get(name)
+    .and_then(|value| value.to_str().ok())
+    .map(|value| value.to_string())
+}
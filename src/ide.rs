@@ -0,0 +1,109 @@
+use crate::{build, package, schema};
+
+const PATH_VSCODE_DIR: &str = ".vscode";
+const PATH_VSCODE_LAUNCH: &str = "launch.json";
+const PATH_VSCODE_TASKS: &str = "tasks.json";
+const BUILD_TASK_LABEL: &str = "grip: build";
+
+/// Editor-agnostic description of how to build and run a package.
+#[derive(serde::Serialize)]
+pub struct RunConfiguration {
+  pub name: String,
+  pub program: String,
+  pub cwd: String,
+  pub env: std::collections::BTreeMap<String, String>,
+  pub build_command: Vec<String>,
+}
+
+pub fn run_configuration(
+  package_manifest: &package::Manifest,
+  artifact_dir: &std::path::Path,
+) -> Result<RunConfiguration, String> {
+  if package_manifest.ty != package::PackageType::Executable {
+    return Err(format!(
+      "package `{}` is a library, and cannot be run",
+      package_manifest.name
+    ));
+  }
+
+  let program = build::executable_path(artifact_dir, &package_manifest.name);
+
+  Ok(RunConfiguration {
+    name: package_manifest.name.clone(),
+    program: program.to_string_lossy().to_string(),
+    cwd: ".".to_string(),
+    env: package_manifest.env.clone(),
+    build_command: vec!["grip".to_string(), "build".to_string()],
+  })
+}
+
+pub fn to_json(run_configuration: &RunConfiguration) -> Result<String, String> {
+  schema::to_json("run-configuration", run_configuration)
+}
+
+/// Write the launch and task configuration files for Visual Studio
+/// Code, refusing to overwrite existing ones unless forced.
+pub fn write_vscode(run_configuration: &RunConfiguration, force: bool) -> Result<(), String> {
+  let vscode_dir = std::path::PathBuf::from(PATH_VSCODE_DIR);
+
+  let launch = serde_json::json!({
+    "version": "0.2.0",
+    "configurations": [{
+      "name": run_configuration.name,
+      // Requires the CodeLLDB extension.
+      "type": "lldb",
+      "request": "launch",
+      "program": format!("${{workspaceFolder}}/{}", run_configuration.program),
+      "cwd": format!("${{workspaceFolder}}/{}", run_configuration.cwd),
+      "env": run_configuration.env,
+      "preLaunchTask": BUILD_TASK_LABEL,
+    }],
+  });
+
+  let tasks = serde_json::json!({
+    "version": "2.0.0",
+    "tasks": [{
+      "label": BUILD_TASK_LABEL,
+      "type": "process",
+      "command": run_configuration.build_command[0],
+      "args": run_configuration.build_command[1..],
+      "group": { "kind": "build", "isDefault": true },
+      "problemMatcher": [],
+    }],
+  });
+
+  if let Err(error) = std::fs::create_dir_all(&vscode_dir) {
+    return Err(format!(
+      "failed to create `{}` directory: {}",
+      PATH_VSCODE_DIR, error
+    ));
+  }
+
+  for (file_name, contents) in [(PATH_VSCODE_LAUNCH, launch), (PATH_VSCODE_TASKS, tasks)] {
+    let file_path = vscode_dir.join(file_name);
+
+    if file_path.exists() && !force {
+      log::warn!(
+        "`{}` already exists, skipping (use `--force` to overwrite it)",
+        file_path.to_string_lossy()
+      );
+
+      continue;
+    }
+
+    // Serializing a `serde_json::Value` cannot fail.
+    let contents = serde_json::to_string_pretty(&contents).unwrap();
+
+    if let Err(error) = std::fs::write(&file_path, contents) {
+      return Err(format!(
+        "failed to write `{}`: {}",
+        file_path.to_string_lossy(),
+        error
+      ));
+    }
+
+    log::info!("wrote `{}`", file_path.to_string_lossy());
+  }
+
+  Ok(())
+}
@@ -0,0 +1,140 @@
+use crate::{cache, dependency, package, tree};
+
+/// Print the details of the current package: its manifest, the amount
+/// of its source files, and its dependency tree (with the installed
+/// version of each dependency).
+pub fn print_package_info(
+  root_manifest: &package::Manifest,
+  package_lock: &package::PackageLock,
+) -> Result<(), String> {
+  print_metadata(root_manifest);
+
+  let source_files = package::read_sources_dir(
+    &package::package_dir().join(crate::PATH_SOURCES),
+    &root_manifest.source_extensions(),
+  )?;
+
+  println!("source files: {}", source_files.len());
+  println!(
+    "path: {}",
+    std::env::current_dir()
+      .unwrap_or_default()
+      .join(package::package_dir())
+      .display()
+  );
+
+  if root_manifest.dependencies.is_empty() {
+    println!("dependencies: (none)");
+
+    return Ok(());
+  }
+
+  println!("dependencies:");
+  tree::print_tree(root_manifest, package_lock, tree::DEFAULT_FORMAT, None)
+}
+
+/// Print the details of an installed dependency: its manifest, where it
+/// was resolved from, where it is installed, and its position in the
+/// dependency graph.
+pub fn print_dependency_info(
+  root_manifest: &package::Manifest,
+  package_lock: &package::PackageLock,
+  package_name: &str,
+) -> Result<(), String> {
+  let package_path = std::path::PathBuf::from(package::PATH_DEPENDENCIES).join(package_name);
+
+  if !package_path.exists() {
+    return Err(format!("package `{}` is not installed", package_name));
+  }
+
+  let manifest = package::fetch_manifest(&package_path.join(package::PATH_MANIFEST_FILE))?;
+  let dependency_graph =
+    dependency::build_dependency_graph(root_manifest.clone(), package::Manifest::all_dependencies)?;
+
+  let mut dependents = dependency_graph
+    .iter()
+    .filter(|(_, dependencies)| dependencies.iter().any(|name| name == package_name))
+    .map(|(name, _)| name.clone())
+    .collect::<Vec<_>>();
+
+  if root_manifest.declares_dependency(package_name) {
+    dependents.push(root_manifest.name.clone());
+  }
+
+  dependents.sort();
+
+  print_metadata(&manifest);
+
+  match package_lock.find_package(package_name) {
+    Some(locked_package) => {
+      println!("source: {}", locked_package.source);
+      println!("reference: {}", locked_package.reference);
+
+      println!(
+        "commit: {}",
+        locked_package.commit.as_deref().unwrap_or("unknown")
+      );
+    }
+    None => println!("source: unknown (not recorded in the lock)"),
+  }
+
+  println!("path: {}", package_path.display());
+
+  println!(
+    "size: {}",
+    indicatif::HumanBytes(cache::directory_size(&package_path))
+  );
+
+  println!(
+    "dependencies: {}",
+    join_or_none(&manifest.dependencies.keys().cloned().collect::<Vec<_>>())
+  );
+  println!("dependents: {}", join_or_none(&dependents));
+
+  Ok(())
+}
+
+/// Print the identity and metadata of a package, omitting any metadata
+/// which isn't set.
+pub fn print_metadata(manifest: &package::Manifest) {
+  println!("name: {}", manifest.name);
+  println!("version: {}", manifest.version);
+
+  println!(
+    "type: {}",
+    match manifest.ty {
+      package::PackageType::Library => "library",
+      package::PackageType::Executable => "executable",
+    }
+  );
+
+  let non_empty = |value: &Option<String>| value.clone().filter(|value| !value.trim().is_empty());
+
+  if let Some(description) = non_empty(&manifest.description) {
+    println!("description: {}", description);
+  }
+
+  if !manifest.authors.is_empty() {
+    println!("authors: {}", manifest.authors.join(", "));
+  }
+
+  if let Some(license) = non_empty(&manifest.license) {
+    println!("license: {}", license);
+  }
+
+  if let Some(repository) = non_empty(&manifest.repository) {
+    println!("repository: {}", repository);
+  }
+
+  if !manifest.keywords.is_empty() {
+    println!("keywords: {}", manifest.keywords.join(", "));
+  }
+}
+
+fn join_or_none(names: &[String]) -> String {
+  if names.is_empty() {
+    "(none)".to_string()
+  } else {
+    names.join(", ")
+  }
+}
@@ -0,0 +1,696 @@
+use crate::error::GripError;
+use crate::{
+  archive, cache, checksum, config, dependency, features, git, package, registry, scheduler,
+};
+use futures_util::StreamExt;
+use std::io::Write;
+
+pub const PATH_DOWNLOADING: &str = ".downloading";
+
+const PATH_PARTIAL_EXTENSION: &str = "part";
+const RETRY_BASE_DELAY_MS: u64 = 500;
+
+/// Why a download attempt failed.
+enum DownloadError {
+  /// The attempt may succeed if retried, such as after a dropped
+  /// connection.
+  Transient(String),
+  /// Retrying would fail the same way, such as when the archive exceeds
+  /// the size limit.
+  Fatal(GripError),
+}
+
+/// Download a package's archive into the dependencies' staging area,
+/// and return the path of the downloaded archive. Failed attempts are
+/// retried with exponential backoff, resuming from where they stopped.
+pub async fn download_package(
+  registry: &(dyn registry::Registry + Send + Sync),
+  package_path: &str,
+  package_reference: &str,
+  package_name: &str,
+  network_config: &config::NetworkConfig,
+) -> Result<std::path::PathBuf, GripError> {
+  let mut file_path = std::path::PathBuf::from(crate::PATH_DEPENDENCIES);
+
+  file_path.push(PATH_DOWNLOADING);
+
+  if !file_path.exists() {
+    if let Err(error) = std::fs::create_dir_all(file_path.clone()) {
+      return Err(GripError::Io(format!(
+        "failed to create the dependencies directory: {}",
+        error
+      )));
+    }
+  }
+
+  file_path.push(format!("{}.zip", package_name));
+
+  let partial_path = file_path.with_extension(format!("zip.{}", PATH_PARTIAL_EXTENSION));
+
+  // A partial download left behind by a previous run may only be
+  // ... resumed if the reference can't have moved since.
+  if partial_path.exists() && !dependency::is_pinned_reference(package_reference) {
+    if let Err(error) = std::fs::remove_file(&partial_path) {
+      return Err(GripError::Io(format!(
+        "failed to remove the partial download: {}",
+        error
+      )));
+    }
+  }
+
+  let mut attempt = 0;
+
+  loop {
+    let download_result = download_attempt(
+      registry,
+      package_path,
+      package_reference,
+      package_name,
+      &partial_path,
+      network_config,
+    )
+    .await;
+
+    match download_result {
+      Ok(()) => break,
+      Err(DownloadError::Transient(error)) if attempt < network_config.retries => {
+        attempt += 1;
+
+        let delay = std::time::Duration::from_millis(RETRY_BASE_DELAY_MS << (attempt - 1));
+
+        log::warn!(
+          "{}; retrying in {:.1}s (attempt {}/{})",
+          error,
+          delay.as_secs_f64(),
+          attempt,
+          network_config.retries
+        );
+
+        tokio::time::sleep(delay).await;
+      }
+      Err(download_error) => {
+        // Best-effort cleanup; the error is more relevant.
+        std::fs::remove_file(&partial_path).ok();
+
+        return Err(match download_error {
+          DownloadError::Transient(error) => GripError::Network(error),
+          DownloadError::Fatal(error) => error,
+        });
+      }
+    }
+  }
+
+  if let Err(error) = std::fs::rename(&partial_path, &file_path) {
+    return Err(GripError::Io(format!(
+      "failed to move the downloaded archive: {}",
+      error
+    )));
+  }
+
+  Ok(file_path)
+}
+
+/// Download a package's archive into a partial file, appending to
+/// whatever was already downloaded if the registry supports it.
+async fn download_attempt(
+  registry: &(dyn registry::Registry + Send + Sync),
+  package_path: &str,
+  package_reference: &str,
+  package_name: &str,
+  partial_path: &std::path::Path,
+  network_config: &config::NetworkConfig,
+) -> Result<(), DownloadError> {
+  let offset = std::fs::metadata(partial_path).map_or(0, |metadata| metadata.len());
+
+  let response = match registry
+    .download_archive(package_path, package_reference, offset)
+    .await
+  {
+    Ok(response) => response,
+    Err(error) => return Err(DownloadError::Transient(error)),
+  };
+
+  let status = response.status();
+
+  let package_zip_file_response =
+    match registry::check_status(response, "failed to download the package") {
+      Ok(response) => response,
+      Err(error) => {
+        // The partial download may be what the registry rejected (such as
+        // ... an unsatisfiable range), so start over on the next attempt.
+        std::fs::remove_file(partial_path).ok();
+
+        if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE
+          || registry::is_transient_status(status)
+        {
+          return Err(DownloadError::Transient(error));
+        }
+
+        return Err(DownloadError::Fatal(GripError::Network(error)));
+      }
+    };
+
+  // The registry may ignore the range, and send the whole archive.
+  let offset = if package_zip_file_response.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+    offset
+  } else {
+    0
+  };
+
+  let file_size = package_zip_file_response
+    .content_length()
+    .map(|content_length| offset + content_length);
+
+  if let Some(file_size) = file_size {
+    if file_size > network_config.max_archive_size {
+      return Err(DownloadError::Fatal(GripError::Network(format!(
+        "failed to download the package: its size ({}) exceeds the limit ({})",
+        indicatif::HumanBytes(file_size),
+        indicatif::HumanBytes(network_config.max_archive_size)
+      ))));
+    }
+  }
+
+  // Without a content length, there is no total to show progress
+  // ... against.
+  let progress_bar = match file_size {
+    Some(file_size) => {
+      let progress_bar = indicatif::ProgressBar::new(file_size);
+
+      progress_bar.set_style(indicatif::ProgressStyle::default_bar().template(
+        "downloading package: {msg} [{bar:30}] {bytes}/{total_bytes} {bytes_per_sec}, {eta}",
+      ));
+
+      progress_bar
+    }
+    None => {
+      let progress_bar = indicatif::ProgressBar::new_spinner();
+
+      progress_bar.set_style(
+        indicatif::ProgressStyle::default_spinner()
+          .template("downloading package: {msg} {spinner} {bytes} {bytes_per_sec}"),
+      );
+
+      progress_bar
+    }
+  };
+
+  progress_bar.set_message(package_name.to_string());
+  progress_bar.set_position(offset);
+
+  let file_result = std::fs::OpenOptions::new()
+    .create(true)
+    .write(true)
+    .append(offset > 0)
+    .truncate(offset == 0)
+    .open(partial_path);
+
+  let mut file = match file_result {
+    Ok(file) => file,
+    Err(error) => {
+      progress_bar.finish_and_clear();
+
+      return Err(DownloadError::Fatal(GripError::Io(format!(
+        "failed to create output file for package download: {}",
+        error
+      ))));
+    }
+  };
+
+  let read_timeout = std::time::Duration::from_secs(network_config.read_timeout);
+  let mut downloaded_bytes = offset;
+  let mut bytes_stream = package_zip_file_response.bytes_stream();
+
+  loop {
+    let chunk_result = match tokio::time::timeout(read_timeout, bytes_stream.next()).await {
+      Ok(Some(chunk_result)) => chunk_result,
+      Ok(None) => break,
+      Err(_) => {
+        progress_bar.finish_and_clear();
+
+        return Err(DownloadError::Transient(format!(
+          "failed to download the package: no data received for {} seconds",
+          network_config.read_timeout
+        )));
+      }
+    };
+
+    let chunk = match chunk_result {
+      Ok(chunk) => chunk,
+      Err(error) => {
+        progress_bar.finish_and_clear();
+
+        return Err(DownloadError::Transient(format!(
+          "failed to download the package: {}",
+          error
+        )));
+      }
+    };
+
+    downloaded_bytes += chunk.len() as u64;
+
+    // The reported content length can't be trusted.
+    if downloaded_bytes > network_config.max_archive_size {
+      progress_bar.finish_and_clear();
+
+      return Err(DownloadError::Fatal(GripError::Network(format!(
+        "failed to download the package: its size exceeds the limit ({})",
+        indicatif::HumanBytes(network_config.max_archive_size)
+      ))));
+    }
+
+    if let Err(error) = file.write_all(&chunk) {
+      progress_bar.finish_and_clear();
+
+      return Err(DownloadError::Fatal(GripError::Io(format!(
+        "failed to write to output file: {}",
+        error
+      ))));
+    }
+
+    progress_bar.set_position(match file_size {
+      Some(file_size) => std::cmp::min(downloaded_bytes, file_size),
+      None => downloaded_bytes,
+    });
+  }
+
+  progress_bar.finish_and_clear();
+
+  // A connection closed early ends the stream as if it were complete.
+  if let Some(file_size) = file_size {
+    if downloaded_bytes < file_size {
+      return Err(DownloadError::Transient(format!(
+        "failed to download the package: the connection was closed after {} of {}",
+        indicatif::HumanBytes(downloaded_bytes),
+        indicatif::HumanBytes(file_size)
+      )));
+    }
+  }
+
+  Ok(())
+}
+
+/// Extract a downloaded package archive into the dependencies
+/// directory. If the archive contains a single top-level folder (as
+/// GitHub archives do), its contents are used as the package root. An
+/// existing installation is only replaced if `replace` is set.
+pub fn extract_package(
+  archive_path: &std::path::Path,
+  package_name: &str,
+  network_config: &config::NetworkConfig,
+  replace: bool,
+) -> Result<std::path::PathBuf, String> {
+  let package_path = std::path::PathBuf::from(crate::PATH_DEPENDENCIES).join(package_name);
+
+  if package_path.exists() && !replace {
+    return Err(format!("package `{}` is already installed", package_name));
+  }
+
+  extract_package_into(archive_path, package_name, &package_path, network_config)?;
+
+  Ok(package_path)
+}
+
+/// Extract a package archive into the given directory, replacing it.
+/// The archive is staged next to the directory.
+pub fn extract_package_into(
+  archive_path: &std::path::Path,
+  package_name: &str,
+  package_path: &std::path::Path,
+  network_config: &config::NetworkConfig,
+) -> Result<(), String> {
+  let staging_path = package_path
+    .parent()
+    .unwrap_or_else(|| std::path::Path::new(""))
+    .join(PATH_DOWNLOADING)
+    .join(format!("{}-{}", package_name, std::process::id()));
+
+  if staging_path.exists() {
+    if let Err(error) = std::fs::remove_dir_all(&staging_path) {
+      return Err(format!("failed to clear the staging directory: {}", error));
+    }
+  }
+
+  archive::extract_zip(archive_path, &staging_path, network_config)?;
+
+  let entries = match std::fs::read_dir(&staging_path) {
+    Ok(read_dir) => read_dir
+      .flatten()
+      .map(|entry| entry.path())
+      .collect::<Vec<_>>(),
+    Err(error) => return Err(format!("failed to read the extracted package: {}", error)),
+  };
+
+  let root_path = match entries.as_slice() {
+    [single_entry] if single_entry.is_dir() => single_entry.clone(),
+    _ => staging_path.clone(),
+  };
+
+  if !root_path.join(package::PATH_MANIFEST_FILE).exists() {
+    // Best-effort cleanup; the error below is more relevant.
+    std::fs::remove_dir_all(&staging_path).ok();
+
+    return Err(format!(
+      "the archive of package `{}` has no `{}` file",
+      package_name,
+      package::PATH_MANIFEST_FILE
+    ));
+  }
+
+  if package_path.exists() {
+    if let Err(error) = std::fs::remove_dir_all(package_path) {
+      return Err(format!(
+        "failed to remove the previous installation: {}",
+        error
+      ));
+    }
+  }
+
+  if let Err(error) = std::fs::rename(&root_path, package_path) {
+    return Err(format!("failed to move the extracted package: {}", error));
+  }
+
+  if staging_path.exists() {
+    std::fs::remove_dir_all(&staging_path).ok();
+  }
+
+  Ok(())
+}
+
+/// Install a package by cloning its git repository directly into the
+/// dependencies directory, and return its manifest along with the
+/// cloned commit. An existing installation is only replaced if
+/// `replace` is set.
+pub fn install_from_git(
+  url: &str,
+  reference: &str,
+  options: &git::CloneOptions,
+  replace: bool,
+) -> Result<(package::Manifest, String), String> {
+  let staging_path = std::path::PathBuf::from(crate::PATH_DEPENDENCIES)
+    .join(PATH_DOWNLOADING)
+    .join(format!("git-{}", std::process::id()));
+
+  if staging_path.exists() {
+    if let Err(error) = std::fs::remove_dir_all(&staging_path) {
+      return Err(format!("failed to clear the staging directory: {}", error));
+    }
+  }
+
+  git::clone(url, reference, &staging_path, options)?;
+
+  let package_manifest = package::fetch_manifest(&staging_path.join(package::PATH_MANIFEST_FILE))?;
+  let commit = git::head_commit(&staging_path)?;
+
+  let package_path =
+    std::path::PathBuf::from(crate::PATH_DEPENDENCIES).join(&package_manifest.name);
+
+  if package_path.exists() && replace {
+    if let Err(error) = std::fs::remove_dir_all(&package_path) {
+      return Err(format!(
+        "failed to remove the previous installation: {}",
+        error
+      ));
+    }
+  } else if package_path.exists() {
+    // Best-effort cleanup; the error below is more relevant.
+    std::fs::remove_dir_all(&staging_path).ok();
+
+    return Err(format!(
+      "package `{}` is already installed",
+      package_manifest.name
+    ));
+  }
+
+  if let Err(error) = std::fs::rename(&staging_path, &package_path) {
+    return Err(format!("failed to move the cloned package: {}", error));
+  }
+
+  Ok((package_manifest, commit))
+}
+
+/// Install a git dependency, at the revision, tag or branch given by
+/// its specification. Returns its manifest, along with its lock entry.
+fn install_git_dependency(
+  name: &str,
+  dependency_spec: &package::DependencySpec,
+) -> Result<(package::Manifest, package::LockedPackage), String> {
+  let (url, branch, tag, rev) = match dependency_spec {
+    package::DependencySpec::Git {
+      git,
+      branch,
+      tag,
+      rev,
+    } => (git, branch, tag, rev),
+    _ => return Err(format!("dependency `{}` is not a git dependency", name)),
+  };
+
+  let reference = tag
+    .as_deref()
+    .or_else(|| branch.as_deref())
+    .unwrap_or(dependency::DEFAULT_REFERENCE);
+
+  // Commits can't be cloned directly, only checked out afterwards.
+  let (manifest, mut commit) = install_from_git(
+    url,
+    reference,
+    &git::CloneOptions {
+      full_history: rev.is_some(),
+      submodules: false,
+    },
+    false,
+  )?;
+
+  let package_path = std::path::PathBuf::from(crate::PATH_DEPENDENCIES).join(&manifest.name);
+
+  if let Some(rev) = rev {
+    git::checkout(&package_path, rev)?;
+    commit = git::head_commit(&package_path)?;
+  }
+
+  let locked_package = package::LockedPackage {
+    name: manifest.name.clone(),
+    kind: package::SourceKind::Git,
+    source: url.clone(),
+    reference: dependency_spec
+      .git_reference()
+      .unwrap_or(reference)
+      .to_string(),
+    commit: Some(commit),
+    version: None,
+    checksum: Some(checksum::sha256_dir(&package_path)?),
+    vendored: None,
+  };
+
+  Ok((manifest, locked_package))
+}
+
+/// Order packages so that each one comes after its dependencies.
+fn topological_order(
+  manifests: &std::collections::BTreeMap<String, package::Manifest>,
+) -> Vec<String> {
+  fn visit(
+    name: &str,
+    manifests: &std::collections::BTreeMap<String, package::Manifest>,
+    visited: &mut std::collections::HashSet<String>,
+    order: &mut Vec<String>,
+  ) {
+    // Cycles are reported before building.
+    if !visited.insert(name.to_string()) {
+      return;
+    }
+
+    if let Some(manifest) = manifests.get(name) {
+      for dependency_name in manifest.all_dependencies().keys() {
+        visit(dependency_name, manifests, visited, order);
+      }
+
+      order.push(name.to_string());
+    }
+  }
+
+  let mut visited = std::collections::HashSet::new();
+  let mut order = Vec::new();
+
+  for name in manifests.keys() {
+    visit(name, manifests, &mut visited, &mut order);
+  }
+
+  order
+}
+
+/// Install the dependencies of a package, and theirs, transitively.
+/// Dependencies which are already installed are kept as-is. Registry
+/// dependencies are installed at the versions chosen by the resolver,
+/// in topological order (each after its own dependencies), while git
+/// dependencies are installed as they are discovered, since their
+/// manifests are only known once cloned. Optional dependencies are only
+/// installed once enabled by the features in use.
+// REVIEW: The dependencies of git packages are resolved separately, so
+// ... conflicts between their requirements and others' aren't detected.
+pub async fn install_dependencies(
+  registry: &(dyn registry::Registry + Send + Sync),
+  scheduler: &scheduler::Scheduler,
+  network_config: &config::NetworkConfig,
+  manifest: &package::Manifest,
+  package_lock: &mut package::PackageLock,
+) -> Result<(), GripError> {
+  let is_installed = |name: &str| {
+    std::path::Path::new(crate::PATH_DEPENDENCIES)
+      .join(name)
+      .exists()
+  };
+
+  let mut visited = std::collections::HashSet::new();
+  let mut pending = std::collections::BTreeMap::new();
+  let mut resolved_versions = std::collections::HashMap::new();
+  let mut feature_requests = features::FeatureRequests::default();
+  let mut manifests = std::collections::VecDeque::from(vec![(
+    manifest.clone(),
+    features::resolve_selected(manifest)?,
+    true,
+  )]);
+
+  while let Some((manifest, resolved_features, needs_resolution)) = manifests.pop_front() {
+    if needs_resolution {
+      for resolved_package in
+        dependency::resolve_versions(registry, &manifest, &resolved_features).await?
+      {
+        if !visited.insert(resolved_package.name.clone()) || is_installed(&resolved_package.name) {
+          continue;
+        }
+
+        // Registry packages are covered by the resolution, but may
+        // themselves depend on git packages.
+        manifests.push_back((
+          resolved_package.manifest.clone(),
+          features::resolve(
+            &resolved_package.manifest,
+            &resolved_package.features,
+            false,
+          )?,
+          false,
+        ));
+        pending.insert(
+          resolved_package.name.clone(),
+          resolved_package.manifest.clone(),
+        );
+        resolved_versions.insert(resolved_package.name.clone(), resolved_package);
+      }
+    }
+
+    for (dependency_name, dependency_spec) in
+      &features::used_dependencies(&manifest, manifest.all_dependencies(), &resolved_features)
+    {
+      match dependency_spec {
+        package::DependencySpec::Git { .. } => {
+          let has_new_features = feature_requests.request(
+            dependency_name,
+            &resolved_features
+              .dependency_features
+              .get(dependency_name)
+              .cloned()
+              .unwrap_or_default(),
+          );
+
+          if visited.contains(dependency_name) || is_installed(dependency_name) {
+            // More features may enable more of its dependencies.
+            if has_new_features {
+              let git_manifest =
+                package::fetch_dependency_manifest(dependency_name, dependency_spec)?;
+              let git_features = feature_requests.resolve(dependency_name, &git_manifest)?;
+
+              manifests.push_back((git_manifest, git_features, true));
+            }
+
+            continue;
+          }
+
+          visited.insert(dependency_name.clone());
+
+          let (git_manifest, locked_package) =
+            install_git_dependency(dependency_name, dependency_spec)?;
+
+          log::info!("installed dependency `{}`", git_manifest.name);
+          package_lock.lock_package(locked_package);
+
+          let git_features = feature_requests.resolve(dependency_name, &git_manifest)?;
+
+          manifests.push_back((git_manifest, git_features, true));
+        }
+        package::DependencySpec::Path { path } => {
+          if !package::dependency_dir(dependency_name, dependency_spec).exists() {
+            log::warn!(
+              "path dependency `{}` does not exist at `{}`",
+              dependency_name,
+              path
+            );
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+
+  // Downloads proceed concurrently, but packages are installed in order.
+  let downloads = pending
+    .keys()
+    .map(|name| {
+      let resolved_package = &resolved_versions[name];
+
+      scheduler.run(download_package(
+        registry,
+        &resolved_package.source,
+        &resolved_package.reference,
+        &resolved_package.name,
+        network_config,
+      ))
+    })
+    .collect::<Vec<_>>();
+
+  let mut archive_paths = std::collections::HashMap::new();
+
+  for (name, archive_path) in pending
+    .keys()
+    .zip(futures_util::future::join_all(downloads).await)
+  {
+    archive_paths.insert(name.clone(), archive_path?);
+  }
+
+  for name in topological_order(&pending) {
+    let resolved_package = &resolved_versions[&name];
+    let archive_path = &archive_paths[&name];
+
+    if let Err(error) = cache::store(archive_path, &format!("{}.zip", name)) {
+      log::warn!("{}", error);
+    }
+
+    let package_path = extract_package(archive_path, &name, network_config, false)?;
+
+    if let Err(error) = std::fs::remove_file(archive_path) {
+      log::warn!("failed to remove the downloaded archive: {}", error);
+    }
+
+    package_lock.lock_package(package::LockedPackage {
+      name: name.clone(),
+      kind: package::SourceKind::Registry,
+      source: resolved_package.source.clone(),
+      reference: resolved_package.reference.clone(),
+      commit: registry
+        .resolve_commit(&resolved_package.source, &resolved_package.reference)
+        .await
+        .map_err(GripError::Network)?,
+      version: Some(resolved_package.version.to_string()),
+      checksum: Some(checksum::sha256_dir(&package_path)?),
+      vendored: None,
+    });
+
+    log::info!(
+      "installed dependency `{}` v{}",
+      name,
+      resolved_package.version
+    );
+  }
+
+  Ok(())
+}
@@ -0,0 +1,117 @@
+use crate::package;
+
+const PATH_LICENSES: &str = "licenses";
+const PATH_ATTRIBUTIONS: &str = "ATTRIBUTIONS.txt";
+
+/// File name prefixes (case-insensitive) of license and notice files.
+const LICENSE_FILE_PREFIXES: &[&str] = &["license", "licence", "copying", "notice"];
+
+/// Find the license and notice files at the root of a package.
+fn find_license_files(root_dir: &std::path::Path) -> Result<Vec<std::path::PathBuf>, String> {
+  let read_dir = match std::fs::read_dir(std::path::Path::new(".").join(root_dir)) {
+    Ok(read_dir) => read_dir,
+    Err(error) => return Err(format!("failed to read package directory: {}", error)),
+  };
+
+  let mut license_files = read_dir
+    .flatten()
+    .map(|dir_entry| dir_entry.path())
+    .filter(|path| path.is_file())
+    .filter(|path| {
+      let file_name = path.file_name().unwrap().to_string_lossy().to_lowercase();
+
+      LICENSE_FILE_PREFIXES
+        .iter()
+        .any(|prefix| file_name.starts_with(prefix))
+    })
+    .collect::<Vec<_>>();
+
+  license_files.sort();
+
+  Ok(license_files)
+}
+
+/// Copy the license and notice files of every dependency into
+/// `<output_dir>/licenses/<package>/`, and write an attribution summary
+/// alongside them. Returns the path of the summary.
+pub fn bundle(
+  build_units: &[package::BuildUnit],
+  output_dir: &std::path::Path,
+) -> Result<std::path::PathBuf, String> {
+  let licenses_dir = output_dir.join(PATH_LICENSES);
+
+  // Start from scratch, so that removed dependencies don't linger.
+  if licenses_dir.exists() {
+    if let Err(error) = std::fs::remove_dir_all(&licenses_dir) {
+      return Err(format!(
+        "failed to remove the licenses directory: {}",
+        error
+      ));
+    }
+  }
+
+  let mut attributions = String::new();
+
+  // The first build unit is the package being built.
+  for build_unit in build_units.iter().skip(1) {
+    let package_name = &build_unit.manifest.name;
+    let license_files = find_license_files(&build_unit.root_dir)?;
+    let package_licenses_dir = licenses_dir.join(package_name);
+
+    attributions.push_str(&format!(
+      "{} v{}\n",
+      package_name, build_unit.manifest.version
+    ));
+
+    if license_files.is_empty() {
+      log::warn!("dependency `{}` has no license file", package_name);
+      attributions.push_str("  (no license file found)\n");
+
+      continue;
+    }
+
+    if let Err(error) = std::fs::create_dir_all(&package_licenses_dir) {
+      return Err(format!(
+        "failed to create the licenses directory: {}",
+        error
+      ));
+    }
+
+    for license_file in license_files {
+      let file_name = license_file.file_name().unwrap();
+
+      if let Err(error) = std::fs::copy(&license_file, package_licenses_dir.join(file_name)) {
+        return Err(format!(
+          "failed to copy `{}`: {}",
+          license_file.display(),
+          error
+        ));
+      }
+
+      attributions.push_str(&format!(
+        "  {}/{}/{}\n",
+        PATH_LICENSES,
+        package_name,
+        file_name.to_string_lossy()
+      ));
+    }
+  }
+
+  if let Err(error) = std::fs::create_dir_all(&licenses_dir) {
+    return Err(format!(
+      "failed to create the licenses directory: {}",
+      error
+    ));
+  }
+
+  let attributions_path = licenses_dir.join(PATH_ATTRIBUTIONS);
+
+  if let Err(error) = std::fs::write(&attributions_path, attributions) {
+    return Err(format!(
+      "failed to write the attribution summary: {}",
+      error
+    ));
+  }
+
+  Ok(attributions_path)
+}
@@ -0,0 +1,247 @@
+use crate::{build, console, error::GripError, package, tool};
+
+/// Compiler drivers used for linking, in order of preference. These
+/// know where the C runtime and system libraries are located, unlike
+/// invoking `ld` directly. On Windows, the MSVC linker is also used if
+/// no compiler driver is available.
+const LINKERS: &[&str] = if cfg!(windows) {
+  &["clang", "cc", "link"]
+} else {
+  &["cc", "clang", "gcc"]
+};
+
+/// The system libraries requested (`[build]`) by the packages being
+/// linked, along with the directories to search for them.
+#[derive(Default)]
+pub struct SystemLibraries {
+  pub names: Vec<String>,
+  pub search_paths: Vec<std::path::PathBuf>,
+}
+
+impl SystemLibraries {
+  pub fn collect(build_units: &[package::BuildUnit]) -> Self {
+    let mut system_libraries = Self::default();
+
+    for build_unit in build_units {
+      for build_config in build_unit.manifest.active_build_configs() {
+        for link_lib in &build_config.link_libs {
+          if !system_libraries.names.contains(link_lib) {
+            system_libraries.names.push(link_lib.clone());
+          }
+        }
+
+        system_libraries.search_paths.extend(
+          build_config
+            .link_search_paths
+            .iter()
+            .map(|search_path| build_unit.root_dir.join(search_path)),
+        );
+      }
+    }
+
+    system_libraries
+  }
+}
+
+pub struct LinkOptions<'a> {
+  /// Where to write a linker map file, if any.
+  pub map_path: Option<&'a std::path::Path>,
+  /// Whether to strip symbols from the executable.
+  pub strip: bool,
+  /// Whether to produce a position-independent executable.
+  pub pie: bool,
+  pub system_libraries: &'a SystemLibraries,
+}
+
+impl<'a> LinkOptions<'a> {
+  pub fn from_profile(profile: &package::Profile, system_libraries: &'a SystemLibraries) -> Self {
+    Self {
+      map_path: None,
+      strip: profile.strip,
+      pie: profile.pie,
+      system_libraries,
+    }
+  }
+}
+
+/// Add the arguments of the MSVC linker, whose syntax differs from that
+/// of compiler drivers.
+fn add_msvc_args(
+  command: &mut std::process::Command,
+  output_path: &std::path::Path,
+  options: &LinkOptions<'_>,
+) {
+  command
+    .arg("/NOLOGO")
+    .arg(format!("/OUT:{}", output_path.display()))
+    // Provides the entry point, which calls `main`.
+    .arg("/DEFAULTLIB:libcmt");
+
+  if let Some(map_path) = options.map_path {
+    command.arg(format!("/MAP:{}", map_path.display()));
+  }
+
+  // Symbols are kept in a separate file unless requested, so there is
+  // nothing to strip.
+  command.arg(if options.pie {
+    "/DYNAMICBASE"
+  } else {
+    "/DYNAMICBASE:NO"
+  });
+
+  for search_path in &options.system_libraries.search_paths {
+    command.arg(format!("/LIBPATH:{}", search_path.display()));
+  }
+
+  for name in &options.system_libraries.names {
+    command.arg(format!("{}.lib", name));
+  }
+}
+
+/// Print each line the linker wrote to its standard error as a
+/// diagnostic.
+fn print_linker_output(stderr: &[u8], severity: gecko::diagnostic::Severity) {
+  let files = codespan_reporting::files::SimpleFiles::new();
+
+  for line in String::from_utf8_lossy(stderr).lines() {
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    console::print_diagnostic(
+      &files,
+      None,
+      &gecko::diagnostic::Diagnostic {
+        severity,
+        message: format!("linker: {}", line.trim()),
+        span: None,
+      },
+    );
+  }
+}
+
+/// Write a module's object files next to the executable (using up to
+/// `jobs` threads), and link them into it. Returns `false` if the step
+/// was skipped because no linker is available.
+pub fn link_module(
+  toolchain: &tool::Toolchain,
+  llvm_module: &inkwell::module::Module<'_>,
+  profile: &package::Profile,
+  executable_path: &std::path::Path,
+  options: &LinkOptions<'_>,
+  jobs: usize,
+) -> Result<bool, GripError> {
+  let object_path = executable_path.with_extension(build::EmitKind::Object.extension());
+  let object_paths = build::write_objects(llvm_module, profile, &object_path, jobs)?;
+
+  link(toolchain, &object_paths, executable_path, options)
+}
+
+/// Link object files into an executable. Returns `false` if the step
+/// was skipped because no linker is available.
+pub fn link(
+  toolchain: &tool::Toolchain,
+  object_files: &[std::path::PathBuf],
+  output_path: &std::path::Path,
+  options: &LinkOptions<'_>,
+) -> Result<bool, GripError> {
+  let linker = match toolchain.find_any(LINKERS, "linking")? {
+    Some(linker) => linker,
+    None => return Ok(false),
+  };
+
+  let mut command = std::process::Command::new(&linker);
+
+  command.args(object_files);
+
+  if linker.file_stem() == Some(std::ffi::OsStr::new("link")) {
+    add_msvc_args(&mut command, output_path, options);
+
+    return run_linker(&linker, command);
+  }
+
+  command.arg("-o").arg(output_path);
+
+  // Only clang can link for targets other than its own.
+  // TODO: The flags below are still chosen for the host's platform.
+  if !package::is_host_target() && linker.file_stem() == Some(std::ffi::OsStr::new("clang")) {
+    command.arg(format!("--target={}", package::target_triple()));
+  }
+
+  if let Some(map_path) = options.map_path {
+    // The Apple linker uses a different flag.
+    let map_flag = if cfg!(target_os = "macos") {
+      "-Wl,-map,"
+    } else {
+      "-Wl,-Map="
+    };
+
+    command.arg(format!("{}{}", map_flag, map_path.display()));
+  }
+
+  if options.strip {
+    // The Apple linker doesn't support `-s`.
+    if cfg!(target_os = "macos") {
+      command.arg("-Wl,-S,-x");
+    } else {
+      command.arg("-s");
+    }
+  }
+
+  if cfg!(target_os = "linux") {
+    if options.pie {
+      // Also make the relocated sections read-only once loaded.
+      command.args(&["-pie", "-Wl,-z,relro,-z,now"]);
+    } else {
+      command.arg("-no-pie");
+    }
+  } else if cfg!(target_os = "macos") && !options.pie {
+    command.arg("-Wl,-no_pie");
+  }
+
+  // Libraries must follow the objects referencing them.
+  for search_path in &options.system_libraries.search_paths {
+    command.arg(format!("-L{}", search_path.display()));
+  }
+
+  for name in &options.system_libraries.names {
+    command.arg(format!("-l{}", name));
+  }
+
+  run_linker(&linker, command)
+}
+
+fn run_linker(
+  linker: &std::path::Path,
+  mut command: std::process::Command,
+) -> Result<bool, GripError> {
+  let output = match command.output() {
+    Ok(output) => output,
+    Err(error) => {
+      return Err(GripError::Link(format!(
+        "failed to run the linker `{}`: {}",
+        linker.display(),
+        error
+      )))
+    }
+  };
+
+  // The MSVC linker reports its errors on the standard output.
+  let mut linker_output = output.stderr;
+
+  linker_output.extend(output.stdout);
+
+  if output.status.success() {
+    print_linker_output(&linker_output, gecko::diagnostic::Severity::Warning);
+
+    Ok(true)
+  } else {
+    print_linker_output(&linker_output, gecko::diagnostic::Severity::Error);
+
+    Err(GripError::Link(format!(
+      "linking failed: `{}` exited with {}",
+      linker.display(),
+      output.status
+    )))
+  }
+}
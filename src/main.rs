@@ -1,55 +1,399 @@
 #![deny(rust_2018_idioms)]
 
-use futures_util::StreamExt;
 use std::{collections::vec_deque, str::FromStr};
 use std::{collections::vec_deque::VecDeque, io::Write};
 
+use error::GripError;
+
+mod archive;
+mod binary;
 mod build;
+mod cache;
+mod checksum;
+mod config;
 mod console;
+mod daemon;
+mod debug_info;
 mod dependency;
+mod error;
+mod features;
+mod formatter;
+mod git;
+mod harness;
+mod hooks;
+mod http_cache;
+mod ide;
+mod info;
+mod install;
+mod license;
+mod link;
+mod manifest_lint;
+mod manifest_validation;
+mod network;
 mod package;
+mod prompt;
+mod publish;
+mod registry;
+mod report;
+mod sandbox;
+mod scheduler;
+mod schema;
+mod script;
+mod search;
+mod size;
+mod tool;
+mod tree;
+mod update;
+mod vendor;
+mod watch;
 
-// TODO: Consider replacing this to a "lex" subcommand.
-const ARG_LIST_TOKENS: &str = "tokens";
 const ARG_BUILD: &str = "build";
 const ARG_BUILD_PRINT_OUTPUT: &str = "print";
+const ARG_BUILD_EMIT: &str = "emit";
 const ARG_BUILD_NO_VERIFY: &str = "no-verify";
 const ARG_BUILD_OPT: &str = "opt";
+const ARG_BUILD_RELEASE: &str = "release";
+const ARG_BUILD_DEBUG_INFO: &str = "debug-info";
+const ARG_BUILD_PLAN: &str = "build-plan";
+const ARG_BUILD_TIMINGS: &str = "timings";
+const ARG_BUILD_RECORD: &str = "record";
+const ARG_BUILD_DIAGNOSTICS_FOR: &str = "diagnostics-for";
+const ARG_BUILD_LICENSES: &str = "licenses";
+const ARG_BUILD_MESSAGE_FORMAT: &str = "message-format";
+const ARG_BUILD_SUMMARY_FILE: &str = "summary-file";
+const ARG_BUILD_SUMMARY_FORMAT: &str = "summary-format";
+const ARG_BUILD_SIZE_REPORT: &str = "size-report";
+const ARG_BUILD_ALLOW_DIRTY_DEPS: &str = "allow-dirty-deps";
+const ARG_BUILD_SANDBOX: &str = "sandbox";
+const ARG_BUILD_NO_CACHE: &str = "no-cache";
+const ARG_WATCH: &str = "watch";
 const ARG_INIT: &str = "init";
 const ARG_INIT_NAME: &str = "name";
 const ARG_INIT_FORCE: &str = "force";
 const ARG_INSTALL: &str = "install";
 const ARG_INSTALL_PATH: &str = "repository-path";
 const ARG_INSTALL_BRANCH: &str = "branch";
+const ARG_INSTALL_DRY_RUN: &str = "dry-run";
+const ARG_INSTALL_GIT: &str = "git";
+const ARG_INSTALL_FULL_HISTORY: &str = "full-history";
+const ARG_INSTALL_SUBMODULES: &str = "submodules";
+const ARG_INSTALL_BIN: &str = "bin";
+const ARG_UNINSTALL: &str = "uninstall";
+const ARG_UNINSTALL_NAME: &str = "name";
+const ARG_LIST: &str = "list";
+const ARG_LIST_INSTALLED: &str = "installed";
+const ARG_ADD: &str = "add";
+const ARG_ADD_PATH: &str = "repository-path";
+const ARG_ADD_NAME: &str = "name";
+const ARG_ADD_VERSION: &str = "version";
+const ARG_ADD_GIT: &str = "git";
+const ARG_ADD_BRANCH: &str = "branch";
+const ARG_ADD_FETCH: &str = "fetch";
+const ARG_REMOVE: &str = "remove";
+const ARG_REMOVE_DEPENDENCY: &str = "dependency";
+const ARG_VENDOR: &str = "vendor";
+const ARG_PUBLISH: &str = "publish";
+const ARG_PUBLISH_DRY_RUN: &str = "dry-run";
+const ARG_PUBLISH_REGISTER: &str = "register";
+const ARG_SEARCH: &str = "search";
+const ARG_SEARCH_QUERY: &str = "query";
+const ARG_SEARCH_LIMIT: &str = "limit";
+const ARG_SEARCH_JSON: &str = "json";
 const ARG_CHECK: &str = "check";
+const ARG_CHECK_FILE: &str = "file";
 const ARG_CLEAN: &str = "clean";
+const ARG_CLEAN_DEP: &str = "dep";
+const ARG_CLEAN_CACHE: &str = "cache";
+const ARG_CLEAN_OLDER_THAN: &str = "older-than";
+const ARG_CLEAN_UNREFERENCED: &str = "unreferenced";
+const ARG_CLEAN_ALL: &str = "all";
+const ARG_CLEAN_DEPS: &str = "deps";
+const ARG_CLEAN_DRY_RUN: &str = "dry-run";
 const ARG_RUN: &str = "run";
+const ARG_RUN_CLEAN_ENV: &str = "clean-env";
+const ARG_RUN_RELEASE: &str = "release";
+const ARG_TEST: &str = "test";
+const ARG_TEST_FILTER: &str = "filter";
+const ARG_METADATA: &str = "metadata";
+const ARG_DAEMON: &str = "daemon";
+const ARG_FMT: &str = "fmt";
+const ARG_FMT_MANIFEST: &str = "manifest";
+const ARG_FMT_CHECK: &str = "check";
+const ARG_GREP: &str = "grep";
+const ARG_GREP_PATTERN: &str = "pattern";
+const ARG_GREP_DEPS: &str = "deps";
+const ARG_GREP_IGNORE_CASE: &str = "ignore-case";
+const ARG_IDE: &str = "ide";
+const ARG_IDE_EDITOR: &str = "editor";
+const ARG_IDE_FORCE: &str = "force";
+const ARG_INFO: &str = "info";
+const ARG_INFO_PACKAGE: &str = "package";
+const ARG_PIN: &str = "pin";
+const ARG_UPDATE: &str = "update";
+const ARG_UPDATE_YES: &str = "yes";
+const ARG_UPDATE_NO_INPUT: &str = "no-input";
+const ARG_UPDATE_PACKAGE: &str = "package";
+const ARG_UPDATE_DRY_RUN: &str = "dry-run";
+const ARG_PIN_DEPENDENCY: &str = "dependency";
+const ARG_RESOLVE: &str = "resolve";
+const ARG_TREE: &str = "tree";
+const ARG_TREE_FORMAT: &str = "format";
+const ARG_TREE_INVERT: &str = "invert";
+const ARG_IR: &str = "ir";
+const ARG_IR_FUNCTION: &str = "function";
+const ARG_IR_OPTIMIZED: &str = "optimized";
+const ARG_LEX: &str = "lex";
+const ARG_SCRIPT: &str = "script";
+const ARG_EVAL: &str = "eval";
+const ARG_EVAL_EXPRESSION: &str = "expression";
+const ARG_PARSE: &str = "parse";
+const ARG_PARSE_JSON: &str = "json";
+const ARG_FRONTEND_FILE: &str = "file";
+const ARG_LEX_JSON: &str = "json";
+const ARG_REPLAY: &str = "replay";
+const ARG_REPLAY_ARCHIVE: &str = "archive";
+const ARG_REPLAY_INTO: &str = "into";
+const ARG_STRICT_TOOLS: &str = "strict-tools";
+const ARG_REFRESH: &str = "refresh";
+const ARG_OFFLINE: &str = "offline";
+const ARG_FROZEN: &str = "frozen";
+const ARG_FEATURES: &str = "features";
+const ARG_NO_DEFAULT_FEATURES: &str = "no-default-features";
+const ARG_TARGET: &str = "target";
+const ARG_MANIFEST_PATH: &str = "manifest-path";
+const ARG_PACKAGE: &str = "package";
+const ARG_JOBS: &str = "jobs";
+const ARG_DIAGNOSTIC_STYLE: &str = "diagnostic-style";
+const ARG_ERROR_LIMIT: &str = "error-limit";
+const ARG_QUIET: &str = "quiet";
 const PATH_SOURCES: &str = "src";
+const PATH_STDIN: &str = "<stdin>";
+const PATH_EVAL: &str = "<eval>";
+const ANONYMOUS_PACKAGE_NAME: &str = "anonymous";
+/// Longer token excerpts are truncated when displayed in a table.
+const MAX_TOKEN_EXCERPT_LENGTH: usize = 40;
+const DEFAULT_SEARCH_LIMIT: &str = "10";
 const DEFAULT_OUTPUT_DIR: &str = "./build";
 const PATH_DEPENDENCIES: &str = "dependencies";
+const PATH_ARTIFACT_INDEX: &str = "manifest.json";
+const PATH_LINKER_MAP_EXTENSION: &str = "map";
+/// Environment variables kept when running with a clean environment,
+/// without which most programs cannot function.
+const MINIMAL_ENV_VARS: &[&str] = &["PATH", "HOME", "TMPDIR", "SYSTEMROOT", "TEMP"];
 
-async fn run() -> Result<(), String> {
-  let app = clap::App::new("Grip")
+async fn run() -> Result<(), GripError> {
+  let mut app = clap::App::new("Grip")
   .version(clap::crate_version!())
   .author(clap::crate_authors!())
   .about("Package manager & command-line utility for the gecko programming language")
+  .arg(
+    clap::Arg::with_name(ARG_REFRESH)
+      .long(ARG_REFRESH)
+      .global(true)
+      .help("Revalidate cached registry responses (such as package versions and manifests), instead of using them"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_OFFLINE)
+      .long(ARG_OFFLINE)
+      .global(true)
+      .help("Refuse to access the network"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_FROZEN)
+      .long(ARG_FROZEN)
+      .global(true)
+      .help("Like --offline, but also refuse to modify the lock"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_FEATURES)
+      .long(ARG_FEATURES)
+      .global(true)
+      .takes_value(true)
+      .value_name("FEATURES")
+      .help("Enable the given (comma-separated) features of the package"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_NO_DEFAULT_FEATURES)
+      .long(ARG_NO_DEFAULT_FEATURES)
+      .global(true)
+      .help("Don't enable the `default` feature of the package"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_TARGET)
+      .long(ARG_TARGET)
+      .global(true)
+      .takes_value(true)
+      .value_name("TRIPLE")
+      .help("Build for the given target triple, instead of the host"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_STRICT_TOOLS)
+      .long(ARG_STRICT_TOOLS)
+      .global(true)
+      .help("Fail instead of skipping steps whose external tools (such as the system linker) are missing"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_MANIFEST_PATH)
+      .long(ARG_MANIFEST_PATH)
+      .global(true)
+      .takes_value(true)
+      .value_name("PATH")
+      .help("Path to the package manifest file (or its directory), instead of searching for it in the current directory and its parents"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_PACKAGE)
+      .long(ARG_PACKAGE)
+      .global(true)
+      .takes_value(true)
+      .value_name("NAME")
+      .help("The member of the workspace to operate on, by its name or directory (defaults to the member containing the current directory)"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_JOBS)
+      .short("j")
+      .long(ARG_JOBS)
+      .global(true)
+      .takes_value(true)
+      .value_name("N")
+      .help("The maximum number of jobs (downloads, compilation) to run at the same time (defaults to the number of CPUs)"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_DIAGNOSTIC_STYLE)
+      .long(ARG_DIAGNOSTIC_STYLE)
+      .global(true)
+      .takes_value(true)
+      .possible_values(&["short", "full", "expanded"])
+      .help("How verbose printed diagnostics are (defaults to `full`)"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_ERROR_LIMIT)
+      .long(ARG_ERROR_LIMIT)
+      .global(true)
+      .takes_value(true)
+      .value_name("COUNT")
+      .help("Stop printing diagnostics after this many errors (defaults to 50; 0 disables the limit)"),
+  )
+  .arg(
+    clap::Arg::with_name(ARG_QUIET)
+      .short("q")
+      .long(ARG_QUIET)
+      .global(true)
+      .help("Do not print status lines or progress bars (diagnostics are still printed)"),
+  )
   .subcommand(
   clap::SubCommand::with_name(ARG_BUILD)
     .about("Build the project in the current directory")
-    .arg(
-      clap::Arg::with_name(ARG_LIST_TOKENS)
-        .short("t")
-        .long(ARG_LIST_TOKENS)
-        .help("Display a list of the lexed tokens"),
-    )
     .arg(
       clap::Arg::with_name(ARG_BUILD_PRINT_OUTPUT)
-        .short("p")
         .long(ARG_BUILD_PRINT_OUTPUT)
-        .help("Print the resulting LLVM IR instead of producing an output file"),
+        .help("Print the output to stdout instead of producing an output file"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_EMIT)
+        .long(ARG_BUILD_EMIT)
+        .takes_value(true)
+        .possible_values(&["ir", "bc", "asm", "obj", "exe", "tokens", "ast"])
+        .default_value("ir")
+        .help("The kind of output to produce"),
     )
     .arg(clap::Arg::with_name(ARG_BUILD_NO_VERIFY).short("v").long(ARG_BUILD_NO_VERIFY).help("Skip LLVM IR verification"))
-    .arg(clap::Arg::with_name(ARG_BUILD_OPT).short("O").long(ARG_BUILD_OPT).help("Specify the optimization level of the produced LLVM IR")),
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_OPT)
+        .short("O")
+        .long(ARG_BUILD_OPT)
+        .takes_value(true)
+        .value_name("LEVEL")
+        .possible_values(&["0", "1", "2", "3", "s", "z"])
+        .help("Specify the optimization level of the produced LLVM IR (such as `-O2`, or `-Os` to favor smaller code), overriding that of the profile"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_RELEASE)
+        .long(ARG_BUILD_RELEASE)
+        .help("Build with the `release` profile (optimized, without debug info) instead of the `debug` profile"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_DEBUG_INFO)
+        .long(ARG_BUILD_DEBUG_INFO)
+        .help("Emit debug info, so that debuggers can map the program back to its sources, even if the profile doesn't"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_PLAN)
+        .long(ARG_BUILD_PLAN)
+        .help("Print the build plan as JSON instead of building"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_TIMINGS)
+        .long(ARG_BUILD_TIMINGS)
+        .help("Print the time spent on each build phase as JSON"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_RECORD)
+        .long(ARG_BUILD_RECORD)
+        .takes_value(true)
+        .value_name("ARCHIVE")
+        .help("Bundle the sources, manifests, lockfile and flags of this build into an archive, for bug reports"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_DIAGNOSTICS_FOR)
+        .long(ARG_BUILD_DIAGNOSTICS_FOR)
+        .takes_value(true)
+        .value_name("PACKAGE")
+        .help("Only print warnings for the given package (errors are always printed)"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_LICENSES)
+        .long(ARG_BUILD_LICENSES)
+        .help("Collect the license and notice files of all dependencies into the build directory"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_MESSAGE_FORMAT)
+        .long(ARG_BUILD_MESSAGE_FORMAT)
+        .takes_value(true)
+        .possible_values(&["human", "json"])
+        .default_value("human")
+        .help("The format of build events and diagnostics (`json` prints newline-delimited JSON)"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_SUMMARY_FILE)
+        .long(ARG_BUILD_SUMMARY_FILE)
+        .takes_value(true)
+        .value_name("PATH")
+        .help("Write a summary of the build (status, durations, diagnostic counts and artifact hashes) to a file, such as for CI"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_SUMMARY_FORMAT)
+        .long(ARG_BUILD_SUMMARY_FORMAT)
+        .takes_value(true)
+        .possible_values(&["json", "markdown"])
+        .default_value("json")
+        .requires(ARG_BUILD_SUMMARY_FILE)
+        .help("The format of the build summary (`markdown` is suitable for pull request comments)"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_SIZE_REPORT)
+        .long(ARG_BUILD_SIZE_REPORT)
+        .help("Write a linker map, and print the contribution of each package and function to the executable's size"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_ALLOW_DIRTY_DEPS)
+        .long(ARG_BUILD_ALLOW_DIRTY_DEPS)
+        .help("Build even if installed dependencies were modified since they were installed"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_SANDBOX)
+        .long(ARG_BUILD_SANDBOX)
+        .help("Build in a hermetic environment (no network, temporary home directory, read-only sources), to catch undeclared inputs"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_BUILD_NO_CACHE)
+        .long(ARG_BUILD_NO_CACHE)
+        .help("Force a full rebuild, ignoring cached results"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_WATCH)
+        .long(ARG_WATCH)
+        .help("Build again whenever the manifest or the sources change"),
+    ),
   )
   .subcommand(
   clap::SubCommand::with_name(ARG_INIT)
@@ -64,308 +408,3847 @@ async fn run() -> Result<(), String> {
   )
   .subcommand(
   clap::SubCommand::with_name(ARG_INSTALL)
-    .about("Install a package from a GitHub repository")
+    .about("Install a package from a GitHub repository, or all of the manifest's dependencies if none is given")
     .arg(
       clap::Arg::with_name(ARG_INSTALL_PATH)
         .index(1)
-        .help("The GitHub repository path where the package lives, in the following format: `user/repository` or `organization/repository`"),
+        .help("The path where the package lives on the registry; for GitHub, in the following format: `user/repository` or `organization/repository` (prefix it with `gitlab:` or `bitbucket:` for other hosts), or a package name listed by the configured registry index"),
     )
     .arg(
       clap::Arg::with_name(ARG_INSTALL_BRANCH)
-        .help("The GitHub repository's branch to use")
+        .help("The branch (or version) of the package to use")
         .short("b")
         .long(ARG_INSTALL_BRANCH)
-        .default_value("master"),
+        .default_value(dependency::DEFAULT_REFERENCE),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_INSTALL_DRY_RUN)
+        .help("Resolve the package and its dependencies, and print what would be downloaded")
+        .long(ARG_INSTALL_DRY_RUN)
+        .requires(ARG_INSTALL_PATH),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_INSTALL_GIT)
+        .help("Treat the path as a git repository URL, and clone it instead of downloading an archive")
+        .long(ARG_INSTALL_GIT)
+        .requires(ARG_INSTALL_PATH)
+        .conflicts_with(ARG_INSTALL_DRY_RUN),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_INSTALL_FULL_HISTORY)
+        .help("Clone the full history (including tags) instead of a shallow clone")
+        .long(ARG_INSTALL_FULL_HISTORY)
+        .requires(ARG_INSTALL_GIT),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_INSTALL_SUBMODULES)
+        .help("Also clone the repository's submodules")
+        .long(ARG_INSTALL_SUBMODULES)
+        .requires(ARG_INSTALL_GIT),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_INSTALL_BIN)
+        .help("Build the package as an executable, and install it into the grip `bin` directory instead of adding it as a dependency")
+        .long(ARG_INSTALL_BIN)
+        .requires(ARG_INSTALL_PATH)
+        .conflicts_with(ARG_INSTALL_DRY_RUN),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_ADD)
+    .about("Declare a dependency in the manifest, without installing it")
+    .arg(
+      clap::Arg::with_name(ARG_ADD_PATH)
+        .index(1)
+        .required(true)
+        .help("The path where the package lives on the registry (`user/repository`, prefixed with `gitlab:` or `bitbucket:` for other hosts), or a git repository URL with `--git`"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_ADD_NAME)
+        .long(ARG_ADD_NAME)
+        .takes_value(true)
+        .help("The name of the dependency (defaults to the repository's name)"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_ADD_VERSION)
+        .long(ARG_ADD_VERSION)
+        .takes_value(true)
+        .conflicts_with(ARG_ADD_GIT)
+        .help("The version requirement of the dependency (defaults to any version)"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_ADD_GIT)
+        .long(ARG_ADD_GIT)
+        .help("Treat the path as a git repository URL"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_ADD_BRANCH)
+        .short("b")
+        .long(ARG_ADD_BRANCH)
+        .takes_value(true)
+        .requires(ARG_ADD_GIT)
+        .help("The branch (or commit) of the repository to use"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_ADD_FETCH)
+        .long(ARG_ADD_FETCH)
+        .help("Also install the dependency (and any other missing dependencies)"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_PUBLISH)
+    .about("Check the package, then tag its current version as a release (`v<version>`) and push the tag")
+    .arg(
+      clap::Arg::with_name(ARG_PUBLISH_DRY_RUN)
+        .help("Perform all checks, and print what would be tagged and registered without doing so")
+        .long(ARG_PUBLISH_DRY_RUN),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_PUBLISH_REGISTER)
+        .help("Also add the release to the configured (git) registry index")
+        .long(ARG_PUBLISH_REGISTER),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_VENDOR)
+    .about("Copy all locked dependencies into the `vendor` directory, so that `grip install --offline` can install them without network access"),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_SEARCH)
+    .about("Search the registry index (or GitHub, if no index is configured) for packages")
+    .arg(
+      clap::Arg::with_name(ARG_SEARCH_QUERY)
+        .index(1)
+        .help("The text to look for in package names and descriptions")
+        .required(true),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_SEARCH_LIMIT)
+        .help("The maximum amount of packages to list")
+        .long(ARG_SEARCH_LIMIT)
+        .takes_value(true)
+        .default_value(DEFAULT_SEARCH_LIMIT),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_SEARCH_JSON)
+        .help("Print the results as JSON instead of a table")
+        .long(ARG_SEARCH_JSON),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_REMOVE)
+    .about("Remove a dependency from the manifest, along with any installed packages no longer depended upon")
+    .arg(
+      clap::Arg::with_name(ARG_REMOVE_DEPENDENCY)
+        .index(1)
+        .required(true)
+        .help("The name of the dependency"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_UNINSTALL)
+    .about("Remove an executable installed with `grip install --bin`")
+    .arg(
+      clap::Arg::with_name(ARG_UNINSTALL_NAME)
+        .index(1)
+        .required(true)
+        .help("The name of the executable"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_LIST)
+    .about("List installed items")
+    .arg(
+      clap::Arg::with_name(ARG_LIST_INSTALLED)
+        .help("List the executables installed with `grip install --bin`")
+        .long(ARG_LIST_INSTALLED)
+        .required(true),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_CHECK)
+    .about("Perform type-checking only")
+    .arg(
+      clap::Arg::with_name(ARG_CHECK_FILE)
+        .index(1)
+        .help("Check a single file outside of a package instead (use `-` to read from stdin)"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_CLEAN)
+    .about("Clean the build directory and any produced artifacts")
+    .arg(
+      clap::Arg::with_name(ARG_CLEAN_DEP)
+        .help("Only invalidate the build record of the given dependency, forcing it to be rebuilt")
+        .long(ARG_CLEAN_DEP)
+        .takes_value(true),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_CLEAN_CACHE)
+        .help("Operate on the user-wide cache instead, reporting its size")
+        .long(ARG_CLEAN_CACHE)
+        .conflicts_with(ARG_CLEAN_DEP),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_CLEAN_OLDER_THAN)
+        .help("Remove cache entries older than the given amount of days")
+        .long(ARG_CLEAN_OLDER_THAN)
+        .takes_value(true)
+        .value_name("DAYS")
+        .requires(ARG_CLEAN_CACHE),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_CLEAN_UNREFERENCED)
+        .help("Remove cache entries not referenced by any known project")
+        .long(ARG_CLEAN_UNREFERENCED)
+        .requires(ARG_CLEAN_CACHE),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_CLEAN_ALL)
+        .help("Remove all cache entries")
+        .long(ARG_CLEAN_ALL)
+        .requires(ARG_CLEAN_CACHE),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_CLEAN_DEPS)
+        .help("Also remove all downloaded dependencies")
+        .long(ARG_CLEAN_DEPS)
+        .conflicts_with_all(&[ARG_CLEAN_DEP, ARG_CLEAN_CACHE]),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_CLEAN_DRY_RUN)
+        .help("List what would be removed, without removing anything")
+        .long(ARG_CLEAN_DRY_RUN)
+        .conflicts_with_all(&[ARG_CLEAN_DEP, ARG_CLEAN_CACHE]),
     ),
   )
-  .subcommand(clap::SubCommand::with_name(ARG_CHECK).about("Perform type-checking only"))
-  .subcommand(clap::SubCommand::with_name(ARG_CLEAN).about("Clean the build directory and any produced artifacts"))
-  .subcommand(clap::SubCommand::with_name(ARG_RUN).about("Build and execute the project"));
+  .subcommand(
+  clap::SubCommand::with_name(ARG_RUN)
+    .about("Build and execute the project")
+    .arg(
+      clap::Arg::with_name(ARG_RUN_CLEAN_ENV)
+        .long(ARG_RUN_CLEAN_ENV)
+        .help("Run the program with a minimal environment, plus the variables declared in the manifest"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_RUN_RELEASE)
+        .long(ARG_RUN_RELEASE)
+        .help("Build with the `release` profile instead of the `debug` profile"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_WATCH)
+        .long(ARG_WATCH)
+        .help("Build and run again whenever the manifest or the sources change, stopping the previous run"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_TEST)
+    .about("Build and run the tests of the project (the source files under `tests/`)")
+    .arg(
+      clap::Arg::with_name(ARG_TEST_FILTER)
+        .index(1)
+        .help("Only run the tests whose names contain this string"),
+    ),
+  )
+  .subcommand(clap::SubCommand::with_name(ARG_METADATA).about("Print the resolved package metadata as JSON"))
+  .subcommand(clap::SubCommand::with_name(ARG_DAEMON).about("Serve queries about the project (such as `grip/projectModel`) as JSON-RPC over stdin and stdout"))
+  .subcommand(
+  clap::SubCommand::with_name(ARG_FMT)
+    .about("Format the package")
+    .arg(
+      clap::Arg::with_name(ARG_FMT_MANIFEST)
+        .long(ARG_FMT_MANIFEST)
+        .help("Normalize the package manifest file (key order, sorted dependencies)"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_FMT_CHECK)
+        .long(ARG_FMT_CHECK)
+        .conflicts_with(ARG_FMT_MANIFEST)
+        .help("Fail if any source file isn't formatted, instead of rewriting it"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_GREP)
+    .about("Search the package's source files, printing module-qualified results")
+    .arg(
+      clap::Arg::with_name(ARG_GREP_PATTERN)
+        .index(1)
+        .required(true)
+        .help("The text to search for"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_GREP_DEPS)
+        .long(ARG_GREP_DEPS)
+        .help("Also search the source files of dependencies"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_GREP_IGNORE_CASE)
+        .short("i")
+        .long(ARG_GREP_IGNORE_CASE)
+        .help("Search case-insensitively"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_IDE)
+    .about("Generate run and debug configurations for an editor")
+    .arg(
+      clap::Arg::with_name(ARG_IDE_EDITOR)
+        .index(1)
+        .required(true)
+        .possible_values(&["vscode", "json"])
+        .help("The editor to generate configurations for (`json` prints an editor-agnostic description)"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_IDE_FORCE)
+        .long(ARG_IDE_FORCE)
+        .help("Overwrite existing configuration files"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_UPDATE)
+    .about("Update dependencies to the newest versions allowed by the manifest, and those tracking a branch to its latest commit")
+    .arg(
+      clap::Arg::with_name(ARG_UPDATE_YES)
+        .long(ARG_UPDATE_YES)
+        .short("y")
+        .help("Accept all updates without prompting, including major version changes"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_UPDATE_NO_INPUT)
+        .long(ARG_UPDATE_NO_INPUT)
+        .conflicts_with(ARG_UPDATE_YES)
+        .help("Never prompt, failing instead if an update requires confirmation (for CI)"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_UPDATE_PACKAGE)
+        .index(1)
+        .help("Only update the given dependency"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_UPDATE_DRY_RUN)
+        .long(ARG_UPDATE_DRY_RUN)
+        .help("Print the available updates, without applying them"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_TREE)
+    .about("Print the dependency tree of the project")
+    .arg(
+      clap::Arg::with_name(ARG_TREE_FORMAT)
+        .long(ARG_TREE_FORMAT)
+        .takes_value(true)
+        .default_value(tree::DEFAULT_FORMAT)
+        .help("The format of each package: `{p}` (name), `{v}` (version), `{s}` (source), `{f}` (enabled features), `{o}` (whether it is overridden)"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_TREE_INVERT)
+        .long(ARG_TREE_INVERT)
+        .short("i")
+        .takes_value(true)
+        .value_name("PACKAGE")
+        .help("Print the packages which depend on the given package instead"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_RESOLVE)
+    .about("Choose the versions of registry dependencies satisfying all requirements, and record them in the lock"),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_INFO)
+    .about("Print the details of the current package, or of an installed dependency")
+    .arg(
+      clap::Arg::with_name(ARG_INFO_PACKAGE)
+        .index(1)
+        .help("The name of the dependency (omit it to describe the current package)"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_PIN)
+    .about("Pin a dependency to the commit it currently resolves to")
+    .arg(
+      clap::Arg::with_name(ARG_PIN_DEPENDENCY)
+        .index(1)
+        .required(true)
+        .help("The name of the dependency to pin"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_IR)
+    .about("Build the project and print the LLVM IR of a single function")
+    .arg(
+      clap::Arg::with_name(ARG_IR_FUNCTION)
+        .index(1)
+        .required(true)
+        .help("The name of the function to print"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_IR_OPTIMIZED)
+        .long(ARG_IR_OPTIMIZED)
+        .help("Print the LLVM IR after optimization passes have run"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_SCRIPT)
+    .about("Compile and immediately run a single source file, without a package manifest")
+    .arg(
+      clap::Arg::with_name(ARG_FRONTEND_FILE)
+        .index(1)
+        .required(true)
+        .help("The script to run (use `-` to read from stdin)"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_EVAL)
+    .about("Evaluate a single expression, and print its value")
+    .arg(
+      clap::Arg::with_name(ARG_EVAL_EXPRESSION)
+        .index(1)
+        .required(true)
+        .help("The expression to evaluate (use `-` to read from stdin)"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_LEX)
+    .about("Print the tokens of a source file, along with their kinds and spans")
+    .arg(
+      clap::Arg::with_name(ARG_FRONTEND_FILE)
+        .index(1)
+        .required(true)
+        .help("The source file to lex (use `-` to read from stdin)"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_LEX_JSON)
+        .long(ARG_LEX_JSON)
+        .help("Print the tokens as JSON, instead of a table"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_PARSE)
+    .alias("ast")
+    .about("Print the AST of a source file (or of the package's sources), running only lexing and parsing")
+    .arg(
+      clap::Arg::with_name(ARG_FRONTEND_FILE)
+        .index(1)
+        .help("The source file to parse (use `-` to read from stdin), instead of the package's sources"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_PARSE_JSON)
+        .long(ARG_PARSE_JSON)
+        .help("Print the AST as JSON, instead of a pretty-printed tree"),
+    ),
+  )
+  .subcommand(
+  clap::SubCommand::with_name(ARG_REPLAY)
+    .about("Reproduce a build recorded with `grip build --record`")
+    .arg(
+      clap::Arg::with_name(ARG_REPLAY_ARCHIVE)
+        .index(1)
+        .required(true)
+        .help("The recorded build archive"),
+    )
+    .arg(
+      clap::Arg::with_name(ARG_REPLAY_INTO)
+        .long(ARG_REPLAY_INTO)
+        .takes_value(true)
+        .value_name("DIRECTORY")
+        .help("The directory in which to reproduce the build (defaults to a temporary directory)"),
+    ),
+  );
+
+  // The app is kept around to print the help if no subcommand is given.
+  let matches = app.clone().get_matches();
+  let llvm_context = inkwell::context::Context::create();
+  let set_logger_result = log::set_logger(&console::LOGGER);
+
+  if let Err(error) = set_logger_result {
+    return Err(GripError::Other(format!(
+      "there was an error initializing the logger: {}",
+      error
+    )));
+  }
+
+  log::set_max_level(log::LevelFilter::Info);
+
+  if let Some(diagnostic_style) = global_value_of(&matches, ARG_DIAGNOSTIC_STYLE) {
+    console::set_diagnostic_style(console::DiagnosticStyle::from_str(diagnostic_style)?);
+  }
+
+  if let Some(error_limit) = global_value_of(&matches, ARG_ERROR_LIMIT) {
+    match error_limit.parse::<usize>() {
+      Ok(error_limit) => console::set_error_limit(error_limit),
+      Err(_) => {
+        return Err(GripError::Other(format!(
+          "invalid error limit `{}`",
+          error_limit
+        )))
+      }
+    }
+  }
+
+  console::set_quiet(is_global_present(&matches, ARG_QUIET));
+
+  package::set_feature_selection(features::FeatureSelection::parse(
+    global_value_of(&matches, ARG_FEATURES),
+    is_global_present(&matches, ARG_NO_DEFAULT_FEATURES),
+  ));
+
+  if let Some(target_triple) = global_value_of(&matches, ARG_TARGET) {
+    package::set_target_triple(target_triple.to_string());
+  }
+
+  let toolchain = tool::Toolchain::new(is_global_present(&matches, ARG_STRICT_TOOLS));
+
+  let network_context = network::NetworkContext::new(
+    is_global_present(&matches, ARG_OFFLINE),
+    is_global_present(&matches, ARG_FROZEN),
+  );
+
+  let jobs = match global_value_of(&matches, ARG_JOBS) {
+    Some(jobs) => match jobs.parse::<usize>() {
+      Ok(jobs) if jobs > 0 => jobs,
+      _ => {
+        return Err(GripError::Other(format!(
+          "invalid number of jobs `{}`",
+          jobs
+        )))
+      }
+    },
+    None => scheduler::Scheduler::default_jobs(),
+  };
+
+  let scheduler = scheduler::Scheduler::new(jobs);
+
+  // Relative paths given as arguments are relative to the directory
+  // grip was invoked from, not the package's root directory.
+  let invocation_dir = match std::env::current_dir() {
+    Ok(current_dir) => current_dir,
+    Err(error) => {
+      return Err(GripError::Other(format!(
+        "failed to read the current directory: {}",
+        error
+      )))
+    }
+  };
+
+  match matches.subcommand() {
+    (ARG_INIT, _) | (ARG_REPLAY, _) | (ARG_SCRIPT, _) | (ARG_EVAL, _) | (ARG_LEX, _) | ("", _) => {}
+    (ARG_PARSE, Some(parse_arg_matches)) if parse_arg_matches.is_present(ARG_FRONTEND_FILE) => {}
+    (ARG_CHECK, Some(check_arg_matches)) if check_arg_matches.is_present(ARG_CHECK_FILE) => {}
+    (ARG_CLEAN, Some(clean_arg_matches)) if clean_arg_matches.is_present(ARG_CLEAN_CACHE) => {}
+    (ARG_INSTALL, Some(install_arg_matches)) if install_arg_matches.is_present(ARG_INSTALL_BIN) => {
+    }
+    (ARG_UNINSTALL, _) | (ARG_LIST, _) | (ARG_SEARCH, _) => {}
+    (ARG_INSTALL, Some(install_arg_matches))
+      if install_arg_matches.is_present(ARG_INSTALL_PATH) =>
+    {
+      enter_project_dir(
+        &invocation_dir,
+        global_value_of(&matches, ARG_MANIFEST_PATH),
+        global_value_of(&matches, ARG_PACKAGE),
+        false,
+      )?
+    }
+    _ => enter_project_dir(
+      &invocation_dir,
+      global_value_of(&matches, ARG_MANIFEST_PATH),
+      global_value_of(&matches, ARG_PACKAGE),
+      true,
+    )?,
+  }
+
+  // A virtual workspace has no sources of its own, so operate on each of
+  // its members in turn instead.
+  match matches.subcommand() {
+    (ARG_CHECK, Some(check_arg_matches)) if check_arg_matches.is_present(ARG_CHECK_FILE) => {}
+    (ARG_BUILD, _) | (ARG_CHECK, _) | (ARG_TEST, _) => {
+      if let Some(members) = package::virtual_workspace_members()? {
+        return run_for_members(&invocation_dir, &members).map_err(GripError::Other);
+      }
+    }
+    _ => {}
+  }
+
+  if let Some(init_arg_matches) = matches.subcommand_matches(ARG_INIT) {
+    package::init_manifest(&init_arg_matches);
+
+    Ok(())
+  } else if let Some(build_arg_matches) = matches.subcommand_matches(ARG_BUILD) {
+    build_command(
+      build_arg_matches,
+      &llvm_context,
+      &toolchain,
+      jobs,
+      &scheduler,
+      &invocation_dir,
+    )
+  } else if matches.subcommand_matches(ARG_METADATA).is_some() {
+    let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+
+    let metadata = schema::Metadata {
+      root: package_manifest.name.clone(),
+      packages: package::collect_build_units(&package_manifest)?
+        .iter()
+        .map(schema::package_metadata)
+        .collect(),
+    };
+
+    println!("{}", schema::to_json("metadata", &metadata)?);
+
+    Ok(())
+  } else if matches.subcommand_matches(ARG_DAEMON).is_some() {
+    daemon::run().map_err(GripError::Other)
+  } else if let Some(script_arg_matches) = matches.subcommand_matches(ARG_SCRIPT) {
+    run_script(
+      &llvm_context,
+      script_arg_matches.value_of(ARG_FRONTEND_FILE).unwrap(),
+    )
+  } else if let Some(eval_arg_matches) = matches.subcommand_matches(ARG_EVAL) {
+    eval_expression(
+      &llvm_context,
+      eval_arg_matches.value_of(ARG_EVAL_EXPRESSION).unwrap(),
+    )
+  } else if let Some(lex_arg_matches) = matches.subcommand_matches(ARG_LEX) {
+    lex_file(
+      &llvm_context,
+      lex_arg_matches.value_of(ARG_FRONTEND_FILE).unwrap(),
+      lex_arg_matches.is_present(ARG_LEX_JSON),
+    )
+  } else if let Some(parse_arg_matches) = matches.subcommand_matches(ARG_PARSE) {
+    let json = parse_arg_matches.is_present(ARG_PARSE_JSON);
+
+    match parse_arg_matches.value_of(ARG_FRONTEND_FILE) {
+      Some(file) => parse_file(&llvm_context, file, json),
+      None => parse_package(&llvm_context, json, scheduler.jobs()),
+    }
+  } else if let Some(fmt_arg_matches) = matches.subcommand_matches(ARG_FMT) {
+    if !fmt_arg_matches.is_present(ARG_FMT_MANIFEST) {
+      return format_package(&llvm_context, fmt_arg_matches.is_present(ARG_FMT_CHECK))
+        .map_err(GripError::Other);
+    }
+
+    let manifest_path = package::manifest_path();
+
+    if package::format_manifest(&manifest_path)? {
+      log::info!("formatted `{}`", manifest_path.display());
+    } else {
+      log::info!("`{}` is already formatted", manifest_path.display());
+    }
+
+    Ok(())
+  } else if let Some(grep_arg_matches) = matches.subcommand_matches(ARG_GREP) {
+    grep_command(grep_arg_matches)
+  } else if let Some(ide_arg_matches) = matches.subcommand_matches(ARG_IDE) {
+    ide_command(ide_arg_matches)
+  } else if let Some(update_arg_matches) = matches.subcommand_matches(ARG_UPDATE) {
+    update_command(update_arg_matches, &matches, &network_context, &scheduler).await
+  } else if let Some(tree_arg_matches) = matches.subcommand_matches(ARG_TREE) {
+    let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+
+    tree::print_tree(
+      &package_manifest,
+      &package::get_or_init_package_lock()?,
+      tree_arg_matches.value_of(ARG_TREE_FORMAT).unwrap(),
+      tree_arg_matches.value_of(ARG_TREE_INVERT),
+    )
+    .map_err(GripError::Other)
+  } else if matches.subcommand_matches(ARG_RESOLVE).is_some() {
+    resolve_command(&matches, &network_context).await
+  } else if let Some(info_arg_matches) = matches.subcommand_matches(ARG_INFO) {
+    let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+
+    let package_lock = package::get_or_init_package_lock()?;
+
+    match info_arg_matches.value_of(ARG_INFO_PACKAGE) {
+      Some(package_name) => {
+        info::print_dependency_info(&package_manifest, &package_lock, package_name)
+      }
+      None => info::print_package_info(&package_manifest, &package_lock),
+    }
+    .map_err(GripError::Other)
+  } else if let Some(pin_arg_matches) = matches.subcommand_matches(ARG_PIN) {
+    pin_command(pin_arg_matches, &network_context)
+  } else if let Some(replay_arg_matches) = matches.subcommand_matches(ARG_REPLAY) {
+    replay_command(replay_arg_matches)
+  } else if let Some(ir_arg_matches) = matches.subcommand_matches(ARG_IR) {
+    ir_command(ir_arg_matches, &llvm_context, &scheduler)
+  } else if let Some(run_arg_matches) = matches.subcommand_matches(ARG_RUN) {
+    run_command(
+      run_arg_matches,
+      &llvm_context,
+      &toolchain,
+      jobs,
+      &scheduler,
+      &invocation_dir,
+    )
+  } else if let Some(test_arg_matches) = matches.subcommand_matches(ARG_TEST) {
+    test_command(
+      test_arg_matches,
+      &llvm_context,
+      &toolchain,
+      jobs,
+      &scheduler,
+    )
+  } else if let Some(check_arg_matches) = matches.subcommand_matches(ARG_CHECK) {
+    check_command(check_arg_matches, &llvm_context, &scheduler)
+  } else if let Some(clean_arg_matches) = matches.subcommand_matches(ARG_CLEAN) {
+    clean_command(clean_arg_matches)
+  } else if let Some(install_arg_matches) = matches.subcommand_matches(ARG_INSTALL) {
+    install_command(install_arg_matches, &matches, &network_context, &scheduler).await
+  } else if let Some(add_arg_matches) = matches.subcommand_matches(ARG_ADD) {
+    add_command(add_arg_matches, &matches, &network_context, &scheduler).await
+  } else if let Some(search_arg_matches) = matches.subcommand_matches(ARG_SEARCH) {
+    search_command(search_arg_matches, &matches, &network_context).await
+  } else if let Some(publish_arg_matches) = matches.subcommand_matches(ARG_PUBLISH) {
+    publish_command(publish_arg_matches, &network_context)
+  } else if matches.subcommand_matches(ARG_VENDOR).is_some() {
+    network_context.require_mutable_lock(ARG_VENDOR)?;
+
+    let config = config::fetch_config()?;
+    let mut package_lock = package::get_or_init_package_lock()?;
+    let vendored_count = vendor::vendor(&mut package_lock, &config.network)?;
+
+    package::write_package_lock(&package_lock)?;
+
+    log::info!(
+      "vendored {} package(s) into `{}`",
+      vendored_count,
+      vendor::PATH_VENDOR
+    );
+
+    Ok(())
+  } else if let Some(remove_arg_matches) = matches.subcommand_matches(ARG_REMOVE) {
+    remove_command(remove_arg_matches, &network_context)
+  } else if let Some(uninstall_arg_matches) = matches.subcommand_matches(ARG_UNINSTALL) {
+    let name = uninstall_arg_matches.value_of(ARG_UNINSTALL_NAME).unwrap();
+
+    binary::uninstall(name)?;
+    log::info!("uninstalled `{}`", name);
+
+    Ok(())
+  } else if matches.subcommand_matches(ARG_LIST).is_some() {
+    let installed = binary::installed()?;
+
+    if installed.is_empty() {
+      log::info!("no executables are installed");
+    }
+
+    for (name, installed_binary) in &installed {
+      println!(
+        "{} v{} ({}@{})",
+        name, installed_binary.version, installed_binary.source, installed_binary.reference
+      );
+    }
+
+    Ok(())
+  } else {
+    print_usage_hints(&invocation_dir);
+    println!();
+
+    if let Err(error) = app.print_long_help() {
+      return Err(GripError::Other(format!(
+        "failed to print the help: {}",
+        error
+      )));
+    }
+
+    println!();
+
+    Ok(())
+  }
+}
+
+/// Build the package in the current directory.
+fn build_command(
+  build_arg_matches: &clap::ArgMatches<'_>,
+  llvm_context: &inkwell::context::Context,
+  toolchain: &tool::Toolchain,
+  jobs: usize,
+  scheduler: &scheduler::Scheduler,
+  invocation_dir: &std::path::Path,
+) -> Result<(), GripError> {
+  if build_arg_matches.is_present(ARG_WATCH) {
+    return watch::run(
+      invocation_dir,
+      &package::package_dir().join(PATH_SOURCES),
+      args_without_flag(ARG_WATCH),
+    )
+    .map_err(GripError::Other);
+  }
+
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+  let mut package_lock = package::get_or_init_package_lock()?;
+  let json_messages = build_arg_matches.value_of(ARG_BUILD_MESSAGE_FORMAT) == Some("json");
+  let summary_path = build_arg_matches
+    .value_of(ARG_BUILD_SUMMARY_FILE)
+    .map(|summary_path| invocation_dir.join(summary_path));
+  let markdown_summary = build_arg_matches.value_of(ARG_BUILD_SUMMARY_FORMAT) == Some("markdown");
+  let build_start = std::time::Instant::now();
+
+  for dependency in package_manifest.active_dependencies().keys() {
+    if let Some(locked_package) = package_lock.find_package(dependency) {
+      if !dependency::is_pinned_reference(&locked_package.reference) {
+        log::warn!(
+          "dependency `{}` tracks the moving branch `{}`; consider running `grip pin {}`",
+          dependency,
+          locked_package.reference,
+          dependency
+        );
+      }
+    }
+  }
+
+  if !json_messages {
+    console::print_status(
+      "Resolving",
+      &format!("dependencies of `{}`", package_manifest.name),
+    );
+  }
+
+  package::verify_dependency_dirs(&package_manifest, &package_lock)?;
+
+  // The sandbox only holds the dependencies' inputs, which were
+  // ... verified before entering it.
+  if std::env::var_os(sandbox::ENV_SANDBOXED).is_none() {
+    package::verify_dependency_checksums(
+      &package_lock,
+      build_arg_matches.is_present(ARG_BUILD_ALLOW_DIRTY_DEPS),
+    )?;
+  }
+
+  expose_out_dir(&package_manifest.name)?;
+
+  let profile = package_manifest.profile(if build_arg_matches.is_present(ARG_BUILD_RELEASE) {
+    package::PROFILE_RELEASE
+  } else {
+    package::PROFILE_DEBUG
+  });
+
+  let is_sandboxing = build_arg_matches.is_present(ARG_BUILD_SANDBOX)
+    && std::env::var_os(sandbox::ENV_SANDBOXED).is_none();
+
+  // The hooks are run within the sandbox instead, if any. They may
+  // ... generate sources, so they're run before those are collected.
+  if !is_sandboxing {
+    hooks::run(hooks::Hook::PreBuild, &package_manifest, &profile)?;
+  }
+
+  let build_units = package::collect_build_units(&package_manifest)?;
+
+  if is_sandboxing {
+    // The sandboxed build finds the manifest within its copy of the
+    // ... project instead.
+    let status = sandbox::run(
+      toolchain,
+      &build_units,
+      args_without_value(args_without_flag(ARG_BUILD_SANDBOX), ARG_MANIFEST_PATH),
+    )?;
+
+    return match status.code() {
+      Some(0) => Ok(()),
+      Some(code) => std::process::exit(code),
+      None => Err(GripError::Other(format!(
+        "the sandboxed build was terminated: {}",
+        status
+      ))),
+    };
+  }
+
+  let target_triple = inkwell::targets::TargetTriple::create(&package::target_triple());
+
+  let opt_level = match build_arg_matches.value_of(ARG_BUILD_OPT) {
+    Some(opt_level) => build::OptLevel::from_str(opt_level)?,
+    None => profile.opt_level,
+  };
+
+  let mut default_output_path =
+    build::artifact_dir(&target_triple.as_str().to_string_lossy(), &profile.name);
+
+  // Keep artifacts of an overridden level apart, so that they don't
+  // ... collide with (or get mistaken as fresh for) those of the
+  // ... profile's own level.
+  if opt_level != profile.opt_level {
+    default_output_path.push(opt_level.name());
+  }
+
+  let print_output = build_arg_matches.is_present(ARG_BUILD_PRINT_OUTPUT);
+  let mut output_path = default_output_path.clone();
+  let emit_kind = build::EmitKind::from_str(build_arg_matches.value_of(ARG_BUILD_EMIT).unwrap())?;
+
+  output_path.push(package_manifest.name.clone());
+  output_path.set_extension(emit_kind.extension());
+
+  if let Some(archive_path) = build_arg_matches.value_of(ARG_BUILD_RECORD) {
+    let mut build_args = Vec::new();
+    let mut args = std::env::args().skip(1);
+
+    // Record the invocation, without the recording itself.
+    while let Some(arg) = args.next() {
+      if arg == format!("--{}", ARG_BUILD_RECORD) {
+        args.next();
+      } else if !arg.starts_with(&format!("--{}=", ARG_BUILD_RECORD)) {
+        build_args.push(arg);
+      }
+    }
+
+    report::record(&invocation_dir.join(archive_path), &build_units, build_args)?;
+
+    log::info!("recorded the build into `{}`", archive_path);
+  }
+
+  if build_arg_matches.is_present(ARG_BUILD_PLAN) {
+    let build_plan = schema::BuildPlan {
+      units: build_units
+        .iter()
+        .map(|build_unit| schema::BuildPlanUnit {
+          package: build_unit.manifest.name.clone(),
+          source_files: schema::package_metadata(build_unit).source_files,
+        })
+        .collect(),
+      output: output_path.to_string_lossy().to_string(),
+    };
+
+    println!("{}", schema::to_json("build-plan", &build_plan)?);
+
+    return Ok(());
+  }
+
+  if !default_output_path.exists() && !print_output {
+    if let Err(error) = std::fs::create_dir_all(&default_output_path) {
+      return Err(GripError::Io(format!(
+        "failed to create output directory: {}",
+        error
+      )));
+    }
+  }
+
+  if build_arg_matches.is_present(ARG_BUILD_LICENSES) && !print_output {
+    let attributions_path =
+      license::bundle(&build_units, std::path::Path::new(DEFAULT_OUTPUT_DIR))?;
+
+    log::info!(
+      "wrote dependency attributions to `{}`",
+      attributions_path.display()
+    );
+  }
+
+  // Front-end dumps don't require the project to be built.
+  if emit_kind == build::EmitKind::Tokens || emit_kind == build::EmitKind::Ast {
+    let llvm_module = llvm_context.create_module(package_manifest.name.as_str());
+    let mut driver = create_driver(
+      llvm_context,
+      &llvm_module,
+      &package_manifest,
+      build_units,
+      scheduler.jobs(),
+    );
+
+    let output = if emit_kind == build::EmitKind::Tokens {
+      driver.dump_tokens()
+    } else {
+      match driver.dump_ast() {
+        Ok(output) => output,
+        Err(diagnostic) => {
+          print_diagnostics(&[diagnostic], &driver.file_contents, None, false);
+
+          return Err(GripError::Compile(
+            "could not parse the project due to previous errors".to_string(),
+          ));
+        }
+      }
+    };
+
+    return print_or_write_output(output.as_bytes(), &output_path, print_output)
+      .map_err(GripError::Other);
+  }
+
+  let verify = profile.verify && !build_arg_matches.is_present(ARG_BUILD_NO_VERIFY);
+  let debug_info = profile.debug || build_arg_matches.is_present(ARG_BUILD_DEBUG_INFO);
+  let mut built_dependencies = Vec::new();
+
+  for build_unit in &build_units {
+    built_dependencies.push(package::BuiltDependency {
+      name: build_unit.manifest.name.clone(),
+      profile: profile.name.clone(),
+      target: target_triple.as_str().to_string_lossy().to_string(),
+      verify,
+      debug_info,
+      fingerprint: package::fingerprint(build_unit)?,
+    });
+  }
+
+  let cache_hits = built_dependencies
+    .iter()
+    .filter(|built_dependency| package_lock.is_fresh(built_dependency))
+    .count();
+
+  let no_cache = build_arg_matches.is_present(ARG_BUILD_NO_CACHE);
+
+  // TODO: Since all packages are linked into a single module, a single
+  // ... stale package requires the final module to be linked again.
+  // Build records only track the default output kind, and timings are
+  // ... only known by building.
+  if !print_output
+    && !no_cache
+    && emit_kind == build::EmitKind::Ir
+    && !build_arg_matches.is_present(ARG_BUILD_TIMINGS)
+    && output_path.exists()
+    && cache_hits == built_dependencies.len()
+  {
+    if json_messages {
+      print_build_finished(true, &build::Statistics::default(), 0, cache_hits, 0)?;
+    } else {
+      console::print_status(
+        "Fresh",
+        &format!("{} v{}", package_manifest.name, package_manifest.version),
+      );
+    }
+
+    if let Some(summary_path) = &summary_path {
+      let build_summary = schema::BuildSummary {
+        package: package_manifest.name.clone(),
+        version: package_manifest.version.clone(),
+        success: true,
+        duration_ms: build_start.elapsed().as_millis(),
+        phases: Vec::new(),
+        warnings: 0,
+        errors: 0,
+        artifacts: vec![schema::Artifact {
+          path: output_path.to_string_lossy().to_string(),
+          sha256: checksum::sha256_file(&output_path)?,
+        }],
+      };
+
+      write_build_summary(summary_path, markdown_summary, &build_summary)?;
+    }
+
+    hooks::run(hooks::Hook::PostBuild, &package_manifest, &profile)?;
+
+    return Ok(());
+  }
+
+  let cache_misses = built_dependencies.len() - cache_hits;
+  let progress_handler = display_progress(&build_units);
+  let system_libraries = link::SystemLibraries::collect(&build_units);
+
+  let llvm_module = llvm_context.create_module(package_manifest.name.as_str());
+  let mut driver = create_driver(
+    llvm_context,
+    &llvm_module,
+    &package_manifest,
+    build_units,
+    scheduler.jobs(),
+  );
+
+  if !json_messages {
+    driver.event_handler = Some(progress_handler);
+  }
+
+  driver.verify = verify;
+  driver.debug_info = debug_info;
+
+  if !no_cache {
+    driver.cache_dir = Some(build::cache_dir(&package_manifest.name));
+    driver.deps_dir = Some(build::deps_dir(&package_manifest.name));
+  }
+
+  driver.profile = profile.name.clone();
+
+  let diagnostics = driver.build();
+
+  console::finish_progress();
+
+  print_diagnostics(
+    &diagnostics,
+    &driver.file_contents,
+    build_arg_matches.value_of(ARG_BUILD_DIAGNOSTICS_FOR),
+    json_messages,
+  );
+
+  build::apply_panic_strategy(&llvm_module, profile.panic);
+  llvm_module.set_triple(&target_triple);
+
+  if opt_level != build::OptLevel::O0 && !has_errors(&diagnostics) {
+    build::optimize(&llvm_module, opt_level);
+  }
+
+  if build_arg_matches.is_present(ARG_BUILD_TIMINGS) {
+    let timings = schema::Timings {
+      phases: driver
+        .timings
+        .iter()
+        .map(|(phase, duration)| schema::PhaseTiming {
+          phase: phase.clone(),
+          duration_ms: duration.as_millis(),
+        })
+        .collect(),
+      total_ms: driver
+        .timings
+        .iter()
+        .map(|(_, duration)| duration.as_millis())
+        .sum(),
+    };
+
+    println!("{}", schema::to_json("timings", &timings)?);
+  }
+
+  let size_report = build_arg_matches.is_present(ARG_BUILD_SIZE_REPORT);
+
+  if size_report && emit_kind != build::EmitKind::Executable {
+    return Err(GripError::Other(
+      "a size report requires emitting an executable (`--emit=exe`)".to_string(),
+    ));
+  }
+
+  let function_packages = driver
+    .interface
+    .iter()
+    .map(|function| (function.name.clone(), function.package.clone()))
+    .collect::<std::collections::HashMap<_, _>>();
+
+  // A module with errors cannot be linked, so no executable is produced.
+  let is_emitted = emit_kind != build::EmitKind::Executable || !has_errors(&diagnostics);
+
+  if emit_kind == build::EmitKind::Executable && is_emitted {
+    if print_output {
+      return Err(GripError::Other(
+        "executables cannot be printed".to_string(),
+      ));
+    }
+
+    let mut map_path = output_path.clone();
+
+    map_path.set_extension(PATH_LINKER_MAP_EXTENSION);
+
+    let mut link_options = link::LinkOptions::from_profile(&profile, &system_libraries);
+
+    if size_report {
+      link_options.map_path = Some(&map_path);
+
+      // The size report is read from the symbol table.
+      if link_options.strip {
+        log::warn!("not stripping the executable, since a size report was requested");
+        link_options.strip = false;
+      }
+    }
+
+    if !json_messages {
+      console::print_status("Linking", &output_path.display().to_string());
+    }
+
+    // The toolchain already warned about the skipped step.
+    if !link::link_module(
+      toolchain,
+      &llvm_module,
+      &profile,
+      &output_path,
+      &link_options,
+      jobs,
+    )? {
+      return Ok(());
+    }
+
+    if size_report {
+      if let Some(symbol_sizes) = size::read_symbol_sizes(toolchain, &output_path)? {
+        size::print_report(&output_path, &symbol_sizes, &function_packages);
+      }
+
+      log::info!("wrote the linker map to `{}`", map_path.display());
+    }
+  } else if emit_kind != build::EmitKind::Executable {
+    let output = build::emit(&llvm_module, emit_kind, &profile)?;
+
+    print_or_write_output(&output, &output_path, print_output)?;
+  }
+
+  let mut artifacts = Vec::new();
+
+  if !print_output && is_emitted && !has_errors(&diagnostics) {
+    if emit_kind == build::EmitKind::Ir {
+      for built_dependency in built_dependencies {
+        package_lock.record(built_dependency);
+      }
+
+      package::write_package_lock(&package_lock)?;
+    }
+
+    let artifact_index = schema::ArtifactIndex {
+      artifacts: vec![schema::Artifact {
+        path: output_path.to_string_lossy().to_string(),
+        sha256: checksum::sha256_file(&output_path)?,
+      }],
+      target_triple: target_triple.as_str().to_string_lossy().to_string(),
+      profile: profile.name.clone(),
+      lockfile_sha256: checksum::sha256_file(std::path::Path::new(package::PATH_PACKAGE_LOCK))?,
+    };
+
+    if let Err(error) = std::fs::write(
+      default_output_path.join(PATH_ARTIFACT_INDEX),
+      schema::to_json("artifact-index", &artifact_index)?,
+    ) {
+      log::error!("failed to write artifact index: {}", error);
+    }
+
+    artifacts = artifact_index.artifacts;
+  }
+
+  if let Some(summary_path) = &summary_path {
+    let build_summary = schema::BuildSummary {
+      package: package_manifest.name.clone(),
+      version: package_manifest.version.clone(),
+      success: !has_errors(&diagnostics),
+      duration_ms: build_start.elapsed().as_millis(),
+      phases: driver
+        .timings
+        .iter()
+        .map(|(phase, duration)| schema::PhaseTiming {
+          phase: phase.clone(),
+          duration_ms: duration.as_millis(),
+        })
+        .collect(),
+      warnings: diagnostics
+        .iter()
+        .filter(|diagnostic| !diagnostic.is_error())
+        .count(),
+      errors: diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.is_error())
+        .count(),
+      artifacts,
+    };
+
+    write_build_summary(summary_path, markdown_summary, &build_summary)?;
+  }
+
+  if !has_errors(&diagnostics) {
+    hooks::run(hooks::Hook::PostBuild, &package_manifest, &profile)?;
+  }
+
+  if !json_messages && !has_errors(&diagnostics) {
+    console::print_status(
+      "Finished",
+      &format!(
+        "{} [{}] in {:.2}s",
+        profile.name,
+        target_triple.as_str().to_string_lossy(),
+        build_start.elapsed().as_secs_f64()
+      ),
+    );
+  }
+
+  if json_messages {
+    print_build_finished(
+      !has_errors(&diagnostics),
+      &driver.statistics,
+      llvm_module
+        .get_functions()
+        .filter(|function| function.count_basic_blocks() > 0)
+        .count(),
+      cache_hits,
+      cache_misses,
+    )?;
+  }
+
+  // Non-executable artifacts are still emitted above (useful for
+  // ... inspecting the output), but the build itself has failed.
+  if has_errors(&diagnostics) {
+    return Err(GripError::Compile(format!(
+      "could not build `{}` due to previous errors",
+      package_manifest.name
+    )));
+  }
+
+  Ok(())
+}
+
+/// Search the package's source files.
+fn grep_command(grep_arg_matches: &clap::ArgMatches<'_>) -> Result<(), GripError> {
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+  let mut build_units = package::collect_build_units(&package_manifest)?;
+
+  // The first build unit is the package itself.
+  if !grep_arg_matches.is_present(ARG_GREP_DEPS) {
+    build_units.truncate(1);
+  }
+
+  let search_matches = search::search(
+    &build_units,
+    grep_arg_matches.value_of(ARG_GREP_PATTERN).unwrap(),
+    grep_arg_matches.is_present(ARG_GREP_IGNORE_CASE),
+    &search::read_ignore_patterns()?,
+  )?;
+
+  for search_match in &search_matches {
+    println!(
+      "{}:{}:{}: {}",
+      search_match.module_path, search_match.line, search_match.column, search_match.text
+    );
+  }
+
+  if search_matches.is_empty() {
+    return Err(GripError::Other("no matches found".to_string()));
+  }
+
+  Ok(())
+}
+
+/// Generate the run configuration of an editor.
+fn ide_command(ide_arg_matches: &clap::ArgMatches<'_>) -> Result<(), GripError> {
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+
+  let artifact_dir = build::artifact_dir(
+    &inkwell::targets::TargetMachine::get_default_triple()
+      .as_str()
+      .to_string_lossy(),
+    package::PROFILE_DEBUG,
+  );
+
+  let run_configuration = ide::run_configuration(&package_manifest, &artifact_dir)?;
+
+  match ide_arg_matches.value_of(ARG_IDE_EDITOR).unwrap() {
+    "vscode" => ide::write_vscode(
+      &run_configuration,
+      ide_arg_matches.is_present(ARG_IDE_FORCE),
+    )
+    .map_err(GripError::Other),
+    _ => {
+      println!("{}", ide::to_json(&run_configuration)?);
+
+      Ok(())
+    }
+  }
+}
+
+/// Update the dependencies to their newest allowed versions.
+async fn update_command(
+  update_arg_matches: &clap::ArgMatches<'_>,
+  matches: &clap::ArgMatches<'_>,
+  network_context: &network::NetworkContext,
+  scheduler: &scheduler::Scheduler,
+) -> Result<(), GripError> {
+  let config = config::fetch_config()?;
+  let registry = registry::from_config(
+    &config,
+    network_context.create_client(ARG_UPDATE, &config.network)?,
+    is_global_present(matches, ARG_REFRESH),
+  )?;
+  let mut package_lock = package::get_or_init_package_lock()?;
+
+  let prompt_mode = if update_arg_matches.is_present(ARG_UPDATE_YES) {
+    prompt::PromptMode::AssumeYes
+  } else if update_arg_matches.is_present(ARG_UPDATE_NO_INPUT) {
+    prompt::PromptMode::NoInput
+  } else {
+    prompt::PromptMode::Interactive
+  };
+
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+  let mut proposed_updates =
+    update::propose(registry.as_ref(), &package_manifest, &package_lock).await?;
+
+  if let Some(package_name) = update_arg_matches.value_of(ARG_UPDATE_PACKAGE) {
+    if package_lock.find_package(package_name).is_none()
+      && !proposed_updates
+        .iter()
+        .any(|proposed_update| proposed_update.locked_package.name == package_name)
+    {
+      return Err(GripError::Other(format!(
+        "`{}` is not a locked dependency",
+        package_name
+      )));
+    }
+
+    proposed_updates.retain(|proposed_update| proposed_update.locked_package.name == package_name);
+  }
+
+  if proposed_updates.is_empty() {
+    log::info!("all dependencies are up to date");
+
+    return Ok(());
+  }
+
+  for proposed_update in &proposed_updates {
+    println!("{}", proposed_update);
+  }
+
+  if update_arg_matches.is_present(ARG_UPDATE_DRY_RUN) {
+    log::info!("{} update(s) available", proposed_updates.len());
+
+    return Ok(());
+  }
+
+  for proposed_update in proposed_updates {
+    let locked_package = &proposed_update.locked_package;
+
+    // Only updates which may contain breaking changes require approval.
+    let question = if proposed_update.is_major() {
+      Some(format!(
+        "update `{}` across major versions?",
+        locked_package.name
+      ))
+    } else if proposed_update.is_version_unknown() {
+      Some(format!(
+        "update `{}`, whose new version is unknown?",
+        locked_package.name
+      ))
+    } else {
+      None
+    };
+
+    if let Some(question) = question {
+      if !prompt::confirm(&question, prompt_mode)? {
+        log::info!("skipped `{}`", locked_package.name);
+
+        continue;
+      }
+    }
+
+    let commit = match locked_package.kind {
+      package::SourceKind::Git => Some(
+        install::install_from_git(
+          &locked_package.source,
+          &locked_package.reference,
+          &git::CloneOptions {
+            full_history: false,
+            submodules: false,
+          },
+          true,
+        )?
+        .1,
+      ),
+      package::SourceKind::Registry => {
+        let archive_path = scheduler
+          .run(install::download_package(
+            registry.as_ref(),
+            &locked_package.source,
+            &proposed_update.new_reference,
+            &locked_package.name,
+            &config.network,
+          ))
+          .await?;
+
+        if let Err(error) = cache::store(&archive_path, &format!("{}.zip", locked_package.name)) {
+          log::warn!("{}", error);
+        }
+
+        install::extract_package(&archive_path, &locked_package.name, &config.network, true)?;
+
+        if let Err(error) = std::fs::remove_file(&archive_path) {
+          log::warn!("failed to remove the downloaded archive: {}", error);
+        }
+
+        proposed_update.new_commit.clone()
+      }
+    };
+
+    package_lock.lock_package(package::LockedPackage {
+      reference: proposed_update.new_reference.clone(),
+      commit,
+      version: if proposed_update.is_resolved {
+        proposed_update.new_version.clone()
+      } else {
+        locked_package.version.clone()
+      },
+      checksum: Some(checksum::sha256_dir(
+        &std::path::Path::new(PATH_DEPENDENCIES).join(&locked_package.name),
+      )?),
+      // The vendored copy (if any) is of the previous version.
+      vendored: None,
+      ..locked_package.clone()
+    });
+
+    // Record progress as it's made, in case a later update fails.
+    package::write_package_lock(&package_lock)?;
+    log::info!("updated `{}`", locked_package.name);
+  }
+
+  Ok(())
+}
+
+/// Resolve the versions of registry dependencies, and record them in the lock.
+async fn resolve_command(
+  matches: &clap::ArgMatches<'_>,
+  network_context: &network::NetworkContext,
+) -> Result<(), GripError> {
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+  let config = config::fetch_config()?;
+  let registry = registry::from_config(
+    &config,
+    network_context.create_client(ARG_RESOLVE, &config.network)?,
+    is_global_present(matches, ARG_REFRESH),
+  )?;
+  let mut package_lock = package::get_or_init_package_lock()?;
+  let resolved_packages = dependency::resolve_versions(
+    registry.as_ref(),
+    &package_manifest,
+    &features::resolve_selected(&package_manifest)?,
+  )
+  .await?;
+
+  for resolved_package in &resolved_packages {
+    let version = resolved_package.version.to_string();
+    let previous_package = package_lock.find_package(&resolved_package.name).cloned();
+
+    let previous_version = previous_package
+      .as_ref()
+      .and_then(|locked_package| locked_package.version.clone());
+
+    // The installed (and vendored) tree is unchanged until the new
+    // ... version is installed.
+    let unchanged_package = previous_package
+      .filter(|locked_package| locked_package.reference == resolved_package.reference);
+
+    match previous_version {
+      Some(previous_version) if previous_version != version => println!(
+        "`{}`: v{} -> v{}",
+        resolved_package.name, previous_version, version
+      ),
+      Some(_) => {}
+      None => println!("`{}`: v{}", resolved_package.name, version),
+    }
+
+    package_lock.lock_package(package::LockedPackage {
+      name: resolved_package.name.clone(),
+      kind: package::SourceKind::Registry,
+      source: resolved_package.source.clone(),
+      reference: resolved_package.reference.clone(),
+      commit: registry
+        .resolve_commit(&resolved_package.source, &resolved_package.reference)
+        .await?,
+      version: Some(version),
+      checksum: unchanged_package
+        .as_ref()
+        .and_then(|locked_package| locked_package.checksum.clone()),
+      vendored: unchanged_package.and_then(|locked_package| locked_package.vendored),
+    });
+  }
+
+  package::write_package_lock(&package_lock)?;
+  log::info!("resolved {} package(s)", resolved_packages.len());
+
+  Ok(())
+}
+
+/// Pin a dependency to the commit it currently resolves to.
+fn pin_command(
+  pin_arg_matches: &clap::ArgMatches<'_>,
+  network_context: &network::NetworkContext,
+) -> Result<(), GripError> {
+  network_context.require_mutable_lock(ARG_PIN)?;
+
+  let dependency_name = pin_arg_matches.value_of(ARG_PIN_DEPENDENCY).unwrap();
+  let mut package_lock = package::get_or_init_package_lock()?;
+
+  let mut locked_package = match package_lock.find_package(dependency_name) {
+    Some(locked_package) => locked_package.clone(),
+    None => {
+      return Err(GripError::Manifest(format!(
+        "dependency `{}` has no installation record in the lock; try reinstalling it",
+        dependency_name
+      )))
+    }
+  };
+
+  let commit = match &locked_package.commit {
+    Some(commit) => commit.clone(),
+    None => {
+      return Err(GripError::Manifest(format!(
+        "the commit of dependency `{}` is unknown; try reinstalling it",
+        dependency_name
+      )))
+    }
+  };
+
+  let manifest_path = package::manifest_path();
+  let mut package_manifest = package::fetch_manifest(&manifest_path)?;
+
+  // Registry dependencies are pinned through the lock alone.
+  if let Some(package::DependencySpec::Git {
+    branch, tag, rev, ..
+  }) = package_manifest.dependencies.get_mut(dependency_name)
+  {
+    // A commit supersedes any branch or tag it was installed from.
+    *branch = None;
+    *tag = None;
+    *rev = Some(commit.clone());
+    package::write_manifest(&manifest_path, &package_manifest)?;
+  }
+
+  locked_package.reference = commit.clone();
+  package_lock.lock_package(locked_package);
+  package::write_package_lock(&package_lock)?;
+  log::info!("pinned `{}` to commit `{}`", dependency_name, commit);
+
+  Ok(())
+}
+
+/// Reproduce a recorded build.
+fn replay_command(replay_arg_matches: &clap::ArgMatches<'_>) -> Result<(), GripError> {
+  let archive_path = std::path::Path::new(replay_arg_matches.value_of(ARG_REPLAY_ARCHIVE).unwrap());
+
+  let sandbox_dir = match replay_arg_matches.value_of(ARG_REPLAY_INTO) {
+    Some(directory) => std::path::PathBuf::from(directory),
+    None => std::env::temp_dir().join(format!("grip-replay-{}", std::process::id())),
+  };
+
+  let status = report::replay(archive_path, &sandbox_dir)?;
+
+  if !status.success() {
+    return Err(GripError::Other(format!(
+      "the replayed build failed ({})",
+      status
+    )));
+  }
+
+  Ok(())
+}
+
+/// Build the package, and print the IR of a single function.
+fn ir_command(
+  ir_arg_matches: &clap::ArgMatches<'_>,
+  llvm_context: &inkwell::context::Context,
+  scheduler: &scheduler::Scheduler,
+) -> Result<(), GripError> {
+  let function_name = ir_arg_matches.value_of(ARG_IR_FUNCTION).unwrap();
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+  let build_units = package::collect_build_units(&package_manifest)?;
+  let llvm_module = llvm_context.create_module(package_manifest.name.as_str());
+  let mut driver = create_driver(
+    llvm_context,
+    &llvm_module,
+    &package_manifest,
+    build_units,
+    scheduler.jobs(),
+  );
+  let diagnostics = driver.build();
+
+  print_diagnostics(&diagnostics, &driver.file_contents, None, false);
+
+  if has_errors(&diagnostics) {
+    return Err(GripError::Compile(format!(
+      "could not build `{}` due to previous errors",
+      package_manifest.name
+    )));
+  }
+
+  if ir_arg_matches.is_present(ARG_IR_OPTIMIZED) {
+    build::optimize(&llvm_module, build::OptLevel::O2);
+  }
+
+  // TODO: Functions that are never referenced from `main` are not lowered.
+  match llvm_module.get_function(function_name) {
+    Some(llvm_function) => println!("{}", llvm_function.print_to_string().to_string()),
+    None => {
+      return Err(GripError::Other(format!(
+        "function `{}` was not found in the lowered module",
+        function_name
+      )))
+    }
+  }
+
+  Ok(())
+}
+
+/// Build and execute the package.
+fn run_command(
+  run_arg_matches: &clap::ArgMatches<'_>,
+  llvm_context: &inkwell::context::Context,
+  toolchain: &tool::Toolchain,
+  jobs: usize,
+  scheduler: &scheduler::Scheduler,
+  invocation_dir: &std::path::Path,
+) -> Result<(), GripError> {
+  if run_arg_matches.is_present(ARG_WATCH) {
+    return watch::run(
+      invocation_dir,
+      &package::package_dir().join(PATH_SOURCES),
+      args_without_flag(ARG_WATCH),
+    )
+    .map_err(GripError::Other);
+  }
+
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+
+  if package_manifest.ty != package::PackageType::Executable {
+    return Err(GripError::Other(format!(
+      "package `{}` is a library, and cannot be run",
+      package_manifest.name
+    )));
+  }
+
+  let package_lock = package::get_or_init_package_lock()?;
+
+  package::verify_dependency_dirs(&package_manifest, &package_lock)?;
+  expose_out_dir(&package_manifest.name)?;
+
+  if !package::is_host_target() {
+    return Err(GripError::Other(format!(
+      "cannot run a program built for `{}` on this host",
+      package::target_triple()
+    )));
+  }
+
+  let profile = package_manifest.profile(if run_arg_matches.is_present(ARG_RUN_RELEASE) {
+    package::PROFILE_RELEASE
+  } else {
+    package::PROFILE_DEBUG
+  });
+
+  hooks::run(hooks::Hook::PreBuild, &package_manifest, &profile)?;
+
+  let build_units = package::collect_build_units(&package_manifest)?;
+  let target_triple = inkwell::targets::TargetMachine::get_default_triple();
+
+  let artifact_dir = build::artifact_dir(&target_triple.as_str().to_string_lossy(), &profile.name);
+
+  let progress_handler = display_progress(&build_units);
+  let system_libraries = link::SystemLibraries::collect(&build_units);
+  let llvm_module = llvm_context.create_module(package_manifest.name.as_str());
+  let mut driver = create_driver(
+    llvm_context,
+    &llvm_module,
+    &package_manifest,
+    build_units,
+    scheduler.jobs(),
+  );
+
+  driver.event_handler = Some(progress_handler);
+  driver.verify = profile.verify;
+  driver.debug_info = profile.debug;
+
+  let diagnostics = driver.build();
+
+  console::finish_progress();
+
+  print_diagnostics(&diagnostics, &driver.file_contents, None, false);
+
+  if has_errors(&diagnostics) {
+    return Err(GripError::Compile(format!(
+      "could not compile `{}` due to previous errors",
+      package_manifest.name
+    )));
+  }
+
+  build::apply_panic_strategy(&llvm_module, profile.panic);
+  llvm_module.set_triple(&target_triple);
+
+  if profile.opt_level != build::OptLevel::O0 {
+    build::optimize(&llvm_module, profile.opt_level);
+  }
+
+  if let Err(error) = std::fs::create_dir_all(&artifact_dir) {
+    return Err(GripError::Io(format!(
+      "failed to create output directory: {}",
+      error
+    )));
+  }
+
+  let executable_path = build::executable_path(&artifact_dir, &package_manifest.name);
+
+  console::print_status("Linking", &executable_path.display().to_string());
+
+  if !link::link_module(
+    toolchain,
+    &llvm_module,
+    &profile,
+    &executable_path,
+    &link::LinkOptions::from_profile(&profile, &system_libraries),
+    jobs,
+  )? {
+    return Err(GripError::Link(
+      "cannot run the program, since it could not be linked".to_string(),
+    ));
+  }
+
+  hooks::run(hooks::Hook::PostBuild, &package_manifest, &profile)?;
+  hooks::run(hooks::Hook::PreRun, &package_manifest, &profile)?;
+
+  console::print_status("Running", &format!("`{}`", executable_path.display()));
+
+  let mut command = std::process::Command::new(&executable_path);
+
+  if run_arg_matches.is_present(ARG_RUN_CLEAN_ENV) {
+    command.env_clear();
+
+    for name in MINIMAL_ENV_VARS {
+      if let Some(value) = std::env::var_os(name) {
+        command.env(name, value);
+      }
+    }
+  }
+
+  command.envs(&package_manifest.env);
+
+  // Standard streams are inherited, so they are forwarded as-is.
+  let status = match command.status() {
+    Ok(status) => status,
+    Err(error) => {
+      return Err(GripError::Other(format!(
+        "failed to run `{}`: {}",
+        executable_path.display(),
+        error
+      )))
+    }
+  };
+
+  match status.code() {
+    Some(0) => Ok(()),
+    Some(code) => std::process::exit(code),
+    None => Err(GripError::Other(format!(
+      "the program was terminated: {}",
+      status
+    ))),
+  }
+}
+
+/// Build and run the tests of the package.
+fn test_command(
+  test_arg_matches: &clap::ArgMatches<'_>,
+  llvm_context: &inkwell::context::Context,
+  toolchain: &tool::Toolchain,
+  jobs: usize,
+  scheduler: &scheduler::Scheduler,
+) -> Result<(), GripError> {
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+  let package_lock = package::get_or_init_package_lock()?;
+
+  package::verify_dependency_dirs(&package_manifest, &package_lock)?;
+  expose_out_dir(&package_manifest.name)?;
+
+  let profile = package_manifest.profile(package::PROFILE_DEBUG);
+
+  hooks::run(hooks::Hook::PreBuild, &package_manifest, &profile)?;
+
+  let build_units = package::collect_build_units(&package_manifest)?;
+
+  let tests = harness::collect_tests(
+    &package_manifest,
+    test_arg_matches.value_of(ARG_TEST_FILTER),
+  )?;
+
+  if tests.is_empty() {
+    log::info!("no tests found under `{}`", harness::PATH_TESTS);
+
+    return Ok(());
+  }
+
+  if !package::is_host_target() {
+    return Err(GripError::Other(format!(
+      "cannot run tests built for `{}` on this host",
+      package::target_triple()
+    )));
+  }
+
+  let target_triple = inkwell::targets::TargetMachine::get_default_triple();
+
+  let artifact_dir = build::artifact_dir(&target_triple.as_str().to_string_lossy(), &profile.name)
+    .join(harness::PATH_TESTS);
+
+  if let Err(error) = std::fs::create_dir_all(&artifact_dir) {
+    return Err(GripError::Io(format!(
+      "failed to create output directory: {}",
+      error
+    )));
+  }
+
+  let mut test_outcomes = Vec::new();
+  let system_libraries = link::SystemLibraries::collect(&build_units);
+
+  for (test_name, test_file) in &tests {
+    console::print_status("Compiling", &format!("test `{}`", test_name));
+
+    let llvm_module = llvm_context.create_module(test_name);
+    let mut driver = create_driver(
+      llvm_context,
+      &llvm_module,
+      &package_manifest,
+      harness::test_build_units(&build_units, test_file),
+      scheduler.jobs(),
+    );
+
+    driver.require_main = true;
+    driver.deps_dir = Some(build::deps_dir(&package_manifest.name));
+    driver.profile = profile.name.clone();
+
+    let diagnostics = driver.build();
+
+    print_diagnostics(&diagnostics, &driver.file_contents, None, false);
+
+    if has_errors(&diagnostics) {
+      return Err(GripError::Compile(format!(
+        "could not compile test `{}` due to previous errors",
+        test_name
+      )));
+    }
+
+    build::apply_panic_strategy(&llvm_module, profile.panic);
+    llvm_module.set_triple(&target_triple);
+
+    let executable_path = build::executable_path(&artifact_dir, test_name);
+
+    if !link::link_module(
+      toolchain,
+      &llvm_module,
+      &profile,
+      &executable_path,
+      &link::LinkOptions::from_profile(&profile, &system_libraries),
+      jobs,
+    )? {
+      return Err(GripError::Link(format!(
+        "cannot run test `{}`, since it could not be linked",
+        test_name
+      )));
+    }
+
+    test_outcomes.push(harness::run_test(
+      test_name,
+      &executable_path,
+      &package_manifest.env,
+    )?);
+  }
+
+  harness::print_summary(&test_outcomes);
+
+  let failed_count = test_outcomes
+    .iter()
+    .filter(|test_outcome| !test_outcome.passed)
+    .count();
+
+  if failed_count > 0 {
+    return Err(GripError::Other(format!("{} test(s) failed", failed_count)));
+  }
+
+  Ok(())
+}
+
+/// Check the package (or a single file) without lowering it.
+fn check_command(
+  check_arg_matches: &clap::ArgMatches<'_>,
+  llvm_context: &inkwell::context::Context,
+  scheduler: &scheduler::Scheduler,
+) -> Result<(), GripError> {
+  if let Some(file) = check_arg_matches.value_of(ARG_CHECK_FILE) {
+    return check_file(llvm_context, file);
+  }
+
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+
+  expose_out_dir(&package_manifest.name)?;
+
+  // Generated sources are checked as well.
+  hooks::run(
+    hooks::Hook::PreBuild,
+    &package_manifest,
+    &package_manifest.profile(package::PROFILE_DEBUG),
+  )?;
+
+  let build_units = package::collect_build_units(&package_manifest)?;
+  let mut diagnostics = manifest_lint::lint(&build_units);
+  let llvm_module = llvm_context.create_module(package_manifest.name.as_str());
+  let mut driver = create_driver(
+    llvm_context,
+    &llvm_module,
+    &package_manifest,
+    build_units,
+    scheduler.jobs(),
+  );
+
+  // Runs the front-end passes only; nothing is lowered.
+  diagnostics.extend(driver.check());
+
+  print_diagnostics(&diagnostics, &driver.file_contents, None, false);
+
+  if has_errors(&diagnostics) {
+    return Err(GripError::Compile(format!(
+      "could not check `{}` due to previous errors",
+      package_manifest.name
+    )));
+  }
+
+  log::info!("`{}` has no errors", package_manifest.name);
+
+  Ok(())
+}
+
+/// Clean the build directory and any produced artifacts.
+fn clean_command(clean_arg_matches: &clap::ArgMatches<'_>) -> Result<(), GripError> {
+  if clean_arg_matches.is_present(ARG_CLEAN_CACHE) {
+    return clean_cache(clean_arg_matches).map_err(GripError::Other);
+  }
+
+  let mut package_lock = package::get_or_init_package_lock()?;
+
+  if let Some(dependency_name) = clean_arg_matches.value_of(ARG_CLEAN_DEP) {
+    if !package_lock.invalidate(dependency_name) {
+      return Err(GripError::Manifest(format!(
+        "dependency `{}` has no build records to invalidate",
+        dependency_name
+      )));
+    }
+
+    package::write_package_lock(&package_lock)?;
+    log::info!("invalidated build records of `{}`", dependency_name);
+
+    return Ok(());
+  }
+
+  // Guard against wiping out arbitrary directories.
+  if !std::path::Path::new(package::PATH_MANIFEST_FILE).exists() {
+    return Err(GripError::Manifest(format!(
+      "refusing to clean a directory without a `{}` file",
+      package::PATH_MANIFEST_FILE
+    )));
+  }
+
+  let dependencies_path = std::path::PathBuf::from(PATH_DEPENDENCIES);
+
+  let mut removal_paths = vec![
+    std::path::PathBuf::from(DEFAULT_OUTPUT_DIR),
+    dependencies_path.join(install::PATH_DOWNLOADING),
+  ];
+
+  if clean_arg_matches.is_present(ARG_CLEAN_DEPS) {
+    // Includes the in-progress downloads.
+    removal_paths.truncate(1);
+    removal_paths.push(dependencies_path);
+  }
+
+  removal_paths.retain(|path| path.exists());
+
+  if clean_arg_matches.is_present(ARG_CLEAN_DRY_RUN) {
+    for path in &removal_paths {
+      println!("would remove `{}`", path.display());
+    }
+
+    log::info!("{} path(s) would be removed", removal_paths.len());
+
+    return Ok(());
+  }
+
+  for path in &removal_paths {
+    if let Err(error) = std::fs::remove_dir_all(path) {
+      return Err(GripError::Io(format!(
+        "failed to remove `{}`: {}",
+        path.display(),
+        error
+      )));
+    }
+  }
+
+  package_lock.built_dependencies.clear();
+  package::write_package_lock(&package_lock)?;
+
+  if clean_arg_matches.is_present(ARG_CLEAN_DEPS) {
+    log::info!("cleaned the build and dependencies directories");
+  } else {
+    log::info!("cleaned the build directory");
+  }
+
+  Ok(())
+}
+
+/// Install packages, along with their dependencies.
+async fn install_command(
+  install_arg_matches: &clap::ArgMatches<'_>,
+  matches: &clap::ArgMatches<'_>,
+  network_context: &network::NetworkContext,
+  scheduler: &scheduler::Scheduler,
+) -> Result<(), GripError> {
+  // Offline, the dependencies can only come from their vendored copies.
+  if !install_arg_matches.is_present(ARG_INSTALL_PATH) && network_context.is_offline() {
+    let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+    let package_lock = package::get_or_init_package_lock()?;
+    let restored_count = vendor::restore(&package_lock)?;
+
+    package::verify_dependency_dirs(&package_manifest, &package_lock)?;
+    package::verify_dependency_checksums(&package_lock, false)?;
+    log::info!("installed {} vendored package(s)", restored_count);
+    record_project();
+
+    return Ok(());
+  }
+
+  let config = config::fetch_config()?;
+  let registry = registry::from_config(
+    &config,
+    network_context.create_client(ARG_INSTALL, &config.network)?,
+    is_global_present(matches, ARG_REFRESH),
+  )?;
+  let package_path = match install_arg_matches.value_of(ARG_INSTALL_PATH) {
+    Some(package_path) => package_path,
+    None => {
+      network_context.require_mutable_lock(ARG_INSTALL)?;
+
+      let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+      let mut package_lock = package::get_or_init_package_lock()?;
+
+      // Dependencies which are already installed are skipped.
+      install::install_dependencies(
+        registry.as_ref(),
+        scheduler,
+        &config.network,
+        &package_manifest,
+        &mut package_lock,
+      )
+      .await?;
+
+      package::write_package_lock(&package_lock)?;
+
+      log::info!("installed the dependencies of `{}`", package_manifest.name);
+
+      record_project();
+
+      return Ok(());
+    }
+  };
+
+  let package_reference = install_arg_matches.value_of(ARG_INSTALL_BRANCH).unwrap();
+
+  if install_arg_matches.is_present(ARG_INSTALL_BIN) {
+    let clone_options = if install_arg_matches.is_present(ARG_INSTALL_GIT) {
+      Some(git::CloneOptions {
+        full_history: install_arg_matches.is_present(ARG_INSTALL_FULL_HISTORY),
+        submodules: install_arg_matches.is_present(ARG_INSTALL_SUBMODULES),
+      })
+    } else {
+      None
+    };
+
+    return install_binary(
+      registry.as_ref(),
+      scheduler,
+      &config.network,
+      package_path,
+      package_reference,
+      clone_options,
+    )
+    .await;
+  }
+
+  if install_arg_matches.is_present(ARG_INSTALL_GIT) {
+    let (package_manifest, commit) = install::install_from_git(
+      package_path,
+      package_reference,
+      &git::CloneOptions {
+        full_history: install_arg_matches.is_present(ARG_INSTALL_FULL_HISTORY),
+        submodules: install_arg_matches.is_present(ARG_INSTALL_SUBMODULES),
+      },
+      false,
+    )?;
+
+    let mut package_lock = package::get_or_init_package_lock()?;
+
+    package_lock.lock_package(package::LockedPackage {
+      name: package_manifest.name.clone(),
+      kind: package::SourceKind::Git,
+      source: package_path.to_string(),
+      reference: package_reference.to_string(),
+      commit: Some(commit),
+      version: None,
+      checksum: Some(checksum::sha256_dir(
+        &std::path::Path::new(PATH_DEPENDENCIES).join(&package_manifest.name),
+      )?),
+      vendored: None,
+    });
+
+    install::install_dependencies(
+      registry.as_ref(),
+      scheduler,
+      &config.network,
+      &package_manifest,
+      &mut package_lock,
+    )
+    .await?;
+
+    package::write_package_lock(&package_lock)?;
+
+    register_dependency(
+      &package_manifest.name,
+      package::DependencySpec::Git {
+        git: package_path.to_string(),
+        branch: Some(package_reference.to_string())
+          .filter(|reference| !dependency::is_pinned_reference(reference)),
+        tag: None,
+        rev: Some(package_reference.to_string())
+          .filter(|reference| dependency::is_pinned_reference(reference)),
+      },
+    )?;
+
+    log::info!("installed package `{}`", package_manifest.name);
+    record_project();
+
+    return Ok(());
+  }
+
+  if install_arg_matches.is_present(ARG_INSTALL_DRY_RUN) {
+    let remote_packages = dependency::resolve_remote(
+      registry.as_ref(),
+      scheduler,
+      package_path,
+      package_reference,
+    )
+    .await?;
+
+    for remote_package in &remote_packages {
+      println!(
+        "would download `{}` v{} ({}@{}) from {} ({})",
+        remote_package.manifest.name,
+        remote_package.manifest.version,
+        remote_package.path,
+        remote_package.reference,
+        remote_package.archive_url,
+        match remote_package.archive_size {
+          Some(size) => indicatif::HumanBytes(size).to_string(),
+          None => "unknown size".to_string(),
+        }
+      );
+    }
+
+    log::info!("{} package(s) would be downloaded", remote_packages.len());
+
+    return Ok(());
+  }
+
+  let package_manifest = registry
+    .fetch_manifest(package_path, package_reference)
+    .await
+    .map_err(GripError::Network)?;
+
+  let archive_path = scheduler
+    .run(install::download_package(
+      registry.as_ref(),
+      package_path,
+      package_reference,
+      &package_manifest.name,
+      &config.network,
+    ))
+    .await?;
+
+  log::info!("downloaded package `{}`", package_manifest.name);
+
+  let mut package_lock = package::get_or_init_package_lock()?;
+  let commit = registry
+    .resolve_commit(package_path, package_reference)
+    .await
+    .map_err(GripError::Network)?;
+
+  if let Err(error) = cache::store(&archive_path, &format!("{}.zip", package_manifest.name)) {
+    log::warn!("{}", error);
+  }
+
+  let installed_path = install::extract_package(
+    &archive_path,
+    &package_manifest.name,
+    &config.network,
+    false,
+  )?;
+
+  package_lock.lock_package(package::LockedPackage {
+    name: package_manifest.name.clone(),
+    kind: package::SourceKind::Registry,
+    source: package_path.to_string(),
+    reference: package_reference.to_string(),
+    commit,
+    version: None,
+    checksum: Some(checksum::sha256_dir(&installed_path)?),
+    vendored: None,
+  });
+
+  // The archive is no longer needed once extracted (a copy is cached).
+  if let Err(error) = std::fs::remove_file(&archive_path) {
+    log::warn!("failed to remove the downloaded archive: {}", error);
+  }
+
+  install::install_dependencies(
+    registry.as_ref(),
+    scheduler,
+    &config.network,
+    &package_manifest,
+    &mut package_lock,
+  )
+  .await?;
+
+  package::write_package_lock(&package_lock)?;
+
+  register_dependency(
+    &package_manifest.name,
+    package::DependencySpec::Registry {
+      version: if dependency::is_pinned_reference(package_reference) {
+        format!("={}", package_manifest.version)
+      } else {
+        package::ANY_VERSION.to_string()
+      },
+      registry: Some(package_path.to_string()),
+    },
+  )?;
+
+  log::info!("installed package `{}`", package_manifest.name);
+  record_project();
+
+  Ok(())
+}
+
+/// Declare a dependency in the manifest.
+async fn add_command(
+  add_arg_matches: &clap::ArgMatches<'_>,
+  matches: &clap::ArgMatches<'_>,
+  network_context: &network::NetworkContext,
+  scheduler: &scheduler::Scheduler,
+) -> Result<(), GripError> {
+  let package_path = add_arg_matches.value_of(ARG_ADD_PATH).unwrap();
+
+  let dependency_name = match add_arg_matches.value_of(ARG_ADD_NAME) {
+    Some(dependency_name) => dependency_name,
+    None => package_path
+      .trim_end_matches('/')
+      .rsplit('/')
+      .next()
+      .unwrap_or(package_path)
+      .trim_end_matches(".git"),
+  };
+
+  let dependency_spec = if add_arg_matches.is_present(ARG_ADD_GIT) {
+    let reference = add_arg_matches.value_of(ARG_ADD_BRANCH);
+
+    package::DependencySpec::Git {
+      git: package_path.to_string(),
+      branch: reference
+        .filter(|reference| !dependency::is_pinned_reference(reference))
+        .map(str::to_string),
+      tag: None,
+      rev: reference
+        .filter(|reference| dependency::is_pinned_reference(reference))
+        .map(str::to_string),
+    }
+  } else {
+    let version = add_arg_matches
+      .value_of(ARG_ADD_VERSION)
+      .unwrap_or(package::ANY_VERSION)
+      .to_string();
+
+    if package_path == dependency_name {
+      package::DependencySpec::Version(version)
+    } else {
+      package::DependencySpec::Registry {
+        version,
+        registry: Some(package_path.to_string()),
+      }
+    }
+  };
+
+  let manifest_path = package::manifest_path();
+
+  if !package::add_dependency(&manifest_path, dependency_name, dependency_spec)? {
+    return Err(GripError::Manifest(format!(
+      "`{}` is already a dependency; use `grip remove {}` first to replace it",
+      dependency_name, dependency_name
+    )));
+  }
+
+  log::info!(
+    "added `{}` to the dependencies in `{}`",
+    dependency_name,
+    manifest_path.display()
+  );
+
+  if add_arg_matches.is_present(ARG_ADD_FETCH) {
+    network_context.require_mutable_lock(ARG_ADD)?;
+
+    let config = config::fetch_config()?;
+    let registry = registry::from_config(
+      &config,
+      network_context.create_client(ARG_ADD, &config.network)?,
+      is_global_present(matches, ARG_REFRESH),
+    )?;
+    let package_manifest = package::fetch_manifest(&manifest_path)?;
+    let mut package_lock = package::get_or_init_package_lock()?;
+
+    install::install_dependencies(
+      registry.as_ref(),
+      scheduler,
+      &config.network,
+      &package_manifest,
+      &mut package_lock,
+    )
+    .await?;
+
+    package::write_package_lock(&package_lock)?;
+    record_project();
+  }
+
+  Ok(())
+}
+
+/// Search the registry for packages.
+async fn search_command(
+  search_arg_matches: &clap::ArgMatches<'_>,
+  matches: &clap::ArgMatches<'_>,
+  network_context: &network::NetworkContext,
+) -> Result<(), GripError> {
+  let limit = search_arg_matches.value_of(ARG_SEARCH_LIMIT).unwrap();
+
+  let limit = match limit.parse::<usize>() {
+    Ok(limit) => limit,
+    Err(_) => return Err(GripError::Other(format!("invalid limit `{}`", limit))),
+  };
+
+  let config = config::fetch_config()?;
+  let registry = registry::from_config(
+    &config,
+    network_context.create_client(ARG_SEARCH, &config.network)?,
+    is_global_present(matches, ARG_REFRESH),
+  )?;
+
+  let package_listings = registry
+    .search(
+      search_arg_matches.value_of(ARG_SEARCH_QUERY).unwrap(),
+      limit,
+    )
+    .await?;
+
+  let mut search_results = Vec::new();
+
+  for package_listing in package_listings {
+    // A package without any (version) tags is still listed.
+    let version = match registry.resolve_versions(&package_listing.path).await {
+      Ok(tags) => dependency::parse_version_tags(tags)
+        .into_iter()
+        .next()
+        .map(|(version, _)| version.to_string()),
+      Err(error) => {
+        log::debug!(
+          "failed to resolve the versions of `{}`: {}",
+          package_listing.path,
+          error
+        );
+
+        None
+      }
+    };
+
+    search_results.push(schema::SearchResult {
+      name: package_listing.path,
+      version,
+      description: package_listing.description,
+    });
+  }
+
+  if search_arg_matches.is_present(ARG_SEARCH_JSON) {
+    println!(
+      "{}",
+      schema::to_json(
+        "search",
+        &schema::SearchResults {
+          packages: search_results,
+        }
+      )?
+    );
+
+    return Ok(());
+  }
+
+  if search_results.is_empty() {
+    log::info!("no packages matched the query");
+
+    return Ok(());
+  }
+
+  let name_width = search_results
+    .iter()
+    .map(|search_result| search_result.name.chars().count())
+    .max()
+    .unwrap_or(0)
+    .max("NAME".len());
+
+  println!(
+    "{:<name_width$}  {:<12}  {}",
+    "NAME",
+    "VERSION",
+    "DESCRIPTION",
+    name_width = name_width
+  );
+
+  for search_result in &search_results {
+    println!(
+      "{:<name_width$}  {:<12}  {}",
+      search_result.name,
+      search_result.version.as_deref().unwrap_or("-"),
+      search_result.description.as_deref().unwrap_or(""),
+      name_width = name_width
+    );
+  }
+
+  Ok(())
+}
+
+/// Tag and push a release of the package.
+fn publish_command(
+  publish_arg_matches: &clap::ArgMatches<'_>,
+  network_context: &network::NetworkContext,
+) -> Result<(), GripError> {
+  let is_dry_run = publish_arg_matches.is_present(ARG_PUBLISH_DRY_RUN);
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+  let tag = publish::validate(&package_manifest).map_err(GripError::Manifest)?;
+
+  publish::verify_repository(&tag)?;
+
+  let config = config::fetch_config()?;
+
+  let index_config = if publish_arg_matches.is_present(ARG_PUBLISH_REGISTER) {
+    match &config.index {
+      Some(index_config) => Some(index_config),
+      None => {
+        return Err(GripError::Other(format!(
+          "`--{}` requires a registry index to be configured",
+          ARG_PUBLISH_REGISTER
+        )))
+      }
+    }
+  } else {
+    None
+  };
+
+  if !is_dry_run {
+    network_context.require_network(ARG_PUBLISH)?;
+  }
+
+  console::print_status("Checking", &format!("`{}`", package_manifest.name));
+
+  let current_exe = match std::env::current_exe() {
+    Ok(current_exe) => current_exe,
+    Err(error) => {
+      return Err(GripError::Other(format!(
+        "failed to locate the grip executable: {}",
+        error
+      )))
+    }
+  };
+
+  match std::process::Command::new(&current_exe)
+    .arg(ARG_CHECK)
+    .status()
+  {
+    Ok(status) if status.success() => {}
+    Ok(_) => {
+      return Err(GripError::Compile(format!(
+        "could not publish `{}` due to previous errors",
+        package_manifest.name
+      )))
+    }
+    Err(error) => return Err(GripError::Other(format!("failed to run grip: {}", error))),
+  }
+
+  let source_url = match index_config {
+    Some(_) => Some(publish::source_url()?),
+    None => None,
+  };
+
+  if is_dry_run {
+    log::info!(
+      "would tag the current commit as `{}`, and push the tag to `{}`",
+      tag,
+      publish::REMOTE
+    );
+
+    if let Some(source_url) = &source_url {
+      log::info!(
+        "would register `{}` v{} (from `{}`) in the registry index",
+        package_manifest.name,
+        package_manifest.version,
+        source_url
+      );
+    }
+
+    return Ok(());
+  }
+
+  // The registration is prepared before tagging, so that a release is
+  // ... either both tagged and registered, or neither.
+  let registration = match (index_config, &source_url) {
+    (Some(index_config), Some(source_url)) => Some(registry::prepare_registration(
+      index_config,
+      &package_manifest.name,
+      &tag,
+      source_url,
+      package_manifest.description.as_deref(),
+    )?),
+    _ => None,
+  };
+
+  console::print_status("Tagging", &tag);
+
+  if let Err(error) = publish::tag_release(&package_manifest, &tag) {
+    if let Some(clone_path) = &registration {
+      registry::discard_registration(clone_path);
+    }
+
+    return Err(error.into());
+  }
+
+  if let Some(clone_path) = &registration {
+    console::print_status(
+      "Registering",
+      &format!("`{}` v{}", package_manifest.name, package_manifest.version),
+    );
+
+    if let Err(error) = registry::push_registration(clone_path) {
+      registry::discard_registration(clone_path);
+
+      if let Err(error) = publish::retract_release(&tag) {
+        log::warn!("{}; delete the tag manually", error);
+      }
+
+      return Err(GripError::Network(format!(
+        "{}; the release was retracted",
+        error
+      )));
+    }
+  }
+
+  log::info!(
+    "published `{}` v{}",
+    package_manifest.name,
+    package_manifest.version
+  );
+
+  Ok(())
+}
+
+/// Remove a dependency, along with any packages it orphans.
+fn remove_command(
+  remove_arg_matches: &clap::ArgMatches<'_>,
+  network_context: &network::NetworkContext,
+) -> Result<(), GripError> {
+  network_context.require_mutable_lock(ARG_REMOVE)?;
+
+  let dependency_name = remove_arg_matches.value_of(ARG_REMOVE_DEPENDENCY).unwrap();
+  let manifest_path = package::manifest_path();
+  let package_manifest = package::fetch_manifest(&manifest_path)?;
+
+  if !package_manifest.dependencies.contains_key(dependency_name) {
+    return Err(GripError::Manifest(format!(
+      "`{}` is not a dependency of `{}`",
+      dependency_name, package_manifest.name
+    )));
+  }
+
+  // Determined before the manifest changes, since the graph is built
+  // ... from the installed packages' manifests.
+  let other_manifests = package::workspace_manifests()?
+    .into_iter()
+    .filter(|manifest| manifest.name != package_manifest.name)
+    .collect::<Vec<_>>();
+
+  let orphaned =
+    match dependency::orphaned_by_removal(&package_manifest, &other_manifests, dependency_name) {
+      Ok(orphaned) => orphaned,
+      Err(error) => {
+        log::warn!(
+          "{}; only `{}` itself will be removed",
+          error,
+          dependency_name
+        );
+
+        vec![dependency_name.to_string()]
+      }
+    };
+
+  package::remove_dependency(&manifest_path, dependency_name)?;
+
+  log::info!(
+    "removed `{}` from the dependencies in `{}`",
+    dependency_name,
+    manifest_path.display()
+  );
+
+  let mut package_lock = package::get_or_init_package_lock()?;
+
+  for orphaned_name in &orphaned {
+    package_lock.unlock_package(orphaned_name);
+
+    let package_path = std::path::Path::new(PATH_DEPENDENCIES).join(orphaned_name);
+
+    if !package_path.exists() {
+      continue;
+    }
+
+    if let Err(error) = std::fs::remove_dir_all(&package_path) {
+      return Err(GripError::Io(format!(
+        "failed to remove `{}`: {}",
+        package_path.display(),
+        error
+      )));
+    }
+
+    log::info!("removed package `{}`", orphaned_name);
+  }
+
+  package::write_package_lock(&package_lock)?;
+
+  Ok(())
+}
+
+/// Suggest what to do next, depending on whether grip was invoked
+/// from within a package.
+fn print_usage_hints(invocation_dir: &std::path::Path) {
+  let manifest = package::find_manifest_dir(invocation_dir).and_then(|project_dir| {
+    package::fetch_manifest(&project_dir.join(package::PATH_MANIFEST_FILE)).ok()
+  });
+
+  let suggestions = match &manifest {
+    Some(manifest) => {
+      println!("in package `{}` v{}; try:", manifest.name, manifest.version);
+
+      let mut suggestions = vec![(ARG_BUILD, "build the package")];
+
+      if manifest.ty == package::PackageType::Executable {
+        suggestions.push((ARG_RUN, "build and run the package"));
+      }
+
+      suggestions.push((ARG_CHECK, "check the package for errors"));
+
+      suggestions
+    }
+    None => {
+      println!(
+        "no `{}` file was found in this directory or its parents; try:",
+        package::PATH_MANIFEST_FILE
+      );
+
+      vec![(ARG_INIT, "create a new package in this directory")]
+    }
+  };
+
+  for (subcommand, description) in suggestions {
+    println!("  grip {:<8} {}", subcommand, description);
+  }
+}
+
+#[tokio::main]
+async fn main() {
+  match run().await {
+    Ok(_) => (),
+    Err(error) => {
+      log::error!("{}", error);
+      std::process::exit(error.exit_code());
+    }
+  }
+}
+
+/// Create the generated sources directory of a package, and expose it
+/// to any processes spawned during the build (such as hooks).
+fn expose_out_dir(package_name: &str) -> Result<(), String> {
+  let out_dir = package::out_dir(package_name);
+
+  if let Err(error) = std::fs::create_dir_all(&out_dir) {
+    return Err(format!(
+      "failed to create the generated sources directory: {}",
+      error
+    ));
+  }
+
+  match out_dir.canonicalize() {
+    Ok(out_dir) => std::env::set_var(package::ENV_OUT_DIR, out_dir),
+    Err(error) => {
+      return Err(format!(
+        "failed to resolve the generated sources directory: {}",
+        error
+      ))
+    }
+  }
+
+  Ok(())
+}
+
+/// Declare an installed package as a dependency of the current project,
+/// if installing from within one.
+fn register_dependency(
+  package_name: &str,
+  dependency_spec: package::DependencySpec,
+) -> Result<(), String> {
+  let manifest_path = package::manifest_path();
+
+  if !manifest_path.exists() {
+    return Ok(());
+  }
+
+  if package::add_dependency(&manifest_path, package_name, dependency_spec)? {
+    log::info!(
+      "added `{}` to the dependencies in `{}`",
+      package_name,
+      manifest_path.display()
+    );
+  }
+
+  Ok(())
+}
+
+/// Determine whether a global argument was given, either before or
+/// after the subcommand.
+fn is_global_present(matches: &clap::ArgMatches<'_>, name: &str) -> bool {
+  matches.is_present(name)
+    || matches.subcommand().1.map_or(false, |subcommand_matches| {
+      subcommand_matches.is_present(name)
+    })
+}
+
+/// Retrieve the value of a global argument, given either before or
+/// after the subcommand.
+fn global_value_of<'a>(matches: &'a clap::ArgMatches<'_>, name: &str) -> Option<&'a str> {
+  matches.value_of(name).or_else(|| {
+    matches
+      .subcommand()
+      .1
+      .and_then(|subcommand_matches| subcommand_matches.value_of(name))
+  })
+}
+
+/// Change the working directory to the root of the package, which is
+/// either specified explicitly, or is the nearest directory (starting
+/// from the current one) containing a package manifest file.
+///
+/// If the package is a member of a workspace, the root of the workspace
+/// is entered instead (since its members share the dependencies and the
+/// lock), and the package directory is set to that of the member. A
+/// member can also be selected explicitly, by its name or directory.
+///
+/// If `required` is set, an error is returned when no package manifest
+/// file could be found.
+fn enter_project_dir(
+  invocation_dir: &std::path::Path,
+  manifest_path: Option<&str>,
+  package_selection: Option<&str>,
+  required: bool,
+) -> Result<(), String> {
+  let project_dir = if let Some(manifest_path) = manifest_path {
+    let manifest_path = invocation_dir.join(manifest_path);
+
+    let manifest_path = if manifest_path.is_dir() {
+      manifest_path.join(package::PATH_MANIFEST_FILE)
+    } else {
+      manifest_path
+    };
 
-  let matches = app.get_matches();
-  let llvm_context = inkwell::context::Context::create();
-  let set_logger_result = log::set_logger(&console::LOGGER);
+    if manifest_path.file_name() != Some(std::ffi::OsStr::new(package::PATH_MANIFEST_FILE)) {
+      return Err(format!(
+        "the manifest path must point to a `{}` file",
+        package::PATH_MANIFEST_FILE
+      ));
+    } else if !manifest_path.is_file() {
+      return Err(format!(
+        "manifest file `{}` does not exist",
+        manifest_path.display()
+      ));
+    }
 
-  if let Err(error) = set_logger_result {
+    manifest_path.parent().map(|parent| parent.to_path_buf())
+  } else {
+    package::find_manifest_dir(invocation_dir)
+  };
+
+  if project_dir.is_none() && required {
     return Err(format!(
-      "there was an error initializing the logger: {}",
+      "could not find `{}` in `{}` or any of its parent directories; run `grip init` to create a new package",
+      package::PATH_MANIFEST_FILE,
+      invocation_dir.display()
+    ));
+  }
+
+  let project_dir = match project_dir {
+    Some(project_dir) => project_dir,
+    None if package_selection.is_some() => {
+      return Err("cannot select a package outside of a workspace".to_string())
+    }
+    None => return Ok(()),
+  };
+
+  let (project_dir, member_dir) = match package::find_workspace_root(&project_dir)? {
+    Some((workspace_root, member_dir)) => (workspace_root, member_dir),
+    None => (project_dir, None),
+  };
+
+  if let Err(error) = std::env::set_current_dir(&project_dir) {
+    return Err(format!(
+      "failed to enter the package directory `{}`: {}",
+      project_dir.display(),
       error
     ));
   }
 
-  log::set_max_level(log::LevelFilter::Info);
+  if let Some(package_selection) = package_selection {
+    package::set_package_dir(package::find_workspace_member(package_selection)?);
+  } else if let Some(member_dir) = member_dir {
+    package::set_package_dir(member_dir);
+  }
 
-  if let Some(init_arg_matches) = matches.subcommand_matches(ARG_INIT) {
-    package::init_manifest(&init_arg_matches);
+  Ok(())
+}
 
-    Ok(())
-  } else if let Some(_build_arg_matches) = matches.subcommand_matches(ARG_BUILD) {
-    let package_manifest = package::fetch_manifest(&package::PATH_MANIFEST_FILE.into())?;
-    let package_lock = package::get_or_init_package_lock()?;
-    let llvm_module = llvm_context.create_module(package_manifest.name.as_str());
-    let mut driver = build::Driver::new(&llvm_context, &llvm_module);
-    let mut build_queue = std::collections::VecDeque::new();
-    let mut is_initial_package = true;
+/// Fetch an executable package (from the registry, or by cloning it if
+/// clone options are given), build it along with its dependencies, and
+/// install the resulting executable into the grip `bin` directory.
+async fn install_binary(
+  registry: &(dyn registry::Registry + Send + Sync),
+  scheduler: &scheduler::Scheduler,
+  network_config: &config::NetworkConfig,
+  package_path: &str,
+  package_reference: &str,
+  clone_options: Option<git::CloneOptions>,
+) -> Result<(), GripError> {
+  let build_dir = binary::build_dir()?;
+
+  let result = build_binary(
+    registry,
+    scheduler,
+    network_config,
+    package_path,
+    package_reference,
+    clone_options,
+    &build_dir,
+  )
+  .await;
+
+  // Best-effort cleanup, whether or not the executable was installed.
+  if let Err(error) = std::fs::remove_dir_all(&build_dir) {
+    log::warn!("failed to remove the build directory: {}", error);
+  }
+
+  result
+}
+
+/// Fetch and build an executable package within a build directory, then
+/// install its executable.
+async fn build_binary(
+  registry: &(dyn registry::Registry + Send + Sync),
+  scheduler: &scheduler::Scheduler,
+  network_config: &config::NetworkConfig,
+  package_path: &str,
+  package_reference: &str,
+  clone_options: Option<git::CloneOptions>,
+  build_dir: &std::path::Path,
+) -> Result<(), GripError> {
+  // The package is fetched into the build directory the same way a
+  // ... dependency would be fetched into a project.
+  if let Err(error) = std::env::set_current_dir(build_dir) {
+    return Err(GripError::Io(format!(
+      "failed to enter the build directory: {}",
+      error
+    )));
+  }
+
+  let package_manifest = match clone_options {
+    Some(clone_options) => {
+      install::install_from_git(package_path, package_reference, &clone_options, true)?.0
+    }
+    None => {
+      let package_manifest = registry
+        .fetch_manifest(package_path, package_reference)
+        .await
+        .map_err(GripError::Network)?;
+
+      let archive_path = scheduler
+        .run(install::download_package(
+          registry,
+          package_path,
+          package_reference,
+          &package_manifest.name,
+          network_config,
+        ))
+        .await?;
+
+      install::extract_package(&archive_path, &package_manifest.name, network_config, true)?;
+
+      package_manifest
+    }
+  };
+
+  if package_manifest.ty != package::PackageType::Executable {
+    return Err(GripError::Other(format!(
+      "package `{}` is a library, and cannot be installed as an executable",
+      package_manifest.name
+    )));
+  }
+
+  let package_dir = build_dir
+    .join(PATH_DEPENDENCIES)
+    .join(&package_manifest.name);
+
+  if let Err(error) = std::env::set_current_dir(&package_dir) {
+    return Err(GripError::Io(format!(
+      "failed to enter the package directory: {}",
+      error
+    )));
+  }
+
+  let mut package_lock = package::get_or_init_package_lock()?;
+
+  install::install_dependencies(
+    registry,
+    scheduler,
+    network_config,
+    &package_manifest,
+    &mut package_lock,
+  )
+  .await?;
+
+  package::write_package_lock(&package_lock)?;
+
+  let current_exe = match std::env::current_exe() {
+    Ok(current_exe) => current_exe,
+    Err(error) => {
+      return Err(GripError::Other(format!(
+        "failed to locate the grip executable: {}",
+        error
+      )))
+    }
+  };
+
+  let status = std::process::Command::new(&current_exe)
+    .arg(ARG_BUILD)
+    .arg(format!("--{}", ARG_BUILD_RELEASE))
+    .arg(format!("--{}=exe", ARG_BUILD_EMIT))
+    .current_dir(&package_dir)
+    .status();
+
+  match status {
+    Ok(status) if status.success() => {}
+    Ok(_) => {
+      return Err(GripError::Compile(format!(
+        "could not install `{}` due to previous errors",
+        package_manifest.name
+      )))
+    }
+    Err(error) => return Err(GripError::Other(format!("failed to run grip: {}", error))),
+  }
+
+  let target_triple = inkwell::targets::TargetMachine::get_default_triple();
+
+  let artifact_dir = build::artifact_dir(
+    &target_triple.as_str().to_string_lossy(),
+    package::PROFILE_RELEASE,
+  );
+
+  let installed_path = binary::install(
+    &package_dir.join(build::executable_path(
+      &artifact_dir,
+      &package_manifest.name,
+    )),
+    &package_manifest.name,
+    binary::InstalledBinary {
+      version: package_manifest.version.clone(),
+      source: package_path.to_string(),
+      reference: package_reference.to_string(),
+    },
+  )?;
+
+  log::info!(
+    "installed `{}` v{} to `{}`",
+    package_manifest.name,
+    package_manifest.version,
+    installed_path.display()
+  );
 
-    build_queue.push_front(package_manifest.clone());
+  if !binary::is_bin_dir_in_path() {
+    log::warn!(
+      "`{}` is not in PATH; add it to PATH to run installed executables by name",
+      binary::bin_dir()?.display()
+    );
+  }
+
+  Ok(())
+}
+
+/// Re-run the current command for each of the given workspace members,
+/// stopping at the first one which fails.
+fn run_for_members(
+  invocation_dir: &std::path::Path,
+  members: &[std::path::PathBuf],
+) -> Result<(), String> {
+  let current_exe = match std::env::current_exe() {
+    Ok(current_exe) => current_exe,
+    Err(error) => return Err(format!("failed to locate the grip executable: {}", error)),
+  };
+
+  for member in members {
+    console::print_status("Member", &member.display().to_string());
+
+    let status = std::process::Command::new(&current_exe)
+      .args(std::env::args().skip(1))
+      .arg(format!("--{}={}", ARG_PACKAGE, member.display()))
+      .current_dir(invocation_dir)
+      .status();
+
+    match status {
+      Ok(status) if status.success() => {}
+      Ok(_) => return Err(format!("failed to process member `{}`", member.display())),
+      Err(error) => return Err(format!("failed to run grip: {}", error)),
+    }
+  }
+
+  Ok(())
+}
+
+/// Retrieve the arguments grip was invoked with (excluding the program
+/// name), without the given flag. Used to re-run the current command.
+fn args_without_flag(flag: &str) -> Vec<std::ffi::OsString> {
+  let flag = format!("--{}", flag);
 
-    while let Some(package) = build_queue.pop_front() {
-      if package.ty == package::PackageType::Executable && !is_initial_package {
-        return Err("dependency is an executable, but was expected to be a library".to_string());
+  std::env::args_os()
+    .skip(1)
+    .filter(|arg| arg.to_str() != Some(flag.as_str()))
+    .collect()
+}
+
+/// Remove an option taking a value (given either as `--name=value` or as
+/// `--name value`) from command-line arguments.
+fn args_without_value(args: Vec<std::ffi::OsString>, name: &str) -> Vec<std::ffi::OsString> {
+  let flag = format!("--{}", name);
+  let flag_prefix = format!("--{}=", name);
+  let mut filtered_args = Vec::new();
+  let mut args = args.into_iter();
+
+  while let Some(arg) = args.next() {
+    match arg.to_str() {
+      Some(arg) if arg == flag => {
+        args.next();
       }
+      Some(arg) if arg.starts_with(&flag_prefix) => {}
+      _ => filtered_args.push(arg),
+    }
+  }
+
+  filtered_args
+}
+
+/// Remember the current project, so that the cache entries it references
+/// are preserved when cleaning the cache.
+fn record_project() {
+  let record_result = match std::env::current_dir() {
+    Ok(current_dir) => cache::record_project(&current_dir),
+    Err(error) => Err(format!("failed to read the current directory: {}", error)),
+  };
+
+  if let Err(error) = record_result {
+    log::warn!("{}", error);
+  }
+}
+
+/// Report the size of the user-wide cache, and remove its entries
+/// according to the given criteria.
+fn clean_cache(clean_arg_matches: &clap::ArgMatches<'_>) -> Result<(), String> {
+  let entries = cache::entries()?;
+  let known_projects = cache::known_projects()?;
+
+  let max_age = match clean_arg_matches.value_of(ARG_CLEAN_OLDER_THAN) {
+    Some(days) => match days.parse::<u64>() {
+      Ok(days) => match days.checked_mul(24 * 60 * 60) {
+        Some(seconds) => Some(std::time::Duration::from_secs(seconds)),
+        None => return Err(format!("amount of days `{}` is too large", days)),
+      },
+      Err(_) => return Err(format!("invalid amount of days `{}`", days)),
+    },
+    None => None,
+  };
+
+  let mut removed_size = 0;
+  let mut removed_count = 0;
+
+  for entry in &entries {
+    let is_expired = max_age.map_or(false, |max_age| {
+      entry
+        .modified
+        .elapsed()
+        .map_or(false, |elapsed| elapsed > max_age)
+    });
+
+    if clean_arg_matches.is_present(ARG_CLEAN_ALL)
+      || is_expired
+      || (clean_arg_matches.is_present(ARG_CLEAN_UNREFERENCED)
+        && !cache::is_referenced(entry, &known_projects))
+    {
+      cache::remove_entry(entry)?;
+      removed_size += entry.size;
+      removed_count += 1;
+    }
+  }
+
+  let total_size = entries.iter().map(|entry| entry.size).sum::<u64>();
+
+  log::info!(
+    "cache at `{}`: {} entries, {}",
+    cache::cache_dir()?.display(),
+    entries.len() - removed_count,
+    indicatif::HumanBytes(total_size - removed_size)
+  );
 
-      let sources_dir = if is_initial_package {
-        let result = std::path::PathBuf::from(PATH_SOURCES);
+  if removed_count > 0 {
+    log::info!(
+      "removed {} entries ({})",
+      removed_count,
+      indicatif::HumanBytes(removed_size)
+    );
+  }
+
+  Ok(())
+}
+
+/// Create a driver for the given package, and register the sources
+/// of all of its build units.
+fn create_driver<'a, 'ctx>(
+  llvm_context: &'ctx inkwell::context::Context,
+  llvm_module: &'a inkwell::module::Module<'ctx>,
+  package_manifest: &package::Manifest,
+  build_units: Vec<package::BuildUnit>,
+  jobs: usize,
+) -> build::Driver<'a, 'ctx> {
+  let mut driver = build::Driver::new(llvm_context, llvm_module);
+
+  driver.jobs = jobs;
+
+  driver.require_main = package_manifest.ty == package::PackageType::Executable;
+  driver.manifest_diagnostics = manifest_lint::check_dependency_types(&build_units);
+
+  // TODO: Shouldn't these source files be saved under a package (HashMap)?
+  for build_unit in build_units {
+    driver
+      .dependencies
+      .insert(build_unit.manifest.name.clone(), build_unit.dependencies);
+
+    driver
+      .features
+      .insert(build_unit.manifest.name.clone(), build_unit.features);
 
-        is_initial_package = false;
+    for source_file in build_unit.source_files {
+      driver
+        .source_files
+        .push((build_unit.manifest.name.clone(), source_file));
+    }
+  }
+
+  driver
+}
+
+/// Create a driver event handler which displays the progress of a
+/// build.
+fn display_progress(build_units: &[package::BuildUnit]) -> Box<dyn FnMut(build::BuildEvent)> {
+  let package_versions = build_units
+    .iter()
+    .map(|build_unit| {
+      (
+        build_unit.manifest.name.clone(),
+        build_unit.manifest.version.clone(),
+      )
+    })
+    .collect::<std::collections::HashMap<_, _>>();
+
+  Box::new(move |build_event| match build_event {
+    build::BuildEvent::Compiling {
+      package_name,
+      index,
+      count,
+    } => console::print_status(
+      "Compiling",
+      &format!(
+        "{} v{} ({}/{})",
+        package_name,
+        package_versions
+          .get(&package_name)
+          .map(String::as_str)
+          .unwrap_or("?"),
+        index,
+        count
+      ),
+    ),
+    build::BuildEvent::Parsing { index, count } => {
+      console::set_progress("Parsing", index as u64, count as u64)
+    }
+    build::BuildEvent::Checking => console::set_progress("Checking", 0, 0),
+    build::BuildEvent::Lowering => console::set_progress("Lowering", 0, 0),
+  })
+}
 
-        result
+/// Print the `build-finished` event, along with the build statistics.
+fn print_build_finished(
+  success: bool,
+  statistics: &build::Statistics,
+  functions_emitted: usize,
+  cache_hits: usize,
+  cache_misses: usize,
+) -> Result<(), String> {
+  let build_finished = schema::BuildFinished {
+    success,
+    statistics: schema::BuildStatistics {
+      files_parsed: statistics.files_parsed,
+      tokens_lexed: statistics.tokens_lexed,
+      nodes_lowered: statistics.nodes_lowered,
+      functions_emitted,
+      cache_hits,
+      cache_misses,
+      cache_hit_rate: if cache_hits + cache_misses == 0 {
+        0.0
       } else {
-        std::path::PathBuf::from(package::PATH_DEPENDENCIES)
-          .join(package.name.clone())
-          .join(PATH_SOURCES)
-      };
+        cache_hits as f64 / (cache_hits + cache_misses) as f64
+      },
+    },
+  };
+
+  println!("{}", schema::to_json("build-finished", &build_finished)?);
+
+  Ok(())
+}
+
+/// Write the summary of a build, as JSON or Markdown.
+fn write_build_summary(
+  summary_path: &std::path::Path,
+  markdown: bool,
+  build_summary: &schema::BuildSummary,
+) -> Result<(), String> {
+  let contents = if markdown {
+    schema::summary_markdown(build_summary)
+  } else {
+    schema::to_json("build-summary", build_summary)?
+  };
+
+  if let Err(error) = std::fs::write(summary_path, contents) {
+    return Err(format!(
+      "failed to write the build summary to `{}`: {}",
+      summary_path.display(),
+      error
+    ));
+  }
+
+  Ok(())
+}
+
+/// Print diagnostics grouped by the package they originated from.
+///
+/// If `diagnostics_for` is specified, only errors are printed for any
+/// other packages. As JSON, all of them are printed regardless of the
+/// error limit.
+fn print_diagnostics(
+  diagnostics: &[build::DriverDiagnostic],
+  file_contents: &std::collections::HashMap<std::path::PathBuf, String>,
+  diagnostics_for: Option<&str>,
+  json_messages: bool,
+) {
+  let mut diagnostics = diagnostics
+    .iter()
+    .filter(|driver_diagnostic| {
+      driver_diagnostic.is_error()
+        || diagnostics_for.is_none()
+        || driver_diagnostic.package_name.is_none()
+        || driver_diagnostic.package_name.as_deref() == diagnostics_for
+    })
+    .collect::<Vec<_>>();
+
+  // The sort is stable, so diagnostics remain in order within each package.
+  diagnostics.sort_by(|a, b| a.package_name.cmp(&b.package_name));
 
-      let source_directories = package::read_sources_dir(&sources_dir)?;
+  if json_messages {
+    for driver_diagnostic in diagnostics {
+      let source_code = driver_diagnostic
+        .source_file
+        .as_ref()
+        .and_then(|source_file| file_contents.get(source_file));
 
-      // TODO: Shouldn't these source files be saved under a package (HashMap)?
-      for source_file in source_directories {
-        driver
-          .source_files
-          .push((package.name.clone(), source_file));
+      if let Err(error) =
+        console::print_json_diagnostic(driver_diagnostic, source_code.map(String::as_str))
+      {
+        log::error!("{}", error);
       }
+    }
+
+    return;
+  }
+
+  let error_count = diagnostics
+    .iter()
+    .filter(|driver_diagnostic| driver_diagnostic.is_error())
+    .count();
 
-      // TODO: Handle cyclic dependencies.
-      // Add dependencies to build queue.
-      for dependency in &package.dependencies {
-        let dependency_manifest = package::fetch_dependency_manifest(dependency)?;
+  let warning_count = diagnostics.len() - error_count;
+  let error_limit = console::error_limit();
+  let mut printed_errors = 0;
+  let mut omitted_errors = 0;
+  let mut files = codespan_reporting::files::SimpleFiles::new();
+  let mut file_ids = std::collections::HashMap::new();
 
-        build_queue.push_front(dependency_manifest);
+  for (source_file, source_code) in file_contents {
+    file_ids.insert(
+      source_file,
+      files.add(source_file.display().to_string(), source_code.clone()),
+    );
+  }
+
+  for driver_diagnostic in diagnostics {
+    // Once the limit is reached, nothing else is printed.
+    if error_limit.map_or(false, |error_limit| printed_errors >= error_limit) {
+      if driver_diagnostic.is_error() {
+        omitted_errors += 1;
       }
+
+      continue;
     }
 
-    // TODO: Use a map to store the sources, then read it here
-    // and provide it to the project builder to link diagnostics
-    // to specific files (via `(source_file_name, diagnostic)`).
+    if driver_diagnostic.is_error() {
+      printed_errors += 1;
+    }
 
-    let diagnostics = driver.build();
+    let mut diagnostic = driver_diagnostic.diagnostic.clone();
 
-    for diagnostic in diagnostics {
-      // TODO: Maybe fix this by clearing then re-writing the progress bar.
-      // FIXME: This will interfere with the progress bar (leave it behind).
-      crate::console::print_diagnostic(
-        vec![(
-          // TODO:
-          &"source_file_path_here_pending".to_string(),
-          // FIXME:
-          &"source_file_path_contents_here_pending".to_string(),
-        )],
-        &diagnostic,
-      );
+    if let Some(package_name) = &driver_diagnostic.package_name {
+      diagnostic.message = format!("[{}] {}", package_name, diagnostic.message);
     }
 
-    llvm_module.set_triple(&inkwell::targets::TargetMachine::get_default_triple());
+    crate::console::print_diagnostic(
+      &files,
+      driver_diagnostic
+        .source_file
+        .as_ref()
+        .and_then(|source_file| file_ids.get(source_file).copied()),
+      &diagnostic,
+    );
+  }
+
+  if omitted_errors > 0 {
+    log::error!(
+      "and {} more error(s); pass `--{} 0` to print all of them",
+      omitted_errors,
+      ARG_ERROR_LIMIT
+    );
+  }
 
-    let llvm_ir = llvm_module.print_to_string().to_string();
-    let default_output_path = std::path::PathBuf::from(DEFAULT_OUTPUT_DIR);
-    let mut output_path = default_output_path.clone();
+  let warnings = match warning_count {
+    1 => "1 warning".to_string(),
+    warning_count => format!("{} warnings", warning_count),
+  };
 
-    output_path.push(package_manifest.name);
-    output_path.set_extension("ll");
+  if error_count > 0 {
+    let errors = match error_count {
+      1 => "1 previous error".to_string(),
+      error_count => format!("{} previous errors", error_count),
+    };
 
-    if !default_output_path.exists() && std::fs::create_dir(crate::DEFAULT_OUTPUT_DIR).is_err() {
-      log::error!("failed to create output directory");
-    } else if let Err(error) = std::fs::write(output_path, llvm_ir) {
-      log::error!("failed to write output file: {}", error);
+    if warning_count > 0 {
+      log::error!("aborting due to {}; {} emitted", errors, warnings);
+    } else {
+      log::error!("aborting due to {}", errors);
     }
+  } else if warning_count > 0 {
+    log::warn!("{} emitted", warnings);
+  }
+}
 
-    Ok(())
-  } else if let Some(_check_arg_matches) = matches.subcommand_matches(ARG_CHECK) {
-    // TODO: Implement.
-    todo!();
-  } else if let Some(install_arg_matches) = matches.subcommand_matches(ARG_INSTALL) {
-    let reqwest_client = reqwest::Client::new();
-    let github_repository_path = install_arg_matches.value_of(ARG_INSTALL_PATH).unwrap();
-    let github_branch = install_arg_matches.value_of(ARG_INSTALL_BRANCH).unwrap();
-
-    // TODO: GitHub might be caching results from this url.
-    let package_manifest_file_response_result = reqwest_client
-      .get(format!(
-        "https://raw.githubusercontent.com/{}/{}/{}",
-        github_repository_path,
-        github_branch,
-        package::PATH_MANIFEST_FILE
-      ))
-      .send()
-      .await;
+fn has_errors(diagnostics: &[build::DriverDiagnostic]) -> bool {
+  diagnostics.iter().any(build::DriverDiagnostic::is_error)
+}
 
-    if let Err(error) = package_manifest_file_response_result {
-      return Err(format!(
-        "failed to fetching the package manifest file: {}",
+/// Read a source file given as an argument, where `-` stands for stdin.
+fn read_source_arg(file: &str) -> Result<(std::path::PathBuf, String), GripError> {
+  if file == "-" {
+    let mut source_code = String::new();
+
+    if let Err(error) = std::io::Read::read_to_string(&mut std::io::stdin(), &mut source_code) {
+      return Err(GripError::Io(format!(
+        "failed to read from stdin: {}",
         error
-      ));
+      )));
     }
 
-    let package_manifest_file_response = package_manifest_file_response_result.unwrap();
+    Ok((std::path::PathBuf::from(PATH_STDIN), source_code))
+  } else {
+    let source_file = std::path::PathBuf::from(file);
+    let source_code = package::fetch_file_contents(&source_file)?;
 
-    if package_manifest_file_response.status() == reqwest::StatusCode::NOT_FOUND {
-      return Err(String::from(
-        "the package manifest file was not found on the requested repository",
-      ));
-    } else if !package_manifest_file_response.status().is_success() {
-      return Err(format!(
-        "failed to fetching the package manifest file: HTTP error {}",
-        package_manifest_file_response.status()
-      ));
-    }
+    Ok((source_file, source_code))
+  }
+}
 
-    let package_manifest_file_text = package_manifest_file_response.text().await;
+/// Create a driver for a single source file outside of a package
+/// context, as part of an implicit anonymous package.
+fn create_anonymous_driver<'a, 'ctx>(
+  llvm_context: &'ctx inkwell::context::Context,
+  llvm_module: &'a inkwell::module::Module<'ctx>,
+  source_file: &std::path::Path,
+  source_code: &str,
+) -> build::Driver<'a, 'ctx> {
+  let mut driver = build::Driver::new(llvm_context, llvm_module);
 
-    if let Err(error) = package_manifest_file_text {
-      return Err(format!(
-        "failed to fetching the package manifest file: {}",
-        error
-      ));
+  // Scratch files aren't required to be complete programs.
+  driver.require_main = false;
+
+  driver
+    .file_contents
+    .insert(source_file.to_path_buf(), source_code.to_string());
+
+  driver.source_files.push((
+    ANONYMOUS_PACKAGE_NAME.to_string(),
+    source_file.to_path_buf(),
+  ));
+
+  driver
+}
+
+/// Check a single file (or stdin) outside of a package context, as
+/// part of an implicit anonymous package.
+fn check_file(llvm_context: &inkwell::context::Context, file: &str) -> Result<(), GripError> {
+  let (source_file, source_code) = read_source_arg(file)?;
+  let llvm_module = llvm_context.create_module(ANONYMOUS_PACKAGE_NAME);
+  let mut driver = create_anonymous_driver(llvm_context, &llvm_module, &source_file, &source_code);
+  let source_file_name = source_file.to_string_lossy().to_string();
+  let diagnostics = driver.check();
+
+  for driver_diagnostic in &diagnostics {
+    console::print_file_diagnostic(
+      &source_file_name,
+      &source_code,
+      &driver_diagnostic.diagnostic,
+    );
+  }
+
+  if has_errors(&diagnostics) {
+    return Err(GripError::Compile(format!(
+      "could not check `{}` due to previous errors",
+      file
+    )));
+  }
+
+  Ok(())
+}
+
+/// Compile a single file (or stdin) and run it through the JIT, exiting
+/// with its exit code. Compiled scripts are cached by their contents.
+fn run_script(llvm_context: &inkwell::context::Context, file: &str) -> Result<(), GripError> {
+  let (source_file, source_code) = read_source_arg(file)?;
+  let source_code = script::strip_shebang(&source_code);
+  let cache_path = script::cache_path(&source_code)?;
+
+  let llvm_module = if cache_path.exists() {
+    script::load(llvm_context, &cache_path)?
+  } else {
+    let llvm_module = llvm_context.create_module(ANONYMOUS_PACKAGE_NAME);
+    let mut driver =
+      create_anonymous_driver(llvm_context, &llvm_module, &source_file, &source_code);
+    let source_file_name = source_file.to_string_lossy().to_string();
+
+    driver.require_main = true;
+
+    let diagnostics = driver.build();
+
+    for driver_diagnostic in &diagnostics {
+      console::print_file_diagnostic(
+        &source_file_name,
+        &source_code,
+        &driver_diagnostic.diagnostic,
+      );
     }
 
-    let package_manifest_result =
-      toml::from_str::<package::Manifest>(package_manifest_file_text.unwrap().as_str());
+    if has_errors(&diagnostics) {
+      return Err(GripError::Compile(format!(
+        "could not run `{}` due to previous errors",
+        file
+      )));
+    }
 
-    if let Err(error) = package_manifest_result {
-      return Err(format!(
-        "failed to parse the package manifest file: {}",
-        error
-      ));
+    // Failing to cache the script shouldn't prevent it from running.
+    if let Err(error) = script::store(&llvm_module, &cache_path) {
+      log::warn!("{}", error);
     }
 
-    let package_manifest = package_manifest_result.unwrap();
+    llvm_module
+  };
 
-    let package_zip_file_response = {
-      let response_result = reqwest_client
-        .get(format!(
-          "https://codeload.github.com/{}/zip/refs/heads/{}",
-          github_repository_path, github_branch
-        ))
-        .send()
-        .await;
+  let exit_code = script::run_jit(&llvm_module)?;
 
-      if let Err(error) = response_result {
-        return Err(format!("failed to download the package: {}", error));
-      }
+  std::process::exit(exit_code);
+}
 
-      response_result.unwrap()
-    };
+/// Compile an expression (or one read from stdin) within an implicit
+/// `main` function, run it through the JIT and print its value.
+fn eval_expression(
+  llvm_context: &inkwell::context::Context,
+  expression: &str,
+) -> Result<(), GripError> {
+  let expression = if expression == "-" {
+    read_source_arg(expression)?.1
+  } else {
+    expression.to_string()
+  };
 
-    if !package_zip_file_response.status().is_success() {
-      return Err(format!(
-        "failed to download the package: HTTP error {}",
-        package_zip_file_response.status()
-      ));
+  let source_file = std::path::PathBuf::from(PATH_EVAL);
+  let source_code = script::wrap_expression(&expression);
+  let llvm_module = llvm_context.create_module(ANONYMOUS_PACKAGE_NAME);
+  let mut driver = create_anonymous_driver(llvm_context, &llvm_module, &source_file, &source_code);
+
+  driver.require_main = true;
+
+  let diagnostics = driver.build();
+
+  for driver_diagnostic in &diagnostics {
+    console::print_file_diagnostic(PATH_EVAL, &source_code, &driver_diagnostic.diagnostic);
+  }
+
+  if has_errors(&diagnostics) {
+    return Err(GripError::Compile(
+      "could not evaluate the expression due to previous errors".to_string(),
+    ));
+  }
+
+  println!("{}", script::run_jit(&llvm_module)?);
+
+  Ok(())
+}
+
+/// Print the tokens of a single file (or stdin), as a table or JSON.
+fn lex_file(
+  llvm_context: &inkwell::context::Context,
+  file: &str,
+  json: bool,
+) -> Result<(), GripError> {
+  let (source_file, source_code) = read_source_arg(file)?;
+  let llvm_module = llvm_context.create_module(ANONYMOUS_PACKAGE_NAME);
+  let driver = create_anonymous_driver(llvm_context, &llvm_module, &source_file, &source_code);
+
+  let tokens = match driver.try_read_and_lex(&source_file) {
+    Ok(tokens) => tokens,
+    Err(diagnostic) => {
+      console::print_file_diagnostic(&source_file.to_string_lossy(), &source_code, &diagnostic);
+
+      return Err(GripError::Compile(format!(
+        "could not lex `{}` due to previous errors",
+        file
+      )));
     }
+  };
 
-    let file_size = {
-      let content_length = package_zip_file_response.content_length();
+  let token_list = schema::TokenList {
+    file: source_file.to_string_lossy().to_string(),
+    tokens: tokens
+      .into_iter()
+      .map(|token| schema::TokenInfo {
+        kind: format!("{:?}", token.0),
+        start: token.1.start,
+        end: token.1.end,
+        text: source_code
+          .get(token.1.start..token.1.end)
+          .unwrap_or("")
+          .to_string(),
+      })
+      .collect(),
+  };
 
-      // FIXME: Getting fragile `failed to download the package: no content length` errors.
-      if content_length.is_none() {
-        return Err("failed to download the package: no content length".to_string());
-      }
+  if json {
+    println!("{}", schema::to_json("tokens", &token_list)?);
 
-      content_length.unwrap()
-    };
+    return Ok(());
+  }
 
-    let progress_bar = indicatif::ProgressBar::new(file_size);
+  println!("{:<24} {:>12}  {}", "KIND", "SPAN", "SOURCE");
 
-    progress_bar.set_style(indicatif::ProgressStyle::default_bar().template(
-      "downloading package: {msg} [{bar:30}] {bytes}/{total_bytes} {bytes_per_sec}, {eta}",
-    ));
+  for token_info in &token_list.tokens {
+    // Escaped, so that each token fits on a single line.
+    let mut excerpt = format!("{:?}", token_info.text);
 
-    progress_bar.set_message(package_manifest.name.clone());
+    if excerpt.chars().count() > MAX_TOKEN_EXCERPT_LENGTH {
+      excerpt = excerpt
+        .chars()
+        .take(MAX_TOKEN_EXCERPT_LENGTH)
+        .chain("...".chars())
+        .collect();
+    }
 
-    let mut file_path = std::path::PathBuf::from(PATH_DEPENDENCIES);
+    let span = format!("{}..{}", token_info.start, token_info.end);
 
-    file_path.push(".downloading");
+    println!("{:<24} {:>12}  {}", token_info.kind, span, excerpt);
+  }
 
-    if !file_path.exists() {
-      if let Err(error) = std::fs::create_dir_all(file_path.clone()) {
-        return Err(format!(
-          "failed to create the dependencies directory: {}",
-          error
-        ));
-      }
+  Ok(())
+}
+
+/// Print the AST of a single file (or stdin), as a tree or JSON.
+fn parse_file(
+  llvm_context: &inkwell::context::Context,
+  file: &str,
+  json: bool,
+) -> Result<(), GripError> {
+  let (source_file, source_code) = read_source_arg(file)?;
+  let llvm_module = llvm_context.create_module(ANONYMOUS_PACKAGE_NAME);
+  let mut driver = create_anonymous_driver(llvm_context, &llvm_module, &source_file, &source_code);
+  let source_file_name = source_file.to_string_lossy().to_string();
+
+  let nodes = match driver.dump_file_ast(&source_file) {
+    Ok(nodes) => nodes,
+    Err(diagnostic) => {
+      console::print_file_diagnostic(&source_file_name, &source_code, &diagnostic);
+
+      return Err(GripError::Compile(format!(
+        "could not parse `{}` due to previous errors",
+        file
+      )));
     }
+  };
 
-    file_path.push(format!("{}.zip", package_manifest.name));
+  if !json {
+    print!(
+      "{}",
+      build::format_parsed_file(ANONYMOUS_PACKAGE_NAME, &source_file, &nodes)
+    );
 
-    let mut file = {
-      let file_result = std::fs::File::create(file_path);
+    return Ok(());
+  }
 
-      if let Err(error) = file_result {
-        progress_bar.finish_and_clear();
+  let ast_dump = schema::AstDump {
+    file: source_file_name,
+    nodes,
+  };
 
-        return Err(format!(
-          "failed to create output file for package download: {}",
-          error
-        ));
-      }
+  println!("{}", schema::to_json("ast", &ast_dump)?);
 
-      file_result.unwrap()
-    };
+  Ok(())
+}
 
-    let mut downloaded_bytes: u64 = 0;
-    let mut bytes_stream = package_zip_file_response.bytes_stream();
+/// Format the source files of the package in place, or only report
+/// those which aren't formatted if `check` is set. Files which can't be
+/// parsed are left untouched.
+fn format_package(llvm_context: &inkwell::context::Context, check: bool) -> Result<(), String> {
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+  let formatter = config::fetch_config()?.formatter;
 
-    while let Some(chunk_result) = bytes_stream.next().await {
-      if let Err(error) = chunk_result {
-        progress_bar.finish_and_clear();
+  // Generated sources (under the out directory) aren't formatted.
+  let source_files = package::read_sources_dir(
+    &package::package_dir().join(PATH_SOURCES),
+    &package_manifest.source_extensions(),
+  )?;
 
-        return Err(format!("failed to download the package: {}", error));
-      }
+  let mut unformatted_files = Vec::new();
+  let mut has_parse_errors = false;
 
-      let chunk = chunk_result.unwrap();
+  for source_file in &source_files {
+    let source_code = package::fetch_file_contents(source_file)?;
+    let source_file_name = source_file.to_string_lossy().to_string();
+    let llvm_module = llvm_context.create_module(ANONYMOUS_PACKAGE_NAME);
+    let mut driver = create_anonymous_driver(llvm_context, &llvm_module, source_file, &source_code);
 
-      if let Err(error) = file.write(&chunk) {
-        progress_bar.finish_and_clear();
+    // Only code which parses is formatted, so that the formatter never
+    // ... has to make sense of malformed code.
+    if let Err(diagnostic) = driver.dump_file_ast(source_file) {
+      console::print_file_diagnostic(&source_file_name, &source_code, &diagnostic);
+      has_parse_errors = true;
 
-        return Err(format!("failed to write to output file: {}", error));
-      }
+      continue;
+    }
 
-      let new_progress_position = std::cmp::min(downloaded_bytes + (chunk.len() as u64), file_size);
+    let formatted_code = match &formatter {
+      Some(formatter) => formatter::run_external(formatter, &source_code)?,
+      None => formatter::normalize(&source_code),
+    };
 
-      downloaded_bytes = new_progress_position;
-      progress_bar.set_position(new_progress_position);
+    if formatted_code == source_code {
+      continue;
     }
 
-    progress_bar.finish_and_clear();
-    log::info!("downloaded package `{}`", package_manifest.name);
+    if check {
+      println!("{}", source_file.display());
+    } else if let Err(error) = std::fs::write(source_file, &formatted_code) {
+      return Err(format!(
+        "failed to write `{}`: {}",
+        source_file.display(),
+        error
+      ));
+    } else {
+      log::info!("formatted `{}`", source_file.display());
+    }
 
-    Ok(())
+    unformatted_files.push(source_file);
+  }
 
-    // TODO: Continue implementation: unzip and process the downloaded package.
-  } else {
-    // TODO:
-    // clap.Error::with_description("no file specified", clap::ErrorKind::MissingArgument);
-    Err("try running `grip --help`".to_string())
-    // app.print_long_help();
+  if has_parse_errors {
+    return Err("some source files could not be formatted due to previous errors".to_string());
+  }
+
+  if check && !unformatted_files.is_empty() {
+    return Err(format!(
+      "{} source file(s) are not formatted; run `grip fmt` to format them",
+      unformatted_files.len()
+    ));
+  }
+
+  if unformatted_files.is_empty() {
+    log::info!(
+      "all {} source file(s) are already formatted",
+      source_files.len()
+    );
   }
+
+  Ok(())
 }
 
-#[tokio::main]
-async fn main() {
-  match run().await {
-    Ok(_) => (),
-    Err(error_message) => {
-      log::error!("{}", error_message);
-      std::process::exit(1);
+/// Print the AST of each source file of the package. As JSON, each file
+/// is printed on its own line.
+fn parse_package(
+  llvm_context: &inkwell::context::Context,
+  json: bool,
+  jobs: usize,
+) -> Result<(), GripError> {
+  let package_manifest = package::fetch_manifest(&package::manifest_path())?;
+  let llvm_module = llvm_context.create_module(package_manifest.name.as_str());
+
+  // Parsing doesn't resolve names, so dependencies aren't needed.
+  let build_units = package::collect_build_units(&package_manifest)?
+    .into_iter()
+    .take(1)
+    .collect();
+
+  let mut driver = create_driver(
+    llvm_context,
+    &llvm_module,
+    &package_manifest,
+    build_units,
+    jobs,
+  );
+
+  let parsed_files = match driver.parse_all() {
+    Ok(parsed_files) => parsed_files,
+    Err(diagnostic) => {
+      print_diagnostics(&[diagnostic], &driver.file_contents, None, false);
+
+      return Err(GripError::Compile(format!(
+        "could not parse `{}` due to previous errors",
+        package_manifest.name
+      )));
+    }
+  };
+
+  for (package_name, source_file, nodes) in parsed_files {
+    if json {
+      let ast_dump = schema::AstDump {
+        file: source_file.to_string_lossy().to_string(),
+        nodes,
+      };
+
+      println!("{}", schema::to_json("ast", &ast_dump)?);
+    } else {
+      print!(
+        "{}",
+        build::format_parsed_file(&package_name, &source_file, &nodes)
+      );
     }
   }
+
+  Ok(())
 }
 
-// TODO: Consider expanding this function (or re-structuring it).
-fn print_or_write_output(output: String, output_file_path: &std::path::PathBuf, print: bool) {
+fn print_or_write_output(
+  output: &[u8],
+  output_file_path: &std::path::Path,
+  print: bool,
+) -> Result<(), String> {
   if print {
-    println!("{}", output);
+    if let Err(error) = std::io::stdout().write_all(output) {
+      return Err(format!("failed to write output to stdout: {}", error));
+    }
   } else if let Err(error) = std::fs::write(output_file_path, output) {
-    log::error!("failed to write output file: {}", error);
+    return Err(format!("failed to write output file: {}", error));
   }
+
+  Ok(())
 }
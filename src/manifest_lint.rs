@@ -0,0 +1,115 @@
+use crate::{build, manifest_validation, package};
+
+fn warning(package_name: &str, message: String) -> build::DriverDiagnostic {
+  diagnostic(gecko::diagnostic::Severity::Warning, package_name, message)
+}
+
+fn diagnostic(
+  severity: gecko::diagnostic::Severity,
+  package_name: &str,
+  message: String,
+) -> build::DriverDiagnostic {
+  build::DriverDiagnostic::new(
+    Some(package_name.to_string()),
+    gecko::diagnostic::Diagnostic {
+      severity,
+      message,
+      // TODO: Point at the manifest's keys, once diagnostics can refer
+      // ... to files other than sources.
+      span: None,
+    },
+  )
+}
+
+/// Check the manifest of the package being built (the first build unit)
+/// against best practices. Problems are reported as warnings, so that
+/// they don't prevent the package from being built.
+pub fn lint(build_units: &[package::BuildUnit]) -> Vec<build::DriverDiagnostic> {
+  let manifest = match build_units.first() {
+    Some(build_unit) => &build_unit.manifest,
+    None => return Vec::new(),
+  };
+
+  let mut diagnostics = Vec::new();
+
+  // Libraries are meant to be used by others, who need to know what
+  // they do, and under which terms.
+  if manifest.ty == package::PackageType::Library {
+    if manifest_validation::is_missing(&manifest.description) {
+      diagnostics.push(warning(
+        &manifest.name,
+        "library has no `description`".to_string(),
+      ));
+    }
+
+    if manifest_validation::is_missing(&manifest.license) {
+      diagnostics.push(warning(
+        &manifest.name,
+        "library has no `license`".to_string(),
+      ));
+    }
+  }
+
+  for (dependency_name, dependency_spec) in &manifest.dependencies {
+    if let package::DependencySpec::Git {
+      branch,
+      tag: None,
+      rev: None,
+      ..
+    } = dependency_spec
+    {
+      diagnostics.push(warning(
+        &manifest.name,
+        format!(
+          "dependency `{}` tracks {}, so its contents may change between installs; pin a `tag` or `rev` instead",
+          dependency_name,
+          match branch {
+            Some(branch) => format!("branch `{}`", branch),
+            None => "the default branch".to_string(),
+          }
+        ),
+      ));
+    }
+
+    if dependency_spec.version_requirement().map(str::trim) == Some(package::ANY_VERSION) {
+      diagnostics.push(warning(
+        &manifest.name,
+        format!(
+          "dependency `{}` accepts any version; specify a version requirement instead",
+          dependency_name
+        ),
+      ));
+    }
+  }
+
+  diagnostics
+}
+
+/// Check that every package only depends on libraries, since executables
+/// can't be linked against. Unlike lints, these are errors, and are
+/// reported by every build.
+pub fn check_dependency_types(build_units: &[package::BuildUnit]) -> Vec<build::DriverDiagnostic> {
+  let mut diagnostics = Vec::new();
+
+  for build_unit in build_units {
+    for dependency_name in &build_unit.dependencies {
+      let is_executable = build_units.iter().skip(1).any(|dependency_unit| {
+        &dependency_unit.manifest.name == dependency_name
+          && dependency_unit.manifest.ty == package::PackageType::Executable
+      });
+
+      if is_executable {
+        diagnostics.push(diagnostic(
+          gecko::diagnostic::Severity::Error,
+          &build_unit.manifest.name,
+          format!(
+            "dependency `{}` is an executable, not a library",
+            dependency_name
+          ),
+        ));
+      }
+    }
+  }
+
+  diagnostics
+}
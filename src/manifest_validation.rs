@@ -0,0 +1,528 @@
+use crate::package;
+
+/// The top-level keys of a manifest, in the order they are formatted in.
+pub const KEYS_MANIFEST: &[&str] = &[
+  "name",
+  "type",
+  "version",
+  "description",
+  "license",
+  "authors",
+  "repository",
+  "keywords",
+  "source-extensions",
+  "dependencies",
+  "env",
+  "features",
+  "build",
+  "target",
+  "hooks",
+  "profile",
+  "workspace",
+];
+
+const KEYS_BUILD: &[&str] = &["link-libs", "link-search-paths"];
+const KEYS_TARGET: &[&str] = &["dependencies", "build"];
+const KEYS_HOOKS: &[&str] = &["pre-build", "post-build", "pre-run"];
+const KEYS_PROFILE: &[&str] = &[
+  "opt-level",
+  "debug",
+  "verify",
+  "strip",
+  "panic",
+  "pie",
+  "codegen-units",
+];
+const KEYS_WORKSPACE: &[&str] = &["members"];
+const KEYS_DEPENDENCY: &[&str] = &["git", "branch", "tag", "rev", "path", "version", "registry"];
+const PACKAGE_TYPES: &[&str] = &["library", "executable"];
+
+/// A problem found in a manifest, at the line and column (both starting
+/// at 1) of the key it concerns, if it could be located.
+struct Problem {
+  position: Option<(usize, usize)>,
+  message: String,
+}
+
+/// Determine whether a package name follows the rules of identifiers
+/// (besides hyphens, which qualify module paths as underscores), so that
+/// it can qualify module paths (`package::module`).
+pub fn is_valid_name(name: &str) -> bool {
+  let mut chars = name.chars();
+
+  match chars.next() {
+    Some(first_char) if first_char.is_ascii_alphabetic() || first_char == '_' => {
+      chars.all(|char| char.is_ascii_alphanumeric() || char == '_' || char == '-')
+    }
+    _ => false,
+  }
+}
+
+/// Compute the amount of single-character edits turning one string into
+/// another.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b_chars = b.chars().collect::<Vec<_>>();
+  let mut previous_row = (0..=b_chars.len()).collect::<Vec<_>>();
+
+  for (a_index, a_char) in a.chars().enumerate() {
+    let mut row = vec![a_index + 1];
+
+    for (b_index, b_char) in b_chars.iter().enumerate() {
+      let substitution_cost = if a_char == *b_char { 0 } else { 1 };
+
+      row.push(
+        (previous_row[b_index] + substitution_cost)
+          .min(previous_row[b_index + 1] + 1)
+          .min(row[b_index] + 1),
+      );
+    }
+
+    previous_row = row;
+  }
+
+  previous_row[b_chars.len()]
+}
+
+/// Find the candidate closest to a misspelled key, if any is close
+/// enough to be what was meant.
+fn suggest<'a>(key: &str, candidates: &[&'a str]) -> Option<&'a str> {
+  let max_distance = std::cmp::max(1, key.chars().count() / 3);
+
+  candidates
+    .iter()
+    .map(|candidate| (edit_distance(key, candidate), *candidate))
+    .filter(|(distance, _)| *distance <= max_distance)
+    .min_by_key(|(distance, _)| *distance)
+    .map(|(_, candidate)| candidate)
+}
+
+fn parse_key_segments(text: &str) -> Vec<String> {
+  text
+    .split('.')
+    .map(|segment| {
+      segment
+        .trim()
+        .trim_matches('"')
+        .trim_matches('\'')
+        .to_string()
+    })
+    .collect()
+}
+
+/// Find a key within an inline table (`{ key = value, ... }`), given the
+/// path leading to it, possibly through nested inline tables or dotted
+/// keys. Returns its byte offset within the value.
+fn find_inline_key(value: &str, key_path: &[&str]) -> Option<usize> {
+  let mut offset = 0;
+
+  for segment in key_path {
+    offset = value[offset..]
+      .match_indices(segment)
+      .find_map(|(index, _)| {
+        let start = offset + index;
+        let end = start + segment.len();
+
+        let preceding = value[..start]
+          .trim_end_matches(|char| char == '"' || char == '\'')
+          .trim_end()
+          .chars()
+          .next_back();
+
+        let following = value[end..]
+          .trim_start_matches(|char| char == '"' || char == '\'')
+          .trim_start()
+          .chars()
+          .next();
+
+        let is_key =
+          matches!(preceding, Some('{' | ',' | '.')) && matches!(following, Some('=' | '.'));
+
+        if is_key {
+          Some(start)
+        } else {
+          None
+        }
+      })?;
+  }
+
+  Some(offset)
+}
+
+/// Locate a key within the manifest's source, given the path of its
+/// table. The search is line-based, which covers keys of (possibly
+/// nested) tables, dotted keys and keys within inline tables; a key
+/// which can't be found is located at its table instead.
+fn locate(contents: &str, table_path: &[&str], key: &str) -> Option<(usize, usize)> {
+  let key_path = table_path
+    .iter()
+    .copied()
+    .chain(std::iter::once(key))
+    .collect::<Vec<_>>();
+
+  let is_prefix =
+    |a: &[String], b: &[&str]| a.len() <= b.len() && a.iter().zip(b).all(|(a, b)| a.as_str() == *b);
+
+  let mut current_table = Vec::<String>::new();
+
+  for (line_index, line) in contents.lines().enumerate() {
+    let trimmed_line = line.trim_start();
+    let column = line.len() - trimmed_line.len() + 1;
+
+    if trimmed_line.starts_with('[') {
+      let header = trimmed_line
+        .trim_start_matches('[')
+        .split(']')
+        .next()
+        .unwrap_or("");
+
+      current_table = parse_key_segments(header);
+
+      // The key may itself be a table (`[key]`).
+      if current_table.len() == key_path.len() && is_prefix(&current_table, &key_path) {
+        return Some((line_index + 1, column));
+      }
+
+      continue;
+    }
+
+    let (line_key, value) = match trimmed_line.split_once('=') {
+      Some(key_value) => key_value,
+      None => continue,
+    };
+
+    // Dotted keys extend the path of the current table.
+    let line_path = current_table
+      .iter()
+      .cloned()
+      .chain(parse_key_segments(line_key))
+      .collect::<Vec<_>>();
+
+    let common_length = std::cmp::min(line_path.len(), key_path.len());
+
+    if !is_prefix(&line_path[..common_length], &key_path) {
+      continue;
+    }
+
+    if line_path.len() >= key_path.len() {
+      return Some((line_index + 1, column));
+    }
+
+    // The rest of the path lies within an inline table.
+    let value_offset = line.len() - value.len();
+
+    return Some(match find_inline_key(value, &key_path[line_path.len()..]) {
+      Some(key_offset) => (line_index + 1, value_offset + key_offset + 1),
+      None => (line_index + 1, column),
+    });
+  }
+
+  match table_path.split_last() {
+    Some((table_key, parent_path)) => locate(contents, parent_path, table_key),
+    None => None,
+  }
+}
+
+struct Validator<'a> {
+  contents: &'a str,
+  problems: Vec<Problem>,
+}
+
+impl<'a> Validator<'a> {
+  fn report(&mut self, table_path: &[&str], key: &str, message: String) {
+    self.problems.push(Problem {
+      position: locate(self.contents, table_path, key),
+      message,
+    });
+  }
+
+  fn check_keys(&mut self, table_path: &[&str], table: &toml::value::Table, known_keys: &[&str]) {
+    for key in table.keys() {
+      if known_keys.contains(&key.as_str()) {
+        continue;
+      }
+
+      let qualified_key = table_path
+        .iter()
+        .copied()
+        .chain(std::iter::once(key.as_str()))
+        .collect::<Vec<_>>()
+        .join(".");
+
+      let message = match suggest(key, known_keys) {
+        Some(suggestion) => format!(
+          "unknown key `{}`; did you mean `{}`?",
+          qualified_key, suggestion
+        ),
+        None => format!(
+          "unknown key `{}`; expected one of: {}",
+          qualified_key,
+          known_keys.join(", ")
+        ),
+      };
+
+      self.report(table_path, key, message);
+    }
+  }
+
+  /// Check a required string value, returning it if it is present.
+  fn check_string<'b>(&mut self, table: &'b toml::value::Table, key: &str) -> Option<&'b str> {
+    match table.get(key) {
+      Some(toml::Value::String(value)) => Some(value),
+      Some(_) => {
+        self.report(&[], key, format!("`{}` must be a string", key));
+
+        None
+      }
+      None => {
+        self.problems.push(Problem {
+          position: None,
+          message: format!("missing required key `{}`", key),
+        });
+
+        None
+      }
+    }
+  }
+
+  fn check_manifest(&mut self, manifest: &toml::value::Table) {
+    self.check_keys(&[], manifest, KEYS_MANIFEST);
+
+    if let Some(name) = self.check_string(manifest, "name") {
+      if !is_valid_name(name) {
+        self.report(
+          &[],
+          "name",
+          format!(
+            "package name `{}` is invalid; it must start with a letter or `_`, followed by letters, digits, `_` or `-`",
+            name
+          ),
+        );
+      }
+    }
+
+    if let Some(version) = self.check_string(manifest, "version") {
+      if let Err(error) = semver::Version::parse(version) {
+        self.report(
+          &[],
+          "version",
+          format!(
+            "package version `{}` is not a semantic version (such as `1.0.0`): {}",
+            version, error
+          ),
+        );
+      }
+    }
+
+    if let Some(ty) = self.check_string(manifest, "type") {
+      if !PACKAGE_TYPES.contains(&ty) {
+        let message = match suggest(ty, PACKAGE_TYPES) {
+          Some(suggestion) => format!(
+            "unknown package type `{}`; did you mean `{}`?",
+            ty, suggestion
+          ),
+          None => format!(
+            "unknown package type `{}`; expected one of: {}",
+            ty,
+            PACKAGE_TYPES.join(", ")
+          ),
+        };
+
+        self.report(&[], "type", message);
+      }
+    }
+
+    if let Some(toml::Value::Table(build)) = manifest.get("build") {
+      self.check_keys(&["build"], build, KEYS_BUILD);
+    }
+
+    if let Some(toml::Value::Table(targets)) = manifest.get("target") {
+      for (target_triple, target_config) in targets {
+        if let toml::Value::Table(target_config) = target_config {
+          let table_path = ["target", target_triple.as_str()];
+
+          self.check_keys(&table_path, target_config, KEYS_TARGET);
+
+          if let Some(toml::Value::Table(build)) = target_config.get("build") {
+            self.check_keys(
+              &["target", target_triple.as_str(), "build"],
+              build,
+              KEYS_BUILD,
+            );
+          }
+
+          if let Some(toml::Value::Table(dependencies)) = target_config.get("dependencies") {
+            for (dependency_name, dependency_spec) in dependencies {
+              if let toml::Value::Table(dependency_spec) = dependency_spec {
+                self.check_keys(
+                  &[
+                    "target",
+                    target_triple.as_str(),
+                    "dependencies",
+                    dependency_name.as_str(),
+                  ],
+                  dependency_spec,
+                  KEYS_DEPENDENCY,
+                );
+              }
+            }
+          }
+        }
+      }
+    }
+
+    if let Some(toml::Value::Table(hooks)) = manifest.get("hooks") {
+      self.check_keys(&["hooks"], hooks, KEYS_HOOKS);
+    }
+
+    if let Some(toml::Value::Table(workspace)) = manifest.get("workspace") {
+      self.check_keys(&["workspace"], workspace, KEYS_WORKSPACE);
+    }
+
+    if let Some(toml::Value::Table(profiles)) = manifest.get("profile") {
+      for (profile_name, profile) in profiles {
+        if let toml::Value::Table(profile) = profile {
+          self.check_keys(&["profile", profile_name.as_str()], profile, KEYS_PROFILE);
+        }
+      }
+    }
+
+    // The legacy format (a list of names) has no keys to check.
+    if let Some(toml::Value::Table(dependencies)) = manifest.get("dependencies") {
+      for (dependency_name, dependency_spec) in dependencies {
+        if let toml::Value::Table(dependency_spec) = dependency_spec {
+          self.check_keys(
+            &["dependencies", dependency_name.as_str()],
+            dependency_spec,
+            KEYS_DEPENDENCY,
+          );
+        }
+      }
+    }
+  }
+}
+
+/// Validate the contents of a manifest file before it is parsed, so that
+/// all of its problems are reported at once, with their positions and
+/// suggested fixes, instead of only the first one the parser runs into.
+pub fn validate(manifest_path: &std::path::Path, contents: &str) -> Result<(), String> {
+  let manifest = match toml::from_str::<toml::Value>(contents) {
+    Ok(toml::Value::Table(manifest)) => manifest,
+    Ok(_) => return Ok(()),
+    Err(error) => {
+      // The position is included in the error message itself.
+      return Err(format!(
+        "failed to parse package manifest file `{}`: {}",
+        manifest_path.display(),
+        error
+      ));
+    }
+  };
+
+  let mut validator = Validator {
+    contents,
+    problems: Vec::new(),
+  };
+
+  validator.check_manifest(&manifest);
+
+  if validator.problems.is_empty() {
+    return Ok(());
+  }
+
+  validator
+    .problems
+    .sort_by_key(|problem| problem.position.unwrap_or((0, 0)));
+
+  let problems = validator
+    .problems
+    .iter()
+    .map(|problem| match problem.position {
+      Some((line, column)) => format!(
+        "  {}:{}:{}: {}",
+        manifest_path.display(),
+        line,
+        column,
+        problem.message
+      ),
+      None => format!("  {}: {}", manifest_path.display(), problem.message),
+    })
+    .collect::<Vec<_>>();
+
+  Err(format!(
+    "invalid package manifest file ({} problem(s)):\n{}",
+    problems.len(),
+    problems.join("\n")
+  ))
+}
+
+/// Determine whether a metadata value is unset or blank, as scaffolded
+/// metadata is until it is filled in.
+pub fn is_missing(value: &Option<String>) -> bool {
+  value
+    .as_deref()
+    .map_or(true, |value| value.trim().is_empty())
+}
+
+/// Ensure that a package manifest has the metadata its users need to find
+/// and evaluate it, as required to publish it.
+pub fn validate_metadata(manifest: &package::Manifest) -> Result<(), String> {
+  let mut missing_keys = Vec::new();
+
+  if is_missing(&manifest.description) {
+    missing_keys.push("description");
+  }
+
+  if manifest
+    .authors
+    .iter()
+    .all(|author| author.trim().is_empty())
+  {
+    missing_keys.push("authors");
+  }
+
+  if is_missing(&manifest.license) {
+    missing_keys.push("license");
+  }
+
+  if is_missing(&manifest.repository) {
+    missing_keys.push("repository");
+  }
+
+  if manifest
+    .keywords
+    .iter()
+    .all(|keyword| keyword.trim().is_empty())
+  {
+    missing_keys.push("keywords");
+  }
+
+  if !missing_keys.is_empty() {
+    return Err(format!(
+      "the manifest is missing metadata required to publish it: {}",
+      missing_keys
+        .iter()
+        .map(|key| format!("`{}`", key))
+        .collect::<Vec<_>>()
+        .join(", ")
+    ));
+  }
+
+  Ok(())
+}
+
+/// Ensure that a package manifest, once parsed, still follows the rules
+/// checked by `validate` (such as after being edited programmatically).
+pub fn validate_manifest(manifest: &package::Manifest) -> Result<(), String> {
+  if !is_valid_name(&manifest.name) {
+    return Err(format!("package name `{}` is invalid", manifest.name));
+  }
+
+  if let Err(error) = semver::Version::parse(&manifest.version) {
+    return Err(format!(
+      "package version `{}` is not a semantic version: {}",
+      manifest.version, error
+    ));
+  }
+
+  Ok(())
+}
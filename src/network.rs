@@ -0,0 +1,71 @@
+use crate::{config, error::GripError, registry};
+
+/// How grip may access the network, as determined by the global
+/// `--offline` and `--frozen` flags. Commands ask it for permission
+/// (or for an HTTP client) instead of inspecting the flags themselves,
+/// so that they are refused consistently.
+pub struct NetworkContext {
+  offline: bool,
+  frozen: bool,
+}
+
+impl NetworkContext {
+  /// Create the context. Being frozen implies being offline.
+  pub fn new(offline: bool, frozen: bool) -> Self {
+    Self {
+      offline: offline || frozen,
+      frozen,
+    }
+  }
+
+  fn flag(&self) -> &'static str {
+    if self.frozen {
+      "--frozen"
+    } else {
+      "--offline"
+    }
+  }
+
+  /// Whether the network may not be accessed, so that commands can fall
+  /// back to local sources instead.
+  pub fn is_offline(&self) -> bool {
+    self.offline
+  }
+
+  /// Ensure that a command may access the network.
+  pub fn require_network(&self, command: &str) -> Result<(), GripError> {
+    if self.offline {
+      return Err(GripError::Network(format!(
+        "`grip {}` requires network access, but `{}` was given; run it again without `{}`",
+        command,
+        self.flag(),
+        self.flag()
+      )));
+    }
+
+    Ok(())
+  }
+
+  /// Ensure that a command may modify the lock.
+  pub fn require_mutable_lock(&self, command: &str) -> Result<(), GripError> {
+    if self.frozen {
+      return Err(GripError::Manifest(format!(
+        "`grip {}` modifies the lock, but `--frozen` was given; run it again without `--frozen`",
+        command
+      )));
+    }
+
+    Ok(())
+  }
+
+  /// Create an HTTP client for a command, if it may access the network.
+  pub fn create_client(
+    &self,
+    command: &str,
+    network_config: &config::NetworkConfig,
+  ) -> Result<reqwest::Client, GripError> {
+    self.require_network(command)?;
+
+    registry::create_client(network_config).map_err(GripError::Network)
+  }
+}
@@ -1,7 +1,14 @@
+use crate::error::GripError;
+
 pub const PATH_MANIFEST_FILE: &str = "grip.toml";
 pub const PATH_DEPENDENCIES: &str = "dependencies";
-const PATH_SOURCE_FILE_EXTENSION: &str = "ko";
-const PATH_PACKAGE_LOCK: &str = "grip.lock";
+const DEFAULT_SOURCE_FILE_EXTENSION: &str = "ko";
+pub const PATH_PACKAGE_LOCK: &str = "grip.lock";
+const PATH_OUT_DIR: &str = "out";
+const KEY_DEPENDENCIES: &str = "dependencies";
+/// The environment variable through which build scripts and hooks are
+/// told where to place generated source files.
+pub const ENV_OUT_DIR: &str = "GRIP_OUT_DIR";
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
 pub enum PackageType {
@@ -11,18 +18,562 @@ pub enum PackageType {
   Executable,
 }
 
+/// Any version of a dependency is acceptable.
+pub const ANY_VERSION: &str = "*";
+
+/// Where a dependency is resolved from, and which of its versions are
+/// acceptable.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum DependencySpec {
+  /// A version requirement, resolved from the registry under the
+  /// dependency's name (`name = "^1.2"`).
+  Version(String),
+  /// A git repository, at a branch, tag or commit (defaulting to the
+  /// repository's default branch).
+  Git {
+    git: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rev: Option<String>,
+  },
+  /// A package on the local filesystem, relative to the project
+  /// directory.
+  Path { path: String },
+  /// A version requirement, resolved from the registry under the given
+  /// path (defaulting to the dependency's name).
+  Registry {
+    version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    registry: Option<String>,
+  },
+}
+
+impl DependencySpec {
+  /// Retrieve the version requirement of a registry dependency.
+  pub fn version_requirement(&self) -> Option<&str> {
+    match self {
+      DependencySpec::Version(version) | DependencySpec::Registry { version, .. } => Some(version),
+      _ => None,
+    }
+  }
+
+  /// Retrieve the reference to check out for a git dependency, if one
+  /// is specified.
+  pub fn git_reference(&self) -> Option<&str> {
+    match self {
+      DependencySpec::Git {
+        branch, tag, rev, ..
+      } => rev.as_deref().or(tag.as_deref()).or(branch.as_deref()),
+      _ => None,
+    }
+  }
+
+  /// Retrieve the registry path of a registry dependency.
+  pub fn registry_path<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+    match self {
+      DependencySpec::Version(_) => Some(name),
+      DependencySpec::Registry { registry, .. } => Some(registry.as_deref().unwrap_or(name)),
+      _ => None,
+    }
+  }
+}
+
+pub type Dependencies = std::collections::BTreeMap<String, DependencySpec>;
+
+/// Accept both the dependencies table, and the legacy list of
+/// dependency names (which accept any version).
+fn deserialize_dependencies<'de, D: serde::Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Dependencies, D::Error> {
+  #[derive(serde::Deserialize)]
+  #[serde(untagged)]
+  enum DependenciesFormat {
+    Legacy(Vec<String>),
+    Table(Dependencies),
+  }
+
+  Ok(
+    match <DependenciesFormat as serde::Deserialize>::deserialize(deserializer)? {
+      DependenciesFormat::Legacy(names) => names
+        .into_iter()
+        .map(|name| (name, DependencySpec::Version(ANY_VERSION.to_string())))
+        .collect(),
+      DependenciesFormat::Table(dependencies) => dependencies,
+    },
+  )
+}
+
+/// Accept the optimization level both as an integer (`2`), and as a
+/// string (`"2"` or `"s"`).
+fn deserialize_opt_level<'de, D: serde::Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Option<crate::build::OptLevel>, D::Error> {
+  #[derive(serde::Deserialize)]
+  #[serde(untagged)]
+  enum OptLevelFormat {
+    Integer(u64),
+    Name(String),
+  }
+
+  let opt_level = match <OptLevelFormat as serde::Deserialize>::deserialize(deserializer)? {
+    OptLevelFormat::Integer(level) => level.to_string(),
+    OptLevelFormat::Name(name) => name,
+  };
+
+  match opt_level.parse::<crate::build::OptLevel>() {
+    Ok(opt_level) => Ok(Some(opt_level)),
+    Err(error) => Err(serde::de::Error::custom(error)),
+  }
+}
+
+pub const PROFILE_DEBUG: &str = "debug";
+pub const PROFILE_RELEASE: &str = "release";
+
+/// What happens when the program panics.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PanicStrategy {
+  Unwind,
+  /// Abort immediately, without unwinding. This produces smaller code.
+  Abort,
+}
+
+/// The settings of a build profile, as specified in the manifest
+/// (`[profile.<name>]`). Unset settings take the profile's defaults.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+pub struct ProfileConfig {
+  /// How much the code is optimized (`0` to `3`, `"s"` or `"z"`).
+  #[serde(
+    default,
+    rename = "opt-level",
+    deserialize_with = "deserialize_opt_level",
+    skip_serializing_if = "Option::is_none"
+  )]
+  pub opt_level: Option<crate::build::OptLevel>,
+  /// Whether to emit debug info.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub debug: Option<bool>,
+  /// Whether the lowered code is checked by the LLVM verifier.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub verify: Option<bool>,
+  /// Whether to strip symbols from executables.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub strip: Option<bool>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub panic: Option<PanicStrategy>,
+  /// Whether to produce position-independent executables.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub pie: Option<bool>,
+  /// The number of units the code is split into, so that they can be
+  /// compiled in parallel (at the expense of cross-unit optimizations).
+  #[serde(
+    default,
+    rename = "codegen-units",
+    skip_serializing_if = "Option::is_none"
+  )]
+  pub codegen_units: Option<usize>,
+}
+
+/// Settings of how the package is built (`[build]`).
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+pub struct BuildConfig {
+  /// System libraries to link executables against, such as `m`.
+  #[serde(default, rename = "link-libs", skip_serializing_if = "Vec::is_empty")]
+  pub link_libs: Vec<String>,
+  /// Additional directories to search for the system libraries,
+  /// relative to the package's directory.
+  #[serde(
+    default,
+    rename = "link-search-paths",
+    skip_serializing_if = "Vec::is_empty"
+  )]
+  pub link_search_paths: Vec<String>,
+}
+
+impl BuildConfig {
+  pub fn is_empty(&self) -> bool {
+    self.link_libs.is_empty() && self.link_search_paths.is_empty()
+  }
+}
+
+/// Accept both a single command, and a list of commands.
+fn deserialize_commands<'de, D: serde::Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+  #[derive(serde::Deserialize)]
+  #[serde(untagged)]
+  enum CommandsFormat {
+    Single(String),
+    List(Vec<String>),
+  }
+
+  Ok(
+    match <CommandsFormat as serde::Deserialize>::deserialize(deserializer)? {
+      CommandsFormat::Single(command) => vec![command],
+      CommandsFormat::List(commands) => commands,
+    },
+  )
+}
+
+/// Shell commands run at points of the build (`[hooks]`), such as to
+/// generate source files.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+pub struct HooksConfig {
+  /// Run before the package is built.
+  #[serde(
+    default,
+    rename = "pre-build",
+    deserialize_with = "deserialize_commands",
+    skip_serializing_if = "Vec::is_empty"
+  )]
+  pub pre_build: Vec<String>,
+  /// Run once the package was built successfully.
+  #[serde(
+    default,
+    rename = "post-build",
+    deserialize_with = "deserialize_commands",
+    skip_serializing_if = "Vec::is_empty"
+  )]
+  pub post_build: Vec<String>,
+  /// Run before the package's executable is run.
+  #[serde(
+    default,
+    rename = "pre-run",
+    deserialize_with = "deserialize_commands",
+    skip_serializing_if = "Vec::is_empty"
+  )]
+  pub pre_run: Vec<String>,
+}
+
+impl HooksConfig {
+  pub fn is_empty(&self) -> bool {
+    self.pre_build.is_empty() && self.post_build.is_empty() && self.pre_run.is_empty()
+  }
+}
+
+/// Dependencies and settings which only apply when building for a
+/// target triple (`[target.'<triple>']`).
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+pub struct TargetConfig {
+  #[serde(
+    default,
+    deserialize_with = "deserialize_dependencies",
+    skip_serializing_if = "Dependencies::is_empty"
+  )]
+  pub dependencies: Dependencies,
+  #[serde(default, skip_serializing_if = "BuildConfig::is_empty")]
+  pub build: BuildConfig,
+}
+
+/// Packages developed together in one repository (`[workspace]`). Its
+/// members share the `dependencies` directory and the lock file at the
+/// root of the workspace.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct WorkspaceConfig {
+  /// The directories of the member packages, relative to the root of
+  /// the workspace.
+  pub members: Vec<String>,
+}
+
+/// The parts of a manifest describing a workspace. Unlike `Manifest`, it
+/// can also be parsed from the manifest of a virtual workspace (one
+/// which isn't a package of its own).
+#[derive(serde::Deserialize)]
+struct WorkspaceManifest {
+  name: Option<String>,
+  workspace: Option<WorkspaceConfig>,
+}
+
+/// The settings a build uses, once the profile's defaults are applied.
+pub struct Profile {
+  pub name: String,
+  pub opt_level: crate::build::OptLevel,
+  pub debug: bool,
+  pub verify: bool,
+  pub strip: bool,
+  pub panic: PanicStrategy,
+  pub pie: bool,
+  pub codegen_units: usize,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub struct Manifest {
   pub name: String,
   #[serde(rename = "type")]
   pub ty: PackageType,
   pub version: String,
-  pub dependencies: Vec<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub description: Option<String>,
+  /// An SPDX license expression, such as `MIT OR Apache-2.0`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub license: Option<String>,
+  /// The authors, as `Name <email>`.
+  #[serde(default)]
+  pub authors: Vec<String>,
+  /// The URL of the package's repository.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub repository: Option<String>,
+  /// Terms the package can be searched by.
+  #[serde(default)]
+  pub keywords: Vec<String>,
+  /// The extensions of source files, such as for projects migrating from
+  /// older layouts. Defaults to `ko`.
+  #[serde(
+    default,
+    rename = "source-extensions",
+    skip_serializing_if = "Vec::is_empty"
+  )]
+  pub source_extensions: Vec<String>,
+  #[serde(default, deserialize_with = "deserialize_dependencies")]
+  pub dependencies: Dependencies,
+  /// Environment variables to set when running the package.
+  #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+  pub env: std::collections::BTreeMap<String, String>,
+  /// Features which can be enabled, and what each enables in turn:
+  /// other features, optional dependencies (`dep:<name>`), or features
+  /// of dependencies (`<name>/<feature>`).
+  #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+  pub features: std::collections::BTreeMap<String, Vec<String>>,
+  #[serde(default, skip_serializing_if = "BuildConfig::is_empty")]
+  pub build: BuildConfig,
+  /// Dependencies and settings specific to target triples.
+  #[serde(
+    default,
+    rename = "target",
+    skip_serializing_if = "std::collections::BTreeMap::is_empty"
+  )]
+  pub targets: std::collections::BTreeMap<String, TargetConfig>,
+  #[serde(default, skip_serializing_if = "HooksConfig::is_empty")]
+  pub hooks: HooksConfig,
+  #[serde(
+    default,
+    rename = "profile",
+    skip_serializing_if = "std::collections::BTreeMap::is_empty"
+  )]
+  pub profiles: std::collections::BTreeMap<String, ProfileConfig>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub workspace: Option<WorkspaceConfig>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+impl Manifest {
+  pub fn source_extensions(&self) -> Vec<&str> {
+    if self.source_extensions.is_empty() {
+      vec![DEFAULT_SOURCE_FILE_EXTENSION]
+    } else {
+      self.source_extensions.iter().map(String::as_str).collect()
+    }
+  }
+
+  /// Retrieve the dependencies used when building for the active target:
+  /// those of all targets, along with those specific to it (which take
+  /// precedence).
+  pub fn active_dependencies(&self) -> Dependencies {
+    let mut dependencies = self.dependencies.clone();
+
+    if let Some(target_config) = self.targets.get(&target_triple()) {
+      dependencies.extend(target_config.dependencies.clone());
+    }
+
+    dependencies
+  }
+
+  /// Retrieve the dependencies of every target. These are the ones
+  /// resolved, installed and locked, so that the lock holds on all
+  /// targets.
+  pub fn all_dependencies(&self) -> Dependencies {
+    let mut dependencies = self.dependencies.clone();
+
+    for target_config in self.targets.values() {
+      dependencies.extend(target_config.dependencies.clone());
+    }
+
+    dependencies
+  }
+
+  /// Determine whether a dependency is declared, for any target.
+  pub fn declares_dependency(&self, dependency_name: &str) -> bool {
+    self.dependencies.contains_key(dependency_name)
+      || self
+        .targets
+        .values()
+        .any(|target_config| target_config.dependencies.contains_key(dependency_name))
+  }
+
+  /// Retrieve the build settings which apply to the active target.
+  pub fn active_build_configs(&self) -> Vec<&BuildConfig> {
+    std::iter::once(&self.build)
+      .chain(
+        self
+          .targets
+          .get(&target_triple())
+          .map(|target_config| &target_config.build),
+      )
+      .collect()
+  }
+
+  /// Retrieve the settings of a build profile. Release builds are
+  /// stripped and abort on panic by default, and all executables are
+  /// position-independent unless disabled.
+  pub fn profile(&self, name: &str) -> Profile {
+    let profile_config = self.profiles.get(name).cloned().unwrap_or_default();
+    let is_release = name == PROFILE_RELEASE;
+
+    Profile {
+      name: name.to_string(),
+      opt_level: profile_config.opt_level.unwrap_or(if is_release {
+        crate::build::OptLevel::O2
+      } else {
+        crate::build::OptLevel::O0
+      }),
+      debug: profile_config.debug.unwrap_or(!is_release),
+      verify: profile_config.verify.unwrap_or(!is_release),
+      strip: profile_config.strip.unwrap_or(is_release),
+      panic: profile_config.panic.unwrap_or(if is_release {
+        PanicStrategy::Abort
+      } else {
+        PanicStrategy::Unwind
+      }),
+      pie: profile_config.pie.unwrap_or(true),
+      codegen_units: profile_config.codegen_units.unwrap_or(1).max(1),
+    }
+  }
+}
+
+/// Records the configuration a package was last built with.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct BuiltDependency {
+  pub name: String,
+  pub profile: String,
+  pub target: String,
+  /// Whether the package was checked by the LLVM verifier, and described
+  /// in debug info. Records predating these are considered stale.
+  #[serde(default)]
+  pub verify: bool,
+  #[serde(default)]
+  pub debug_info: bool,
+  pub fingerprint: String,
+}
+
+/// Accept both the build records, and the legacy list of built package
+/// names. The latter don't record how the packages were built, so they
+/// are dropped (and the packages considered stale).
+fn deserialize_built_dependencies<'de, D: serde::Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Vec<BuiltDependency>, D::Error> {
+  #[derive(serde::Deserialize)]
+  #[serde(untagged)]
+  enum BuiltDependencyFormat {
+    Legacy(String),
+    Record(BuiltDependency),
+  }
+
+  Ok(
+    <Vec<BuiltDependencyFormat> as serde::Deserialize>::deserialize(deserializer)?
+      .into_iter()
+      .filter_map(|built_dependency| match built_dependency {
+        BuiltDependencyFormat::Legacy(_) => None,
+        BuiltDependencyFormat::Record(built_dependency) => Some(built_dependency),
+      })
+      .collect(),
+  )
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq)]
+pub enum SourceKind {
+  #[serde(rename = "registry")]
+  Registry,
+  #[serde(rename = "git")]
+  Git,
+}
+
+impl Default for SourceKind {
+  fn default() -> Self {
+    SourceKind::Registry
+  }
+}
+
+/// Records where an installed package was fetched from.
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct LockedPackage {
+  pub name: String,
+  #[serde(default)]
+  pub kind: SourceKind,
+  /// The registry path of the package, or its repository URL.
+  pub source: String,
+  pub reference: String,
+  pub commit: Option<String>,
+  /// The version chosen by the resolver, for registry packages.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+  /// The checksum of the installed package's tree.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub checksum: Option<String>,
+  /// Where a copy of the package was vendored (relative to the project
+  /// directory), to be installed from when offline.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub vendored: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub struct PackageLock {
-  pub built_dependencies: Vec<String>,
+  #[serde(default, deserialize_with = "deserialize_built_dependencies")]
+  pub built_dependencies: Vec<BuiltDependency>,
+  #[serde(default)]
+  pub packages: Vec<LockedPackage>,
+}
+
+impl PackageLock {
+  /// Determine whether a package was last built with the given
+  /// configuration and fingerprint.
+  pub fn is_fresh(&self, built_dependency: &BuiltDependency) -> bool {
+    self.built_dependencies.contains(built_dependency)
+  }
+
+  /// Record a package build, replacing any previous record for the
+  /// same package, profile and target.
+  pub fn record(&mut self, built_dependency: BuiltDependency) {
+    self.built_dependencies.retain(|entry| {
+      entry.name != built_dependency.name
+        || entry.profile != built_dependency.profile
+        || entry.target != built_dependency.target
+    });
+
+    self.built_dependencies.push(built_dependency);
+  }
+
+  /// Record an installed package, replacing any previous record of it.
+  pub fn lock_package(&mut self, locked_package: LockedPackage) {
+    self
+      .packages
+      .retain(|entry| entry.name != locked_package.name);
+
+    self.packages.push(locked_package);
+  }
+
+  /// Remove the record of an installed package, along with its build
+  /// records.
+  pub fn unlock_package(&mut self, name: &str) {
+    self.packages.retain(|entry| entry.name != name);
+    self.invalidate(name);
+  }
+
+  pub fn find_package(&self, name: &str) -> Option<&LockedPackage> {
+    self.packages.iter().find(|entry| entry.name == name)
+  }
+
+  /// Remove all build records of a package, forcing it to be rebuilt.
+  /// Returns whether any records were removed.
+  pub fn invalidate(&mut self, name: &str) -> bool {
+    let previous_length = self.built_dependencies.len();
+
+    self.built_dependencies.retain(|entry| entry.name != name);
+
+    self.built_dependencies.len() != previous_length
+  }
 }
 
 // TODO: Make use of return value.
@@ -36,6 +587,17 @@ pub fn init_manifest(matches: &clap::ArgMatches<'_>) -> bool {
     return false;
   }
 
+  let name = matches.value_of(crate::ARG_INIT_NAME).unwrap();
+
+  if !crate::manifest_validation::is_valid_name(name) {
+    log::error!(
+      "package name `{}` is invalid; it must start with a letter or `_`, followed by letters, digits, `_` or `-`",
+      name
+    );
+
+    return false;
+  }
+
   if std::fs::create_dir(crate::PATH_SOURCES).is_err() {
     log::error!("failed to create sources directory");
 
@@ -43,10 +605,26 @@ pub fn init_manifest(matches: &clap::ArgMatches<'_>) -> bool {
   }
 
   let default_manifest = toml::ser::to_string_pretty(&Manifest {
-    name: String::from(matches.value_of(crate::ARG_INIT_NAME).unwrap()),
+    name: String::from(name),
     ty: PackageType::Executable,
     version: String::from("0.0.1"),
-    dependencies: Vec::new(),
+    // The metadata is scaffolded, to be filled in before publishing.
+    description: Some(String::new()),
+    license: Some(String::new()),
+    authors: crate::git::author().into_iter().collect(),
+    repository: Some(
+      crate::git::remote_url(std::path::Path::new("."), "origin").unwrap_or_default(),
+    ),
+    keywords: Vec::new(),
+    source_extensions: Vec::new(),
+    dependencies: Dependencies::new(),
+    env: std::collections::BTreeMap::new(),
+    features: std::collections::BTreeMap::new(),
+    build: BuildConfig::default(),
+    targets: std::collections::BTreeMap::new(),
+    hooks: HooksConfig::default(),
+    profiles: std::collections::BTreeMap::new(),
+    workspace: None,
   });
 
   if let Err(error) = default_manifest {
@@ -73,24 +651,25 @@ pub fn init_manifest(matches: &clap::ArgMatches<'_>) -> bool {
   true
 }
 
-pub fn get_or_init_package_lock() -> Result<PackageLock, String> {
+pub fn get_or_init_package_lock() -> Result<PackageLock, GripError> {
   let package_lock_path = std::path::Path::new(PATH_PACKAGE_LOCK);
 
   if !package_lock_path.exists() {
     let default_package_lock = toml::ser::to_string_pretty(&PackageLock {
       built_dependencies: Vec::new(),
+      packages: Vec::new(),
     });
 
     if let Err(error) = default_package_lock {
-      return Err(format!(
+      return Err(GripError::Other(format!(
         "failed to stringify default package lock: {}",
         error
-      ));
+      )));
     } else if let Err(error) = std::fs::write(PATH_PACKAGE_LOCK, default_package_lock.unwrap()) {
-      return Err(format!(
+      return Err(GripError::Io(format!(
         "failed to write default package manifest file: {}",
         error
-      ));
+      )));
     }
   }
 
@@ -99,54 +678,722 @@ pub fn get_or_init_package_lock() -> Result<PackageLock, String> {
   if let Ok(package_lock) = toml::from_str(&package_lock_contents) {
     Ok(package_lock)
   } else {
-    Err("failed to parse package lock".to_string())
+    Err(GripError::Manifest(
+      "failed to parse package lock".to_string(),
+    ))
+  }
+}
+
+pub fn write_package_lock(package_lock: &PackageLock) -> Result<(), GripError> {
+  let mut package_lock = package_lock.clone();
+
+  // Keep the output canonical regardless of the order in which entries
+  // were recorded, so that diffs of the lock stay minimal.
+  package_lock
+    .built_dependencies
+    .sort_by(|a, b| (&a.name, &a.profile, &a.target).cmp(&(&b.name, &b.profile, &b.target)));
+
+  package_lock.packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+  let package_lock_contents = match toml::ser::to_string_pretty(&package_lock) {
+    Ok(contents) => contents,
+    Err(error) => {
+      return Err(GripError::Other(format!(
+        "failed to stringify package lock: {}",
+        error
+      )))
+    }
+  };
+
+  if let Err(error) = std::fs::write(PATH_PACKAGE_LOCK, package_lock_contents) {
+    return Err(GripError::Io(format!(
+      "failed to write package lock: {}",
+      error
+    )));
+  }
+
+  Ok(())
+}
+
+/// Update a table of a manifest document to hold the given values, given
+/// those it held as far as the manifest is modelled. See `update_item`.
+fn update_table(
+  table: &mut dyn toml_edit::TableLike,
+  previous: &toml::value::Table,
+  values: &toml::value::Table,
+  value_items: &dyn toml_edit::TableLike,
+) {
+  for (key, value) in values {
+    // Unchanged values (including defaults which were never written)
+    // ... are left as they are.
+    if previous.get(key) == Some(value) {
+      continue;
+    }
+
+    let value_item = match value_items.get(key) {
+      Some(value_item) => value_item,
+      None => continue,
+    };
+
+    match table.get_mut(key) {
+      Some(item) => update_item(item, previous.get(key), value, value_item),
+      None => {
+        table.insert(key, value_item.clone());
+      }
+    }
+  }
+
+  for key in previous.keys() {
+    if !values.contains_key(key) {
+      table.remove(key);
+    }
+  }
+}
+
+/// Update an item of a manifest document to hold a new value, given the
+/// value it held as far as the manifest is modelled. Entries whose values
+/// are unchanged keep their comments and formatting, and entries which
+/// the manifest doesn't model (such as unknown keys) are left untouched.
+fn update_item(
+  item: &mut toml_edit::Item,
+  previous: Option<&toml::Value>,
+  value: &toml::Value,
+  value_item: &toml_edit::Item,
+) {
+  if let (toml::Value::Table(values), Some(toml::Value::Table(previous)), Some(value_items)) =
+    (value, previous, value_item.as_table_like())
+  {
+    if let Some(table) = item.as_table_like_mut() {
+      update_table(table, previous, values, value_items);
+
+      return;
+    }
+  }
+
+  let mut replacement = value_item.clone();
+
+  // Values (such as within inline tables) remain values, keeping their
+  // ... surrounding whitespace and comments.
+  if let Some(existing_value) = item.as_value() {
+    if let Ok(mut value) = replacement.clone().into_value() {
+      *value.decor_mut() = existing_value.decor().clone();
+      replacement = toml_edit::Item::Value(value);
+    }
+  }
+
+  *item = replacement;
+}
+
+/// Write a package manifest file. If the file exists, only the entries
+/// which changed are rewritten, so that comments, formatting and keys
+/// unknown to grip are preserved.
+pub fn write_manifest(path: &std::path::PathBuf, manifest: &Manifest) -> Result<(), GripError> {
+  // Serializing the manifest directly fails once dependencies mix version
+  // ... strings with tables, as values may not follow tables. A value
+  // ... places its tables last.
+  let values = match toml::Value::try_from(manifest) {
+    Ok(values) => values,
+    Err(error) => {
+      return Err(GripError::Other(format!(
+        "failed to convert the package manifest: {}",
+        error
+      )))
+    }
+  };
+
+  let manifest_contents = match toml::ser::to_string_pretty(&values) {
+    Ok(contents) => contents,
+    Err(error) => {
+      return Err(GripError::Other(format!(
+        "failed to stringify package manifest: {}",
+        error
+      )))
+    }
+  };
+
+  let manifest_contents = if path.exists() {
+    let mut document = match fetch_file_contents(path)?.parse::<toml_edit::Document>() {
+      Ok(document) => document,
+      Err(error) => {
+        return Err(GripError::Manifest(format!(
+          "failed to parse `{}`: {}",
+          path.display(),
+          error
+        )))
+      }
+    };
+
+    let value_document = match manifest_contents.parse::<toml_edit::Document>() {
+      Ok(value_document) => value_document,
+      Err(error) => {
+        return Err(GripError::Other(format!(
+          "failed to parse the stringified package manifest: {}",
+          error
+        )))
+      }
+    };
+
+    // The previous manifest, as far as it is modelled, tells apart the
+    // ... entries which were removed from those which are unknown.
+    let (previous, values) = match (toml::Value::try_from(fetch_manifest(path)?), &values) {
+      (Ok(toml::Value::Table(previous)), toml::Value::Table(values)) => (previous, values),
+      _ => {
+        return Err(GripError::Other(
+          "failed to convert the package manifest".to_string(),
+        ))
+      }
+    };
+
+    update_table(
+      document.as_table_mut(),
+      &previous,
+      values,
+      value_document.as_table(),
+    );
+
+    document.to_string()
+  } else {
+    manifest_contents
+  };
+
+  if let Err(error) = std::fs::write(path, manifest_contents) {
+    return Err(GripError::Io(format!(
+      "failed to write package manifest file: {}",
+      error
+    )));
+  }
+
+  Ok(())
+}
+
+/// Convert a dependency specification into its manifest entry.
+fn dependency_spec_item(dependency_spec: &DependencySpec) -> toml_edit::Item {
+  let mut inline_table = toml_edit::InlineTable::new();
+
+  match dependency_spec {
+    DependencySpec::Version(version) => return toml_edit::value(version.as_str()),
+    DependencySpec::Git {
+      git,
+      branch,
+      tag,
+      rev,
+    } => {
+      inline_table.insert("git", git.as_str().into());
+
+      for (key, value) in [("branch", branch), ("tag", tag), ("rev", rev)] {
+        if let Some(value) = value {
+          inline_table.insert(key, value.as_str().into());
+        }
+      }
+    }
+    DependencySpec::Path { path } => {
+      inline_table.insert("path", path.as_str().into());
+    }
+    DependencySpec::Registry { version, registry } => {
+      inline_table.insert("version", version.as_str().into());
+
+      if let Some(registry) = registry {
+        inline_table.insert("registry", registry.as_str().into());
+      }
+    }
+  }
+
+  toml_edit::value(inline_table)
+}
+
+/// Declare a dependency in a package manifest file, unless it is
+/// already declared. Returns whether the manifest was changed. The rest
+/// of the file (such as comments and key order) is left untouched.
+pub fn add_dependency(
+  path: &std::path::PathBuf,
+  dependency_name: &str,
+  dependency_spec: DependencySpec,
+) -> Result<bool, String> {
+  let mut manifest = fetch_manifest(path)?;
+
+  if manifest.dependencies.contains_key(dependency_name) {
+    return Ok(false);
+  }
+
+  let mut document = match fetch_file_contents(path)?.parse::<toml_edit::Document>() {
+    Ok(document) => document,
+    Err(error) => return Err(format!("failed to parse `{}`: {}", path.display(), error)),
+  };
+
+  // The legacy list of dependency names cannot hold a specification, so
+  // ... the whole manifest is rewritten (as a table) instead.
+  if document
+    .get(KEY_DEPENDENCIES)
+    .map_or(false, toml_edit::Item::is_array)
+  {
+    manifest
+      .dependencies
+      .insert(dependency_name.to_string(), dependency_spec);
+
+    write_manifest(path, &manifest)?;
+
+    return Ok(true);
+  }
+
+  let dependencies = document
+    .entry(KEY_DEPENDENCIES)
+    .or_insert(toml_edit::table());
+
+  match dependencies.as_table_like_mut() {
+    Some(dependencies) => {
+      dependencies.insert(dependency_name, dependency_spec_item(&dependency_spec));
+    }
+    None => {
+      return Err(format!(
+        "the `{}` key in `{}` is not a table",
+        KEY_DEPENDENCIES,
+        path.display()
+      ))
+    }
+  }
+
+  if let Err(error) = std::fs::write(path, document.to_string()) {
+    return Err(format!("failed to write `{}`: {}", path.display(), error));
+  }
+
+  Ok(true)
+}
+
+/// Remove a dependency from a package manifest file. Returns whether it
+/// was declared. The rest of the file (such as comments and key order)
+/// is left untouched.
+pub fn remove_dependency(path: &std::path::PathBuf, dependency_name: &str) -> Result<bool, String> {
+  let mut document = match fetch_file_contents(path)?.parse::<toml_edit::Document>() {
+    Ok(document) => document,
+    Err(error) => return Err(format!("failed to parse `{}`: {}", path.display(), error)),
+  };
+
+  let dependencies = match document.get_mut(KEY_DEPENDENCIES) {
+    Some(dependencies) => dependencies,
+    None => return Ok(false),
+  };
+
+  let is_removed = if let Some(dependency_names) = dependencies.as_array_mut() {
+    // The legacy list of dependency names.
+    match dependency_names
+      .iter()
+      .position(|name| name.as_str() == Some(dependency_name))
+    {
+      Some(index) => {
+        dependency_names.remove(index);
+
+        true
+      }
+      None => false,
+    }
+  } else if let Some(dependencies) = dependencies.as_table_like_mut() {
+    dependencies.remove(dependency_name).is_some()
+  } else {
+    false
+  };
+
+  if !is_removed {
+    return Ok(false);
   }
+
+  if let Err(error) = std::fs::write(path, document.to_string()) {
+    return Err(format!("failed to write `{}`: {}", path.display(), error));
+  }
+
+  Ok(true)
 }
 
-pub fn fetch_file_contents(file_path: &std::path::PathBuf) -> Result<String, String> {
+/// Normalize the formatting of a package manifest file: top-level
+/// keys are written in a fixed order, and dependencies are sorted
+/// (legacy dependency lists are converted into tables). Comments and
+/// unknown keys are preserved. Returns whether the file was changed.
+pub fn format_manifest(path: &std::path::PathBuf) -> Result<bool, String> {
+  let contents = fetch_file_contents(path)?;
+  let manifest = fetch_manifest(path)?;
+
+  let mut document = match contents.parse::<toml_edit::Document>() {
+    Ok(document) => document,
+    Err(error) => return Err(format!("failed to parse `{}`: {}", path.display(), error)),
+  };
+
+  if document
+    .get(KEY_DEPENDENCIES)
+    .map_or(false, toml_edit::Item::is_array)
+  {
+    let mut dependencies = toml_edit::Table::new();
+
+    for (dependency_name, dependency_spec) in &manifest.dependencies {
+      dependencies.insert(dependency_name, dependency_spec_item(dependency_spec));
+    }
+
+    document.insert(KEY_DEPENDENCIES, toml_edit::Item::Table(dependencies));
+  }
+
+  if let Some(dependencies) = document
+    .get_mut(KEY_DEPENDENCIES)
+    .and_then(toml_edit::Item::as_table_like_mut)
+  {
+    dependencies.sort_values();
+  }
+
+  // Tables are written after the other keys, in their own order.
+  let key_rank = |key: &toml_edit::Key| {
+    crate::manifest_validation::KEYS_MANIFEST
+      .iter()
+      .position(|known_key| *known_key == key.get())
+      .unwrap_or(crate::manifest_validation::KEYS_MANIFEST.len())
+  };
+
+  document
+    .as_table_mut()
+    .sort_values_by(|a, _, b, _| key_rank(a).cmp(&key_rank(b)));
+
+  let formatted_contents = document.to_string();
+
+  if formatted_contents == contents {
+    return Ok(false);
+  }
+
+  if let Err(error) = std::fs::write(path, formatted_contents) {
+    return Err(format!("failed to write package manifest file: {}", error));
+  }
+
+  Ok(true)
+}
+
+/// Compute a fingerprint of a package's manifest and source files,
+/// which changes whenever any of them change.
+pub fn fingerprint(build_unit: &BuildUnit) -> Result<String, String> {
+  let mut source_files = build_unit.source_files.clone();
+  let mut fingerprint_input = String::new();
+
+  // The order in which the directory was read is not guaranteed.
+  source_files.sort();
+
+  match serde_json::to_string(&build_unit.manifest) {
+    Ok(manifest_contents) => fingerprint_input.push_str(manifest_contents.as_str()),
+    Err(error) => return Err(format!("failed to stringify package manifest: {}", error)),
+  }
+
+  fingerprint_input.push_str(&format!("\nfeatures: {}", build_unit.features.join(",")));
+
+  for source_file in source_files {
+    fingerprint_input.push_str(&format!(
+      "\n{}:{}",
+      source_file.to_string_lossy(),
+      crate::checksum::sha256_file(&source_file)?
+    ));
+  }
+
+  Ok(crate::checksum::sha256_bytes(fingerprint_input.as_bytes()))
+}
+
+pub fn fetch_file_contents(file_path: &std::path::PathBuf) -> Result<String, GripError> {
   if !file_path.is_file() {
-    return Err(String::from(
+    return Err(GripError::Io(String::from(
       "path does not exist, is not a file, or is inaccessible",
-    ));
+    )));
   }
 
   let read_result = std::fs::read_to_string(file_path);
 
   if read_result.is_err() {
-    return Err(String::from(
+    return Err(GripError::Io(String::from(
       "path does not exist or its contents are not valid utf-8",
-    ));
+    )));
   }
 
   Ok(read_result.unwrap())
 }
 
-pub fn fetch_manifest(path: &std::path::PathBuf) -> Result<Manifest, String> {
+pub fn fetch_manifest(path: &std::path::PathBuf) -> Result<Manifest, GripError> {
   let manifest_read_result = std::fs::read_to_string(path);
 
   if let Err(error) = manifest_read_result {
-    return Err(format!("failed to read package manifest file: {}", error));
+    return Err(GripError::Manifest(format!(
+      "failed to read package manifest file: {}",
+      error
+    )));
   }
 
-  let manifest_result = toml::from_str::<Manifest>(manifest_read_result.unwrap().as_str());
+  let manifest_contents = manifest_read_result.unwrap();
+
+  crate::manifest_validation::validate(path, &manifest_contents).map_err(GripError::Manifest)?;
+
+  let manifest_result = toml::from_str::<Manifest>(manifest_contents.as_str());
 
   if let Err(error) = manifest_result {
-    return Err(format!("failed to parse package manifest file: {}", error));
+    return Err(GripError::Manifest(format!(
+      "failed to parse package manifest file: {}",
+      error
+    )));
   }
 
   Ok(manifest_result.unwrap())
 }
 
-pub fn fetch_dependency_manifest(name: &str) -> Result<Manifest, String> {
-  let dependency_manifest_path = std::path::PathBuf::from(PATH_DEPENDENCIES)
-    .join(name)
-    .join(PATH_MANIFEST_FILE);
+fn fetch_workspace_manifest(path: &std::path::PathBuf) -> Result<WorkspaceManifest, String> {
+  match toml::from_str::<WorkspaceManifest>(&fetch_file_contents(path)?) {
+    Ok(workspace_manifest) => Ok(workspace_manifest),
+    Err(error) => Err(format!("failed to parse package manifest file: {}", error)),
+  }
+}
+
+static PACKAGE_DIR: std::sync::OnceLock<std::path::PathBuf> = std::sync::OnceLock::new();
+
+/// Set the directory of the package being operated on, relative to the
+/// project directory. Only set for members of a workspace, since the
+/// project directory is then the root of the workspace.
+pub fn set_package_dir(package_dir: std::path::PathBuf) {
+  PACKAGE_DIR.set(package_dir).ok();
+}
+
+/// Retrieve the directory of the package being operated on, relative to
+/// the project directory (empty unless it's a member of a workspace).
+pub fn package_dir() -> &'static std::path::Path {
+  PACKAGE_DIR
+    .get()
+    .map_or(std::path::Path::new(""), |package_dir| {
+      package_dir.as_path()
+    })
+}
+
+static FEATURE_SELECTION: std::sync::OnceLock<crate::features::FeatureSelection> =
+  std::sync::OnceLock::new();
+
+/// Set the features requested for the package being operated on.
+pub fn set_feature_selection(feature_selection: crate::features::FeatureSelection) {
+  FEATURE_SELECTION.set(feature_selection).ok();
+}
+
+/// Retrieve the features requested for the package being operated on
+/// (none, besides the default ones, unless set).
+pub fn feature_selection() -> crate::features::FeatureSelection {
+  FEATURE_SELECTION.get().cloned().unwrap_or_default()
+}
+
+static TARGET_TRIPLE: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+/// Set the target triple being built for.
+pub fn set_target_triple(target_triple: String) {
+  TARGET_TRIPLE.set(target_triple).ok();
+}
+
+/// Retrieve the target triple being built for (the host, unless set).
+pub fn target_triple() -> String {
+  match TARGET_TRIPLE.get() {
+    Some(target_triple) => target_triple.clone(),
+    None => inkwell::targets::TargetMachine::get_default_triple()
+      .as_str()
+      .to_string_lossy()
+      .to_string(),
+  }
+}
+
+/// Determine whether the active target triple is that of the host.
+pub fn is_host_target() -> bool {
+  target_triple()
+    == inkwell::targets::TargetMachine::get_default_triple()
+      .as_str()
+      .to_string_lossy()
+}
+
+/// Retrieve the path of the manifest of the package being operated on.
+pub fn manifest_path() -> std::path::PathBuf {
+  package_dir().join(PATH_MANIFEST_FILE)
+}
+
+/// Find the root of the workspace a package directory belongs to, along
+/// with the package's directory relative to it (unless it's the root
+/// itself). Returns `None` if the package isn't part of a workspace.
+pub fn find_workspace_root(
+  project_dir: &std::path::Path,
+) -> Result<Option<(std::path::PathBuf, Option<std::path::PathBuf>)>, String> {
+  for directory in project_dir.ancestors() {
+    let manifest_path = directory.join(PATH_MANIFEST_FILE);
+
+    if !manifest_path.is_file() {
+      continue;
+    }
+
+    let workspace = match fetch_workspace_manifest(&manifest_path)?.workspace {
+      Some(workspace) => workspace,
+      None => continue,
+    };
+
+    let member_dir = project_dir.strip_prefix(directory).unwrap();
+
+    if member_dir.as_os_str().is_empty() {
+      return Ok(Some((directory.to_path_buf(), None)));
+    }
+
+    // Nested packages which aren't members are independent.
+    if workspace.members.iter().any(|member| {
+      std::path::Path::new(member)
+        .components()
+        .eq(member_dir.components())
+    }) {
+      return Ok(Some((
+        directory.to_path_buf(),
+        Some(member_dir.to_path_buf()),
+      )));
+    }
+  }
+
+  Ok(None)
+}
+
+/// Find a member of the workspace at the project directory, by its
+/// package name or directory.
+pub fn find_workspace_member(selection: &str) -> Result<std::path::PathBuf, String> {
+  let workspace = match fetch_workspace_manifest(&PATH_MANIFEST_FILE.into())?.workspace {
+    Some(workspace) => workspace,
+    None => {
+      return Err(format!(
+        "cannot select package `{}`, since the project isn't a workspace",
+        selection
+      ))
+    }
+  };
+
+  for member in &workspace.members {
+    let member_dir = std::path::PathBuf::from(member);
+
+    if member_dir
+      .components()
+      .eq(std::path::Path::new(selection).components())
+      || fetch_manifest(&member_dir.join(PATH_MANIFEST_FILE))
+        .map_or(false, |manifest| manifest.name == selection)
+    {
+      return Ok(member_dir);
+    }
+  }
+
+  Err(format!(
+    "package `{}` is not a member of the workspace",
+    selection
+  ))
+}
+
+/// Retrieve the member directories of the workspace at the project
+/// directory, if it's a virtual workspace (one which isn't a package of
+/// its own).
+pub fn virtual_workspace_members() -> Result<Option<Vec<std::path::PathBuf>>, String> {
+  let manifest_path = std::path::PathBuf::from(PATH_MANIFEST_FILE);
+
+  if !package_dir().as_os_str().is_empty() || !manifest_path.is_file() {
+    return Ok(None);
+  }
+
+  let workspace_manifest = fetch_workspace_manifest(&manifest_path)?;
+
+  Ok(match workspace_manifest.workspace {
+    Some(workspace) if workspace_manifest.name.is_none() => Some(
+      workspace
+        .members
+        .iter()
+        .map(std::path::PathBuf::from)
+        .collect(),
+    ),
+    _ => None,
+  })
+}
+
+/// Retrieve the manifests of the packages of the workspace at the project
+/// directory (its members, and the root unless it's a virtual workspace).
+/// Empty if the project isn't a workspace.
+pub fn workspace_manifests() -> Result<Vec<Manifest>, String> {
+  let manifest_path = std::path::PathBuf::from(PATH_MANIFEST_FILE);
+
+  if !manifest_path.is_file() {
+    return Ok(Vec::new());
+  }
 
-  fetch_manifest(&dependency_manifest_path)
+  let workspace_manifest = fetch_workspace_manifest(&manifest_path)?;
+
+  let workspace = match workspace_manifest.workspace {
+    Some(workspace) => workspace,
+    None => return Ok(Vec::new()),
+  };
+
+  let mut manifests = Vec::new();
+
+  if workspace_manifest.name.is_some() {
+    manifests.push(fetch_manifest(&manifest_path)?);
+  }
+
+  for member in &workspace.members {
+    manifests.push(fetch_manifest(
+      &std::path::Path::new(member).join(PATH_MANIFEST_FILE),
+    )?);
+  }
+
+  Ok(manifests)
+}
+
+/// Find the nearest directory containing a package manifest file,
+/// starting from the given directory and walking up its ancestors.
+pub fn find_manifest_dir(start_dir: &std::path::Path) -> Option<std::path::PathBuf> {
+  start_dir
+    .ancestors()
+    .find(|directory| directory.join(PATH_MANIFEST_FILE).is_file())
+    .map(|directory| directory.to_path_buf())
+}
+
+/// Retrieve the directory of a dependency, relative to the project
+/// directory. Paths are relative to the package being operated on; those
+/// declared by other dependencies are rebased onto it as their manifests
+/// are read (see `fetch_dependency_manifest`).
+pub fn dependency_dir(name: &str, dependency_spec: &DependencySpec) -> std::path::PathBuf {
+  match dependency_spec {
+    DependencySpec::Path { path } => package_dir().join(path),
+    _ => std::path::PathBuf::from(PATH_DEPENDENCIES).join(name),
+  }
+}
+
+/// Retrieve the manifest of a dependency. The paths of the dependencies
+/// it declares are made relative to the package being operated on,
+/// rather than to the dependency itself.
+pub fn fetch_dependency_manifest(
+  name: &str,
+  dependency_spec: &DependencySpec,
+) -> Result<Manifest, GripError> {
+  let dependency_manifest_path = dependency_dir(name, dependency_spec).join(PATH_MANIFEST_FILE);
+  let mut manifest = fetch_manifest(&dependency_manifest_path)?;
+
+  // The dependency's directory, relative to the package's directory.
+  let declaring_dir = match dependency_spec {
+    DependencySpec::Path { path } => std::path::PathBuf::from(path),
+    _ => package_dir()
+      .components()
+      .map(|_| std::path::Component::ParentDir)
+      .collect::<std::path::PathBuf>()
+      .join(PATH_DEPENDENCIES)
+      .join(name),
+  };
+
+  let nested_specs = manifest.dependencies.values_mut().chain(
+    manifest
+      .targets
+      .values_mut()
+      .flat_map(|target_config| target_config.dependencies.values_mut()),
+  );
+
+  for nested_spec in nested_specs {
+    if let DependencySpec::Path { path } = nested_spec {
+      *path = declaring_dir
+        .join(path.as_str())
+        .to_string_lossy()
+        .into_owned();
+    }
+  }
+
+  Ok(manifest)
 }
 
 pub fn read_sources_dir(
   sources_dir: &std::path::PathBuf,
+  source_extensions: &[&str],
 ) -> Result<Vec<std::path::PathBuf>, String> {
   let read_dir_result = std::fs::read_dir(sources_dir);
 
@@ -162,12 +1409,258 @@ pub fn read_sources_dir(
         return false;
       }
 
-      let extension = path.extension();
-
-      extension.is_some() && extension.unwrap() == PATH_SOURCE_FILE_EXTENSION
+      match path.extension() {
+        Some(extension) => source_extensions
+          .iter()
+          .any(|source_extension| extension == *source_extension),
+        None => false,
+      }
     })
     .collect::<Vec<std::path::PathBuf>>()
     .into();
 
   Ok(files)
 }
+
+/// Retrieve the directory where build-time generated source files of
+/// a package are placed. Any source files in it are compiled along
+/// with the package's own.
+pub fn out_dir(package_name: &str) -> std::path::PathBuf {
+  std::path::PathBuf::from(crate::DEFAULT_OUTPUT_DIR)
+    .join(PATH_OUT_DIR)
+    .join(package_name)
+}
+
+/// A package scheduled for building, along with its source files.
+#[derive(Clone)]
+pub struct BuildUnit {
+  pub manifest: Manifest,
+  /// The directory containing the package's manifest file, relative
+  /// to the project directory (empty for the initial package, unless
+  /// it's a member of a workspace).
+  pub root_dir: std::path::PathBuf,
+  pub source_files: Vec<std::path::PathBuf>,
+  /// The enabled features of the package.
+  pub features: Vec<String>,
+  /// The names of the packages it depends on.
+  pub dependencies: Vec<String>,
+}
+
+/// Verify that installed dependencies were not modified since they were
+/// installed, by comparing their checksums against those recorded in the
+/// lock. If `allow_dirty` is set, modifications only produce a warning.
+pub fn verify_dependency_checksums(
+  package_lock: &PackageLock,
+  allow_dirty: bool,
+) -> Result<(), String> {
+  let mut modified = Vec::new();
+
+  for locked_package in &package_lock.packages {
+    let package_path = std::path::Path::new(PATH_DEPENDENCIES).join(&locked_package.name);
+
+    // Missing packages are reported when verifying the directories.
+    let checksum = match &locked_package.checksum {
+      Some(checksum) if package_path.exists() => checksum,
+      _ => continue,
+    };
+
+    if &crate::checksum::sha256_dir(&package_path)? != checksum {
+      modified.push(locked_package.name.clone());
+    }
+  }
+
+  if modified.is_empty() {
+    return Ok(());
+  }
+
+  let message = format!(
+    "the following dependencies were modified since they were installed: {}",
+    modified.join(", ")
+  );
+
+  if allow_dirty {
+    log::warn!("{}", message);
+
+    return Ok(());
+  }
+
+  Err(format!(
+    "{}; reinstall them, or pass `--allow-dirty-deps` to build anyway",
+    message
+  ))
+}
+
+/// Cross-check the dependencies declared (directly or transitively) by
+/// the manifest, and those recorded in the lock, against the contents
+/// of the dependencies directory. Missing dependencies are an error,
+/// while extraneous ones only produce a warning.
+pub fn verify_dependency_dirs(
+  root_manifest: &Manifest,
+  package_lock: &PackageLock,
+) -> Result<(), String> {
+  let mut declared = std::collections::HashSet::new();
+  let mut missing = Vec::new();
+  let mut queue = root_manifest
+    .all_dependencies()
+    .into_iter()
+    .collect::<std::collections::VecDeque<_>>();
+
+  while let Some((dependency, dependency_spec)) = queue.pop_front() {
+    if !declared.insert(dependency.clone()) {
+      continue;
+    }
+
+    let manifest_path = dependency_dir(&dependency, &dependency_spec).join(PATH_MANIFEST_FILE);
+
+    if !manifest_path.exists() {
+      missing.push(dependency);
+
+      continue;
+    }
+
+    queue.extend(fetch_dependency_manifest(&dependency, &dependency_spec)?.all_dependencies());
+  }
+
+  for locked_package in &package_lock.packages {
+    if !std::path::Path::new(PATH_DEPENDENCIES)
+      .join(&locked_package.name)
+      .exists()
+    {
+      log::warn!(
+        "`{}` is recorded in the lock, but is not installed; try running `grip install`",
+        locked_package.name
+      );
+    }
+  }
+
+  if let Ok(read_dir) = std::fs::read_dir(PATH_DEPENDENCIES) {
+    for dir_entry in read_dir.flatten() {
+      let dir_name = dir_entry.file_name().to_string_lossy().to_string();
+
+      // Skip directories used internally, such as in-progress downloads.
+      if !dir_entry.path().is_dir() || dir_name.starts_with('.') {
+        continue;
+      }
+
+      if !declared.contains(&dir_name) {
+        log::warn!(
+          "`{}/{}` is not a declared dependency; try running `grip clean --deps`",
+          PATH_DEPENDENCIES,
+          dir_name
+        );
+      }
+    }
+  }
+
+  if !missing.is_empty() {
+    missing.sort();
+
+    return Err(format!(
+      "missing dependencies: {}; try running `grip install`",
+      missing
+        .iter()
+        .map(|dependency| format!("`{}`", dependency))
+        .collect::<Vec<_>>()
+        .join(", ")
+    ));
+  }
+
+  Ok(())
+}
+
+/// Collect the initial package and all of its dependencies (in
+/// build order) along with their source files.
+///
+/// The features of each package are computed beforehand, to a fixpoint:
+/// a package is visited again whenever a dependent requests more of its
+/// features, since those may enable more of its dependencies.
+pub fn collect_build_units(root_manifest: &Manifest) -> Result<Vec<BuildUnit>, String> {
+  crate::dependency::check_cycles(root_manifest)?;
+
+  // Packages are identified by their names, since dependents may refer
+  // to them under other keys.
+  let mut packages = vec![(root_manifest.clone(), package_dir().to_path_buf())];
+  let mut package_indices = std::collections::HashMap::new();
+  let mut feature_requests = crate::features::FeatureRequests::default();
+  let mut resolutions = std::collections::HashMap::new();
+  let mut queue = std::collections::VecDeque::from(vec![0]);
+
+  package_indices.insert(root_manifest.name.clone(), 0);
+
+  while let Some(index) = queue.pop_front() {
+    let package = packages[index].0.clone();
+
+    crate::features::validate(&package)?;
+
+    let resolved_features = if index == 0 {
+      crate::features::resolve_selected(&package)?
+    } else {
+      feature_requests.resolve(&package.name, &package)?
+    };
+
+    let mut dependencies = Vec::new();
+
+    for (dependency, dependency_spec) in &crate::features::used_dependencies(
+      &package,
+      package.active_dependencies(),
+      &resolved_features,
+    ) {
+      let dependency_manifest = fetch_dependency_manifest(dependency, dependency_spec)?;
+
+      let has_new_features = feature_requests.request(
+        &dependency_manifest.name,
+        &resolved_features
+          .dependency_features
+          .get(dependency)
+          .cloned()
+          .unwrap_or_default(),
+      );
+
+      dependencies.push(dependency_manifest.name.clone());
+
+      // Shared dependencies are only built once.
+      match package_indices.get(&dependency_manifest.name) {
+        Some(dependency_index) => {
+          if has_new_features && !queue.contains(dependency_index) {
+            queue.push_back(*dependency_index);
+          }
+        }
+        None => {
+          package_indices.insert(dependency_manifest.name.clone(), packages.len());
+          queue.push_back(packages.len());
+
+          packages.push((
+            dependency_manifest,
+            dependency_dir(dependency, dependency_spec),
+          ));
+        }
+      }
+    }
+
+    resolutions.insert(index, (resolved_features, dependencies));
+  }
+
+  let mut build_units = Vec::new();
+
+  for (index, (package, root_dir)) in packages.into_iter().enumerate() {
+    let (resolved_features, dependencies) = resolutions.remove(&index).unwrap_or_default();
+    let source_extensions = package.source_extensions();
+    let mut source_files =
+      read_sources_dir(&root_dir.join(crate::PATH_SOURCES), &source_extensions)?;
+    let out_dir = out_dir(&package.name);
+
+    if out_dir.exists() {
+      source_files.extend(read_sources_dir(&out_dir, &source_extensions)?);
+    }
+
+    build_units.push(BuildUnit {
+      manifest: package,
+      root_dir,
+      source_files,
+      features: resolved_features.enabled.into_iter().collect(),
+      dependencies,
+    });
+  }
+
+  Ok(build_units)
+}
@@ -0,0 +1,36 @@
+/// How to answer questions that require the user's confirmation.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PromptMode {
+  /// Ask the user on the terminal.
+  Interactive,
+  /// Accept automatically (`--yes`).
+  AssumeYes,
+  /// Never ask, failing instead (`--no-input`, for CI).
+  NoInput,
+}
+
+/// Ask the user a yes/no question, defaulting to no.
+pub fn confirm(question: &str, prompt_mode: PromptMode) -> Result<bool, String> {
+  match prompt_mode {
+    PromptMode::AssumeYes => Ok(true),
+    PromptMode::NoInput => Err(format!(
+      "{} (confirmation is required; pass `--yes` to accept)",
+      question
+    )),
+    PromptMode::Interactive => {
+      print!("{} [y/N] ", question);
+
+      if let Err(error) = std::io::Write::flush(&mut std::io::stdout()) {
+        return Err(format!("failed to write to stdout: {}", error));
+      }
+
+      let mut answer = String::new();
+
+      if let Err(error) = std::io::stdin().read_line(&mut answer) {
+        return Err(format!("failed to read from stdin: {}", error));
+      }
+
+      Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+    }
+  }
+}
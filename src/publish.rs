@@ -0,0 +1,81 @@
+use crate::{git, manifest_validation, package};
+
+/// The remote that release tags are pushed to.
+pub const REMOTE: &str = "origin";
+
+/// Ensure that a manifest describes a publishable package, returning the
+/// tag its release is published under. Dependents resolve versions from
+/// the tags, which must be semantic versions to be recognized.
+pub fn validate(manifest: &package::Manifest) -> Result<String, String> {
+  manifest_validation::validate_manifest(manifest)?;
+  manifest_validation::validate_metadata(manifest)?;
+
+  Ok(format!("v{}", manifest.version))
+}
+
+/// Ensure that the package's repository is ready to be tagged: nothing
+/// is left uncommitted, and the release wasn't already tagged.
+pub fn verify_repository(tag: &str) -> Result<(), String> {
+  let repository_path = std::path::Path::new(".");
+
+  if !git::is_clean(repository_path)? {
+    return Err(
+      "the working tree has uncommitted changes; commit or stash them before publishing"
+        .to_string(),
+    );
+  }
+
+  if git::tag_exists(repository_path, tag) {
+    return Err(format!(
+      "tag `{}` already exists; bump the package version before publishing",
+      tag
+    ));
+  }
+
+  Ok(())
+}
+
+/// Retrieve the URL of the package's repository, as an HTTPS URL if it
+/// is an SSH one (`git@host:path`), so that it can be installed from.
+pub fn source_url() -> Result<String, String> {
+  let remote_url = git::remote_url(std::path::Path::new("."), REMOTE)?;
+
+  match remote_url
+    .strip_prefix("git@")
+    .and_then(|remote_url| remote_url.split_once(':'))
+  {
+    Some((host, path)) => Ok(format!("https://{}/{}", host, path)),
+    None => Ok(remote_url),
+  }
+}
+
+/// Tag the checked out commit as a release, and push the tag. The tag
+/// is deleted again if it can't be pushed.
+pub fn tag_release(manifest: &package::Manifest, tag: &str) -> Result<(), String> {
+  let repository_path = std::path::Path::new(".");
+
+  git::create_tag(
+    repository_path,
+    tag,
+    &format!("Release {} {}", manifest.name, manifest.version),
+  )?;
+
+  if let Err(error) = git::push(repository_path, REMOTE, tag) {
+    if let Err(error) = git::delete_tag(repository_path, tag) {
+      log::warn!("{}", error);
+    }
+
+    return Err(error);
+  }
+
+  Ok(())
+}
+
+/// Delete a pushed release tag, such as when the release couldn't be
+/// registered.
+pub fn retract_release(tag: &str) -> Result<(), String> {
+  let repository_path = std::path::Path::new(".");
+
+  git::delete_remote_tag(repository_path, REMOTE, tag)?;
+  git::delete_tag(repository_path, tag)
+}
@@ -0,0 +1,1204 @@
+use crate::{cache, checksum, config, dependency, git, http_cache, package};
+
+/// A source from which packages can be resolved and downloaded.
+///
+/// Packages are identified by a registry-specific path (for example,
+/// `user/repository` for GitHub), and a reference, which is either
+/// a version or a branch name.
+#[async_trait::async_trait]
+pub trait Registry {
+  /// Retrieve the list of available versions of a package.
+  async fn resolve_versions(&self, package_path: &str) -> Result<Vec<String>, String>;
+
+  /// Fetch and parse the package manifest file of a package.
+  async fn fetch_manifest(
+    &self,
+    package_path: &str,
+    reference: &str,
+  ) -> Result<package::Manifest, String>;
+
+  /// Resolve a reference (such as a branch name) into the commit it
+  /// currently points to, if the registry supports it.
+  async fn resolve_commit(
+    &self,
+    _package_path: &str,
+    _reference: &str,
+  ) -> Result<Option<String>, String> {
+    Ok(None)
+  }
+
+  /// Retrieve the URL of a package's zip archive.
+  fn archive_url(&self, package_path: &str, reference: &str) -> String;
+
+  /// Retrieve the size of a package's zip archive, if the registry
+  /// reports it.
+  async fn archive_size(&self, package_path: &str, reference: &str) -> Result<Option<u64>, String>;
+
+  /// Begin the download of a package's zip archive, starting at the
+  /// given byte offset (to resume a partial download). Servers may
+  /// ignore the offset, and respond with the whole archive instead.
+  /// Unsuccessful responses are returned as they are, so that the caller
+  /// can tell which are worth retrying (see `is_transient_status`).
+  async fn download_archive(
+    &self,
+    package_path: &str,
+    reference: &str,
+    offset: u64,
+  ) -> Result<reqwest::Response, String>;
+
+  /// Find packages whose name or description matches a query, returning
+  /// at most `limit` of them.
+  async fn search(&self, _query: &str, _limit: usize) -> Result<Vec<PackageListing>, String> {
+    Err("the registry does not support searching".to_string())
+  }
+}
+
+/// The prefix of package paths hosted on GitLab (`gitlab:group/project`).
+pub const PREFIX_GITLAB: &str = "gitlab:";
+/// The prefix of package paths hosted on Bitbucket
+/// (`bitbucket:workspace/repository`).
+pub const PREFIX_BITBUCKET: &str = "bitbucket:";
+
+const PATH_INDEX: &str = "index";
+/// The index document, at the root of a git index.
+const PATH_INDEX_FILE: &str = "index.json";
+
+/// A package found by searching a registry.
+pub struct PackageListing {
+  /// The path to install the package by.
+  pub path: String,
+  pub description: Option<String>,
+}
+
+/// A tag, as listed by the GitHub and GitLab APIs.
+#[derive(serde::Deserialize)]
+struct Tag {
+  name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct GitHubRepository {
+  full_name: String,
+  description: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct GitHubCodeResult {
+  repository: GitHubRepository,
+}
+
+#[derive(serde::Deserialize)]
+struct GitHubCodeResults {
+  items: Vec<GitHubCodeResult>,
+}
+
+pub struct GitHubRegistry {
+  client: reqwest::Client,
+  http_cache: http_cache::HttpCache,
+  token: Option<String>,
+}
+
+impl GitHubRegistry {
+  pub fn new(
+    client: reqwest::Client,
+    http_cache: http_cache::HttpCache,
+    token: Option<String>,
+  ) -> Self {
+    Self {
+      client,
+      http_cache,
+      token,
+    }
+  }
+
+  /// Prepare a request to the GitHub API, authenticated if a token is
+  /// configured.
+  fn request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+    // The GitHub API rejects requests without a user agent.
+    let request = self
+      .client
+      .request(method, url)
+      .header(reqwest::header::USER_AGENT, "grip");
+
+    match &self.token {
+      Some(token) => request.bearer_auth(token),
+      None => request,
+    }
+  }
+}
+
+#[async_trait::async_trait]
+impl Registry for GitHubRegistry {
+  async fn resolve_versions(&self, package_path: &str) -> Result<Vec<String>, String> {
+    let url = format!("https://api.github.com/repos/{}/tags", package_path);
+
+    let tags_json = self
+      .http_cache
+      .get_text(
+        self.request(reqwest::Method::GET, &url),
+        &url,
+        "failed to fetch the package versions",
+      )
+      .await?;
+
+    let tags = serde_json::from_str::<Vec<Tag>>(tags_json.as_str());
+
+    match tags {
+      Ok(tags) => Ok(tags.into_iter().map(|tag| tag.name).collect()),
+      Err(error) => Err(format!("failed to parse the package versions: {}", error)),
+    }
+  }
+
+  async fn fetch_manifest(
+    &self,
+    package_path: &str,
+    reference: &str,
+  ) -> Result<package::Manifest, String> {
+    // Unlike `raw.githubusercontent.com`, the contents API accepts the
+    // ... token, so that private repositories can be read.
+    let url = format!(
+      "https://api.github.com/repos/{}/contents/{}?ref={}",
+      package_path,
+      package::PATH_MANIFEST_FILE,
+      reference
+    );
+
+    let manifest_contents = self
+      .http_cache
+      .get_text(
+        self
+          .request(reqwest::Method::GET, &url)
+          .header(reqwest::header::ACCEPT, "application/vnd.github.raw"),
+        &url,
+        "failed to fetch the package manifest file",
+      )
+      .await?;
+
+    parse_manifest(manifest_contents.as_str())
+  }
+
+  async fn resolve_commit(
+    &self,
+    package_path: &str,
+    reference: &str,
+  ) -> Result<Option<String>, String> {
+    let response = send(
+      self
+        .request(
+          reqwest::Method::GET,
+          &format!(
+            "https://api.github.com/repos/{}/commits/{}",
+            package_path, reference
+          ),
+        )
+        // Respond with the commit SHA only.
+        .header(reqwest::header::ACCEPT, "application/vnd.github.sha"),
+      "failed to resolve the package commit",
+    )
+    .await?;
+
+    Ok(Some(read_text(response).await?.trim().to_string()))
+  }
+
+  fn archive_url(&self, package_path: &str, reference: &str) -> String {
+    // Both branches and (version) tags may be referenced. Unlike
+    // ... `codeload.github.com`, the API accepts the token. The zipball
+    // ... (rather than the tarball) keeps the format extraction expects.
+    format!(
+      "https://api.github.com/repos/{}/zipball/{}",
+      package_path, reference
+    )
+  }
+
+  async fn archive_size(&self, package_path: &str, reference: &str) -> Result<Option<u64>, String> {
+    let response = send(
+      self.request(
+        reqwest::Method::HEAD,
+        &self.archive_url(package_path, reference),
+      ),
+      "failed to fetch the package size",
+    )
+    .await?;
+
+    Ok(response.content_length())
+  }
+
+  async fn download_archive(
+    &self,
+    package_path: &str,
+    reference: &str,
+    offset: u64,
+  ) -> Result<reqwest::Response, String> {
+    send_unchecked(
+      with_range(
+        self.request(
+          reqwest::Method::GET,
+          &self.archive_url(package_path, reference),
+        ),
+        offset,
+      ),
+      "failed to download the package",
+    )
+    .await
+  }
+
+  async fn search(&self, query: &str, limit: usize) -> Result<Vec<PackageListing>, String> {
+    // Code search finds repositories containing a manifest file which
+    // ... mentions the query, but is only available when authenticated.
+    if self.token.is_none() {
+      return Err(
+        "searching GitHub requires a token; set `GRIP_GITHUB_TOKEN`, or configure a registry index"
+          .to_string(),
+      );
+    }
+
+    let response = send(
+      self
+        .request(reqwest::Method::GET, "https://api.github.com/search/code")
+        .query(&[
+          (
+            "q",
+            format!("{} filename:{}", query, package::PATH_MANIFEST_FILE),
+          ),
+          ("per_page", "100".to_string()),
+        ]),
+      "failed to search the registry",
+    )
+    .await?;
+
+    let code_results = match serde_json::from_str::<GitHubCodeResults>(&read_text(response).await?)
+    {
+      Ok(code_results) => code_results,
+      Err(error) => return Err(format!("failed to parse the search results: {}", error)),
+    };
+
+    let mut package_listings = Vec::<PackageListing>::new();
+
+    // A repository matches once per manifest file it contains.
+    for code_result in code_results.items {
+      if package_listings.len() == limit {
+        break;
+      } else if package_listings
+        .iter()
+        .any(|package_listing| package_listing.path == code_result.repository.full_name)
+      {
+        continue;
+      }
+
+      package_listings.push(PackageListing {
+        path: code_result.repository.full_name,
+        description: code_result.repository.description,
+      });
+    }
+
+    Ok(package_listings)
+  }
+}
+
+/// Packages hosted on `gitlab.com`, identified by their project path
+/// (`group/project`, or with subgroups).
+// REVISE: Self-hosted instances could be supported through a configured URL.
+pub struct GitLabRegistry {
+  client: reqwest::Client,
+  http_cache: http_cache::HttpCache,
+}
+
+#[derive(serde::Deserialize)]
+struct GitLabCommit {
+  id: String,
+}
+
+impl GitLabRegistry {
+  pub fn new(client: reqwest::Client, http_cache: http_cache::HttpCache) -> Self {
+    Self { client, http_cache }
+  }
+
+  fn project_url(package_path: &str) -> String {
+    // The project path is a single (encoded) segment.
+    format!(
+      "https://gitlab.com/api/v4/projects/{}",
+      package_path.replace('/', "%2F")
+    )
+  }
+}
+
+#[async_trait::async_trait]
+impl Registry for GitLabRegistry {
+  async fn resolve_versions(&self, package_path: &str) -> Result<Vec<String>, String> {
+    let url = format!("{}/repository/tags", Self::project_url(package_path));
+
+    let tags_json = self
+      .http_cache
+      .get_text(
+        self.client.get(&url),
+        &url,
+        "failed to fetch the package versions",
+      )
+      .await?;
+
+    match serde_json::from_str::<Vec<Tag>>(tags_json.as_str()) {
+      Ok(tags) => Ok(tags.into_iter().map(|tag| tag.name).collect()),
+      Err(error) => Err(format!("failed to parse the package versions: {}", error)),
+    }
+  }
+
+  async fn fetch_manifest(
+    &self,
+    package_path: &str,
+    reference: &str,
+  ) -> Result<package::Manifest, String> {
+    let url = format!(
+      "{}/repository/files/{}/raw?ref={}",
+      Self::project_url(package_path),
+      package::PATH_MANIFEST_FILE,
+      reference
+    );
+
+    let manifest_contents = self
+      .http_cache
+      .get_text(
+        self.client.get(&url),
+        &url,
+        "failed to fetch the package manifest file",
+      )
+      .await?;
+
+    parse_manifest(manifest_contents.as_str())
+  }
+
+  async fn resolve_commit(
+    &self,
+    package_path: &str,
+    reference: &str,
+  ) -> Result<Option<String>, String> {
+    let response = send(
+      self.client.get(format!(
+        "{}/repository/commits/{}",
+        Self::project_url(package_path),
+        reference
+      )),
+      "failed to resolve the package commit",
+    )
+    .await?;
+
+    match serde_json::from_str::<GitLabCommit>(&read_text(response).await?) {
+      Ok(commit) => Ok(Some(commit.id)),
+      Err(error) => Err(format!("failed to parse the package commit: {}", error)),
+    }
+  }
+
+  fn archive_url(&self, package_path: &str, reference: &str) -> String {
+    format!(
+      "{}/repository/archive.zip?sha={}",
+      Self::project_url(package_path),
+      reference
+    )
+  }
+
+  async fn archive_size(&self, package_path: &str, reference: &str) -> Result<Option<u64>, String> {
+    let response = send(
+      self.client.head(self.archive_url(package_path, reference)),
+      "failed to fetch the package size",
+    )
+    .await?;
+
+    Ok(response.content_length())
+  }
+
+  async fn download_archive(
+    &self,
+    package_path: &str,
+    reference: &str,
+    offset: u64,
+  ) -> Result<reqwest::Response, String> {
+    send_unchecked(
+      with_range(
+        self.client.get(self.archive_url(package_path, reference)),
+        offset,
+      ),
+      "failed to download the package",
+    )
+    .await
+  }
+}
+
+/// Packages hosted on `bitbucket.org`, identified by their
+/// `workspace/repository` path.
+pub struct BitbucketRegistry {
+  client: reqwest::Client,
+  http_cache: http_cache::HttpCache,
+}
+
+#[derive(serde::Deserialize)]
+struct BitbucketTags {
+  values: Vec<Tag>,
+}
+
+#[derive(serde::Deserialize)]
+struct BitbucketCommit {
+  hash: String,
+}
+
+impl BitbucketRegistry {
+  pub fn new(client: reqwest::Client, http_cache: http_cache::HttpCache) -> Self {
+    Self { client, http_cache }
+  }
+
+  fn repository_url(package_path: &str) -> String {
+    format!(
+      "https://api.bitbucket.org/2.0/repositories/{}",
+      package_path
+    )
+  }
+}
+
+#[async_trait::async_trait]
+impl Registry for BitbucketRegistry {
+  async fn resolve_versions(&self, package_path: &str) -> Result<Vec<String>, String> {
+    // REVISE: Only the first page of tags is read.
+    let url = format!(
+      "{}/refs/tags?pagelen=100",
+      Self::repository_url(package_path)
+    );
+
+    let tags_json = self
+      .http_cache
+      .get_text(
+        self.client.get(&url),
+        &url,
+        "failed to fetch the package versions",
+      )
+      .await?;
+
+    match serde_json::from_str::<BitbucketTags>(tags_json.as_str()) {
+      Ok(tags) => Ok(tags.values.into_iter().map(|tag| tag.name).collect()),
+      Err(error) => Err(format!("failed to parse the package versions: {}", error)),
+    }
+  }
+
+  async fn fetch_manifest(
+    &self,
+    package_path: &str,
+    reference: &str,
+  ) -> Result<package::Manifest, String> {
+    let url = format!(
+      "{}/src/{}/{}",
+      Self::repository_url(package_path),
+      reference,
+      package::PATH_MANIFEST_FILE
+    );
+
+    let manifest_contents = self
+      .http_cache
+      .get_text(
+        self.client.get(&url),
+        &url,
+        "failed to fetch the package manifest file",
+      )
+      .await?;
+
+    parse_manifest(manifest_contents.as_str())
+  }
+
+  async fn resolve_commit(
+    &self,
+    package_path: &str,
+    reference: &str,
+  ) -> Result<Option<String>, String> {
+    let response = send(
+      self.client.get(format!(
+        "{}/commit/{}",
+        Self::repository_url(package_path),
+        reference
+      )),
+      "failed to resolve the package commit",
+    )
+    .await?;
+
+    match serde_json::from_str::<BitbucketCommit>(&read_text(response).await?) {
+      Ok(commit) => Ok(Some(commit.hash)),
+      Err(error) => Err(format!("failed to parse the package commit: {}", error)),
+    }
+  }
+
+  fn archive_url(&self, package_path: &str, reference: &str) -> String {
+    format!(
+      "https://bitbucket.org/{}/get/{}.zip",
+      package_path, reference
+    )
+  }
+
+  async fn archive_size(&self, package_path: &str, reference: &str) -> Result<Option<u64>, String> {
+    let response = send(
+      self.client.head(self.archive_url(package_path, reference)),
+      "failed to fetch the package size",
+    )
+    .await?;
+
+    Ok(response.content_length())
+  }
+
+  async fn download_archive(
+    &self,
+    package_path: &str,
+    reference: &str,
+    offset: u64,
+  ) -> Result<reqwest::Response, String> {
+    send_unchecked(
+      with_range(
+        self.client.get(self.archive_url(package_path, reference)),
+        offset,
+      ),
+      "failed to download the package",
+    )
+    .await
+  }
+}
+
+/// A package listed by the registry index.
+#[derive(serde::Deserialize, Clone)]
+pub struct IndexEntry {
+  /// The repository URL (such as `https://github.com/user/repository`)
+  /// or registry path of the package.
+  pub source: String,
+  pub description: Option<String>,
+  /// The published versions, as the tags they are fetched from (such as
+  /// `v1.2.3`). If empty, those of the source are used.
+  #[serde(default)]
+  pub versions: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct IndexContents {
+  packages: std::collections::BTreeMap<String, IndexEntry>,
+}
+
+/// The configured registry index, fetched (at most once) when it is
+/// first needed.
+pub struct RegistryIndex {
+  client: reqwest::Client,
+  http_cache: http_cache::HttpCache,
+  index_config: config::IndexConfig,
+  packages: tokio::sync::OnceCell<std::collections::BTreeMap<String, IndexEntry>>,
+}
+
+impl RegistryIndex {
+  pub fn new(
+    client: reqwest::Client,
+    http_cache: http_cache::HttpCache,
+    index_config: config::IndexConfig,
+  ) -> Self {
+    Self {
+      client,
+      http_cache,
+      index_config,
+      packages: tokio::sync::OnceCell::new(),
+    }
+  }
+
+  /// Read the git index, from its clone in the cache. A stale clone is
+  /// used if it can't be updated.
+  fn fetch_git(&self) -> Result<String, String> {
+    let clone_path = match sync_git_index(&self.index_config) {
+      Ok(clone_path) => clone_path,
+      Err(error) => match git_index_path(&self.index_config) {
+        Ok(clone_path) if clone_path.exists() => {
+          log::warn!("using the previously fetched registry index; {}", error);
+
+          clone_path
+        }
+        _ => return Err(error),
+      },
+    };
+
+    package::fetch_file_contents(&clone_path.join(PATH_INDEX_FILE))
+  }
+
+  async fn fetch(&self) -> Result<std::collections::BTreeMap<String, IndexEntry>, String> {
+    let contents = match self.index_config.kind {
+      // Responses are cached (and revalidated) like any other.
+      config::IndexKind::Http => {
+        self
+          .http_cache
+          .get_text(
+            self.client.get(&self.index_config.url),
+            &self.index_config.url,
+            "failed to fetch the registry index",
+          )
+          .await?
+      }
+      config::IndexKind::Git => self.fetch_git()?,
+    };
+
+    match serde_json::from_str::<IndexContents>(&contents) {
+      Ok(index_contents) => Ok(index_contents.packages),
+      Err(error) => Err(format!("failed to parse the registry index: {}", error)),
+    }
+  }
+
+  /// Retrieve all packages of the index, by name.
+  pub async fn packages(&self) -> Result<&std::collections::BTreeMap<String, IndexEntry>, String> {
+    self.packages.get_or_try_init(|| self.fetch()).await
+  }
+
+  /// Find a package of the index by name.
+  pub async fn lookup(&self, name: &str) -> Result<&IndexEntry, String> {
+    match self.packages().await?.get(name) {
+      Some(index_entry) => Ok(index_entry),
+      None => Err(format!(
+        "no package named `{}` exists in the registry index",
+        name
+      )),
+    }
+  }
+
+  /// Find the packages whose name or description contains the query,
+  /// ignoring case, in order of name.
+  pub async fn search(&self, query: &str) -> Result<Vec<(&String, &IndexEntry)>, String> {
+    let query = query.to_lowercase();
+
+    Ok(
+      self
+        .packages()
+        .await?
+        .iter()
+        .filter(|(name, index_entry)| {
+          name.to_lowercase().contains(&query)
+            || index_entry
+              .description
+              .as_ref()
+              .map_or(false, |description| {
+                description.to_lowercase().contains(&query)
+              })
+        })
+        .collect(),
+    )
+  }
+}
+
+fn git_index_path(index_config: &config::IndexConfig) -> Result<std::path::PathBuf, String> {
+  Ok(
+    cache::cache_dir()?
+      .join(PATH_INDEX)
+      .join(checksum::sha256_bytes(index_config.url.as_bytes())),
+  )
+}
+
+/// Clone a git index into the cache, or update its existing clone.
+/// Returns the path of the clone.
+fn sync_git_index(index_config: &config::IndexConfig) -> Result<std::path::PathBuf, String> {
+  let clone_path = git_index_path(index_config)?;
+
+  if clone_path.exists() {
+    git::pull(&clone_path)?;
+  } else {
+    git::clone(
+      &index_config.url,
+      index_config
+        .branch
+        .as_deref()
+        .unwrap_or(dependency::DEFAULT_REFERENCE),
+      &clone_path,
+      &git::CloneOptions {
+        // The clone is pushed to when registering releases.
+        full_history: true,
+        submodules: false,
+      },
+    )?;
+  }
+
+  Ok(clone_path)
+}
+
+/// Add a released version of a package to a git index, creating its
+/// entry if it is new, and commit the change without pushing it yet. The
+/// version is recorded as its tag (such as `v1.2.3`), which is the
+/// reference it is fetched from. Returns the path of the index's clone,
+/// to push (or discard) the registration from.
+pub fn prepare_registration(
+  index_config: &config::IndexConfig,
+  name: &str,
+  tag: &str,
+  source: &str,
+  description: Option<&str>,
+) -> Result<std::path::PathBuf, String> {
+  if index_config.kind != config::IndexKind::Git {
+    return Err("releases can only be registered in a `git` registry index".to_string());
+  }
+
+  let clone_path = sync_git_index(index_config)?;
+
+  if let Err(error) = register_release(&clone_path, name, tag, source, description) {
+    discard_registration(&clone_path);
+
+    return Err(error);
+  }
+
+  Ok(clone_path)
+}
+
+/// Publish a registration prepared in an index's clone.
+pub fn push_registration(clone_path: &std::path::Path) -> Result<(), String> {
+  git::push(clone_path, "origin", "HEAD")
+}
+
+/// Drop a registration prepared in an index's clone, so that the clone
+/// can still be updated.
+pub fn discard_registration(clone_path: &std::path::Path) {
+  if let Err(error) = git::reset_to_upstream(clone_path) {
+    log::warn!("{}", error);
+  }
+}
+
+fn register_release(
+  clone_path: &std::path::Path,
+  name: &str,
+  tag: &str,
+  source: &str,
+  description: Option<&str>,
+) -> Result<(), String> {
+  let index_path = clone_path.join(PATH_INDEX_FILE);
+
+  // Edited as plain JSON, so that unknown fields are kept.
+  let mut index_contents =
+    match serde_json::from_str::<serde_json::Value>(&package::fetch_file_contents(&index_path)?) {
+      Ok(index_contents) => index_contents,
+      Err(error) => return Err(format!("failed to parse the registry index: {}", error)),
+    };
+
+  let index_entry = match index_contents
+    .get_mut("packages")
+    .and_then(serde_json::Value::as_object_mut)
+  {
+    Some(packages) => packages
+      .entry(name.to_string())
+      .or_insert_with(|| serde_json::json!({ "source": source, "versions": [] })),
+    None => return Err("the registry index has no `packages`".to_string()),
+  };
+
+  if let Some(description) = description {
+    index_entry["description"] = serde_json::Value::from(description);
+  }
+
+  match index_entry
+    .get_mut("versions")
+    .and_then(serde_json::Value::as_array_mut)
+  {
+    Some(versions)
+      if versions
+        .iter()
+        .any(|existing| existing.as_str() == Some(tag)) =>
+    {
+      return Err(format!("`{}` of `{}` is already registered", tag, name))
+    }
+    Some(versions) => versions.push(serde_json::Value::from(tag)),
+    None => index_entry["versions"] = serde_json::json!([tag]),
+  }
+
+  let contents = match serde_json::to_string_pretty(&index_contents) {
+    Ok(contents) => contents,
+    Err(error) => return Err(format!("failed to serialize the registry index: {}", error)),
+  };
+
+  if let Err(error) = std::fs::write(&index_path, contents + "\n") {
+    return Err(format!("failed to write the registry index: {}", error));
+  }
+
+  git::commit_all(clone_path, &format!("Register {} {}", name, tag))
+}
+
+/// Determine whether a package path is a bare name, to be resolved
+/// through the registry index.
+pub fn is_package_name(package_path: &str) -> bool {
+  !package_path.is_empty() && !package_path.contains('/') && !package_path.contains(':')
+}
+
+/// Convert the source of an index entry into a package path. Repository
+/// URLs of the supported hosts are recognized, and anything other than
+/// a URL is taken as a path as-is.
+fn source_path(source: &str) -> Result<String, String> {
+  let trimmed_source = source.trim_end_matches('/').trim_end_matches(".git");
+
+  let hosts = [
+    ("github.com/", ""),
+    ("gitlab.com/", PREFIX_GITLAB),
+    ("bitbucket.org/", PREFIX_BITBUCKET),
+  ];
+
+  for &(host, prefix) in hosts.iter() {
+    let path = trimmed_source
+      .strip_prefix("https://")
+      .and_then(|url| url.strip_prefix(host));
+
+    if let Some(path) = path {
+      return Ok(format!("{}{}", prefix, path));
+    }
+  }
+
+  if source.contains("://") {
+    return Err(format!("unsupported package source `{}`", source));
+  }
+
+  Ok(source.to_string())
+}
+
+/// Routes packages whose path names their host (such as
+/// `gitlab:group/project`) to that host's registry, and all others to
+/// the configured registry. This lets a single manifest depend on
+/// packages from several hosts. If an index is configured, bare package
+/// names are first resolved through it.
+pub struct SourceRegistry {
+  default: Box<dyn Registry + Send + Sync>,
+  gitlab: GitLabRegistry,
+  bitbucket: BitbucketRegistry,
+  index: Option<RegistryIndex>,
+}
+
+impl SourceRegistry {
+  /// Resolve a bare package name into the path of its source.
+  async fn resolve_name(&self, package_path: &str) -> Result<String, String> {
+    match &self.index {
+      Some(index) if is_package_name(package_path) => {
+        source_path(&index.lookup(package_path).await?.source)
+      }
+      _ => Ok(package_path.to_string()),
+    }
+  }
+
+  /// Select the registry of a package, along with its path within it.
+  fn route<'a>(&'a self, package_path: &'a str) -> (&'a (dyn Registry + Send + Sync), &'a str) {
+    if let Some(package_path) = package_path.strip_prefix(PREFIX_GITLAB) {
+      (&self.gitlab, package_path)
+    } else if let Some(package_path) = package_path.strip_prefix(PREFIX_BITBUCKET) {
+      (&self.bitbucket, package_path)
+    } else {
+      (self.default.as_ref(), package_path)
+    }
+  }
+}
+
+#[async_trait::async_trait]
+impl Registry for SourceRegistry {
+  async fn resolve_versions(&self, package_path: &str) -> Result<Vec<String>, String> {
+    // The versions listed by the index take precedence.
+    if let Some(index) = &self.index {
+      if is_package_name(package_path) {
+        let index_entry = index.lookup(package_path).await?;
+
+        if !index_entry.versions.is_empty() {
+          return Ok(index_entry.versions.clone());
+        }
+      }
+    }
+
+    let source_path = self.resolve_name(package_path).await?;
+    let (registry, package_path) = self.route(&source_path);
+
+    registry.resolve_versions(package_path).await
+  }
+
+  async fn fetch_manifest(
+    &self,
+    package_path: &str,
+    reference: &str,
+  ) -> Result<package::Manifest, String> {
+    let source_path = self.resolve_name(package_path).await?;
+    let (registry, package_path) = self.route(&source_path);
+
+    registry.fetch_manifest(package_path, reference).await
+  }
+
+  async fn resolve_commit(
+    &self,
+    package_path: &str,
+    reference: &str,
+  ) -> Result<Option<String>, String> {
+    let source_path = self.resolve_name(package_path).await?;
+    let (registry, package_path) = self.route(&source_path);
+
+    registry.resolve_commit(package_path, reference).await
+  }
+
+  fn archive_url(&self, package_path: &str, reference: &str) -> String {
+    // The index has been fetched by the time a package is downloaded,
+    // ... but this can't fetch it otherwise.
+    let source_path = match self.index.as_ref().and_then(|index| index.packages.get()) {
+      Some(packages) if is_package_name(package_path) => packages
+        .get(package_path)
+        .and_then(|index_entry| source_path(&index_entry.source).ok())
+        .unwrap_or_else(|| package_path.to_string()),
+      _ => package_path.to_string(),
+    };
+
+    let (registry, package_path) = self.route(&source_path);
+
+    registry.archive_url(package_path, reference)
+  }
+
+  async fn archive_size(&self, package_path: &str, reference: &str) -> Result<Option<u64>, String> {
+    let source_path = self.resolve_name(package_path).await?;
+    let (registry, package_path) = self.route(&source_path);
+
+    registry.archive_size(package_path, reference).await
+  }
+
+  async fn download_archive(
+    &self,
+    package_path: &str,
+    reference: &str,
+    offset: u64,
+  ) -> Result<reqwest::Response, String> {
+    let source_path = self.resolve_name(package_path).await?;
+    let (registry, package_path) = self.route(&source_path);
+
+    registry
+      .download_archive(package_path, reference, offset)
+      .await
+  }
+
+  async fn search(&self, query: &str, limit: usize) -> Result<Vec<PackageListing>, String> {
+    match &self.index {
+      Some(index) => Ok(
+        index
+          .search(query)
+          .await?
+          .into_iter()
+          .take(limit)
+          .map(|(name, index_entry)| PackageListing {
+            path: name.clone(),
+            description: index_entry.description.clone(),
+          })
+          .collect(),
+      ),
+      None => self.default.search(query, limit).await,
+    }
+  }
+}
+
+/// A registry served over plain HTTP(S), such as an Artifactory
+/// instance or an S3 bucket, using the following layout:
+///
+/// * `<url>/<package>/versions` (newline-separated list of versions).
+/// * `<url>/<package>/<reference>/grip.toml`.
+/// * `<url>/<package>/<reference>/package.zip`.
+pub struct HttpRegistry {
+  client: reqwest::Client,
+  http_cache: http_cache::HttpCache,
+  url: String,
+}
+
+impl HttpRegistry {
+  pub fn new(client: reqwest::Client, http_cache: http_cache::HttpCache, url: String) -> Self {
+    Self {
+      client,
+      http_cache,
+      url: url.trim_end_matches('/').to_string(),
+    }
+  }
+}
+
+#[async_trait::async_trait]
+impl Registry for HttpRegistry {
+  async fn resolve_versions(&self, package_path: &str) -> Result<Vec<String>, String> {
+    let url = format!("{}/{}/versions", self.url, package_path);
+
+    Ok(
+      self
+        .http_cache
+        .get_text(
+          self.client.get(&url),
+          &url,
+          "failed to fetch the package versions",
+        )
+        .await?
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect(),
+    )
+  }
+
+  async fn fetch_manifest(
+    &self,
+    package_path: &str,
+    reference: &str,
+  ) -> Result<package::Manifest, String> {
+    let url = format!(
+      "{}/{}/{}/{}",
+      self.url,
+      package_path,
+      reference,
+      package::PATH_MANIFEST_FILE
+    );
+
+    let manifest_contents = self
+      .http_cache
+      .get_text(
+        self.client.get(&url),
+        &url,
+        "failed to fetch the package manifest file",
+      )
+      .await?;
+
+    parse_manifest(manifest_contents.as_str())
+  }
+
+  fn archive_url(&self, package_path: &str, reference: &str) -> String {
+    format!("{}/{}/{}/package.zip", self.url, package_path, reference)
+  }
+
+  async fn archive_size(&self, package_path: &str, reference: &str) -> Result<Option<u64>, String> {
+    let response = send(
+      self.client.head(self.archive_url(package_path, reference)),
+      "failed to fetch the package size",
+    )
+    .await?;
+
+    Ok(response.content_length())
+  }
+
+  async fn download_archive(
+    &self,
+    package_path: &str,
+    reference: &str,
+    offset: u64,
+  ) -> Result<reqwest::Response, String> {
+    send_unchecked(
+      with_range(
+        self.client.get(self.archive_url(package_path, reference)),
+        offset,
+      ),
+      "failed to download the package",
+    )
+    .await
+  }
+}
+
+/// Create an HTTP client honoring the configured timeouts.
+pub fn create_client(network_config: &config::NetworkConfig) -> Result<reqwest::Client, String> {
+  let client = reqwest::Client::builder()
+    .connect_timeout(std::time::Duration::from_secs(
+      network_config.connect_timeout,
+    ))
+    .build();
+
+  match client {
+    Ok(client) => Ok(client),
+    Err(error) => Err(format!("failed to create the HTTP client: {}", error)),
+  }
+}
+
+/// Create the registry specified by the user-wide configuration,
+/// defaulting to GitHub. Packages whose path is prefixed by their host
+/// (`gitlab:` or `bitbucket:`) are resolved from that host instead, and
+/// bare package names through the registry index, if one is configured.
+/// Cached responses are revalidated if `refresh` is set.
+pub fn from_config(
+  config: &config::Config,
+  client: reqwest::Client,
+  refresh: bool,
+) -> Result<Box<dyn Registry + Send + Sync>, String> {
+  let default: Box<dyn Registry + Send + Sync> = match &config.registry {
+    None => Box::new(GitHubRegistry::new(
+      client.clone(),
+      http_cache::HttpCache::new(refresh),
+      config::github_token()?,
+    )),
+    Some(registry_config) => match registry_config.kind {
+      config::RegistryKind::GitHub => Box::new(GitHubRegistry::new(
+        client.clone(),
+        http_cache::HttpCache::new(refresh),
+        config::github_token()?,
+      )),
+      config::RegistryKind::Http => match &registry_config.url {
+        Some(url) => Box::new(HttpRegistry::new(
+          client.clone(),
+          http_cache::HttpCache::new(refresh),
+          url.clone(),
+        )),
+        None => return Err("the `http` registry requires a `url` to be configured".to_string()),
+      },
+    },
+  };
+
+  Ok(Box::new(SourceRegistry {
+    default,
+    gitlab: GitLabRegistry::new(client.clone(), http_cache::HttpCache::new(refresh)),
+    bitbucket: BitbucketRegistry::new(client.clone(), http_cache::HttpCache::new(refresh)),
+    index: config.index.as_ref().map(|index_config| {
+      RegistryIndex::new(
+        client,
+        http_cache::HttpCache::new(refresh),
+        index_config.clone(),
+      )
+    }),
+  }))
+}
+
+async fn send(
+  request: reqwest::RequestBuilder,
+  context: &str,
+) -> Result<reqwest::Response, String> {
+  check_status(send_unchecked(request, context).await?, context)
+}
+
+/// Send a request, without turning unsuccessful responses into errors.
+async fn send_unchecked(
+  request: reqwest::RequestBuilder,
+  context: &str,
+) -> Result<reqwest::Response, String> {
+  match request.send().await {
+    Ok(response) => Ok(response),
+    Err(error) => Err(format!("{}: {}", context, error)),
+  }
+}
+
+/// Request only the bytes from an offset onwards, if it isn't zero.
+fn with_range(request: reqwest::RequestBuilder, offset: u64) -> reqwest::RequestBuilder {
+  if offset == 0 {
+    request
+  } else {
+    request.header(reqwest::header::RANGE, format!("bytes={}-", offset))
+  }
+}
+
+/// Turn unsuccessful responses into errors.
+pub fn check_status(
+  response: reqwest::Response,
+  context: &str,
+) -> Result<reqwest::Response, String> {
+  if response.status() == reqwest::StatusCode::NOT_FOUND {
+    return Err(format!("{}: not found on the registry", context));
+  } else if !response.status().is_success() {
+    return Err(format!("{}: HTTP error {}", context, response.status()));
+  }
+
+  Ok(response)
+}
+
+/// Determine whether an unsuccessful response may succeed if the request
+/// is retried (timeouts, rate limiting and server errors), as opposed to
+/// being rejected outright (such as a missing package).
+pub fn is_transient_status(status: reqwest::StatusCode) -> bool {
+  status == reqwest::StatusCode::REQUEST_TIMEOUT
+    || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+    || status.is_server_error()
+}
+
+pub async fn read_text(response: reqwest::Response) -> Result<String, String> {
+  match response.text().await {
+    Ok(text) => Ok(text),
+    Err(error) => Err(format!("failed to read the response: {}", error)),
+  }
+}
+
+fn parse_manifest(contents: &str) -> Result<package::Manifest, String> {
+  match toml::from_str::<package::Manifest>(contents) {
+    Ok(manifest) => Ok(manifest),
+    Err(error) => Err(format!(
+      "failed to parse the package manifest file: {}",
+      error
+    )),
+  }
+}
@@ -0,0 +1,154 @@
+use crate::{package, schema};
+
+const PATH_REPORT_INFO: &str = "report.json";
+
+/// Describes how a recorded build was invoked.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ReportInfo {
+  pub schema_version: u32,
+  pub grip_version: String,
+  pub target_triple: String,
+  pub build_args: Vec<String>,
+}
+
+/// Bundle everything needed to reproduce a build (sources, manifests,
+/// the lockfile, the build arguments and version information) into a
+/// gzipped tarball.
+pub fn record(
+  archive_path: &std::path::Path,
+  build_units: &[package::BuildUnit],
+  build_args: Vec<String>,
+) -> Result<(), String> {
+  let archive_file = match std::fs::File::create(archive_path) {
+    Ok(file) => file,
+    Err(error) => return Err(format!("failed to create the report archive: {}", error)),
+  };
+
+  let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+    archive_file,
+    flate2::Compression::default(),
+  ));
+
+  let mut paths = vec![
+    std::path::PathBuf::from(package::PATH_MANIFEST_FILE),
+    std::path::PathBuf::from(package::PATH_PACKAGE_LOCK),
+  ];
+
+  for build_unit in build_units {
+    paths.extend(build_unit.source_files.iter().cloned());
+
+    // The initial package's manifest is already included.
+    if let Some(sources_dir) = build_unit
+      .source_files
+      .first()
+      .and_then(|path| path.parent())
+    {
+      if let Some(package_dir) = sources_dir.parent() {
+        if package_dir.as_os_str() != "" {
+          paths.push(package_dir.join(package::PATH_MANIFEST_FILE));
+        }
+      }
+    }
+  }
+
+  for path in paths.iter().filter(|path| path.exists()) {
+    if let Err(error) = archive.append_path(path) {
+      return Err(format!(
+        "failed to add `{}` to the report archive: {}",
+        path.display(),
+        error
+      ));
+    }
+  }
+
+  let report_info = ReportInfo {
+    schema_version: schema::SCHEMA_VERSION,
+    grip_version: clap::crate_version!().to_string(),
+    target_triple: inkwell::targets::TargetMachine::get_default_triple()
+      .as_str()
+      .to_string_lossy()
+      .to_string(),
+    build_args,
+  };
+
+  let report_info_json = match serde_json::to_string_pretty(&report_info) {
+    Ok(json) => json,
+    Err(error) => return Err(format!("failed to serialize the report info: {}", error)),
+  };
+
+  let mut header = tar::Header::new_gnu();
+
+  header.set_size(report_info_json.len() as u64);
+  header.set_mode(0o644);
+  header.set_cksum();
+
+  if let Err(error) =
+    archive.append_data(&mut header, PATH_REPORT_INFO, report_info_json.as_bytes())
+  {
+    return Err(format!("failed to write the report archive: {}", error));
+  }
+
+  if let Err(error) = archive.into_inner().and_then(|encoder| encoder.finish()) {
+    return Err(format!("failed to write the report archive: {}", error));
+  }
+
+  Ok(())
+}
+
+/// Extract a report archive into the given directory, and reproduce
+/// the recorded build there using the current executable.
+pub fn replay(
+  archive_path: &std::path::Path,
+  sandbox_dir: &std::path::Path,
+) -> Result<std::process::ExitStatus, String> {
+  let archive_file = match std::fs::File::open(archive_path) {
+    Ok(file) => file,
+    Err(error) => return Err(format!("failed to open the report archive: {}", error)),
+  };
+
+  if let Err(error) = std::fs::create_dir_all(sandbox_dir) {
+    return Err(format!("failed to create the sandbox directory: {}", error));
+  }
+
+  let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive_file));
+
+  if let Err(error) = archive.unpack(sandbox_dir) {
+    return Err(format!("failed to extract the report archive: {}", error));
+  }
+
+  let report_info_contents = package::fetch_file_contents(&sandbox_dir.join(PATH_REPORT_INFO))?;
+
+  let report_info = match serde_json::from_str::<ReportInfo>(report_info_contents.as_str()) {
+    Ok(report_info) => report_info,
+    Err(error) => return Err(format!("failed to parse the report info: {}", error)),
+  };
+
+  if report_info.grip_version != clap::crate_version!() {
+    log::warn!(
+      "the report was recorded with grip v{}, but this is v{}",
+      report_info.grip_version,
+      clap::crate_version!()
+    );
+  }
+
+  let current_exe = match std::env::current_exe() {
+    Ok(path) => path,
+    Err(error) => return Err(format!("failed to locate the grip executable: {}", error)),
+  };
+
+  log::info!(
+    "replaying `grip {}` in `{}`",
+    report_info.build_args.join(" "),
+    sandbox_dir.display()
+  );
+
+  let status = std::process::Command::new(current_exe)
+    .args(&report_info.build_args)
+    .current_dir(sandbox_dir)
+    .status();
+
+  match status {
+    Ok(status) => Ok(status),
+    Err(error) => Err(format!("failed to replay the build: {}", error)),
+  }
+}
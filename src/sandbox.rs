@@ -0,0 +1,220 @@
+use crate::{checksum, package, tool, vendor};
+
+/// Set within the sandbox, so that the sandboxed command doesn't try to
+/// sandbox itself again.
+pub const ENV_SANDBOXED: &str = "GRIP_SANDBOXED";
+
+/// Environment variables kept within the sandbox.
+const SANDBOX_ENV_VARS: &[&str] = &["PATH", "SYSTEMROOT"];
+
+/// Re-run the current command (with the given arguments) in a hermetic
+/// environment: with no network access, a temporary home directory, only
+/// a minimal set of environment variables, and within a copy of the
+/// project holding only the sources and manifests of all packages. This
+/// catches builds which depend on undeclared inputs, without ever
+/// touching the project's own files. Once done, the build directory is
+/// copied back into the project.
+pub fn run(
+  toolchain: &tool::Toolchain,
+  build_units: &[package::BuildUnit],
+  args: Vec<std::ffi::OsString>,
+) -> Result<std::process::ExitStatus, String> {
+  let sandbox_dir = std::env::temp_dir().join(format!("grip-sandbox-{}", std::process::id()));
+  let result = run_in(toolchain, build_units, args, &sandbox_dir);
+
+  std::fs::remove_dir_all(&sandbox_dir).ok();
+
+  result
+}
+
+fn run_in(
+  toolchain: &tool::Toolchain,
+  build_units: &[package::BuildUnit],
+  args: Vec<std::ffi::OsString>,
+  sandbox_dir: &std::path::Path,
+) -> Result<std::process::ExitStatus, String> {
+  let home_dir = sandbox_dir.join("home");
+  let temp_dir = sandbox_dir.join("tmp");
+
+  for dir in &[&home_dir, &temp_dir] {
+    if let Err(error) = std::fs::create_dir_all(dir) {
+      return Err(format!("failed to create the sandbox directory: {}", error));
+    }
+  }
+
+  let inputs = collect_inputs(build_units)?;
+
+  // Path dependencies outside of the project (such as `../lib`) must
+  // ... also resolve within the copy, so it is nested deep enough.
+  let mut project_dir = sandbox_dir.join("tree");
+
+  for level in 0..inputs
+    .iter()
+    .map(|input| parent_depth(input))
+    .max()
+    .unwrap_or(0)
+  {
+    project_dir.push(level.to_string());
+  }
+
+  let mut input_checksums = Vec::new();
+
+  for input in &inputs {
+    let input_copy = project_dir.join(input);
+
+    if let Some(parent) = input_copy.parent() {
+      if let Err(error) = std::fs::create_dir_all(parent) {
+        return Err(format!("failed to create the sandbox directory: {}", error));
+      }
+    }
+
+    if let Err(error) = std::fs::copy(input, &input_copy) {
+      return Err(format!(
+        "failed to copy `{}` into the sandbox: {}",
+        input.display(),
+        error
+      ));
+    }
+
+    input_checksums.push((input, checksum::sha256_file(&input_copy)?));
+  }
+
+  let current_exe = match std::env::current_exe() {
+    Ok(path) => path,
+    Err(error) => return Err(format!("failed to locate the grip executable: {}", error)),
+  };
+
+  // REVISE: Network isolation is only supported on Linux, through
+  // ... unprivileged user namespaces.
+  let mut command = match isolate_network(toolchain)? {
+    Some(unshare) => {
+      let mut command = std::process::Command::new(unshare);
+
+      command
+        .args(&["--net", "--map-root-user"])
+        .arg(&current_exe);
+
+      command
+    }
+    None => std::process::Command::new(&current_exe),
+  };
+
+  command
+    .args(args)
+    .current_dir(project_dir.join(package::package_dir()))
+    .env_clear();
+
+  for name in SANDBOX_ENV_VARS {
+    if let Some(value) = std::env::var_os(name) {
+      command.env(name, value);
+    }
+  }
+
+  command
+    .env("HOME", &home_dir)
+    .env("TMPDIR", &temp_dir)
+    .env(ENV_SANDBOXED, "1");
+
+  let status = match command.status() {
+    Ok(status) => status,
+    Err(error) => return Err(format!("failed to run the sandboxed build: {}", error)),
+  };
+
+  // Permissions can't protect the inputs from a build running as the
+  // ... (mapped) root user, so modifications are detected instead.
+  let modified_inputs = input_checksums
+    .iter()
+    .filter(|(input, input_checksum)| {
+      checksum::sha256_file(&project_dir.join(input))
+        .ok()
+        .as_ref()
+        != Some(input_checksum)
+    })
+    .map(|(input, _)| format!("`{}`", input.display()))
+    .collect::<Vec<_>>();
+
+  if !modified_inputs.is_empty() {
+    return Err(format!(
+      "the sandboxed build modified its inputs: {}",
+      modified_inputs.join(", ")
+    ));
+  }
+
+  let output_dir = project_dir.join(crate::DEFAULT_OUTPUT_DIR);
+
+  if output_dir.exists() {
+    vendor::copy_dir(&output_dir, std::path::Path::new(crate::DEFAULT_OUTPUT_DIR))?;
+  }
+
+  Ok(status)
+}
+
+fn isolate_network(toolchain: &tool::Toolchain) -> Result<Option<std::path::PathBuf>, String> {
+  if !cfg!(target_os = "linux") {
+    log::warn!("network access cannot be restricted on this platform");
+
+    return Ok(None);
+  }
+
+  toolchain.find("unshare", "network isolation")
+}
+
+/// Count how many directories above the project directory a path
+/// reaches into.
+fn parent_depth(path: &std::path::Path) -> usize {
+  let mut level = 0isize;
+  let mut max_depth = 0;
+
+  for component in path.components() {
+    match component {
+      std::path::Component::ParentDir => level -= 1,
+      std::path::Component::Normal(_) => level += 1,
+      _ => {}
+    }
+
+    max_depth = std::cmp::max(max_depth, -level);
+  }
+
+  max_depth as usize
+}
+
+/// Collect the sources and manifests of all packages, along with the
+/// lock and the manifest of the workspace (if any), which are the
+/// declared inputs of a build. They are relative to the project
+/// directory.
+fn collect_inputs(build_units: &[package::BuildUnit]) -> Result<Vec<std::path::PathBuf>, String> {
+  let mut inputs = Vec::new();
+
+  for path in &[package::PATH_MANIFEST_FILE, package::PATH_PACKAGE_LOCK] {
+    if std::path::Path::new(path).is_file() {
+      inputs.push(std::path::PathBuf::from(path));
+    }
+  }
+
+  for build_unit in build_units {
+    let out_dir = package::out_dir(&build_unit.manifest.name);
+
+    inputs.push(build_unit.root_dir.join(package::PATH_MANIFEST_FILE));
+
+    // Generated sources are outputs of the build, not inputs.
+    inputs.extend(
+      build_unit
+        .source_files
+        .iter()
+        .filter(|source_file| !source_file.starts_with(&out_dir))
+        .cloned(),
+    );
+  }
+
+  if let Some(input) = inputs.iter().find(|input| input.is_absolute()) {
+    return Err(format!(
+      "`{}` is outside of the project; sandboxed builds require path dependencies to be relative",
+      input.display()
+    ));
+  }
+
+  inputs.sort();
+  inputs.dedup();
+
+  Ok(inputs)
+}
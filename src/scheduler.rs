@@ -0,0 +1,53 @@
+/// Bounds the amount of work running at the same time, so that a
+/// single `--jobs` setting applies to every subsystem (downloads,
+/// front-end work and code generation) rather than each spawning its
+/// own unbounded tasks.
+#[derive(Clone)]
+pub struct Scheduler {
+  jobs: usize,
+  semaphore: std::sync::Arc<tokio::sync::Semaphore>,
+}
+
+impl Scheduler {
+  pub fn new(jobs: usize) -> Self {
+    // A scheduler with no slots would never make progress.
+    let jobs = jobs.max(1);
+
+    Self {
+      jobs,
+      semaphore: std::sync::Arc::new(tokio::sync::Semaphore::new(jobs)),
+    }
+  }
+
+  /// The number of jobs to use when `--jobs` isn't specified.
+  pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |jobs| jobs.get())
+  }
+
+  pub fn jobs(&self) -> usize {
+    self.jobs
+  }
+
+  /// Run a future once a job slot becomes available.
+  pub async fn run<F: std::future::Future>(&self, future: F) -> F::Output {
+    // The semaphore is never closed, so acquiring cannot fail.
+    let _permit = self.semaphore.acquire().await.unwrap();
+
+    future.await
+  }
+
+  /// Run blocking work on a separate thread once a job slot becomes
+  /// available.
+  pub async fn run_blocking<F, T>(&self, work: F) -> Result<T, String>
+  where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+  {
+    let _permit = self.semaphore.acquire().await.unwrap();
+
+    match tokio::task::spawn_blocking(work).await {
+      Ok(result) => Ok(result),
+      Err(error) => Err(format!("a scheduled job failed: {}", error)),
+    }
+  }
+}
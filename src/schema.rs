@@ -0,0 +1,404 @@
+// NOTE: Any breaking change to the structures in this module (renaming
+// ... or removing fields, changing their types or meaning) must be
+// ... accompanied by a bump of `SCHEMA_VERSION`. Adding new fields is
+// ... considered non-breaking.
+
+/// The version of the machine-readable output format.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Wraps every machine-readable output, tagging it with the schema
+/// version and the kind of record it represents.
+#[derive(serde::Serialize)]
+struct Envelope<'a, T: serde::Serialize> {
+  schema_version: u32,
+  reason: &'a str,
+  #[serde(flatten)]
+  data: &'a T,
+}
+
+#[derive(serde::Serialize)]
+pub struct PackageMetadata {
+  pub name: String,
+  pub version: String,
+  #[serde(rename = "type")]
+  pub ty: String,
+  pub dependencies: Vec<String>,
+  /// The enabled features.
+  pub features: Vec<String>,
+  pub source_files: Vec<String>,
+  pub modules: Vec<ModuleMetadata>,
+}
+
+/// A module, and the source file defining it.
+#[derive(serde::Serialize)]
+pub struct ModuleMetadata {
+  pub name: String,
+  pub file: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct Metadata {
+  pub root: String,
+  pub packages: Vec<PackageMetadata>,
+}
+
+#[derive(serde::Serialize)]
+pub struct BuildPlanUnit {
+  pub package: String,
+  pub source_files: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct BuildPlan {
+  pub units: Vec<BuildPlanUnit>,
+  pub output: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct PhaseTiming {
+  pub phase: String,
+  pub duration_ms: u128,
+}
+
+#[derive(serde::Serialize)]
+pub struct Timings {
+  pub phases: Vec<PhaseTiming>,
+  pub total_ms: u128,
+}
+
+#[derive(serde::Serialize)]
+pub struct BuildStatistics {
+  pub files_parsed: usize,
+  pub tokens_lexed: usize,
+  pub nodes_lowered: usize,
+  pub functions_emitted: usize,
+  pub cache_hits: usize,
+  pub cache_misses: usize,
+  pub cache_hit_rate: f64,
+}
+
+#[derive(serde::Serialize)]
+pub struct BuildFinished {
+  pub success: bool,
+  pub statistics: BuildStatistics,
+}
+
+#[derive(serde::Serialize)]
+pub struct Artifact {
+  pub path: String,
+  pub sha256: String,
+}
+
+/// The outcome of a build, written for CI jobs to publish.
+#[derive(serde::Serialize)]
+pub struct BuildSummary {
+  pub package: String,
+  pub version: String,
+  pub success: bool,
+  pub duration_ms: u128,
+  pub phases: Vec<PhaseTiming>,
+  pub warnings: usize,
+  pub errors: usize,
+  pub artifacts: Vec<Artifact>,
+}
+
+/// Render a build summary as Markdown, such as for a comment on a pull
+/// request.
+pub fn summary_markdown(build_summary: &BuildSummary) -> String {
+  let mut markdown = format!(
+    "### Build of `{}` v{} {}\n\n\
+    | Duration | Warnings | Errors |\n\
+    | --- | --- | --- |\n\
+    | {:.2}s | {} | {} |\n",
+    build_summary.package,
+    build_summary.version,
+    if build_summary.success {
+      "succeeded"
+    } else {
+      "failed"
+    },
+    build_summary.duration_ms as f64 / 1000.0,
+    build_summary.warnings,
+    build_summary.errors
+  );
+
+  if !build_summary.phases.is_empty() {
+    markdown.push_str("\n| Phase | Duration |\n| --- | --- |\n");
+
+    for phase_timing in &build_summary.phases {
+      markdown.push_str(&format!(
+        "| {} | {}ms |\n",
+        phase_timing.phase, phase_timing.duration_ms
+      ));
+    }
+  }
+
+  if !build_summary.artifacts.is_empty() {
+    markdown.push_str("\n| Artifact | SHA-256 |\n| --- | --- |\n");
+
+    for artifact in &build_summary.artifacts {
+      markdown.push_str(&format!(
+        "| `{}` | `{}` |\n",
+        artifact.path, artifact.sha256
+      ));
+    }
+  }
+
+  markdown
+}
+
+/// Describes the artifacts produced by a build, and what they
+/// were built from.
+#[derive(serde::Serialize)]
+pub struct ArtifactIndex {
+  pub artifacts: Vec<Artifact>,
+  pub target_triple: String,
+  pub profile: String,
+  pub lockfile_sha256: String,
+}
+
+/// A position within a source file. Lines and columns are one-based,
+/// and columns are counted in characters.
+#[derive(serde::Serialize)]
+pub struct SourcePosition {
+  pub offset: usize,
+  pub line: usize,
+  pub column: usize,
+}
+
+#[derive(serde::Serialize)]
+pub struct DiagnosticSpan {
+  pub start: SourcePosition,
+  pub end: SourcePosition,
+}
+
+#[derive(serde::Serialize)]
+pub struct DiagnosticMessage {
+  pub package: Option<String>,
+  pub file: Option<String>,
+  /// The location the diagnostic refers to. Absent if unknown, or if
+  /// the file isn't known.
+  pub span: Option<DiagnosticSpan>,
+  pub severity: String,
+  pub message: String,
+  // TODO: Populate, once diagnostics carry notes.
+  pub notes: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct TokenInfo {
+  pub kind: String,
+  pub start: usize,
+  pub end: usize,
+  pub text: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct TokenList {
+  pub file: String,
+  pub tokens: Vec<TokenInfo>,
+}
+
+#[derive(serde::Serialize)]
+pub struct AstDump {
+  pub file: String,
+  // TODO: Use a structured representation, once AST nodes are serializable.
+  pub nodes: Vec<String>,
+}
+
+/// A top-level function, and the package and module which declared it.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct InterfaceFunction {
+  pub package: String,
+  pub module: String,
+  pub name: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct SearchResult {
+  pub name: String,
+  /// The newest version, if any could be determined.
+  pub version: Option<String>,
+  pub description: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SearchResults {
+  pub packages: Vec<SearchResult>,
+}
+
+pub fn to_json<T: serde::Serialize>(reason: &str, data: &T) -> Result<String, String> {
+  let envelope = Envelope {
+    schema_version: SCHEMA_VERSION,
+    reason,
+    data,
+  };
+
+  match serde_json::to_string(&envelope) {
+    Ok(json) => Ok(json),
+    Err(error) => Err(format!(
+      "failed to serialize `{}` output: {}",
+      reason, error
+    )),
+  }
+}
+
+pub fn package_metadata(build_unit: &crate::package::BuildUnit) -> PackageMetadata {
+  PackageMetadata {
+    name: build_unit.manifest.name.clone(),
+    version: build_unit.manifest.version.clone(),
+    ty: match build_unit.manifest.ty {
+      crate::package::PackageType::Library => "library".to_string(),
+      crate::package::PackageType::Executable => "executable".to_string(),
+    },
+    dependencies: build_unit
+      .manifest
+      .active_dependencies()
+      .into_keys()
+      .collect(),
+    features: build_unit.features.clone(),
+    source_files: build_unit
+      .source_files
+      .iter()
+      .map(|path| path.to_string_lossy().to_string())
+      .collect(),
+    // Modules are named after their files.
+    modules: build_unit
+      .source_files
+      .iter()
+      .map(|path| ModuleMetadata {
+        name: path
+          .file_stem()
+          .map(|file_stem| file_stem.to_string_lossy().to_string())
+          .unwrap_or_default(),
+        file: path.to_string_lossy().to_string(),
+      })
+      .collect(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Compare an output with its golden file, which holds the exact
+  /// output on a single line.
+  fn assert_golden(output: &str, golden: &str) {
+    assert_eq!(output, golden.trim_end());
+  }
+
+  fn artifact() -> Artifact {
+    Artifact {
+      path: "build/hello".to_string(),
+      sha256: "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".to_string(),
+    }
+  }
+
+  #[test]
+  fn build_plan() {
+    let build_plan = BuildPlan {
+      units: vec![
+        BuildPlanUnit {
+          package: "greeter".to_string(),
+          source_files: vec!["dependencies/greeter/src/greeter.ko".to_string()],
+        },
+        BuildPlanUnit {
+          package: "hello".to_string(),
+          source_files: vec!["src/main.ko".to_string(), "src/util.ko".to_string()],
+        },
+      ],
+      output: "build/hello".to_string(),
+    };
+
+    assert_golden(
+      &to_json("build-plan", &build_plan).unwrap(),
+      include_str!("../tests/golden/build-plan.json"),
+    );
+  }
+
+  #[test]
+  fn artifact_index() {
+    let artifact_index = ArtifactIndex {
+      artifacts: vec![artifact()],
+      target_triple: "x86_64-unknown-linux-gnu".to_string(),
+      profile: "debug".to_string(),
+      lockfile_sha256: "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        .to_string(),
+    };
+
+    assert_golden(
+      &to_json("artifact-index", &artifact_index).unwrap(),
+      include_str!("../tests/golden/artifact-index.json"),
+    );
+  }
+
+  #[test]
+  fn build_summary() {
+    let build_summary = BuildSummary {
+      package: "hello".to_string(),
+      version: "0.1.0".to_string(),
+      success: true,
+      duration_ms: 1250,
+      phases: vec![
+        PhaseTiming {
+          phase: "lex".to_string(),
+          duration_ms: 12,
+        },
+        PhaseTiming {
+          phase: "lower".to_string(),
+          duration_ms: 340,
+        },
+      ],
+      warnings: 1,
+      errors: 0,
+      artifacts: vec![artifact()],
+    };
+
+    assert_golden(
+      &to_json("build-summary", &build_summary).unwrap(),
+      include_str!("../tests/golden/build-summary.json"),
+    );
+  }
+
+  #[test]
+  fn tokens() {
+    let token_list = TokenList {
+      file: "src/main.ko".to_string(),
+      tokens: vec![
+        TokenInfo {
+          kind: "KeywordFn".to_string(),
+          start: 0,
+          end: 2,
+          text: "fn".to_string(),
+        },
+        TokenInfo {
+          kind: "Identifier(\"main\")".to_string(),
+          start: 3,
+          end: 7,
+          text: "main".to_string(),
+        },
+      ],
+    };
+
+    assert_golden(
+      &to_json("tokens", &token_list).unwrap(),
+      include_str!("../tests/golden/tokens.json"),
+    );
+  }
+
+  #[test]
+  fn ast() {
+    let ast_dump = AstDump {
+      file: "src/main.ko".to_string(),
+      nodes: vec![
+        "Function(main)".to_string(),
+        "Return(\"hello\")".to_string(),
+      ],
+    };
+
+    assert_golden(
+      &to_json("ast", &ast_dump).unwrap(),
+      include_str!("../tests/golden/ast.json"),
+    );
+  }
+}
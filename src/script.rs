@@ -0,0 +1,94 @@
+use crate::{cache, checksum};
+
+const PATH_SCRIPT_PREFIX: &str = "script-";
+const PATH_SCRIPT_EXTENSION: &str = "bc";
+
+/// Blank out a leading `#!` (shebang) line, so that the lexer doesn't
+/// trip on it. The line is replaced by spaces to preserve the offsets
+/// of the rest of the source code.
+pub fn strip_shebang(source_code: &str) -> String {
+  if !source_code.starts_with("#!") {
+    return source_code.to_string();
+  }
+
+  let line_end = source_code.find('\n').unwrap_or(source_code.len());
+
+  format!("{}{}", " ".repeat(line_end), &source_code[line_end..])
+}
+
+/// Wrap an expression in an implicit `main` function which returns its
+/// value.
+// REVIEW: Assumes the expression evaluates to an `i32`, since that is
+// ... what `main` returns.
+pub fn wrap_expression(expression: &str) -> String {
+  format!(
+    "func main(): i32 {{\n  return {};\n}}\n",
+    expression.trim().trim_end_matches(';')
+  )
+}
+
+/// Retrieve the path under which the compiled module of a script is
+/// cached. The key includes grip's version, since the output of the
+/// compiler may change between versions.
+pub fn cache_path(source_code: &str) -> Result<std::path::PathBuf, String> {
+  let key =
+    checksum::sha256_bytes(format!("{}\n{}", clap::crate_version!(), source_code).as_bytes());
+  let mut cache_path = cache::cache_dir()?.join(format!("{}{}", PATH_SCRIPT_PREFIX, key));
+
+  cache_path.set_extension(PATH_SCRIPT_EXTENSION);
+
+  Ok(cache_path)
+}
+
+/// Store the compiled module of a script in the cache.
+pub fn store(
+  llvm_module: &inkwell::module::Module<'_>,
+  cache_path: &std::path::Path,
+) -> Result<(), String> {
+  if let Some(cache_dir) = cache_path.parent() {
+    if let Err(error) = std::fs::create_dir_all(cache_dir) {
+      return Err(format!("failed to create the cache directory: {}", error));
+    }
+  }
+
+  if !llvm_module.write_bitcode_to_path(cache_path) {
+    return Err("failed to store the compiled script in the cache".to_string());
+  }
+
+  Ok(())
+}
+
+/// Load a previously compiled script from the cache.
+pub fn load<'ctx>(
+  llvm_context: &'ctx inkwell::context::Context,
+  cache_path: &std::path::Path,
+) -> Result<inkwell::module::Module<'ctx>, String> {
+  match inkwell::module::Module::parse_bitcode_from_path(cache_path, llvm_context) {
+    Ok(llvm_module) => Ok(llvm_module),
+    Err(error) => Err(format!("failed to load the cached script: {}", error)),
+  }
+}
+
+/// JIT-compile a module and run its `main` function, returning its
+/// exit code.
+pub fn run_jit(llvm_module: &inkwell::module::Module<'_>) -> Result<i32, String> {
+  inkwell::targets::Target::initialize_native(&inkwell::targets::InitializationConfig::default())?;
+
+  let execution_engine =
+    match llvm_module.create_jit_execution_engine(inkwell::OptimizationLevel::None) {
+      Ok(execution_engine) => execution_engine,
+      Err(error) => return Err(format!("failed to create the JIT: {}", error)),
+    };
+
+  // SAFETY: The driver verified that `main` takes no parameters.
+  // REVIEW: Assumes `main` returns an `i32`.
+  unsafe {
+    let main_function = execution_engine
+      .get_function::<unsafe extern "C" fn() -> i32>(gecko::llvm_lowering::MAIN_FUNCTION_NAME);
+
+    match main_function {
+      Ok(main_function) => Ok(main_function.call()),
+      Err(error) => Err(format!("failed to find the main function: {}", error)),
+    }
+  }
+}
@@ -0,0 +1,120 @@
+use crate::package;
+
+const PATH_GRIPIGNORE: &str = ".gripignore";
+
+pub struct SearchMatch {
+  /// The module-qualified path of the file (`package::module`).
+  pub module_path: String,
+  pub line: usize,
+  pub column: usize,
+  pub text: String,
+}
+
+/// Read the patterns of the `.gripignore` file, if any. Each non-empty
+/// line which isn't a comment (`#`) is a pattern.
+pub fn read_ignore_patterns() -> Result<Vec<String>, String> {
+  let gripignore_path = std::path::PathBuf::from(PATH_GRIPIGNORE);
+
+  if !gripignore_path.exists() {
+    return Ok(Vec::new());
+  }
+
+  Ok(
+    package::fetch_file_contents(&gripignore_path)?
+      .lines()
+      .map(|line| line.trim())
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .map(|line| line.to_string())
+      .collect(),
+  )
+}
+
+/// Determine whether a path (relative to the project directory) is
+/// ignored. Patterns support `*` (any sequence of characters) and `?`
+/// (any single character), and a pattern ending with `/` ignores
+/// everything under that directory.
+pub fn is_ignored(path: &std::path::Path, ignore_patterns: &[String]) -> bool {
+  // Patterns always use forward slashes.
+  let path = path.to_string_lossy().replace('\\', "/");
+
+  ignore_patterns.iter().any(|pattern| {
+    if let Some(directory) = pattern.strip_suffix('/') {
+      path.starts_with(&format!("{}/", directory))
+    } else {
+      matches_wildcard(pattern.as_bytes(), path.as_bytes())
+    }
+  })
+}
+
+fn matches_wildcard(pattern: &[u8], text: &[u8]) -> bool {
+  match (pattern.first(), text.first()) {
+    (None, None) => true,
+    (Some(b'*'), _) => {
+      matches_wildcard(&pattern[1..], text)
+        || (!text.is_empty() && matches_wildcard(pattern, &text[1..]))
+    }
+    (Some(b'?'), Some(_)) => matches_wildcard(&pattern[1..], &text[1..]),
+    (Some(pattern_char), Some(text_char)) if pattern_char == text_char => {
+      matches_wildcard(&pattern[1..], &text[1..])
+    }
+    _ => false,
+  }
+}
+
+/// Search the source files of the given build units for a literal
+/// pattern.
+pub fn search(
+  build_units: &[package::BuildUnit],
+  pattern: &str,
+  ignore_case: bool,
+  ignore_patterns: &[String],
+) -> Result<Vec<SearchMatch>, String> {
+  let pattern = if ignore_case {
+    pattern.to_lowercase()
+  } else {
+    pattern.to_string()
+  };
+
+  let mut search_matches = Vec::new();
+
+  for build_unit in build_units {
+    let mut source_files = build_unit.source_files.clone();
+
+    // The order in which the directory was read is not guaranteed.
+    source_files.sort();
+
+    for source_file in source_files {
+      if is_ignored(&source_file, ignore_patterns) {
+        continue;
+      }
+
+      let module_path = format!(
+        "{}::{}",
+        build_unit.manifest.name,
+        source_file.file_stem().unwrap().to_string_lossy()
+      );
+
+      for (line_index, line) in package::fetch_file_contents(&source_file)?
+        .lines()
+        .enumerate()
+      {
+        let haystack = if ignore_case {
+          line.to_lowercase()
+        } else {
+          line.to_string()
+        };
+
+        if let Some(byte_index) = haystack.find(&pattern) {
+          search_matches.push(SearchMatch {
+            module_path: module_path.clone(),
+            line: line_index + 1,
+            column: haystack[..byte_index].chars().count() + 1,
+            text: line.to_string(),
+          });
+        }
+      }
+    }
+  }
+
+  Ok(search_matches)
+}
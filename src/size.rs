@@ -0,0 +1,132 @@
+use crate::tool;
+
+/// Symbols which don't belong to any package (such as the C runtime's)
+/// are reported under this name.
+const UNKNOWN_PACKAGE: &str = "(other)";
+
+/// The number of largest functions to report.
+const TOP_FUNCTIONS: usize = 20;
+
+pub struct SymbolSize {
+  pub name: String,
+  pub size: u64,
+}
+
+/// Read the sizes of the symbols defined in a binary, using `nm`.
+/// Returns `None` if the step was skipped because `nm` is not
+/// available.
+pub fn read_symbol_sizes(
+  toolchain: &tool::Toolchain,
+  binary_path: &std::path::Path,
+) -> Result<Option<Vec<SymbolSize>>, String> {
+  let nm = match toolchain.find("nm", "the size report")? {
+    Some(nm) => nm,
+    None => return Ok(None),
+  };
+
+  let output = match std::process::Command::new(&nm)
+    .args(&["--print-size", "--size-sort", "--defined-only"])
+    .arg(binary_path)
+    .output()
+  {
+    Ok(output) if output.status.success() => output,
+    Ok(output) => {
+      return Err(format!(
+        "failed to read the symbol table: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+      ))
+    }
+    Err(error) => {
+      return Err(format!(
+        "failed to read the symbol table: failed to run `{}`: {}",
+        nm.display(),
+        error
+      ))
+    }
+  };
+
+  // Each line is in the following format: `<address> <size> <type> <name>`.
+  Ok(Some(
+    String::from_utf8_lossy(&output.stdout)
+      .lines()
+      .filter_map(|line| {
+        let columns = line.split_whitespace().collect::<Vec<_>>();
+
+        match columns.as_slice() {
+          [_, size, _, name] => Some(SymbolSize {
+            name: name.to_string(),
+            size: u64::from_str_radix(size, 16).ok()?,
+          }),
+          _ => None,
+        }
+      })
+      .collect(),
+  ))
+}
+
+/// Print the contribution of each package, and of the largest functions,
+/// to the size of a binary. Symbols are attributed to packages through
+/// the names of the functions they define.
+pub fn print_report(
+  binary_path: &std::path::Path,
+  symbol_sizes: &[SymbolSize],
+  function_packages: &std::collections::HashMap<String, String>,
+) {
+  let package_of = |symbol_name: &str| {
+    function_packages
+      .get(symbol_name)
+      // Some platforms prefix symbols with an underscore.
+      .or_else(|| function_packages.get(symbol_name.strip_prefix('_')?))
+      .map(|package_name| package_name.as_str())
+  };
+
+  let mut package_sizes = std::collections::BTreeMap::<&str, u64>::new();
+
+  for symbol_size in symbol_sizes {
+    *package_sizes
+      .entry(package_of(&symbol_size.name).unwrap_or(UNKNOWN_PACKAGE))
+      .or_default() += symbol_size.size;
+  }
+
+  let mut package_sizes = package_sizes.into_iter().collect::<Vec<_>>();
+
+  package_sizes.sort_by(|a, b| b.1.cmp(&a.1));
+
+  let total_size = std::fs::metadata(binary_path)
+    .map(|metadata| metadata.len())
+    .unwrap_or(0);
+
+  println!(
+    "size report for `{}` ({} total):",
+    binary_path.display(),
+    indicatif::HumanBytes(total_size)
+  );
+
+  println!("\n  by package:");
+
+  for (package_name, size) in package_sizes {
+    println!(
+      "  {:>12}  {}",
+      indicatif::HumanBytes(size).to_string(),
+      package_name
+    );
+  }
+
+  let mut function_sizes = symbol_sizes
+    .iter()
+    .filter_map(|symbol_size| Some((package_of(&symbol_size.name)?, symbol_size)))
+    .collect::<Vec<_>>();
+
+  function_sizes.sort_by(|a, b| b.1.size.cmp(&a.1.size));
+
+  println!("\n  largest functions:");
+
+  for (package_name, symbol_size) in function_sizes.into_iter().take(TOP_FUNCTIONS) {
+    println!(
+      "  {:>12}  {}::{}",
+      indicatif::HumanBytes(symbol_size.size).to_string(),
+      package_name,
+      symbol_size.name
+    );
+  }
+}
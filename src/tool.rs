@@ -0,0 +1,75 @@
+/// Locates external tools (such as the system linker), degrading
+/// gracefully when optional ones are missing.
+pub struct Toolchain {
+  /// Whether a missing tool should fail the current command instead of
+  /// skipping the step that requires it.
+  pub strict: bool,
+}
+
+impl Toolchain {
+  pub fn new(strict: bool) -> Self {
+    Self { strict }
+  }
+
+  /// Locate a tool required for an optional step. If the tool is missing,
+  /// a warning is emitted and `None` is returned, so that the caller can
+  /// skip the step (unless in strict mode, in which case an error is
+  /// returned instead).
+  pub fn find(&self, name: &str, purpose: &str) -> Result<Option<std::path::PathBuf>, String> {
+    if let Some(path) = find_in_path(name) {
+      return Ok(Some(path));
+    }
+
+    if self.strict {
+      return Err(format!(
+        "`{}` is required for {}, but it was not found in PATH",
+        name, purpose
+      ));
+    }
+
+    log::warn!(
+      "`{}` was not found in PATH; skipping {} (pass `--strict-tools` to fail instead)",
+      name,
+      purpose
+    );
+
+    Ok(None)
+  }
+
+  /// Locate the first available tool out of a list of alternatives.
+  pub fn find_any(
+    &self,
+    names: &[&str],
+    purpose: &str,
+  ) -> Result<Option<std::path::PathBuf>, String> {
+    for name in names {
+      if let Some(path) = find_in_path(name) {
+        return Ok(Some(path));
+      }
+    }
+
+    self.find(names.join("` or `").as_str(), purpose)
+  }
+}
+
+pub fn find_in_path(name: &str) -> Option<std::path::PathBuf> {
+  let paths = std::env::var_os("PATH")?;
+
+  for directory in std::env::split_paths(&paths) {
+    let candidate = directory.join(name);
+
+    if candidate.is_file() {
+      return Some(candidate);
+    }
+
+    if cfg!(windows) {
+      let candidate = candidate.with_extension("exe");
+
+      if candidate.is_file() {
+        return Some(candidate);
+      }
+    }
+  }
+
+  None
+}
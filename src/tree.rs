@@ -0,0 +1,183 @@
+use crate::package;
+
+pub const DEFAULT_FORMAT: &str = "{p} v{v}";
+
+/// A package in the dependency tree.
+struct TreeNode {
+  manifest: package::Manifest,
+  /// The names of the dependencies used for the active target.
+  dependencies: Vec<String>,
+  source: String,
+  /// Whether the package is a local override of a published one.
+  is_overridden: bool,
+}
+
+/// Collect the root package and all installed dependencies, keyed by
+/// name.
+fn collect_nodes(
+  root_manifest: &package::Manifest,
+  package_lock: &package::PackageLock,
+) -> Result<std::collections::BTreeMap<String, TreeNode>, String> {
+  let mut nodes = std::collections::BTreeMap::new();
+  let mut queue = std::collections::VecDeque::new();
+
+  nodes.insert(
+    root_manifest.name.clone(),
+    TreeNode {
+      manifest: root_manifest.clone(),
+      dependencies: root_manifest.active_dependencies().into_keys().collect(),
+      source: "(root)".to_string(),
+      is_overridden: false,
+    },
+  );
+
+  queue.extend(root_manifest.active_dependencies());
+
+  while let Some((dependency_name, dependency_spec)) = queue.pop_front() {
+    if nodes.contains_key(&dependency_name) {
+      continue;
+    }
+
+    let manifest = package::fetch_dependency_manifest(&dependency_name, &dependency_spec)?;
+
+    let source = match (
+      &dependency_spec,
+      package_lock.find_package(&dependency_name),
+    ) {
+      (package::DependencySpec::Path { path }, _) => path.clone(),
+      (_, Some(locked_package)) => {
+        format!("{}@{}", locked_package.source, locked_package.reference)
+      }
+      (_, None) => "unknown".to_string(),
+    };
+
+    let dependencies = manifest.active_dependencies();
+
+    queue.extend(dependencies.clone());
+
+    nodes.insert(
+      dependency_name,
+      TreeNode {
+        manifest,
+        dependencies: dependencies.into_keys().collect(),
+        source,
+        is_overridden: matches!(dependency_spec, package::DependencySpec::Path { .. }),
+      },
+    );
+  }
+
+  Ok(nodes)
+}
+
+/// Render a node according to a format string, which may contain the
+/// following tokens: `{p}` (name), `{v}` (version), `{s}` (source),
+/// `{f}` (enabled features) and `{o}` (whether it is overridden).
+fn format_node(format: &str, name: &str, node: &TreeNode) -> String {
+  format
+    .replace("{p}", name)
+    .replace("{v}", &node.manifest.version)
+    .replace("{s}", &node.source)
+    // TODO: List the enabled features, once packages can declare them.
+    .replace("{f}", "")
+    .replace(
+      "{o}",
+      if node.is_overridden {
+        "(overridden)"
+      } else {
+        ""
+      },
+    )
+    .trim_end()
+    .to_string()
+}
+
+/// Print the dependency tree of a package. If `invert` is given, the
+/// tree of packages depending on it is printed instead.
+pub fn print_tree(
+  root_manifest: &package::Manifest,
+  package_lock: &package::PackageLock,
+  format: &str,
+  invert: Option<&str>,
+) -> Result<(), String> {
+  let nodes = collect_nodes(root_manifest, package_lock)?;
+  let mut edges = std::collections::BTreeMap::<&str, Vec<&str>>::new();
+
+  for (name, node) in &nodes {
+    for dependency_name in &node.dependencies {
+      if invert.is_some() {
+        edges.entry(dependency_name).or_default().push(name);
+      } else {
+        edges.entry(name).or_default().push(dependency_name);
+      }
+    }
+  }
+
+  let start = match invert {
+    Some(package_name) if !nodes.contains_key(package_name) => {
+      return Err(format!("`{}` is not a dependency", package_name))
+    }
+    Some(package_name) => package_name,
+    None => root_manifest.name.as_str(),
+  };
+
+  let mut printed = std::collections::HashSet::new();
+
+  print_node(
+    start,
+    &nodes,
+    &edges,
+    format,
+    "",
+    &mut Vec::new(),
+    &mut printed,
+  );
+
+  Ok(())
+}
+
+fn print_node<'a>(
+  name: &'a str,
+  nodes: &std::collections::BTreeMap<String, TreeNode>,
+  edges: &std::collections::BTreeMap<&'a str, Vec<&'a str>>,
+  format: &str,
+  prefix: &str,
+  path: &mut Vec<&'a str>,
+  printed: &mut std::collections::HashSet<&'a str>,
+) {
+  println!("{}", format_node(format, name, &nodes[name]));
+
+  path.push(name);
+
+  let children = edges.get(name).cloned().unwrap_or_default();
+
+  for (index, child) in children.iter().enumerate() {
+    let is_last = index + 1 == children.len();
+    let (branch, indent) = if is_last {
+      ("└── ", "    ")
+    } else {
+      ("├── ", "│   ")
+    };
+
+    print!("{}{}", prefix, branch);
+
+    // Packages already shown (or on the current path, for cycles) are not
+    // expanded again.
+    if path.contains(child) || !printed.insert(*child) {
+      println!("{} (*)", format_node(format, child, &nodes[*child]));
+
+      continue;
+    }
+
+    print_node(
+      *child,
+      nodes,
+      edges,
+      format,
+      &format!("{}{}", prefix, indent),
+      path,
+      printed,
+    );
+  }
+
+  path.pop();
+}
@@ -0,0 +1,211 @@
+use crate::{dependency, features, git, package, registry};
+
+/// A change to an installed package, pending the user's approval.
+pub struct ProposedUpdate {
+  pub locked_package: package::LockedPackage,
+  /// The reference to install, which differs from the locked one when
+  /// a newer version was resolved.
+  pub new_reference: String,
+  pub new_commit: Option<String>,
+  pub old_version: Option<String>,
+  pub new_version: Option<String>,
+  /// Whether the new version was chosen by the resolver (as opposed to
+  /// following a branch).
+  pub is_resolved: bool,
+}
+
+impl ProposedUpdate {
+  /// Determine whether the update crosses a major version, in which case
+  /// it may contain breaking changes. For `0.x` versions, the minor
+  /// version is considered to be the major one.
+  pub fn is_major(&self) -> bool {
+    match (&self.old_version, &self.new_version) {
+      (Some(old_version), Some(new_version)) => {
+        breaking_component(old_version) != breaking_component(new_version)
+      }
+      _ => false,
+    }
+  }
+
+  /// Determine whether the version being updated to is unknown (such as
+  /// for git packages, whose manifest is only known once cloned), in
+  /// which case it may just as well contain breaking changes.
+  pub fn is_version_unknown(&self) -> bool {
+    self.new_version.is_none()
+  }
+}
+
+impl std::fmt::Display for ProposedUpdate {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.new_reference == self.locked_package.reference {
+      write!(
+        formatter,
+        "`{}` ({}): {} -> {}",
+        self.locked_package.name,
+        self.locked_package.reference,
+        short_commit(self.locked_package.commit.as_deref().unwrap_or("unknown")),
+        short_commit(self.new_commit.as_deref().unwrap_or("unknown"))
+      )?;
+    } else {
+      write!(
+        formatter,
+        "`{}`: {} -> {}",
+        self.locked_package.name, self.locked_package.reference, self.new_reference
+      )?;
+    }
+
+    if let (Some(old_version), Some(new_version)) = (&self.old_version, &self.new_version) {
+      if old_version != new_version {
+        write!(formatter, ", v{} -> v{}", old_version, new_version)?;
+      }
+    }
+
+    if self.is_major() {
+      write!(formatter, " (major)")?;
+    } else if self.is_version_unknown() {
+      write!(formatter, " (new version unknown)")?;
+    }
+
+    Ok(())
+  }
+}
+
+fn breaking_component(version: &str) -> String {
+  let mut components = version.trim_start_matches('v').split('.');
+  let major = components.next().unwrap_or("");
+
+  if major == "0" {
+    format!("0.{}", components.next().unwrap_or(""))
+  } else {
+    major.to_string()
+  }
+}
+
+fn short_commit(commit: &str) -> &str {
+  &commit[..std::cmp::min(commit.len(), 7)]
+}
+
+fn installed_version(package_name: &str) -> Option<String> {
+  package::fetch_manifest(
+    &std::path::PathBuf::from(package::PATH_DEPENDENCIES)
+      .join(package_name)
+      .join(package::PATH_MANIFEST_FILE),
+  )
+  .ok()
+  .map(|manifest| manifest.version)
+}
+
+/// Compute the updates available for the installed packages. Registry
+/// dependencies are re-resolved within the version requirements of the
+/// manifests (conflicts between them are reported as errors), while
+/// packages which track a branch are updated to its latest commit.
+/// Packages pinned to a tag or commit are left as-is.
+pub async fn propose(
+  registry: &(dyn registry::Registry + Send + Sync),
+  root_manifest: &package::Manifest,
+  package_lock: &package::PackageLock,
+) -> Result<Vec<ProposedUpdate>, String> {
+  let mut proposed_updates = Vec::new();
+  let resolved_packages = dependency::resolve_versions(
+    registry,
+    root_manifest,
+    &features::resolve_selected(root_manifest)?,
+  )
+  .await?;
+
+  for resolved_package in &resolved_packages {
+    let new_version = resolved_package.version.to_string();
+    let locked_package = package_lock.find_package(&resolved_package.name);
+
+    if let Some(locked_package) = locked_package {
+      if locked_package.reference == resolved_package.reference
+        && locked_package.version.as_ref() == Some(&new_version)
+      {
+        continue;
+      }
+    }
+
+    proposed_updates.push(ProposedUpdate {
+      locked_package: locked_package
+        .cloned()
+        .unwrap_or_else(|| package::LockedPackage {
+          name: resolved_package.name.clone(),
+          kind: package::SourceKind::Registry,
+          source: resolved_package.source.clone(),
+          reference: resolved_package.reference.clone(),
+          commit: None,
+          version: None,
+          checksum: None,
+          vendored: None,
+        }),
+      new_reference: resolved_package.reference.clone(),
+      new_commit: registry
+        .resolve_commit(&resolved_package.source, &resolved_package.reference)
+        .await?,
+      old_version: locked_package
+        .and_then(|locked_package| locked_package.version.clone())
+        .or_else(|| installed_version(&resolved_package.name)),
+      new_version: Some(new_version),
+      is_resolved: true,
+    });
+  }
+
+  for locked_package in &package_lock.packages {
+    if dependency::is_pinned_reference(&locked_package.reference)
+      || resolved_packages
+        .iter()
+        .any(|resolved_package| resolved_package.name == locked_package.name)
+    {
+      continue;
+    }
+
+    let new_commit = match locked_package.kind {
+      package::SourceKind::Git => {
+        git::remote_commit(&locked_package.source, &locked_package.reference)?
+      }
+      package::SourceKind::Registry => {
+        match registry
+          .resolve_commit(&locked_package.source, &locked_package.reference)
+          .await?
+        {
+          Some(commit) => commit,
+          None => {
+            log::warn!(
+              "cannot determine whether `{}` has updates, since the registry doesn't report commits",
+              locked_package.name
+            );
+
+            continue;
+          }
+        }
+      }
+    };
+
+    if locked_package.commit.as_ref() == Some(&new_commit) {
+      continue;
+    }
+
+    let old_version = installed_version(&locked_package.name);
+
+    // REVISE: The manifest of git packages is only known once cloned.
+    let new_version = match locked_package.kind {
+      package::SourceKind::Git => None,
+      package::SourceKind::Registry => registry
+        .fetch_manifest(&locked_package.source, &new_commit)
+        .await
+        .ok()
+        .map(|manifest| manifest.version),
+    };
+
+    proposed_updates.push(ProposedUpdate {
+      locked_package: locked_package.clone(),
+      new_reference: locked_package.reference.clone(),
+      new_commit: Some(new_commit),
+      old_version,
+      new_version,
+      is_resolved: false,
+    });
+  }
+
+  Ok(proposed_updates)
+}
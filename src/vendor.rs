@@ -0,0 +1,147 @@
+use crate::{cache, checksum, config, install, package};
+
+pub const PATH_VENDOR: &str = "vendor";
+
+/// Recursively copy a package's tree, excluding any git metadata (which
+/// is also excluded from the tree's checksum).
+pub fn copy_dir(source: &std::path::Path, destination: &std::path::Path) -> Result<(), String> {
+  if let Err(error) = std::fs::create_dir_all(destination) {
+    return Err(format!(
+      "failed to create `{}`: {}",
+      destination.display(),
+      error
+    ));
+  }
+
+  let read_dir = match std::fs::read_dir(source) {
+    Ok(read_dir) => read_dir,
+    Err(error) => return Err(format!("failed to read `{}`: {}", source.display(), error)),
+  };
+
+  for dir_entry in read_dir.flatten() {
+    let entry_path = dir_entry.path();
+    let destination_path = destination.join(dir_entry.file_name());
+
+    if entry_path.is_dir() {
+      if dir_entry.file_name() != ".git" {
+        copy_dir(&entry_path, &destination_path)?;
+      }
+    } else if let Err(error) = std::fs::copy(&entry_path, &destination_path) {
+      return Err(format!(
+        "failed to copy `{}`: {}",
+        entry_path.display(),
+        error
+      ));
+    }
+  }
+
+  Ok(())
+}
+
+/// Replace a directory with a copy of another.
+fn replace_dir(source: &std::path::Path, destination: &std::path::Path) -> Result<(), String> {
+  if destination.exists() {
+    if let Err(error) = std::fs::remove_dir_all(destination) {
+      return Err(format!(
+        "failed to remove `{}`: {}",
+        destination.display(),
+        error
+      ));
+    }
+  }
+
+  copy_dir(source, destination)
+}
+
+/// Copy every locked package into the vendor directory, and record its
+/// location in the lock. Packages which aren't installed are extracted
+/// from the user-wide cache instead. Each copy must match the checksum
+/// recorded in the lock. Returns the number of vendored packages.
+pub fn vendor(
+  package_lock: &mut package::PackageLock,
+  network_config: &config::NetworkConfig,
+) -> Result<usize, String> {
+  for locked_package in &mut package_lock.packages {
+    let package_path = std::path::Path::new(package::PATH_DEPENDENCIES).join(&locked_package.name);
+    let vendored_path = std::path::Path::new(PATH_VENDOR).join(&locked_package.name);
+
+    if package_path.exists() {
+      replace_dir(&package_path, &vendored_path)?;
+    } else {
+      let archive_path = cache::cache_dir()?.join(format!("{}.zip", locked_package.name));
+
+      if !archive_path.exists() {
+        return Err(format!(
+          "`{}` is neither installed nor cached; run `grip install` first",
+          locked_package.name
+        ));
+      }
+
+      install::extract_package_into(
+        &archive_path,
+        &locked_package.name,
+        &vendored_path,
+        network_config,
+      )?;
+    }
+
+    if let Some(locked_checksum) = &locked_package.checksum {
+      if &checksum::sha256_dir(&vendored_path)? != locked_checksum {
+        // Best-effort cleanup; the error below is more relevant.
+        std::fs::remove_dir_all(&vendored_path).ok();
+
+        return Err(format!(
+          "the contents of `{}` don't match the checksum in the lock; reinstall it with `grip install`",
+          locked_package.name
+        ));
+      }
+    }
+
+    locked_package.vendored = Some(
+      vendored_path
+        .to_string_lossy()
+        // Recorded paths are the same on every platform.
+        .replace('\\', "/"),
+    );
+  }
+
+  Ok(package_lock.packages.len())
+}
+
+/// Install any missing locked packages from their vendored copies,
+/// without accessing the network. Returns the number of restored
+/// packages.
+pub fn restore(package_lock: &package::PackageLock) -> Result<usize, String> {
+  let mut restored_count = 0;
+
+  for locked_package in &package_lock.packages {
+    let package_path = std::path::Path::new(package::PATH_DEPENDENCIES).join(&locked_package.name);
+
+    if package_path.exists() {
+      continue;
+    }
+
+    let vendored_path = match &locked_package.vendored {
+      Some(vendored_path) => std::path::PathBuf::from(vendored_path),
+      None => {
+        return Err(format!(
+          "`{}` is not installed, and was never vendored; run `grip vendor` while online first",
+          locked_package.name
+        ))
+      }
+    };
+
+    if !vendored_path.exists() {
+      return Err(format!(
+        "the vendored copy of `{}` is missing from `{}`",
+        locked_package.name,
+        vendored_path.display()
+      ));
+    }
+
+    copy_dir(&vendored_path, &package_path)?;
+    restored_count += 1;
+  }
+
+  Ok(restored_count)
+}
@@ -0,0 +1,103 @@
+use crate::package;
+
+/// How often the watched files are checked for changes.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Clears the screen, and moves the cursor to its top-left corner.
+const CLEAR_TERMINAL: &str = "\x1B[2J\x1B[1;1H";
+
+type Snapshot = std::collections::BTreeMap<std::path::PathBuf, std::time::SystemTime>;
+
+/// Record the modification time of the manifest, and of every file
+/// under the sources directory.
+fn snapshot(sources_dir: &std::path::Path) -> Snapshot {
+  let mut snapshot = Snapshot::new();
+  let mut pending_paths = vec![package::manifest_path(), sources_dir.to_path_buf()];
+
+  while let Some(path) = pending_paths.pop() {
+    let metadata = match std::fs::metadata(&path) {
+      Ok(metadata) => metadata,
+      // Removed files are noticed by their absence.
+      Err(_) => continue,
+    };
+
+    if metadata.is_dir() {
+      if let Ok(read_dir) = std::fs::read_dir(&path) {
+        pending_paths.extend(read_dir.flatten().map(|dir_entry| dir_entry.path()));
+      }
+    } else {
+      snapshot.insert(
+        path,
+        metadata
+          .modified()
+          .unwrap_or(std::time::SystemTime::UNIX_EPOCH),
+      );
+    }
+  }
+
+  snapshot
+}
+
+/// Re-run the current command (with the given arguments) each time the
+/// manifest or the sources change, clearing the terminal between runs.
+/// A run still in progress when a change is detected (such as a program
+/// which hasn't exited) is stopped first. Never returns unless the
+/// command cannot be started.
+pub fn run(
+  invocation_dir: &std::path::Path,
+  sources_dir: &std::path::Path,
+  args: Vec<std::ffi::OsString>,
+) -> Result<(), String> {
+  let current_exe = match std::env::current_exe() {
+    Ok(path) => path,
+    Err(error) => return Err(format!("failed to locate the grip executable: {}", error)),
+  };
+
+  let mut last_snapshot = snapshot(sources_dir);
+
+  loop {
+    print!("{}", CLEAR_TERMINAL);
+    std::io::Write::flush(&mut std::io::stdout()).ok();
+
+    // Arguments are relative to the directory grip was invoked from.
+    let mut child = match std::process::Command::new(&current_exe)
+      .args(&args)
+      .current_dir(invocation_dir)
+      .spawn()
+    {
+      Ok(child) => child,
+      Err(error) => return Err(format!("failed to start grip: {}", error)),
+    };
+
+    let mut is_running = true;
+
+    loop {
+      std::thread::sleep(POLL_INTERVAL);
+
+      if is_running {
+        if let Ok(Some(status)) = child.try_wait() {
+          is_running = false;
+
+          if !status.success() {
+            log::error!("the command failed ({})", status);
+          }
+
+          log::info!("waiting for changes");
+        }
+      }
+
+      let current_snapshot = snapshot(sources_dir);
+
+      if current_snapshot != last_snapshot {
+        last_snapshot = current_snapshot;
+
+        break;
+      }
+    }
+
+    if is_running {
+      child.kill().ok();
+      child.wait().ok();
+    }
+  }
+}